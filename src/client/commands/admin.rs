@@ -0,0 +1,168 @@
+use super::unknown_subcommand;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec};
+
+// Commands that describe the server itself
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    CommandCount,
+    // COMMAND DOCS, describing every command when no names are given
+    CommandDocs(Vec<String>),
+    // A command line to find the keys of, its name included
+    CommandGetKeys(Vec<Vec<u8>>),
+    // COMMAND and COMMAND INFO, describing every command when no names are given
+    CommandInfo(Vec<String>),
+    CommandList(Option<CommandFilter>),
+}
+
+// The FILTERBY clause of COMMAND LIST
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandFilter {
+    AclCategory(String),
+    Module(String),
+    Pattern(String),
+}
+
+pub const COMMANDS: &[CommandSpec] =
+    &[
+        CommandSpec::new("command", -1, &["loading", "stale"], &["connection"])
+            .doc(
+                "server",
+                "2.8.13",
+                "Returns detailed information about all commands.",
+            )
+            .subcommands(COMMAND_SUBCOMMANDS),
+    ];
+
+const COMMAND_SUBCOMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("command|count", 2, &["loading", "stale"], &["connection"]).doc(
+        "server",
+        "2.8.13",
+        "Returns a count of commands.",
+    ),
+    CommandSpec::new("command|docs", -2, &["loading", "stale"], &["connection"]).doc(
+        "server",
+        "7.0.0",
+        "Returns documentary information about one, multiple or all commands.",
+    ),
+    CommandSpec::new(
+        "command|getkeys",
+        -3,
+        &["loading", "stale"],
+        &["connection"],
+    )
+    .doc(
+        "server",
+        "2.8.13",
+        "Extracts the key names from an arbitrary command.",
+    ),
+    CommandSpec::new("command|info", -2, &["loading", "stale"], &["connection"]).doc(
+        "server",
+        "2.8.13",
+        "Returns information about one, multiple or all commands.",
+    ),
+    CommandSpec::new("command|list", -2, &["loading", "stale"], &["connection"]).doc(
+        "server",
+        "7.0.0",
+        "Returns a list of command names.",
+    ),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<AdminCommand>, CommandParseError> {
+    let command = match name {
+        "command" => parse_command(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+// COMMAND
+// COMMAND COUNT
+// COMMAND DOCS [command-name [command-name ...]]
+// COMMAND GETKEYS command [arg [arg ...]]
+// COMMAND INFO [command-name [command-name ...]]
+// COMMAND LIST [FILTERBY MODULE module-name | ACLCAT category | PATTERN pattern]
+fn parse_command(args: &CommandArgs) -> Result<AdminCommand, CommandParseError> {
+    if args.len() == 0 {
+        return Ok(AdminCommand::CommandInfo(Vec::new()));
+    }
+    let subcommand = args.take_keyword(0)?;
+    match subcommand.as_str() {
+        "count" => Ok(AdminCommand::CommandCount),
+        "docs" => Ok(AdminCommand::CommandDocs(args.take_strings(1)?)),
+        "getkeys" => {
+            let command_line = (1..args.len())
+                .map(|i| Ok(args.take_bytes(i)?.to_vec()))
+                .collect::<Result<_, CommandParseError>>()?;
+            Ok(AdminCommand::CommandGetKeys(command_line))
+        }
+        "info" => Ok(AdminCommand::CommandInfo(args.take_strings(1)?)),
+        "list" => {
+            let filter = match args.len() {
+                1 => None,
+                4 if args.take_keyword(1)? == "filterby" => {
+                    let value = args.take_string(3)?;
+                    Some(match args.take_keyword(2)?.as_str() {
+                        "aclcat" => CommandFilter::AclCategory(value),
+                        "module" => CommandFilter::Module(value),
+                        "pattern" => CommandFilter::Pattern(value),
+                        _ => return Err(CommandParseError::InvalidSyntax),
+                    })
+                }
+                _ => return Err(CommandParseError::InvalidSyntax),
+            };
+            Ok(AdminCommand::CommandList(filter))
+        }
+        _ => Err(unknown_subcommand("command", &subcommand)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<AdminCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Admin(command) => Ok(command),
+            other => panic!("expected an admin command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_command() {
+        assert_eq!(
+            parse(&["COMMAND"]).unwrap(),
+            AdminCommand::CommandInfo(Vec::new())
+        );
+        assert_eq!(
+            parse(&["command", "info", "get", "client|setname"]).unwrap(),
+            AdminCommand::CommandInfo(vec!["get".into(), "client|setname".into()])
+        );
+        assert_eq!(
+            parse(&["COMMAND", "COUNT"]).unwrap(),
+            AdminCommand::CommandCount
+        );
+        assert_eq!(
+            parse(&["COMMAND", "GETKEYS", "SET", "k", "v"]).unwrap(),
+            AdminCommand::CommandGetKeys(vec![b"SET".to_vec(), b"k".to_vec(), b"v".to_vec()])
+        );
+        assert_eq!(
+            parse(&["COMMAND", "LIST", "FILTERBY", "ACLCAT", "hash"]).unwrap(),
+            AdminCommand::CommandList(Some(CommandFilter::AclCategory("hash".into())))
+        );
+        assert!(matches!(
+            parse(&["COMMAND", "COUNT", "x"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "command|count"
+        ));
+        assert!(matches!(
+            parse(&["COMMAND", "GETKEYS"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "command|getkeys"
+        ));
+        assert!(matches!(
+            parse(&["COMMAND", "LIST", "FILTERBY", "NAME", "x"]),
+            Err(CommandParseError::InvalidSyntax)
+        ));
+        assert!(parse(&["COMMAND", "BOGUS"]).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use super::unknown_subcommand;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec};
+
+// Commands that read and change the server's configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigCommand {
+    // Glob patterns matched against the parameter names
+    Get(Vec<String>),
+    ResetStat,
+    Rewrite,
+    Set(Vec<(String, String)>),
+}
+
+pub const COMMANDS: &[CommandSpec] = &[CommandSpec::new("config", -2, &[], &[])
+    .doc(
+        "server",
+        "2.0.0",
+        "A container for server configuration commands.",
+    )
+    .subcommands(CONFIG_SUBCOMMANDS)];
+
+const CONFIG_SUBCOMMANDS: &[CommandSpec] = &[
+    CommandSpec::new(
+        "config|get",
+        -3,
+        &["admin", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "server",
+        "2.0.0",
+        "Returns the effective values of configuration parameters.",
+    ),
+    CommandSpec::new(
+        "config|resetstat",
+        2,
+        &["admin", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc("server", "2.0.0", "Resets the server's statistics."),
+    CommandSpec::new(
+        "config|rewrite",
+        2,
+        &["admin", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "server",
+        "2.8.0",
+        "Persists the effective configuration to file.",
+    ),
+    CommandSpec::new(
+        "config|set",
+        -4,
+        &["admin", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "server",
+        "2.0.0",
+        "Sets configuration parameters in-flight.",
+    ),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<ConfigCommand>, CommandParseError> {
+    let command = match name {
+        "config" => parse_config(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+// CONFIG GET parameter [parameter ...]
+// CONFIG RESETSTAT
+// CONFIG REWRITE
+// CONFIG SET parameter value [parameter value ...]
+fn parse_config(args: &CommandArgs) -> Result<ConfigCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    match subcommand.as_str() {
+        "get" => Ok(ConfigCommand::Get(args.take_strings(1)?)),
+        "resetstat" => Ok(ConfigCommand::ResetStat),
+        "rewrite" => Ok(ConfigCommand::Rewrite),
+        "set" => {
+            if args.len().is_multiple_of(2) {
+                return Err(CommandParseError::ArityMismatch("config|set".into()));
+            }
+            let pairs = (1..args.len())
+                .step_by(2)
+                .map(|i| Ok((args.take_string(i)?, args.take_string(i + 1)?)))
+                .collect::<Result<_, CommandParseError>>()?;
+            Ok(ConfigCommand::Set(pairs))
+        }
+        _ => Err(unknown_subcommand("config", &subcommand)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<ConfigCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Config(command) => Ok(command),
+            other => panic!("expected a config command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        assert_eq!(
+            parse(&["CONFIG", "GET", "port", "proto-*"]).unwrap(),
+            ConfigCommand::Get(vec!["port".into(), "proto-*".into()])
+        );
+        assert_eq!(
+            parse(&["config", "set", "timeout", "30", "loglevel", "debug"]).unwrap(),
+            ConfigCommand::Set(vec![
+                ("timeout".into(), "30".into()),
+                ("loglevel".into(), "debug".into()),
+            ])
+        );
+        assert_eq!(
+            parse(&["CONFIG", "REWRITE"]).unwrap(),
+            ConfigCommand::Rewrite
+        );
+        assert!(matches!(
+            parse(&["CONFIG", "SET", "timeout", "30", "loglevel"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "config|set"
+        ));
+        assert!(matches!(
+            parse(&["CONFIG", "GET"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "config|get"
+        ));
+        assert!(parse(&["CONFIG", "RESETSTAT", "now"]).is_err());
+        assert!(parse(&["CONFIG", "BOGUS"]).is_err());
+    }
+}
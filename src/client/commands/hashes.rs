@@ -0,0 +1,363 @@
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+
+// Commands that operate on hash values
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashCommand {
+    Del {
+        key: String,
+        fields: Vec<Vec<u8>>,
+    },
+    Exists {
+        key: String,
+        field: Vec<u8>,
+    },
+    Get {
+        key: String,
+        field: Vec<u8>,
+    },
+    GetAll(String),
+    IncrBy {
+        key: String,
+        field: Vec<u8>,
+        delta: i64,
+    },
+    IncrByFloat {
+        key: String,
+        field: Vec<u8>,
+        delta: f64,
+    },
+    Keys(String),
+    Len(String),
+    MGet {
+        key: String,
+        fields: Vec<Vec<u8>>,
+    },
+    RandField {
+        key: String,
+        count: Option<i64>,
+        with_values: bool,
+    },
+    Scan {
+        key: String,
+        cursor: u64,
+        pattern: Option<Vec<u8>>,
+        count: Option<usize>,
+        no_values: bool,
+    },
+    // HMSET is kept apart from HSET because it replies OK instead of a count
+    Set {
+        key: String,
+        pairs: Vec<(Vec<u8>, Vec<u8>)>,
+        legacy: bool,
+    },
+    SetNx {
+        key: String,
+        field: Vec<u8>,
+        value: Vec<u8>,
+    },
+    StrLen {
+        key: String,
+        field: Vec<u8>,
+    },
+    Vals(String),
+}
+
+impl HashCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            HashCommand::Del { .. }
+                | HashCommand::IncrBy { .. }
+                | HashCommand::IncrByFloat { .. }
+                | HashCommand::Set { .. }
+                | HashCommand::SetNx { .. }
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("hset", -4, &["write", "denyoom", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("hash", "2.0.0", "Creates or modifies the value of a field in a hash."),
+    CommandSpec::new("hmset", -4, &["write", "denyoom", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("hash", "2.0.0", "Sets the values of multiple fields."),
+    CommandSpec::new("hsetnx", 4, &["write", "denyoom", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "hash",
+            "2.0.0",
+            "Sets the value of a field in a hash only when the field doesn't exist.",
+        ),
+    CommandSpec::new("hget", 3, &["readonly", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "2.0.0", "Returns the value of a field in a hash."),
+    CommandSpec::new("hexists", 3, &["readonly", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("hash", "2.0.0", "Determines whether a field exists in a hash."),
+    CommandSpec::new("hstrlen", 3, &["readonly", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("hash", "3.2.0", "Returns the length of the value of a field."),
+    CommandSpec::new("hmget", -3, &["readonly", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "2.0.0", "Returns the values of all fields in a hash."),
+    CommandSpec::new("hdel", -3, &["write", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc(
+            "hash",
+            "2.0.0",
+            "Deletes one or more fields and their values from a hash. Deletes the hash if no fields remain.",
+        ),
+    CommandSpec::new("hgetall", 2, &["readonly"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "2.0.0", "Returns all fields and values in a hash."),
+    CommandSpec::new("hkeys", 2, &["readonly"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("hash", "2.0.0", "Returns all fields in a hash."),
+    CommandSpec::new("hvals", 2, &["readonly"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "2.0.0", "Returns all values in a hash."),
+    CommandSpec::new("hlen", 2, &["readonly", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("hash", "2.0.0", "Returns the number of fields in a hash."),
+    CommandSpec::new("hincrby", 4, &["write", "denyoom", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "UPDATE"])])
+        .doc(
+            "hash",
+            "2.0.0",
+            "Increments the integer value of a field in a hash by a number. Uses 0 as initial value if the field doesn't exist.",
+        ),
+    CommandSpec::new("hincrbyfloat", 4, &["write", "denyoom", "fast"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "UPDATE"])])
+        .doc(
+            "hash",
+            "2.6.0",
+            "Increments the floating point value of a field by a number. Uses 0 as initial value if the field doesn't exist.",
+        ),
+    CommandSpec::new("hrandfield", -2, &["readonly"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "6.2.0", "Returns one or more random fields from a hash."),
+    CommandSpec::new("hscan", -3, &["readonly"], &["hash"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("hash", "2.8.0", "Iterates over fields and values of a hash."),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<HashCommand>, CommandParseError> {
+    let arity_error = || CommandParseError::ArityMismatch(name.into());
+    let command = match name {
+        // HSET key field value [field value ...]
+        // HMSET key field value [field value ...]
+        "hset" | "hmset" => {
+            if args.len().is_multiple_of(2) {
+                return Err(arity_error());
+            }
+            parse_hset(name, args)?
+        }
+        // HSETNX key field value
+        "hsetnx" => HashCommand::SetNx {
+            key: args.take_string(0)?,
+            field: take_field(args, 1)?,
+            value: take_field(args, 2)?,
+        },
+        // HGET key field
+        // HEXISTS key field
+        // HSTRLEN key field
+        "hget" | "hexists" | "hstrlen" => {
+            let key = args.take_string(0)?;
+            let field = take_field(args, 1)?;
+            match name {
+                "hget" => HashCommand::Get { key, field },
+                "hexists" => HashCommand::Exists { key, field },
+                _ => HashCommand::StrLen { key, field },
+            }
+        }
+        // HMGET key field [field ...]
+        // HDEL key field [field ...]
+        "hmget" | "hdel" => {
+            let key = args.take_string(0)?;
+            let fields = (1..args.len())
+                .map(|i| take_field(args, i))
+                .collect::<Result<_, _>>()?;
+            if name == "hmget" {
+                HashCommand::MGet { key, fields }
+            } else {
+                HashCommand::Del { key, fields }
+            }
+        }
+        // HGETALL key
+        // HKEYS key
+        // HVALS key
+        // HLEN key
+        "hgetall" | "hkeys" | "hvals" | "hlen" => {
+            let key = args.take_string(0)?;
+            match name {
+                "hgetall" => HashCommand::GetAll(key),
+                "hkeys" => HashCommand::Keys(key),
+                "hvals" => HashCommand::Vals(key),
+                _ => HashCommand::Len(key),
+            }
+        }
+        // HINCRBY key field increment
+        "hincrby" => HashCommand::IncrBy {
+            key: args.take_string(0)?,
+            field: take_field(args, 1)?,
+            delta: args.take_int(2)?,
+        },
+        // HINCRBYFLOAT key field increment
+        "hincrbyfloat" => HashCommand::IncrByFloat {
+            key: args.take_string(0)?,
+            field: take_field(args, 1)?,
+            delta: args.take_float(2)?,
+        },
+        // HRANDFIELD key [count [WITHVALUES]]
+        "hrandfield" => {
+            if args.len() > 3 {
+                return Err(arity_error());
+            }
+            parse_hrandfield(args)?
+        }
+        // HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+        "hscan" => parse_hscan(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn take_field(args: &CommandArgs, index: usize) -> Result<Vec<u8>, CommandParseError> {
+    Ok(args.take_bytes(index)?.to_vec())
+}
+
+fn parse_hset(name: &str, args: &CommandArgs) -> Result<HashCommand, CommandParseError> {
+    let pairs = (1..args.len())
+        .step_by(2)
+        .map(|i| Ok((take_field(args, i)?, take_field(args, i + 1)?)))
+        .collect::<Result<_, CommandParseError>>()?;
+    Ok(HashCommand::Set {
+        key: args.take_string(0)?,
+        pairs,
+        legacy: name == "hmset",
+    })
+}
+
+fn parse_hrandfield(args: &CommandArgs) -> Result<HashCommand, CommandParseError> {
+    let with_values = args.len() == 3;
+    if with_values && args.take_keyword(2)? != "withvalues" {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    Ok(HashCommand::RandField {
+        key: args.take_string(0)?,
+        count: args.take_opt_int(1)?,
+        with_values,
+    })
+}
+
+fn parse_hscan(args: &CommandArgs) -> Result<HashCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let cursor = u64::try_from(args.take_int(1)?).map_err(|_| CommandParseError::InvalidCursor)?;
+
+    let mut pattern = None;
+    let mut count = None;
+    let mut no_values = false;
+    let mut index = 2;
+    while index < args.len() {
+        let option = args.take_keyword(index)?;
+        match option.as_str() {
+            "match" if index + 1 < args.len() => {
+                pattern = Some(take_field(args, index + 1)?);
+                index += 2;
+            }
+            "count" if index + 1 < args.len() => {
+                let value = usize::try_from(args.take_int(index + 1)?)
+                    .map_err(|_| CommandParseError::InvalidSyntax)?;
+                if value == 0 {
+                    return Err(CommandParseError::InvalidSyntax);
+                }
+                count = Some(value);
+                index += 2;
+            }
+            "novalues" => {
+                no_values = true;
+                index += 1;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+    }
+
+    Ok(HashCommand::Scan {
+        key,
+        cursor,
+        pattern,
+        count,
+        no_values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<HashCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Hash(command) => Ok(command),
+            other => panic!("expected a hash command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_hset() {
+        let got = parse(&["HSET", "user:1", "name", "ada", "age", "36"]).unwrap();
+        let expected = HashCommand::Set {
+            key: "user:1".into(),
+            pairs: vec![
+                (b"name".to_vec(), b"ada".to_vec()),
+                (b"age".to_vec(), b"36".to_vec()),
+            ],
+            legacy: false,
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["HSET", "user:1"]).is_err());
+        assert!(parse(&["HMSET", "user:1", "name"]).is_err());
+    }
+
+    #[test]
+    fn test_hincrby() {
+        let got = parse(&["HINCRBY", "h", "f", "-5"]).unwrap();
+        let expected = HashCommand::IncrBy {
+            key: "h".into(),
+            field: b"f".to_vec(),
+            delta: -5,
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["HINCRBYFLOAT", "h", "f", "abc"]);
+        assert!(matches!(got, Err(CommandParseError::NotFloat)));
+    }
+
+    #[test]
+    fn test_hrandfield() {
+        let got = parse(&["HRANDFIELD", "h", "-3", "WITHVALUES"]).unwrap();
+        let expected = HashCommand::RandField {
+            key: "h".into(),
+            count: Some(-3),
+            with_values: true,
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["HRANDFIELD", "h", "3", "BOGUS"]).is_err());
+    }
+
+    #[test]
+    fn test_hscan() {
+        let got = parse(&["HSCAN", "h", "0", "MATCH", "a*", "NOVALUES", "COUNT", "5"]).unwrap();
+        let expected = HashCommand::Scan {
+            key: "h".into(),
+            cursor: 0,
+            pattern: Some(b"a*".to_vec()),
+            count: Some(5),
+            no_values: true,
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["HSCAN", "h", "0", "COUNT"]).is_err());
+    }
+}
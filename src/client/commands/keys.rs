@@ -0,0 +1,333 @@
+use crate::client::Expiry;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::{ExpireCondition, KeyKind};
+
+// Commands that operate on keys regardless of their type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyCommand {
+    Del(Vec<String>),
+    Exists(Vec<String>),
+    Expire {
+        key: String,
+        expiry: Expiry,
+        condition: ExpireCondition,
+    },
+    ExpireTime(String),
+    Persist(String),
+    PexpireTime(String),
+    Pttl(String),
+    Scan {
+        cursor: u64,
+        pattern: Option<String>,
+        count: Option<usize>,
+        kind: Option<KeyKind>,
+    },
+    Ttl(String),
+    Type(String),
+}
+
+impl KeyCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            KeyCommand::Del(_) | KeyCommand::Expire { .. } | KeyCommand::Persist(_)
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("del", -2, &["write"], &["keyspace"])
+        .keys(&[KeySpec::range(1, -1, 1, &["RM", "DELETE"])])
+        .doc("generic", "1.0.0", "Deletes one or more keys."),
+    CommandSpec::new("exists", -2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::range(1, -1, 1, &["RO"])])
+        .doc(
+            "generic",
+            "1.0.0",
+            "Determines whether one or more keys exist.",
+        ),
+    CommandSpec::new("expire", -3, &["write", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc(
+            "generic",
+            "1.0.0",
+            "Sets the expiration time of a key in seconds.",
+        ),
+    CommandSpec::new("pexpire", -3, &["write", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc(
+            "generic",
+            "2.6.0",
+            "Sets the expiration time of a key in milliseconds.",
+        ),
+    CommandSpec::new("expireat", -3, &["write", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc(
+            "generic",
+            "1.2.0",
+            "Sets the expiration time of a key to a Unix timestamp.",
+        ),
+    CommandSpec::new("pexpireat", -3, &["write", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc(
+            "generic",
+            "2.6.0",
+            "Sets the expiration time of a key to a Unix milliseconds timestamp.",
+        ),
+    CommandSpec::new("expiretime", 2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc(
+            "generic",
+            "7.0.0",
+            "Returns the expiration time of a key as a Unix timestamp.",
+        ),
+    CommandSpec::new("pexpiretime", 2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc(
+            "generic",
+            "7.0.0",
+            "Returns the expiration time of a key as a Unix milliseconds timestamp.",
+        ),
+    CommandSpec::new("ttl", 2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc(
+            "generic",
+            "1.0.0",
+            "Returns the expiration time in seconds of a key.",
+        ),
+    CommandSpec::new("pttl", 2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc(
+            "generic",
+            "2.6.0",
+            "Returns the expiration time in milliseconds of a key.",
+        ),
+    CommandSpec::new("persist", 2, &["write", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("generic", "2.2.0", "Removes the expiration time of a key."),
+    CommandSpec::new("type", 2, &["readonly", "fast"], &["keyspace"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc(
+            "generic",
+            "1.0.0",
+            "Determines the type of value stored at a key.",
+        ),
+    CommandSpec::new("scan", -2, &["readonly"], &["keyspace"]).doc(
+        "generic",
+        "2.8.0",
+        "Iterates over the key names in the database.",
+    ),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<KeyCommand>, CommandParseError> {
+    let command = match name {
+        // DEL key [key ...]
+        // EXISTS key [key ...]
+        "del" | "exists" => {
+            let keys = args.take_strings(0)?;
+            if name == "del" {
+                KeyCommand::Del(keys)
+            } else {
+                KeyCommand::Exists(keys)
+            }
+        }
+        // EXPIRE key seconds [NX | XX | GT | LT]
+        // PEXPIRE key milliseconds [NX | XX | GT | LT]
+        // EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+        // PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+        "expire" | "pexpire" | "expireat" | "pexpireat" => parse_expire(name, args)?,
+        // EXPIRETIME key
+        // PEXPIRETIME key
+        // TTL key
+        // PTTL key
+        // PERSIST key
+        // TYPE key
+        "expiretime" | "pexpiretime" | "ttl" | "pttl" | "persist" | "type" => {
+            let key = args.take_string(0)?;
+            match name {
+                "expiretime" => KeyCommand::ExpireTime(key),
+                "pexpiretime" => KeyCommand::PexpireTime(key),
+                "ttl" => KeyCommand::Ttl(key),
+                "pttl" => KeyCommand::Pttl(key),
+                "type" => KeyCommand::Type(key),
+                _ => KeyCommand::Persist(key),
+            }
+        }
+        // SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+        "scan" => parse_scan(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn parse_expire(name: &str, args: &CommandArgs) -> Result<KeyCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let time = args.take_int(1)?;
+    let expiry = match name {
+        "expire" => Expiry::Seconds(time),
+        "pexpire" => Expiry::Milliseconds(time),
+        "expireat" => Expiry::UnixSeconds(time),
+        _ => Expiry::UnixMilliseconds(time),
+    };
+
+    let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+    for index in 2..args.len() {
+        match args.take_keyword(index)?.as_str() {
+            "nx" => nx = true,
+            "xx" => xx = true,
+            "gt" => gt = true,
+            "lt" => lt = true,
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+    }
+
+    if nx && (xx || gt || lt) {
+        return Err(CommandParseError::IncompatibleOptions(
+            "NX and XX, GT or LT options at the same time are not compatible".into(),
+        ));
+    }
+    if gt && lt {
+        return Err(CommandParseError::IncompatibleOptions(
+            "GT and LT options at the same time are not compatible".into(),
+        ));
+    }
+
+    // GT already implies an existing expiry, since persistent keys count as infinite
+    let condition = match (nx, xx, gt, lt) {
+        (true, ..) => ExpireCondition::IfNoExpiry,
+        (_, _, true, _) => ExpireCondition::IfGreater,
+        (_, true, _, true) => ExpireCondition::IfHasExpiryAndLess,
+        (_, _, _, true) => ExpireCondition::IfLess,
+        (_, true, ..) => ExpireCondition::IfHasExpiry,
+        _ => ExpireCondition::Always,
+    };
+
+    Ok(KeyCommand::Expire {
+        key,
+        expiry,
+        condition,
+    })
+}
+
+fn parse_scan(args: &CommandArgs) -> Result<KeyCommand, CommandParseError> {
+    let cursor = u64::try_from(args.take_int(0)?).map_err(|_| CommandParseError::InvalidCursor)?;
+
+    let mut pattern = None;
+    let mut count = None;
+    let mut kind = None;
+    let mut index = 1;
+    while index < args.len() {
+        let option = args.take_keyword(index)?;
+        match option.as_str() {
+            "match" if index + 1 < args.len() => {
+                pattern = Some(args.take_string(index + 1)?);
+            }
+            "count" if index + 1 < args.len() => {
+                let value = usize::try_from(args.take_int(index + 1)?)
+                    .map_err(|_| CommandParseError::InvalidSyntax)?;
+                if value == 0 {
+                    return Err(CommandParseError::InvalidSyntax);
+                }
+                count = Some(value);
+            }
+            "type" if index + 1 < args.len() => {
+                let name = args.take_string(index + 1)?;
+                let parsed = KeyKind::from_name(&name.to_ascii_lowercase());
+                kind = Some(parsed.ok_or(CommandParseError::UnknownTypeName(name))?);
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        index += 2;
+    }
+
+    Ok(KeyCommand::Scan {
+        cursor,
+        pattern,
+        count,
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<KeyCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Key(command) => Ok(command),
+            other => panic!("expected a key command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_del_exists() {
+        let got = parse(&["DEL", "a", "b"]).unwrap();
+        assert_eq!(got, KeyCommand::Del(vec!["a".into(), "b".into()]));
+
+        let got = parse(&["exists", "a"]).unwrap();
+        assert_eq!(got, KeyCommand::Exists(vec!["a".into()]));
+
+        assert!(parse(&["DEL"]).is_err());
+    }
+
+    #[test]
+    fn test_expire() {
+        let got = parse(&["PEXPIRE", "key", "1500", "xx", "lt"]).unwrap();
+        let expected = KeyCommand::Expire {
+            key: "key".into(),
+            expiry: Expiry::Milliseconds(1500),
+            condition: ExpireCondition::IfHasExpiryAndLess,
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["EXPIREAT", "key", "100", "GT"]).unwrap();
+        let expected = KeyCommand::Expire {
+            key: "key".into(),
+            expiry: Expiry::UnixSeconds(100),
+            condition: ExpireCondition::IfGreater,
+        };
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn test_expire_incompatible_options() {
+        let got = parse(&["EXPIRE", "key", "10", "NX", "GT"]);
+        assert!(matches!(
+            got,
+            Err(CommandParseError::IncompatibleOptions(_))
+        ));
+
+        let got = parse(&["EXPIRE", "key", "10", "GT", "LT"]);
+        assert!(matches!(
+            got,
+            Err(CommandParseError::IncompatibleOptions(_))
+        ));
+
+        let got = parse(&["EXPIRE", "key", "10", "FOREVER"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidSyntax)));
+    }
+
+    #[test]
+    fn test_scan() {
+        let got = parse(&[
+            "SCAN", "20", "MATCH", "user:*", "COUNT", "5", "TYPE", "HASH",
+        ])
+        .unwrap();
+        let expected = KeyCommand::Scan {
+            cursor: 20,
+            pattern: Some("user:*".into()),
+            count: Some(5),
+            kind: Some(KeyKind::Hash),
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["SCAN", "-1"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidCursor)));
+        assert!(parse(&["SCAN", "0", "COUNT", "0"]).is_err());
+        assert!(parse(&["SCAN", "0", "MATCH"]).is_err());
+
+        let got = parse(&["SCAN", "0", "TYPE", "blob"]);
+        assert!(matches!(got, Err(CommandParseError::UnknownTypeName(name)) if name == "blob"));
+    }
+}
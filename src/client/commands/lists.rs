@@ -0,0 +1,559 @@
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::ListEnd;
+use std::time::Duration;
+
+// Commands that operate on list values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListCommand {
+    // BLPOP and BRPOP, where a zero timeout blocks forever
+    BlockingPop {
+        keys: Vec<String>,
+        end: ListEnd,
+        timeout: Duration,
+    },
+    // BLMOVE and BRPOPLPUSH
+    BlockingMove {
+        source: String,
+        destination: String,
+        from: ListEnd,
+        to: ListEnd,
+        timeout: Duration,
+    },
+    Index {
+        key: String,
+        index: i64,
+    },
+    Insert {
+        key: String,
+        before: bool,
+        pivot: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Len(String),
+    // LMOVE and RPOPLPUSH
+    Move {
+        source: String,
+        destination: String,
+        from: ListEnd,
+        to: ListEnd,
+    },
+    // Without a count a single element is popped and replied as a bulk string
+    Pop {
+        key: String,
+        end: ListEnd,
+        count: Option<usize>,
+    },
+    Pos {
+        key: String,
+        element: Vec<u8>,
+        rank: i64,
+        count: Option<usize>,
+        max_len: usize,
+    },
+    // LPUSH, RPUSH and the LPUSHX/RPUSHX variants that require an existing list
+    Push {
+        key: String,
+        values: Vec<Vec<u8>>,
+        end: ListEnd,
+        only_existing: bool,
+    },
+    Range {
+        key: String,
+        start: i64,
+        stop: i64,
+    },
+    Rem {
+        key: String,
+        count: i64,
+        value: Vec<u8>,
+    },
+    Set {
+        key: String,
+        index: i64,
+        value: Vec<u8>,
+    },
+    Trim {
+        key: String,
+        start: i64,
+        stop: i64,
+    },
+}
+
+impl ListCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            ListCommand::BlockingPop { .. }
+                | ListCommand::BlockingMove { .. }
+                | ListCommand::Insert { .. }
+                | ListCommand::Move { .. }
+                | ListCommand::Pop { .. }
+                | ListCommand::Push { .. }
+                | ListCommand::Rem { .. }
+                | ListCommand::Set { .. }
+                | ListCommand::Trim { .. }
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("lpush", -3, &["write", "denyoom", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+        ),
+    CommandSpec::new("rpush", -3, &["write", "denyoom", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        ),
+    CommandSpec::new("lpushx", -3, &["write", "denyoom", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "list",
+            "2.2.0",
+            "Prepends one or more elements to a list only when the list exists.",
+        ),
+    CommandSpec::new("rpushx", -3, &["write", "denyoom", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "list",
+            "2.2.0",
+            "Appends an element to a list only when the list exists.",
+        ),
+    CommandSpec::new("lpop", -2, &["write", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "DELETE"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.",
+        ),
+    CommandSpec::new("rpop", -2, &["write", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "DELETE"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Returns and removes the last elements of a list. Deletes the list if the last element was popped.",
+        ),
+    CommandSpec::new("lrange", 4, &["readonly"], &["list"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("list", "1.0.0", "Returns a range of elements from a list."),
+    CommandSpec::new("ltrim", 4, &["write"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Removes elements from both ends a list. Deletes the list if all elements were trimmed.",
+        ),
+    CommandSpec::new("lindex", 3, &["readonly"], &["list"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("list", "1.0.0", "Returns an element from a list by its index."),
+    CommandSpec::new("lset", 4, &["write", "denyoom"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("list", "1.0.0", "Sets the value of an element in a list by its index."),
+    CommandSpec::new("linsert", 5, &["write", "denyoom"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "list",
+            "2.2.0",
+            "Inserts an element before or after another element in a list.",
+        ),
+    CommandSpec::new("lrem", 4, &["write"], &["list"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc(
+            "list",
+            "1.0.0",
+            "Removes elements from a list. Deletes the list if the last element was removed.",
+        ),
+    CommandSpec::new("llen", 2, &["readonly", "fast"], &["list"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("list", "1.0.0", "Returns the length of a list."),
+    CommandSpec::new("lpos", -3, &["readonly"], &["list"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("list", "6.0.6", "Returns the index of matching elements in a list."),
+    CommandSpec::new("lmove", 5, &["write", "denyoom"], &["list"])
+        .keys(&[
+            KeySpec::key(1, &["RW", "ACCESS", "DELETE"]),
+            KeySpec::key(2, &["RW", "INSERT"]),
+        ])
+        .doc(
+            "list",
+            "6.2.0",
+            "Returns an element after popping it from one list and pushing it to another. Deletes the list if the last element was moved.",
+        ),
+    CommandSpec::new("blmove", 6, &["write", "denyoom", "blocking"], &["list"])
+        .keys(&[
+            KeySpec::key(1, &["RW", "ACCESS", "DELETE"]),
+            KeySpec::key(2, &["RW", "INSERT"]),
+        ])
+        .doc(
+            "list",
+            "6.2.0",
+            "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is available otherwise. Deletes the list if the last element was moved.",
+        ),
+    CommandSpec::new("rpoplpush", 3, &["write", "denyoom"], &["list"])
+        .keys(&[
+            KeySpec::key(1, &["RW", "ACCESS", "DELETE"]),
+            KeySpec::key(2, &["RW", "INSERT"]),
+        ])
+        .doc(
+            "list",
+            "1.2.0",
+            "Returns the last element of a list after removing and pushing it to another list. Deletes the list if the last element was popped.",
+        ),
+    CommandSpec::new("brpoplpush", 4, &["write", "denyoom", "blocking"], &["list"])
+        .keys(&[
+            KeySpec::key(1, &["RW", "ACCESS", "DELETE"]),
+            KeySpec::key(2, &["RW", "INSERT"]),
+        ])
+        .doc(
+            "list",
+            "2.2.0",
+            "Pops an element from a list, pushes it to another list and returns it. Block until an element is available otherwise. Deletes the list if the last element was popped.",
+        ),
+    CommandSpec::new("blpop", -3, &["write", "blocking"], &["list"])
+        .keys(&[KeySpec::range(1, -2, 1, &["RW", "ACCESS", "DELETE"])])
+        .doc(
+            "list",
+            "2.0.0",
+            "Removes and returns the first element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        ),
+    CommandSpec::new("brpop", -3, &["write", "blocking"], &["list"])
+        .keys(&[KeySpec::range(1, -2, 1, &["RW", "ACCESS", "DELETE"])])
+        .doc(
+            "list",
+            "2.0.0",
+            "Removes and returns the last element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        ),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<ListCommand>, CommandParseError> {
+    let arity_error = || CommandParseError::ArityMismatch(name.into());
+    let command = match name {
+        // LPUSH key element [element ...]
+        // RPUSH key element [element ...]
+        // LPUSHX key element [element ...]
+        // RPUSHX key element [element ...]
+        "lpush" | "rpush" | "lpushx" | "rpushx" => ListCommand::Push {
+            key: args.take_string(0)?,
+            values: take_values(args, 1)?,
+            end: if name.starts_with('l') {
+                ListEnd::Left
+            } else {
+                ListEnd::Right
+            },
+            only_existing: name.ends_with('x'),
+        },
+        // LPOP key [count]
+        // RPOP key [count]
+        "lpop" | "rpop" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            let count = match args.take_opt_int(1)? {
+                Some(count) => Some(usize::try_from(count).map_err(|_| not_positive())?),
+                None => None,
+            };
+            ListCommand::Pop {
+                key: args.take_string(0)?,
+                end: if name == "lpop" {
+                    ListEnd::Left
+                } else {
+                    ListEnd::Right
+                },
+                count,
+            }
+        }
+        // LRANGE key start stop
+        // LTRIM key start stop
+        "lrange" | "ltrim" => {
+            let key = args.take_string(0)?;
+            let start = args.take_int(1)?;
+            let stop = args.take_int(2)?;
+            if name == "lrange" {
+                ListCommand::Range { key, start, stop }
+            } else {
+                ListCommand::Trim { key, start, stop }
+            }
+        }
+        // LINDEX key index
+        "lindex" => ListCommand::Index {
+            key: args.take_string(0)?,
+            index: args.take_int(1)?,
+        },
+        // LSET key index element
+        "lset" => ListCommand::Set {
+            key: args.take_string(0)?,
+            index: args.take_int(1)?,
+            value: take_value(args, 2)?,
+        },
+        // LINSERT key BEFORE | AFTER pivot element
+        "linsert" => parse_linsert(args)?,
+        // LREM key count element
+        "lrem" => ListCommand::Rem {
+            key: args.take_string(0)?,
+            count: args.take_int(1)?,
+            value: take_value(args, 2)?,
+        },
+        // LLEN key
+        "llen" => ListCommand::Len(args.take_string(0)?),
+        // LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+        "lpos" => parse_lpos(args)?,
+        _ => return parse_move(name, args),
+    };
+    Ok(Some(command))
+}
+
+// Parses the commands that move elements between lists, including the blocking pops
+fn parse_move(name: &str, args: &CommandArgs) -> Result<Option<ListCommand>, CommandParseError> {
+    let command = match name {
+        // LMOVE source destination LEFT | RIGHT LEFT | RIGHT
+        // BLMOVE source destination LEFT | RIGHT LEFT | RIGHT timeout
+        "lmove" | "blmove" => {
+            let blocking = name == "blmove";
+            let source = args.take_string(0)?;
+            let destination = args.take_string(1)?;
+            let from = take_end(args, 2)?;
+            let to = take_end(args, 3)?;
+            if blocking {
+                ListCommand::BlockingMove {
+                    source,
+                    destination,
+                    from,
+                    to,
+                    timeout: args.take_timeout(4)?,
+                }
+            } else {
+                ListCommand::Move {
+                    source,
+                    destination,
+                    from,
+                    to,
+                }
+            }
+        }
+        // RPOPLPUSH source destination
+        // BRPOPLPUSH source destination timeout
+        "rpoplpush" | "brpoplpush" => {
+            let blocking = name == "brpoplpush";
+            let source = args.take_string(0)?;
+            let destination = args.take_string(1)?;
+            let (from, to) = (ListEnd::Right, ListEnd::Left);
+            if blocking {
+                ListCommand::BlockingMove {
+                    source,
+                    destination,
+                    from,
+                    to,
+                    timeout: args.take_timeout(2)?,
+                }
+            } else {
+                ListCommand::Move {
+                    source,
+                    destination,
+                    from,
+                    to,
+                }
+            }
+        }
+        // BLPOP key [key ...] timeout
+        // BRPOP key [key ...] timeout
+        "blpop" | "brpop" => {
+            let keys = (0..args.len() - 1)
+                .map(|i| args.take_string(i))
+                .collect::<Result<_, _>>()?;
+            ListCommand::BlockingPop {
+                keys,
+                end: if name == "blpop" {
+                    ListEnd::Left
+                } else {
+                    ListEnd::Right
+                },
+                timeout: args.take_timeout(args.len() - 1)?,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn parse_linsert(args: &CommandArgs) -> Result<ListCommand, CommandParseError> {
+    let before = match args.take_keyword(1)?.as_str() {
+        "before" => true,
+        "after" => false,
+        _ => return Err(CommandParseError::InvalidSyntax),
+    };
+    Ok(ListCommand::Insert {
+        key: args.take_string(0)?,
+        before,
+        pivot: take_value(args, 2)?,
+        value: take_value(args, 3)?,
+    })
+}
+
+fn parse_lpos(args: &CommandArgs) -> Result<ListCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let element = take_value(args, 1)?;
+
+    let mut rank = 1;
+    let mut count = None;
+    let mut max_len = 0;
+    let mut index = 2;
+    while index < args.len() {
+        if index + 1 >= args.len() {
+            return Err(CommandParseError::InvalidSyntax);
+        }
+        let value = args.take_int(index + 1)?;
+        match args.take_keyword(index)?.as_str() {
+            "rank" if value == 0 => {
+                return Err(CommandParseError::InvalidArgument(
+                    "RANK can't be zero: use 1 to start from the first match, 2 from the \
+                     second ... or use negative to start from the end of the list"
+                        .into(),
+                ));
+            }
+            "rank" => rank = value,
+            "count" => {
+                let value = usize::try_from(value).map_err(|_| {
+                    CommandParseError::InvalidArgument("COUNT can't be negative".into())
+                })?;
+                count = Some(value);
+            }
+            "maxlen" => {
+                max_len = usize::try_from(value).map_err(|_| {
+                    CommandParseError::InvalidArgument("MAXLEN can't be negative".into())
+                })?;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        index += 2;
+    }
+
+    Ok(ListCommand::Pos {
+        key,
+        element,
+        rank,
+        count,
+        max_len,
+    })
+}
+
+fn not_positive() -> CommandParseError {
+    CommandParseError::InvalidArgument("value is out of range, must be positive".into())
+}
+
+fn take_value(args: &CommandArgs, index: usize) -> Result<Vec<u8>, CommandParseError> {
+    Ok(args.take_bytes(index)?.to_vec())
+}
+
+fn take_values(args: &CommandArgs, start: usize) -> Result<Vec<Vec<u8>>, CommandParseError> {
+    (start..args.len()).map(|i| take_value(args, i)).collect()
+}
+
+fn take_end(args: &CommandArgs, index: usize) -> Result<ListEnd, CommandParseError> {
+    match args.take_keyword(index)?.as_str() {
+        "left" => Ok(ListEnd::Left),
+        "right" => Ok(ListEnd::Right),
+        _ => Err(CommandParseError::InvalidSyntax),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<ListCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::List(command) => Ok(command),
+            other => panic!("expected a list command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_push() {
+        let got = parse(&["RPUSHX", "queue", "a", "b"]).unwrap();
+        let expected = ListCommand::Push {
+            key: "queue".into(),
+            values: vec![b"a".to_vec(), b"b".to_vec()],
+            end: ListEnd::Right,
+            only_existing: true,
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["LPUSH", "queue"]).is_err());
+    }
+
+    #[test]
+    fn test_pop() {
+        let got = parse(&["LPOP", "queue", "3"]).unwrap();
+        let expected = ListCommand::Pop {
+            key: "queue".into(),
+            end: ListEnd::Left,
+            count: Some(3),
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["RPOP", "queue", "-1"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_linsert() {
+        let got = parse(&["LINSERT", "l", "after", "p", "v"]).unwrap();
+        let expected = ListCommand::Insert {
+            key: "l".into(),
+            before: false,
+            pivot: b"p".to_vec(),
+            value: b"v".to_vec(),
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["LINSERT", "l", "around", "p", "v"]).is_err());
+    }
+
+    #[test]
+    fn test_lpos() {
+        let got = parse(&["LPOS", "l", "x", "RANK", "-2", "COUNT", "0", "MAXLEN", "10"]).unwrap();
+        let expected = ListCommand::Pos {
+            key: "l".into(),
+            element: b"x".to_vec(),
+            rank: -2,
+            count: Some(0),
+            max_len: 10,
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["LPOS", "l", "x", "RANK", "0"]).is_err());
+        assert!(parse(&["LPOS", "l", "x", "COUNT"]).is_err());
+    }
+
+    #[test]
+    fn test_blocking() {
+        let got = parse(&["BRPOP", "a", "b", "1.5"]).unwrap();
+        let expected = ListCommand::BlockingPop {
+            keys: vec!["a".into(), "b".into()],
+            end: ListEnd::Right,
+            timeout: Duration::from_millis(1500),
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["BRPOPLPUSH", "a", "b", "0"]).unwrap();
+        let expected = ListCommand::BlockingMove {
+            source: "a".into(),
+            destination: "b".into(),
+            from: ListEnd::Right,
+            to: ListEnd::Left,
+            timeout: Duration::ZERO,
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["BLPOP", "a", "-1"]);
+        assert!(
+            matches!(got, Err(CommandParseError::InvalidArgument(m)) if m == "timeout is negative")
+        );
+        assert!(parse(&["BLMOVE", "a", "b", "LEFT", "UP", "0"]).is_err());
+    }
+}
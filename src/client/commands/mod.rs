@@ -0,0 +1,490 @@
+mod admin;
+mod config;
+mod hashes;
+mod keys;
+mod lists;
+mod pubsub;
+mod scripting;
+mod sets;
+mod sorted_sets;
+mod streams;
+mod strings;
+
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::resp::RespValue;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+pub use admin::{AdminCommand, CommandFilter};
+pub use config::ConfigCommand;
+
+pub use hashes::HashCommand;
+pub use keys::KeyCommand;
+pub use lists::ListCommand;
+pub use pubsub::PubSubCommand;
+pub use scripting::{RestorePolicy, ScriptCommand};
+pub use sets::SetCommand;
+pub use sorted_sets::SortedSetCommand;
+pub use streams::StreamCommand;
+pub use strings::{Expiry, StringCommand};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientCommand {
+    Admin(AdminCommand),
+    ClientGetName,
+    ClientSetName(String),
+    Config(ConfigCommand),
+    Discard,
+    Exec,
+    Hash(HashCommand),
+    // Negotiates the protocol, optionally authenticating and naming the connection
+    Hello {
+        protocol: Option<i64>,
+        auth: Option<(String, String)>,
+        name: Option<String>,
+    },
+    // A command that failed to parse, sent on so its error is replied to in order
+    Invalid(String),
+    Key(KeyCommand),
+    List(ListCommand),
+    Multi,
+    Ping(Option<String>),
+    PubSub(PubSubCommand),
+    // Also sent on behalf of a client that disconnected to release its state
+    Reset,
+    Script(ScriptCommand),
+    Set(SetCommand),
+    SortedSet(SortedSetCommand),
+    Stream(StreamCommand),
+    String(StringCommand),
+    Unwatch,
+    Watch(Vec<String>),
+}
+
+impl ClientCommand {
+    // Whether the command may modify the keyspace, which read-only functions may not do
+    pub fn is_write(&self) -> bool {
+        match self {
+            ClientCommand::Hash(command) => command.is_write(),
+            ClientCommand::Key(command) => command.is_write(),
+            ClientCommand::List(command) => command.is_write(),
+            ClientCommand::Set(command) => command.is_write(),
+            ClientCommand::SortedSet(command) => command.is_write(),
+            ClientCommand::Stream(command) => command.is_write(),
+            ClientCommand::String(command) => command.is_write(),
+            _ => false,
+        }
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("ping", -1, &["fast"], &["connection"]).doc(
+        "connection",
+        "1.0.0",
+        "Returns the server's liveliness response.",
+    ),
+    CommandSpec::new(
+        "hello",
+        -1,
+        &[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ],
+        &["connection"],
+    )
+    .doc("connection", "6.0.0", "Handshakes with the Redis server."),
+    CommandSpec::new("client", -2, &[], &[])
+        .doc(
+            "connection",
+            "2.4.0",
+            "A container for client connection commands.",
+        )
+        .subcommands(&[
+            CommandSpec::new(
+                "client|getname",
+                2,
+                &["noscript", "loading", "stale"],
+                &["connection"],
+            )
+            .doc("connection", "2.6.9", "Returns the name of the connection."),
+            CommandSpec::new(
+                "client|setname",
+                3,
+                &["noscript", "loading", "stale"],
+                &["connection"],
+            )
+            .doc("connection", "2.6.9", "Sets the connection name."),
+        ]),
+    CommandSpec::new(
+        "multi",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        &["transaction"],
+    )
+    .doc("transactions", "1.2.0", "Starts a transaction."),
+    CommandSpec::new(
+        "exec",
+        1,
+        &["noscript", "loading", "stale", "skip_slowlog"],
+        &["transaction"],
+    )
+    .doc(
+        "transactions",
+        "1.2.0",
+        "Executes all commands in a transaction.",
+    ),
+    CommandSpec::new(
+        "discard",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        &["transaction"],
+    )
+    .doc("transactions", "2.0.0", "Discards a transaction."),
+    CommandSpec::new(
+        "watch",
+        -2,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        &["transaction"],
+    )
+    .keys(&[KeySpec::range(1, -1, 1, &["RO"])])
+    .doc(
+        "transactions",
+        "2.2.0",
+        "Monitors changes to keys to determine the execution of a transaction.",
+    ),
+    CommandSpec::new(
+        "unwatch",
+        1,
+        &["noscript", "loading", "stale", "fast", "allow_busy"],
+        &["transaction"],
+    )
+    .doc(
+        "transactions",
+        "2.2.0",
+        "Forgets about watched keys of a transaction.",
+    ),
+    CommandSpec::new(
+        "reset",
+        1,
+        &[
+            "noscript",
+            "loading",
+            "stale",
+            "fast",
+            "no_auth",
+            "allow_busy",
+        ],
+        &["connection"],
+    )
+    .doc("connection", "6.2.0", "Resets the connection."),
+];
+
+// Each family's table, in the order COMMAND lists them
+const TABLES: &[&[CommandSpec]] = &[
+    COMMANDS,
+    admin::COMMANDS,
+    config::COMMANDS,
+    keys::COMMANDS,
+    strings::COMMANDS,
+    hashes::COMMANDS,
+    lists::COMMANDS,
+    sets::COMMANDS,
+    sorted_sets::COMMANDS,
+    streams::COMMANDS,
+    pubsub::COMMANDS,
+    scripting::COMMANDS,
+];
+
+static COMMAND_TABLE: LazyLock<HashMap<&'static str, &'static CommandSpec>> =
+    LazyLock::new(|| command_specs().map(|spec| (spec.name, spec)).collect());
+
+// Every top-level command the server implements
+pub fn command_specs() -> impl Iterator<Item = &'static CommandSpec> {
+    TABLES.iter().flat_map(|table| table.iter())
+}
+
+// Looks up a command by name, or a subcommand by `container|subcommand`, in any case
+pub fn command_spec(name: &str) -> Option<&'static CommandSpec> {
+    let name = name.to_ascii_lowercase();
+    match name.split_once('|') {
+        Some((container, subcommand)) => COMMAND_TABLE.get(container)?.subcommand(subcommand),
+        None => COMMAND_TABLE.get(name.as_str()).copied(),
+    }
+}
+
+impl TryFrom<RespValue> for ClientCommand {
+    type Error = CommandParseError;
+
+    fn try_from(resp: RespValue) -> Result<ClientCommand, Self::Error> {
+        if let RespValue::Array(mut array) = resp {
+            if array.is_empty() {
+                return Err(CommandParseError::InvalidSyntax);
+            }
+
+            // Pull the command name from the head of the argument list
+            let command_name = match array.remove(0) {
+                RespValue::BulkString(bs) => String::from_utf8(bs)
+                    .map_err(|_| CommandParseError::InvalidUtf8)?
+                    .to_ascii_lowercase(),
+                _ => return Err(CommandParseError::InvalidType),
+            };
+
+            let args = CommandArgs::new(&array);
+            let spec = COMMAND_TABLE
+                .get(command_name.as_str())
+                .ok_or_else(|| CommandParseError::UnknownCommand(command_name.clone()))?;
+            check_arity(spec, &args)?;
+
+            // Each command family parses the names it recognizes
+            if let Some(command) = keys::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Key(command));
+            }
+            if let Some(command) = hashes::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Hash(command));
+            }
+            if let Some(command) = lists::parse(&command_name, &args)? {
+                return Ok(ClientCommand::List(command));
+            }
+            if let Some(command) = pubsub::parse(&command_name, &args)? {
+                return Ok(ClientCommand::PubSub(command));
+            }
+            if let Some(command) = scripting::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Script(command));
+            }
+            if let Some(command) = sets::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Set(command));
+            }
+            if let Some(command) = sorted_sets::parse(&command_name, &args)? {
+                return Ok(ClientCommand::SortedSet(command));
+            }
+            if let Some(command) = streams::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Stream(command));
+            }
+            if let Some(command) = strings::parse(&command_name, &args)? {
+                return Ok(ClientCommand::String(command));
+            }
+            if let Some(command) = admin::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Admin(command));
+            }
+            if let Some(command) = config::parse(&command_name, &args)? {
+                return Ok(ClientCommand::Config(command));
+            }
+
+            match command_name.as_str() {
+                // PING [message]
+                "ping" => {
+                    if args.len() > 1 {
+                        return Err(CommandParseError::ArityMismatch(command_name));
+                    }
+                    let message = args.take_opt_string(0)?;
+                    Ok(ClientCommand::Ping(message))
+                }
+                // HELLO [protover [AUTH username password] [SETNAME clientname]]
+                "hello" => parse_hello(&args),
+                // CLIENT GETNAME
+                // CLIENT SETNAME connection-name
+                "client" => {
+                    let subcommand = args.take_keyword(0)?;
+                    match subcommand.as_str() {
+                        "getname" => Ok(ClientCommand::ClientGetName),
+                        "setname" => Ok(ClientCommand::ClientSetName(args.take_string(1)?)),
+                        _ => Err(unknown_subcommand("client", &subcommand)),
+                    }
+                }
+                // MULTI
+                // EXEC
+                // DISCARD
+                // RESET
+                "multi" => Ok(ClientCommand::Multi),
+                "exec" => Ok(ClientCommand::Exec),
+                "discard" => Ok(ClientCommand::Discard),
+                "reset" => Ok(ClientCommand::Reset),
+                // WATCH key [key ...]
+                "watch" => Ok(ClientCommand::Watch(args.take_strings(0)?)),
+                // UNWATCH
+                "unwatch" => Ok(ClientCommand::Unwatch),
+                other => Err(CommandParseError::UnknownCommand(other.to_string())),
+            }
+        } else {
+            Err(CommandParseError::InvalidSyntax)
+        }
+    }
+}
+
+// Checks the number of arguments against the command table, which also turns away
+// unknown subcommands of a container before its family parses it
+fn check_arity(spec: &CommandSpec, args: &CommandArgs) -> Result<(), CommandParseError> {
+    if !spec.accepts(args.len() + 1) {
+        return Err(CommandParseError::ArityMismatch(spec.name.into()));
+    }
+    if spec.subcommands.is_empty() || args.len() == 0 {
+        return Ok(());
+    }
+    let subcommand = args.take_keyword(0)?;
+    let spec = spec
+        .subcommand(&subcommand)
+        .ok_or_else(|| unknown_subcommand(spec.name, &subcommand))?;
+    if !spec.accepts(args.len() + 1) {
+        return Err(CommandParseError::ArityMismatch(spec.name.into()));
+    }
+    Ok(())
+}
+
+fn parse_hello(args: &CommandArgs) -> Result<ClientCommand, CommandParseError> {
+    let protocol = args.take_opt_int(0).map_err(|_| {
+        CommandParseError::InvalidArgument(
+            "Protocol version is not an integer or out of range".into(),
+        )
+    })?;
+    let mut auth = None;
+    let mut name = None;
+    let mut i = 1;
+    while i < args.len() {
+        let option = args.take_keyword(i)?;
+        match option.as_str() {
+            "auth" if i + 2 < args.len() => {
+                auth = Some((args.take_string(i + 1)?, args.take_string(i + 2)?));
+                i += 3;
+            }
+            "setname" if i + 1 < args.len() => {
+                name = Some(args.take_string(i + 1)?);
+                i += 2;
+            }
+            _ => {
+                return Err(CommandParseError::InvalidArgument(format!(
+                    "Syntax error in HELLO option '{}'",
+                    args.take_string(i)?
+                )));
+            }
+        }
+    }
+    Ok(ClientCommand::Hello {
+        protocol,
+        auth,
+        name,
+    })
+}
+
+fn unknown_subcommand(name: &str, subcommand: &str) -> CommandParseError {
+    CommandParseError::InvalidArgument(format!(
+        "unknown subcommand '{subcommand}'. Try {} HELP.",
+        name.to_ascii_uppercase()
+    ))
+}
+
+// Parses a command from its arguments as they would be typed into redis-cli
+#[cfg(test)]
+pub fn parse_args(args: &[&str]) -> Result<ClientCommand, CommandParseError> {
+    let array = args
+        .iter()
+        .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec()))
+        .collect();
+    ClientCommand::try_from(RespValue::Array(array))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping() {
+        assert_eq!(parse_args(&["PING"]).unwrap(), ClientCommand::Ping(None));
+        assert_eq!(
+            parse_args(&["ping", "hello"]).unwrap(),
+            ClientCommand::Ping(Some("hello".into()))
+        );
+        assert!(parse_args(&["PING", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn test_hello() {
+        assert_eq!(
+            parse_args(&["HELLO"]).unwrap(),
+            ClientCommand::Hello {
+                protocol: None,
+                auth: None,
+                name: None,
+            }
+        );
+        assert_eq!(
+            parse_args(&["hello", "3", "SETNAME", "app", "auth", "default", "pw"]).unwrap(),
+            ClientCommand::Hello {
+                protocol: Some(3),
+                auth: Some(("default".into(), "pw".into())),
+                name: Some("app".into()),
+            }
+        );
+        assert!(parse_args(&["HELLO", "three"]).is_err());
+        assert!(parse_args(&["HELLO", "3", "AUTH", "default"]).is_err());
+
+        assert_eq!(
+            parse_args(&["CLIENT", "SETNAME", "app"]).unwrap(),
+            ClientCommand::ClientSetName("app".into())
+        );
+        assert_eq!(
+            parse_args(&["client", "getname"]).unwrap(),
+            ClientCommand::ClientGetName
+        );
+        assert!(parse_args(&["CLIENT", "GETNAME", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_transaction() {
+        assert_eq!(parse_args(&["MULTI"]).unwrap(), ClientCommand::Multi);
+        assert_eq!(parse_args(&["exec"]).unwrap(), ClientCommand::Exec);
+        assert_eq!(parse_args(&["Discard"]).unwrap(), ClientCommand::Discard);
+        assert!(parse_args(&["MULTI", "x"]).is_err());
+        assert_eq!(
+            parse_args(&["WATCH", "a", "b"]).unwrap(),
+            ClientCommand::Watch(vec!["a".into(), "b".into()])
+        );
+        assert!(parse_args(&["WATCH"]).is_err());
+        assert_eq!(parse_args(&["unwatch"]).unwrap(), ClientCommand::Unwatch);
+    }
+
+    #[test]
+    fn test_command_table() {
+        // Every command in the table has a parser behind it
+        for spec in command_specs() {
+            let parsed = parse_args(&[spec.name]);
+            assert!(
+                !matches!(parsed, Err(CommandParseError::UnknownCommand(_))),
+                "{} is not parsed",
+                spec.name
+            );
+        }
+
+        assert!(matches!(
+            parse_args(&["GET"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "get"
+        ));
+        assert!(matches!(
+            parse_args(&["CLIENT", "SETNAME"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "client|setname"
+        ));
+        assert!(matches!(
+            parse_args(&["CLIENT"]),
+            Err(CommandParseError::ArityMismatch(name)) if name == "client"
+        ));
+        assert!(matches!(
+            parse_args(&["XINFO", "BOGUS", "s"]),
+            Err(CommandParseError::InvalidArgument(message))
+                if message == "unknown subcommand 'bogus'. Try XINFO HELP."
+        ));
+        assert_eq!(
+            command_spec("Client|SetName").map(|spec| spec.arity),
+            Some(3)
+        );
+        assert!(command_spec("get|x").is_none());
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let got = parse_args(&["BOGUS", "key"]);
+        assert!(matches!(got, Err(CommandParseError::UnknownCommand(name)) if name == "bogus"));
+    }
+}
@@ -0,0 +1,279 @@
+use super::unknown_subcommand;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+
+// Commands that subscribe to channels or publish messages to them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubSubCommand {
+    // PUBSUB CHANNELS, optionally filtered by a glob pattern
+    Channels(Option<String>),
+    NumPat,
+    NumSub(Vec<String>),
+    PSubscribe(Vec<String>),
+    PUnsubscribe(Vec<String>),
+    Publish { channel: String, message: Vec<u8> },
+    // PUBSUB SHARDCHANNELS, optionally filtered by a glob pattern
+    ShardChannels(Option<String>),
+    ShardNumSub(Vec<String>),
+    SPublish { channel: String, message: Vec<u8> },
+    SSubscribe(Vec<String>),
+    Subscribe(Vec<String>),
+    SUnsubscribe(Vec<String>),
+    // Without channels every subscription of the kind is dropped
+    Unsubscribe(Vec<String>),
+}
+
+impl PubSubCommand {
+    // Whether a client in subscriber mode may send the command
+    pub fn allowed_when_subscribed(&self) -> bool {
+        matches!(
+            self,
+            PubSubCommand::Subscribe(_)
+                | PubSubCommand::PSubscribe(_)
+                | PubSubCommand::Unsubscribe(_)
+                | PubSubCommand::PUnsubscribe(_)
+                | PubSubCommand::SSubscribe(_)
+                | PubSubCommand::SUnsubscribe(_)
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new(
+        "subscribe",
+        -2,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "pubsub",
+        "2.0.0",
+        "Listens for messages published to channels.",
+    ),
+    CommandSpec::new(
+        "psubscribe",
+        -2,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "pubsub",
+        "2.0.0",
+        "Listens for messages published to channels that match one or more patterns.",
+    ),
+    CommandSpec::new(
+        "ssubscribe",
+        -2,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .keys(&[KeySpec::range(1, -1, 1, &["NOT_KEY"])])
+    .doc(
+        "pubsub",
+        "7.0.0",
+        "Listens for messages published to shard channels.",
+    ),
+    CommandSpec::new(
+        "unsubscribe",
+        -1,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "pubsub",
+        "2.0.0",
+        "Stops listening to messages posted to channels.",
+    ),
+    CommandSpec::new(
+        "punsubscribe",
+        -1,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .doc(
+        "pubsub",
+        "2.0.0",
+        "Stops listening to messages published to channels that match one or more patterns.",
+    ),
+    CommandSpec::new(
+        "sunsubscribe",
+        -1,
+        &["pubsub", "noscript", "loading", "stale"],
+        &[],
+    )
+    .keys(&[KeySpec::range(1, -1, 1, &["NOT_KEY"])])
+    .doc(
+        "pubsub",
+        "7.0.0",
+        "Stops listening to messages posted to shard channels.",
+    ),
+    CommandSpec::new("publish", 3, &["pubsub", "loading", "stale", "fast"], &[]).doc(
+        "pubsub",
+        "2.0.0",
+        "Posts a message to a channel.",
+    ),
+    CommandSpec::new("spublish", 3, &["pubsub", "loading", "stale", "fast"], &[])
+        .keys(&[KeySpec::key(1, &["NOT_KEY"])])
+        .doc("pubsub", "7.0.0", "Post a message to a shard channel"),
+    CommandSpec::new("pubsub", -2, &[], &[])
+        .doc("pubsub", "2.8.0", "A container for Pub/Sub commands.")
+        .subcommands(&[
+            CommandSpec::new("pubsub|channels", -2, &["pubsub", "loading", "stale"], &[]).doc(
+                "pubsub",
+                "2.8.0",
+                "Returns the active channels.",
+            ),
+            CommandSpec::new("pubsub|numpat", 2, &["pubsub", "loading", "stale"], &[]).doc(
+                "pubsub",
+                "2.8.0",
+                "Returns a count of unique pattern subscriptions.",
+            ),
+            CommandSpec::new("pubsub|numsub", -2, &["pubsub", "loading", "stale"], &[]).doc(
+                "pubsub",
+                "2.8.0",
+                "Returns a count of subscribers to channels.",
+            ),
+            CommandSpec::new(
+                "pubsub|shardchannels",
+                -2,
+                &["pubsub", "loading", "stale"],
+                &[],
+            )
+            .doc("pubsub", "7.0.0", "Returns the active shard channels."),
+            CommandSpec::new(
+                "pubsub|shardnumsub",
+                -2,
+                &["pubsub", "loading", "stale"],
+                &[],
+            )
+            .doc(
+                "pubsub",
+                "7.0.0",
+                "Returns the count of subscribers of shard channels.",
+            ),
+        ]),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<PubSubCommand>, CommandParseError> {
+    let command = match name {
+        // SUBSCRIBE channel [channel ...]
+        // PSUBSCRIBE pattern [pattern ...]
+        // SSUBSCRIBE shardchannel [shardchannel ...]
+        "subscribe" | "psubscribe" | "ssubscribe" => {
+            let channels = args.take_strings(0)?;
+            match name {
+                "subscribe" => PubSubCommand::Subscribe(channels),
+                "psubscribe" => PubSubCommand::PSubscribe(channels),
+                _ => PubSubCommand::SSubscribe(channels),
+            }
+        }
+        // UNSUBSCRIBE [channel [channel ...]]
+        // PUNSUBSCRIBE [pattern [pattern ...]]
+        // SUNSUBSCRIBE [shardchannel [shardchannel ...]]
+        "unsubscribe" => PubSubCommand::Unsubscribe(args.take_strings(0)?),
+        "punsubscribe" => PubSubCommand::PUnsubscribe(args.take_strings(0)?),
+        "sunsubscribe" => PubSubCommand::SUnsubscribe(args.take_strings(0)?),
+        // PUBLISH channel message
+        // SPUBLISH shardchannel message
+        "publish" | "spublish" => {
+            let channel = args.take_string(0)?;
+            let message = args.take_bytes(1)?.to_vec();
+            if name == "publish" {
+                PubSubCommand::Publish { channel, message }
+            } else {
+                PubSubCommand::SPublish { channel, message }
+            }
+        }
+        "pubsub" => parse_pubsub(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+// PUBSUB CHANNELS [pattern]
+// PUBSUB NUMSUB [channel [channel ...]]
+// PUBSUB NUMPAT
+// PUBSUB SHARDCHANNELS [pattern]
+// PUBSUB SHARDNUMSUB [shardchannel [shardchannel ...]]
+fn parse_pubsub(args: &CommandArgs) -> Result<PubSubCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    let arity_error = || CommandParseError::ArityMismatch(format!("pubsub|{subcommand}"));
+    match subcommand.as_str() {
+        "channels" | "shardchannels" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            let pattern = args.take_opt_string(1)?;
+            if subcommand == "channels" {
+                Ok(PubSubCommand::Channels(pattern))
+            } else {
+                Ok(PubSubCommand::ShardChannels(pattern))
+            }
+        }
+        "numsub" => Ok(PubSubCommand::NumSub(args.take_strings(1)?)),
+        "shardnumsub" => Ok(PubSubCommand::ShardNumSub(args.take_strings(1)?)),
+        "numpat" => Ok(PubSubCommand::NumPat),
+        _ => Err(unknown_subcommand("pubsub", &subcommand)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse_pubsub(args: &[&str]) -> PubSubCommand {
+        match parse_args(args).unwrap() {
+            ClientCommand::PubSub(command) => command,
+            other => panic!("expected a pub/sub command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_subscribe() {
+        assert_eq!(
+            parse_pubsub(&["SUBSCRIBE", "a", "b"]),
+            PubSubCommand::Subscribe(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(
+            parse_pubsub(&["PUNSUBSCRIBE"]),
+            PubSubCommand::PUnsubscribe(Vec::new())
+        );
+        assert_eq!(
+            parse_pubsub(&["ssubscribe", "s"]),
+            PubSubCommand::SSubscribe(vec!["s".into()])
+        );
+        assert!(parse_args(&["PSUBSCRIBE"]).is_err());
+    }
+
+    #[test]
+    fn test_pubsub() {
+        assert_eq!(
+            parse_pubsub(&["PUBLISH", "news", "hi"]),
+            PubSubCommand::Publish {
+                channel: "news".into(),
+                message: b"hi".to_vec(),
+            }
+        );
+        assert_eq!(
+            parse_pubsub(&["PUBSUB", "channels", "n*"]),
+            PubSubCommand::Channels(Some("n*".into()))
+        );
+        assert_eq!(
+            parse_pubsub(&["PUBSUB", "NUMSUB"]),
+            PubSubCommand::NumSub(Vec::new())
+        );
+        assert_eq!(
+            parse_pubsub(&["SPUBLISH", "s", "hi"]),
+            PubSubCommand::SPublish {
+                channel: "s".into(),
+                message: b"hi".to_vec(),
+            }
+        );
+        assert_eq!(
+            parse_pubsub(&["PUBSUB", "SHARDNUMSUB", "s"]),
+            PubSubCommand::ShardNumSub(vec!["s".into()])
+        );
+        assert!(parse_args(&["PUBSUB", "NUMPAT", "x"]).is_err());
+        assert!(parse_args(&["PUBSUB", "BOGUS"]).is_err());
+    }
+}
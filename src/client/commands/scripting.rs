@@ -0,0 +1,439 @@
+use super::unknown_subcommand;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+
+// Commands that run Lua scripts or manage the script cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    Eval {
+        script: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+    },
+    // The SHA1 digest is lowercased so it matches the cache regardless of case
+    EvalSha {
+        sha: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+    },
+    Exists(Vec<String>),
+    // FCALL, or FCALL_RO when `read_only` is set
+    FCall {
+        function: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+        read_only: bool,
+    },
+    Flush,
+    FunctionDelete(String),
+    FunctionDump,
+    FunctionFlush,
+    FunctionList {
+        pattern: Option<String>,
+        with_code: bool,
+    },
+    FunctionLoad {
+        code: String,
+        replace: bool,
+    },
+    FunctionRestore {
+        payload: Vec<u8>,
+        policy: RestorePolicy,
+    },
+    Load(String),
+}
+
+// What FUNCTION RESTORE does with the libraries that already exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestorePolicy {
+    // Fail if any restored library already exists
+    Append,
+    // Delete every library first
+    Flush,
+    // Overwrite libraries of the same name
+    Replace,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new(
+        "eval",
+        -3,
+        &[
+            "noscript",
+            "stale",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+        ],
+        &["scripting"],
+    )
+    .keys(&[KeySpec::keynum(2, &["RW", "ACCESS", "UPDATE"])])
+    .doc("scripting", "2.6.0", "Executes a server-side Lua script."),
+    CommandSpec::new(
+        "evalsha",
+        -3,
+        &[
+            "noscript",
+            "stale",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+        ],
+        &["scripting"],
+    )
+    .keys(&[KeySpec::keynum(2, &["RW", "ACCESS", "UPDATE"])])
+    .doc(
+        "scripting",
+        "2.6.0",
+        "Executes a server-side Lua script by SHA1 digest.",
+    ),
+    CommandSpec::new(
+        "fcall",
+        -3,
+        &[
+            "noscript",
+            "stale",
+            "skip_monitor",
+            "may_replicate",
+            "no_mandatory_keys",
+        ],
+        &["scripting"],
+    )
+    .keys(&[KeySpec::keynum(2, &["RW", "ACCESS", "UPDATE"])])
+    .doc("scripting", "7.0.0", "Invokes a function."),
+    CommandSpec::new(
+        "fcall_ro",
+        -3,
+        &[
+            "noscript",
+            "stale",
+            "skip_monitor",
+            "no_mandatory_keys",
+            "readonly",
+        ],
+        &["scripting"],
+    )
+    .keys(&[KeySpec::keynum(2, &["RO", "ACCESS"])])
+    .doc("scripting", "7.0.0", "Invokes a read-only function."),
+    CommandSpec::new("script", -2, &[], &[])
+        .doc(
+            "scripting",
+            "2.6.0",
+            "A container for Lua scripts management commands.",
+        )
+        .subcommands(&[
+            CommandSpec::new("script|exists", -3, &["noscript"], &["scripting"]).doc(
+                "scripting",
+                "2.6.0",
+                "Determines whether server-side Lua scripts exist in the script cache.",
+            ),
+            CommandSpec::new("script|flush", -2, &["noscript"], &["scripting"]).doc(
+                "scripting",
+                "2.6.0",
+                "Removes all server-side Lua scripts from the script cache.",
+            ),
+            CommandSpec::new("script|load", 3, &["noscript", "stale"], &["scripting"]).doc(
+                "scripting",
+                "2.6.0",
+                "Loads a server-side Lua script to the script cache.",
+            ),
+        ]),
+    CommandSpec::new("function", -2, &[], &[])
+        .doc("scripting", "7.0.0", "A container for function commands.")
+        .subcommands(&[
+            CommandSpec::new("function|delete", 3, &["noscript", "write"], &["scripting"]).doc(
+                "scripting",
+                "7.0.0",
+                "Deletes a library and its functions.",
+            ),
+            CommandSpec::new("function|dump", 2, &["noscript"], &["scripting"]).doc(
+                "scripting",
+                "7.0.0",
+                "Dumps all libraries into a serialized binary payload.",
+            ),
+            CommandSpec::new("function|flush", -2, &["noscript", "write"], &["scripting"]).doc(
+                "scripting",
+                "7.0.0",
+                "Deletes all libraries and functions.",
+            ),
+            CommandSpec::new("function|list", -2, &["noscript"], &["scripting"]).doc(
+                "scripting",
+                "7.0.0",
+                "Returns information about all libraries.",
+            ),
+            CommandSpec::new(
+                "function|load",
+                -3,
+                &["noscript", "write", "denyoom"],
+                &["scripting"],
+            )
+            .doc("scripting", "7.0.0", "Creates a library."),
+            CommandSpec::new(
+                "function|restore",
+                -3,
+                &["noscript", "write", "denyoom"],
+                &["scripting"],
+            )
+            .doc(
+                "scripting",
+                "7.0.0",
+                "Restores all libraries from a payload.",
+            ),
+        ]),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<ScriptCommand>, CommandParseError> {
+    let command = match name {
+        // EVAL script numkeys [key [key ...]] [arg [arg ...]]
+        // EVALSHA sha1 numkeys [key [key ...]] [arg [arg ...]]
+        "eval" | "evalsha" => {
+            let script = args.take_string(0)?;
+            let (keys, args) = parse_keys_and_args(args, 1)?;
+            if name == "eval" {
+                ScriptCommand::Eval { script, keys, args }
+            } else {
+                let sha = script.to_ascii_lowercase();
+                ScriptCommand::EvalSha { sha, keys, args }
+            }
+        }
+        // FCALL function numkeys [key [key ...]] [arg [arg ...]]
+        // FCALL_RO function numkeys [key [key ...]] [arg [arg ...]]
+        "fcall" | "fcall_ro" => {
+            let function = args.take_string(0)?;
+            let (keys, args) = parse_keys_and_args(args, 1)?;
+            ScriptCommand::FCall {
+                function,
+                keys,
+                args,
+                read_only: name == "fcall_ro",
+            }
+        }
+        "function" => parse_function(args)?,
+        "script" => parse_script(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+// Splits the arguments following the key count into the keys and the other arguments
+fn parse_keys_and_args(
+    args: &CommandArgs,
+    start: usize,
+) -> Result<(Vec<String>, Vec<Vec<u8>>), CommandParseError> {
+    let num_keys = args.take_int(start)?;
+    let first_arg = usize::try_from(num_keys)
+        .ok()
+        .and_then(|n| n.checked_add(start + 1))
+        .ok_or_else(|| {
+            CommandParseError::InvalidArgument("Number of keys can't be negative".into())
+        })?;
+    if first_arg > args.len() {
+        return Err(CommandParseError::InvalidArgument(
+            "Number of keys can't be greater than number of args".into(),
+        ));
+    }
+    let keys = (start + 1..first_arg)
+        .map(|i| args.take_string(i))
+        .collect::<Result<_, _>>()?;
+    let args = (first_arg..args.len())
+        .map(|i| Ok(args.take_bytes(i)?.to_vec()))
+        .collect::<Result<_, CommandParseError>>()?;
+    Ok((keys, args))
+}
+
+// SCRIPT EXISTS sha1 [sha1 ...]
+// SCRIPT FLUSH [ASYNC | SYNC]
+// SCRIPT LOAD script
+fn parse_script(args: &CommandArgs) -> Result<ScriptCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    let arity_error = || CommandParseError::ArityMismatch(format!("script|{subcommand}"));
+    match subcommand.as_str() {
+        "exists" => {
+            let shas = args.take_strings(1)?;
+            Ok(ScriptCommand::Exists(
+                shas.iter().map(|sha| sha.to_ascii_lowercase()).collect(),
+            ))
+        }
+        "flush" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            parse_flush_mode(args)?;
+            Ok(ScriptCommand::Flush)
+        }
+        "load" => Ok(ScriptCommand::Load(args.take_string(1)?)),
+        _ => Err(unknown_subcommand("script", &subcommand)),
+    }
+}
+
+// FUNCTION DELETE library-name
+// FUNCTION DUMP
+// FUNCTION FLUSH [ASYNC | SYNC]
+// FUNCTION LIST [LIBRARYNAME library-name-pattern] [WITHCODE]
+// FUNCTION LOAD [REPLACE] function-code
+// FUNCTION RESTORE serialized-value [FLUSH | APPEND | REPLACE]
+fn parse_function(args: &CommandArgs) -> Result<ScriptCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    let arity_error = || CommandParseError::ArityMismatch(format!("function|{subcommand}"));
+    match subcommand.as_str() {
+        "delete" => Ok(ScriptCommand::FunctionDelete(args.take_string(1)?)),
+        "dump" => Ok(ScriptCommand::FunctionDump),
+        "flush" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            parse_flush_mode(args)?;
+            Ok(ScriptCommand::FunctionFlush)
+        }
+        "list" => {
+            let mut pattern = None;
+            let mut with_code = false;
+            let mut i = 1;
+            while i < args.len() {
+                match args.take_keyword(i)?.as_str() {
+                    "withcode" => with_code = true,
+                    "libraryname" if i + 1 < args.len() && pattern.is_none() => {
+                        i += 1;
+                        pattern = Some(args.take_string(i)?);
+                    }
+                    _ => return Err(CommandParseError::InvalidSyntax),
+                }
+                i += 1;
+            }
+            Ok(ScriptCommand::FunctionList { pattern, with_code })
+        }
+        "load" => {
+            let replace = match args.len() {
+                2 => false,
+                3 if args.take_keyword(1)? == "replace" => true,
+                3 => {
+                    return Err(CommandParseError::InvalidArgument(format!(
+                        "Unknown option given: {}",
+                        args.take_string(1)?
+                    )));
+                }
+                _ => return Err(arity_error()),
+            };
+            let code = args.take_string(args.len() - 1)?;
+            Ok(ScriptCommand::FunctionLoad { code, replace })
+        }
+        "restore" => {
+            let policy = match args.len() {
+                2 => RestorePolicy::Append,
+                3 => match args.take_keyword(2)?.as_str() {
+                    "append" => RestorePolicy::Append,
+                    "flush" => RestorePolicy::Flush,
+                    "replace" => RestorePolicy::Replace,
+                    _ => return Err(CommandParseError::InvalidSyntax),
+                },
+                _ => return Err(arity_error()),
+            };
+            let payload = args.take_bytes(1)?.to_vec();
+            Ok(ScriptCommand::FunctionRestore { payload, policy })
+        }
+        _ => Err(unknown_subcommand("function", &subcommand)),
+    }
+}
+
+// Flushing is always synchronous, so the ASYNC or SYNC argument is only validated
+fn parse_flush_mode(args: &CommandArgs) -> Result<(), CommandParseError> {
+    if let Some(mode) = args.take_opt_string(1)?
+        && !mode.eq_ignore_ascii_case("async")
+        && !mode.eq_ignore_ascii_case("sync")
+    {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse_script(args: &[&str]) -> ScriptCommand {
+        match parse_args(args).unwrap() {
+            ClientCommand::Script(command) => command,
+            other => panic!("expected a script command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(
+            parse_script(&["EVAL", "return 1", "1", "k", "a", "b"]),
+            ScriptCommand::Eval {
+                script: "return 1".into(),
+                keys: vec!["k".into()],
+                args: vec![b"a".to_vec(), b"b".to_vec()],
+            }
+        );
+        assert_eq!(
+            parse_script(&["evalsha", "ABC", "0"]),
+            ScriptCommand::EvalSha {
+                sha: "abc".into(),
+                keys: Vec::new(),
+                args: Vec::new(),
+            }
+        );
+        assert!(parse_args(&["EVAL", "return 1", "2", "k"]).is_err());
+        assert!(parse_args(&["EVAL", "return 1", "-1"]).is_err());
+        assert!(parse_args(&["EVAL", "return 1"]).is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        assert_eq!(
+            parse_script(&["SCRIPT", "LOAD", "return 1"]),
+            ScriptCommand::Load("return 1".into())
+        );
+        assert_eq!(
+            parse_script(&["script", "exists", "A", "b"]),
+            ScriptCommand::Exists(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(
+            parse_script(&["SCRIPT", "FLUSH", "async"]),
+            ScriptCommand::Flush
+        );
+        assert!(parse_args(&["SCRIPT", "FLUSH", "later"]).is_err());
+        assert!(parse_args(&["SCRIPT", "EXISTS"]).is_err());
+        assert!(parse_args(&["SCRIPT", "BOGUS"]).is_err());
+    }
+
+    #[test]
+    fn test_function() {
+        assert_eq!(
+            parse_script(&["FCALL_RO", "f", "1", "k", "a"]),
+            ScriptCommand::FCall {
+                function: "f".into(),
+                keys: vec!["k".into()],
+                args: vec![b"a".to_vec()],
+                read_only: true,
+            }
+        );
+        assert_eq!(
+            parse_script(&["FUNCTION", "LOAD", "REPLACE", "code"]),
+            ScriptCommand::FunctionLoad {
+                code: "code".into(),
+                replace: true,
+            }
+        );
+        assert_eq!(
+            parse_script(&["function", "list", "withcode", "LIBRARYNAME", "my*"]),
+            ScriptCommand::FunctionList {
+                pattern: Some("my*".into()),
+                with_code: true,
+            }
+        );
+        assert_eq!(
+            parse_script(&["FUNCTION", "RESTORE", "payload", "FLUSH"]),
+            ScriptCommand::FunctionRestore {
+                payload: b"payload".to_vec(),
+                policy: RestorePolicy::Flush,
+            }
+        );
+        assert!(parse_args(&["FUNCTION", "LOAD", "NOW", "code"]).is_err());
+        assert!(parse_args(&["FUNCTION", "LIST", "LIBRARYNAME"]).is_err());
+        assert!(parse_args(&["FUNCTION", "DELETE"]).is_err());
+        assert!(parse_args(&["FCALL", "f"]).is_err());
+    }
+}
@@ -0,0 +1,403 @@
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::SetOp;
+
+// Commands that operate on set values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetCommand {
+    Add {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    Card(String),
+    // SUNION, SINTER and SDIFF
+    Combine {
+        op: SetOp,
+        keys: Vec<String>,
+    },
+    // SUNIONSTORE, SINTERSTORE and SDIFFSTORE
+    CombineStore {
+        op: SetOp,
+        destination: String,
+        keys: Vec<String>,
+    },
+    InterCard {
+        keys: Vec<String>,
+        limit: Option<usize>,
+    },
+    IsMember {
+        key: String,
+        member: Vec<u8>,
+    },
+    MIsMember {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    Members(String),
+    Move {
+        source: String,
+        destination: String,
+        member: Vec<u8>,
+    },
+    // Without a count a single member is popped and replied as a bulk string
+    Pop {
+        key: String,
+        count: Option<usize>,
+    },
+    RandMember {
+        key: String,
+        count: Option<i64>,
+    },
+    Rem {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    Scan {
+        key: String,
+        cursor: u64,
+        pattern: Option<Vec<u8>>,
+        count: Option<usize>,
+    },
+}
+
+impl SetCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            SetCommand::Add { .. }
+                | SetCommand::CombineStore { .. }
+                | SetCommand::Move { .. }
+                | SetCommand::Pop { .. }
+                | SetCommand::Rem { .. }
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("sadd", -3, &["write", "denyoom", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc(
+            "set",
+            "1.0.0",
+            "Adds one or more members to a set. Creates the key if it doesn't exist.",
+        ),
+    CommandSpec::new("srem", -3, &["write", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc(
+            "set",
+            "1.0.0",
+            "Removes one or more members from a set. Deletes the set if the last member was removed.",
+        ),
+    CommandSpec::new("smismember", -3, &["readonly", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc(
+            "set",
+            "6.2.0",
+            "Determines whether multiple members belong to a set.",
+        ),
+    CommandSpec::new("smembers", 2, &["readonly"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("set", "1.0.0", "Returns all members of a set."),
+    CommandSpec::new("scard", 2, &["readonly", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("set", "1.0.0", "Returns the number of members in a set."),
+    CommandSpec::new("sismember", 3, &["readonly", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("set", "1.0.0", "Determines whether a member belongs to a set."),
+    CommandSpec::new("spop", -2, &["write", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "DELETE"])])
+        .doc(
+            "set",
+            "1.0.0",
+            "Returns one or more random members from a set after removing them. Deletes the set if the last member was popped.",
+        ),
+    CommandSpec::new("srandmember", -2, &["readonly"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("set", "1.0.0", "Get one or multiple random members from a set"),
+    CommandSpec::new("smove", 4, &["write", "fast"], &["set"])
+        .keys(&[
+            KeySpec::key(1, &["RW", "ACCESS", "DELETE"]),
+            KeySpec::key(2, &["RW", "INSERT"]),
+        ])
+        .doc("set", "1.0.0", "Moves a member from one set to another."),
+    CommandSpec::new("sunion", -2, &["readonly"], &["set"])
+        .keys(&[KeySpec::range(1, -1, 1, &["RO", "ACCESS"])])
+        .doc("set", "1.0.0", "Returns the union of multiple sets."),
+    CommandSpec::new("sinter", -2, &["readonly"], &["set"])
+        .keys(&[KeySpec::range(1, -1, 1, &["RO", "ACCESS"])])
+        .doc("set", "1.0.0", "Returns the intersect of multiple sets."),
+    CommandSpec::new("sdiff", -2, &["readonly"], &["set"])
+        .keys(&[KeySpec::range(1, -1, 1, &["RO", "ACCESS"])])
+        .doc("set", "1.0.0", "Returns the difference of multiple sets."),
+    CommandSpec::new("sunionstore", -3, &["write", "denyoom"], &["set"])
+        .keys(&[
+            KeySpec::key(1, &["OW", "UPDATE"]),
+            KeySpec::range(2, -1, 1, &["RO", "ACCESS"]),
+        ])
+        .doc("set", "1.0.0", "Stores the union of multiple sets in a key."),
+    CommandSpec::new("sinterstore", -3, &["write", "denyoom"], &["set"])
+        .keys(&[
+            KeySpec::key(1, &["OW", "UPDATE"]),
+            KeySpec::range(2, -1, 1, &["RO", "ACCESS"]),
+        ])
+        .doc("set", "1.0.0", "Stores the intersect of multiple sets in a key."),
+    CommandSpec::new("sdiffstore", -3, &["write", "denyoom"], &["set"])
+        .keys(&[
+            KeySpec::key(1, &["OW", "UPDATE"]),
+            KeySpec::range(2, -1, 1, &["RO", "ACCESS"]),
+        ])
+        .doc("set", "1.0.0", "Stores the difference of multiple sets in a key."),
+    CommandSpec::new("sintercard", -3, &["readonly"], &["set"])
+        .keys(&[KeySpec::keynum(1, &["RO", "ACCESS"])])
+        .doc(
+            "set",
+            "7.0.0",
+            "Returns the number of members of the intersect of multiple sets.",
+        ),
+    CommandSpec::new("sscan", -3, &["readonly"], &["set"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("set", "2.8.0", "Iterates over members of a set."),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<SetCommand>, CommandParseError> {
+    let arity_error = || CommandParseError::ArityMismatch(name.into());
+    let command = match name {
+        // SADD key member [member ...]
+        // SREM key member [member ...]
+        // SMISMEMBER key member [member ...]
+        "sadd" | "srem" | "smismember" => {
+            let key = args.take_string(0)?;
+            let members = take_members(args, 1)?;
+            match name {
+                "sadd" => SetCommand::Add { key, members },
+                "srem" => SetCommand::Rem { key, members },
+                _ => SetCommand::MIsMember { key, members },
+            }
+        }
+        // SMEMBERS key
+        // SCARD key
+        "smembers" | "scard" => {
+            let key = args.take_string(0)?;
+            if name == "smembers" {
+                SetCommand::Members(key)
+            } else {
+                SetCommand::Card(key)
+            }
+        }
+        // SISMEMBER key member
+        "sismember" => SetCommand::IsMember {
+            key: args.take_string(0)?,
+            member: take_member(args, 1)?,
+        },
+        // SPOP key [count]
+        "spop" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            let count = match args.take_opt_int(1)? {
+                Some(count) => Some(usize::try_from(count).map_err(|_| not_positive())?),
+                None => None,
+            };
+            SetCommand::Pop {
+                key: args.take_string(0)?,
+                count,
+            }
+        }
+        // SRANDMEMBER key [count]
+        "srandmember" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            SetCommand::RandMember {
+                key: args.take_string(0)?,
+                count: args.take_opt_int(1)?,
+            }
+        }
+        // SMOVE source destination member
+        "smove" => SetCommand::Move {
+            source: args.take_string(0)?,
+            destination: args.take_string(1)?,
+            member: take_member(args, 2)?,
+        },
+        // SUNION key [key ...]
+        // SINTER key [key ...]
+        // SDIFF key [key ...]
+        "sunion" | "sinter" | "sdiff" => SetCommand::Combine {
+            op: set_op(name),
+            keys: args.take_strings(0)?,
+        },
+        // SUNIONSTORE destination key [key ...]
+        // SINTERSTORE destination key [key ...]
+        // SDIFFSTORE destination key [key ...]
+        "sunionstore" | "sinterstore" | "sdiffstore" => SetCommand::CombineStore {
+            op: set_op(name),
+            destination: args.take_string(0)?,
+            keys: args.take_strings(1)?,
+        },
+        // SINTERCARD numkeys key [key ...] [LIMIT limit]
+        "sintercard" => parse_sintercard(args)?,
+        // SSCAN key cursor [MATCH pattern] [COUNT count]
+        "sscan" => parse_sscan(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn set_op(name: &str) -> SetOp {
+    if name.starts_with("sunion") {
+        SetOp::Union
+    } else if name.starts_with("sinter") {
+        SetOp::Intersect
+    } else {
+        SetOp::Difference
+    }
+}
+
+fn not_positive() -> CommandParseError {
+    CommandParseError::InvalidArgument("value is out of range, must be positive".into())
+}
+
+fn take_member(args: &CommandArgs, index: usize) -> Result<Vec<u8>, CommandParseError> {
+    Ok(args.take_bytes(index)?.to_vec())
+}
+
+fn take_members(args: &CommandArgs, start: usize) -> Result<Vec<Vec<u8>>, CommandParseError> {
+    (start..args.len()).map(|i| take_member(args, i)).collect()
+}
+
+fn parse_sintercard(args: &CommandArgs) -> Result<SetCommand, CommandParseError> {
+    let num_keys = usize::try_from(args.take_int(0)?).unwrap_or(0);
+    if num_keys == 0 {
+        return Err(CommandParseError::InvalidArgument(
+            "numkeys should be greater than 0".into(),
+        ));
+    }
+    if num_keys > args.len() - 1 {
+        return Err(CommandParseError::InvalidArgument(
+            "Number of keys can't be greater than number of args".into(),
+        ));
+    }
+    let keys = (1..=num_keys)
+        .map(|i| args.take_string(i))
+        .collect::<Result<_, _>>()?;
+
+    // A limit of zero means no limit
+    let mut limit = None;
+    let mut index = num_keys + 1;
+    while index < args.len() {
+        match args.take_keyword(index)?.as_str() {
+            "limit" if index + 1 < args.len() => {
+                let value = usize::try_from(args.take_int(index + 1)?).map_err(|_| {
+                    CommandParseError::InvalidArgument("LIMIT can't be negative".into())
+                })?;
+                limit = (value > 0).then_some(value);
+                index += 2;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+    }
+
+    Ok(SetCommand::InterCard { keys, limit })
+}
+
+fn parse_sscan(args: &CommandArgs) -> Result<SetCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let cursor = u64::try_from(args.take_int(1)?).map_err(|_| CommandParseError::InvalidCursor)?;
+
+    let mut pattern = None;
+    let mut count = None;
+    let mut index = 2;
+    while index < args.len() {
+        let option = args.take_keyword(index)?;
+        match option.as_str() {
+            "match" if index + 1 < args.len() => {
+                pattern = Some(take_member(args, index + 1)?);
+            }
+            "count" if index + 1 < args.len() => {
+                let value = usize::try_from(args.take_int(index + 1)?)
+                    .map_err(|_| CommandParseError::InvalidSyntax)?;
+                if value == 0 {
+                    return Err(CommandParseError::InvalidSyntax);
+                }
+                count = Some(value);
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        index += 2;
+    }
+
+    Ok(SetCommand::Scan {
+        key,
+        cursor,
+        pattern,
+        count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<SetCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Set(command) => Ok(command),
+            other => panic!("expected a set command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_sadd() {
+        let got = parse(&["SADD", "s", "a", "b"]).unwrap();
+        let expected = SetCommand::Add {
+            key: "s".into(),
+            members: vec![b"a".to_vec(), b"b".to_vec()],
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["SADD", "s"]).is_err());
+    }
+
+    #[test]
+    fn test_combine() {
+        let got = parse(&["SDIFFSTORE", "dst", "a", "b"]).unwrap();
+        let expected = SetCommand::CombineStore {
+            op: SetOp::Difference,
+            destination: "dst".into(),
+            keys: vec!["a".into(), "b".into()],
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["SINTER", "a"]).unwrap();
+        let expected = SetCommand::Combine {
+            op: SetOp::Intersect,
+            keys: vec!["a".into()],
+        };
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn test_spop() {
+        let got = parse(&["SPOP", "s", "-1"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidArgument(_))));
+        let got = parse(&["SRANDMEMBER", "s", "-3"]).unwrap();
+        let expected = SetCommand::RandMember {
+            key: "s".into(),
+            count: Some(-3),
+        };
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn test_sintercard() {
+        let got = parse(&["SINTERCARD", "2", "a", "b", "LIMIT", "5"]).unwrap();
+        let expected = SetCommand::InterCard {
+            keys: vec!["a".into(), "b".into()],
+            limit: Some(5),
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["SINTERCARD", "0", "a"]).is_err());
+        assert!(parse(&["SINTERCARD", "3", "a", "b"]).is_err());
+        assert!(parse(&["SINTERCARD", "1", "a", "LIMIT", "-1"]).is_err());
+        assert!(parse(&["SINTERCARD", "1", "a", "b"]).is_err());
+    }
+}
@@ -0,0 +1,566 @@
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::{
+    LexBound, ScoreBound, ScoreEnd, ScoreUpdate, SetCondition, ZAddOptions, ZRange, ZRangeBy,
+};
+use std::time::Duration;
+
+// Commands that operate on sorted set values
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortedSetCommand {
+    // ZADD, where `changed` (CH) also counts updated scores and `increment` (INCR)
+    // behaves like ZINCRBY for its single member
+    Add {
+        key: String,
+        members: Vec<(f64, Vec<u8>)>,
+        options: ZAddOptions,
+        changed: bool,
+        increment: bool,
+    },
+    BlockingPop {
+        keys: Vec<String>,
+        end: ScoreEnd,
+        timeout: Duration,
+    },
+    Card(String),
+    // ZCOUNT and ZLEXCOUNT
+    Count {
+        key: String,
+        range: ZRangeBy,
+    },
+    IncrBy {
+        key: String,
+        member: Vec<u8>,
+        delta: f64,
+    },
+    MScore {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    // Without a count a single member is popped, still replied as an array
+    Pop {
+        key: String,
+        end: ScoreEnd,
+        count: Option<usize>,
+    },
+    Range {
+        key: String,
+        range: ZRange,
+        with_scores: bool,
+    },
+    Rank {
+        key: String,
+        member: Vec<u8>,
+        reverse: bool,
+        with_score: bool,
+    },
+    Rem {
+        key: String,
+        members: Vec<Vec<u8>>,
+    },
+    // ZREMRANGEBYRANK, ZREMRANGEBYSCORE and ZREMRANGEBYLEX
+    RemRange {
+        key: String,
+        range: ZRangeBy,
+    },
+    Score {
+        key: String,
+        member: Vec<u8>,
+    },
+}
+
+impl SortedSetCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            SortedSetCommand::Add { .. }
+                | SortedSetCommand::BlockingPop { .. }
+                | SortedSetCommand::IncrBy { .. }
+                | SortedSetCommand::Pop { .. }
+                | SortedSetCommand::Rem { .. }
+                | SortedSetCommand::RemRange { .. }
+        )
+    }
+}
+
+// How the start and stop arguments of a range are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("zadd", -4, &["write", "denyoom", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("sorted-set", "1.2.0", "Adds one or more members to a sorted set, or updates their scores. Creates the key if it doesn't exist."),
+    CommandSpec::new("zincrby", 4, &["write", "denyoom", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "UPDATE"])])
+        .doc("sorted-set", "1.2.0", "Increments the score of a member in a sorted set."),
+    CommandSpec::new("zscore", 3, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "1.2.0", "Returns the score of a member in a sorted set."),
+    CommandSpec::new("zmscore", -3, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "6.2.0", "Returns the score of one or more members in a sorted set."),
+    CommandSpec::new("zrem", -3, &["write", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("sorted-set", "1.2.0", "Removes one or more members from a sorted set. Deletes the sorted set if all members were removed."),
+    CommandSpec::new("zcard", 2, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("sorted-set", "1.2.0", "Returns the number of members in a sorted set."),
+    CommandSpec::new("zrank", -3, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("sorted-set", "2.0.0", "Returns the index of a member in a sorted set ordered by ascending scores."),
+    CommandSpec::new("zrevrank", -3, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("sorted-set", "2.0.0", "Returns the index of a member in a sorted set ordered by descending scores."),
+    CommandSpec::new("zcount", 4, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("sorted-set", "2.0.0", "Returns the count of members in a sorted set that have scores within a range."),
+    CommandSpec::new("zlexcount", 4, &["readonly", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("sorted-set", "2.8.9", "Returns the number of members in a sorted set within a lexicographical range."),
+    CommandSpec::new("zremrangebyrank", 4, &["write"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("sorted-set", "2.0.0", "Removes members in a sorted set within a range of indexes. Deletes the sorted set if all members were removed."),
+    CommandSpec::new("zremrangebyscore", 4, &["write"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("sorted-set", "1.2.0", "Removes members in a sorted set within a range of scores. Deletes the sorted set if all members were removed."),
+    CommandSpec::new("zremrangebylex", 4, &["write"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("sorted-set", "2.8.9", "Removes members in a sorted set within a lexicographical range. Deletes the sorted set if all members were removed."),
+    CommandSpec::new("zrange", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "1.2.0", "Returns members in a sorted set within a range of indexes."),
+    CommandSpec::new("zrevrange", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "1.2.0", "Returns members in a sorted set within a range of indexes in reverse order."),
+    CommandSpec::new("zrangebyscore", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "1.0.5", "Returns members in a sorted set within a range of scores."),
+    CommandSpec::new("zrevrangebyscore", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "2.2.0", "Returns members in a sorted set within a range of scores in reverse order."),
+    CommandSpec::new("zrangebylex", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "2.8.9", "Returns members in a sorted set within a lexicographical range."),
+    CommandSpec::new("zrevrangebylex", -4, &["readonly"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("sorted-set", "2.8.9", "Returns members in a sorted set within a lexicographical range in reverse order."),
+    CommandSpec::new("zpopmin", -2, &["write", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "DELETE"])])
+        .doc("sorted-set", "5.0.0", "Returns the lowest-scoring members from a sorted set after removing them. Deletes the sorted set if the last member was popped."),
+    CommandSpec::new("zpopmax", -2, &["write", "fast"], &["sortedset"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "DELETE"])])
+        .doc("sorted-set", "5.0.0", "Returns the highest-scoring members from a sorted set after removing them. Deletes the sorted set if the last member was popped."),
+    CommandSpec::new("bzpopmin", -3, &["write", "fast", "blocking"], &["sortedset"])
+        .keys(&[KeySpec::range(1, -2, 1, &["RW", "ACCESS", "DELETE"])])
+        .doc("sorted-set", "5.0.0", "Removes and returns the member with the lowest score from one or more sorted sets. Blocks until a member is available otherwise. Deletes the sorted set if the last element was popped."),
+    CommandSpec::new("bzpopmax", -3, &["write", "fast", "blocking"], &["sortedset"])
+        .keys(&[KeySpec::range(1, -2, 1, &["RW", "ACCESS", "DELETE"])])
+        .doc("sorted-set", "5.0.0", "Removes and returns the member with the highest score from one or more sorted sets. Blocks until a member is available otherwise. Deletes the sorted set if the last element was popped."),
+];
+
+pub fn parse(
+    name: &str,
+    args: &CommandArgs,
+) -> Result<Option<SortedSetCommand>, CommandParseError> {
+    let arity_error = || CommandParseError::ArityMismatch(name.into());
+    let command = match name {
+        // ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+        "zadd" => parse_zadd(args)?,
+        // ZINCRBY key increment member
+        "zincrby" => SortedSetCommand::IncrBy {
+            key: args.take_string(0)?,
+            delta: args.take_float(1)?,
+            member: take_member(args, 2)?,
+        },
+        // ZSCORE key member
+        "zscore" => SortedSetCommand::Score {
+            key: args.take_string(0)?,
+            member: take_member(args, 1)?,
+        },
+        // ZMSCORE key member [member ...]
+        // ZREM key member [member ...]
+        "zmscore" | "zrem" => {
+            let key = args.take_string(0)?;
+            let members = (1..args.len())
+                .map(|i| take_member(args, i))
+                .collect::<Result<_, _>>()?;
+            if name == "zmscore" {
+                SortedSetCommand::MScore { key, members }
+            } else {
+                SortedSetCommand::Rem { key, members }
+            }
+        }
+        // ZCARD key
+        "zcard" => SortedSetCommand::Card(args.take_string(0)?),
+        // ZRANK key member [WITHSCORE]
+        // ZREVRANK key member [WITHSCORE]
+        "zrank" | "zrevrank" => {
+            if args.len() > 3 {
+                return Err(arity_error());
+            }
+            parse_zrank(name, args)?
+        }
+        // ZCOUNT key min max
+        // ZLEXCOUNT key min max
+        // ZREMRANGEBYRANK key start stop
+        // ZREMRANGEBYSCORE key min max
+        // ZREMRANGEBYLEX key min max
+        "zcount" | "zlexcount" | "zremrangebyrank" | "zremrangebyscore" | "zremrangebylex" => {
+            let key = args.take_string(0)?;
+            let range = take_range(args, range_kind(name), 1, 2)?;
+            if name.starts_with("zrem") {
+                SortedSetCommand::RemRange { key, range }
+            } else {
+                SortedSetCommand::Count { key, range }
+            }
+        }
+        // ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+        // ZREVRANGE key start stop [WITHSCORES]
+        // ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+        // ZREVRANGEBYSCORE key max min [WITHSCORES] [LIMIT offset count]
+        // ZRANGEBYLEX key min max [LIMIT offset count]
+        // ZREVRANGEBYLEX key max min [LIMIT offset count]
+        "zrange" | "zrevrange" | "zrangebyscore" | "zrevrangebyscore" | "zrangebylex"
+        | "zrevrangebylex" => parse_zrange(name, args)?,
+        // ZPOPMIN key [count]
+        // ZPOPMAX key [count]
+        "zpopmin" | "zpopmax" => {
+            if args.len() > 2 {
+                return Err(arity_error());
+            }
+            let count = match args.take_opt_int(1)? {
+                Some(count) => Some(usize::try_from(count).map_err(|_| not_positive())?),
+                None => None,
+            };
+            SortedSetCommand::Pop {
+                key: args.take_string(0)?,
+                end: pop_end(name),
+                count,
+            }
+        }
+        // BZPOPMIN key [key ...] timeout
+        // BZPOPMAX key [key ...] timeout
+        "bzpopmin" | "bzpopmax" => SortedSetCommand::BlockingPop {
+            keys: args.take_strings(0)?[..args.len() - 1].to_vec(),
+            end: pop_end(name),
+            timeout: args.take_timeout(args.len() - 1)?,
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn range_kind(name: &str) -> RangeKind {
+    if name.contains("lex") {
+        RangeKind::Lex
+    } else if name.ends_with("rank") {
+        RangeKind::Rank
+    } else {
+        RangeKind::Score
+    }
+}
+
+fn pop_end(name: &str) -> ScoreEnd {
+    if name.ends_with("min") {
+        ScoreEnd::Min
+    } else {
+        ScoreEnd::Max
+    }
+}
+
+fn not_positive() -> CommandParseError {
+    CommandParseError::InvalidArgument("value is out of range, must be positive".into())
+}
+
+fn take_member(args: &CommandArgs, index: usize) -> Result<Vec<u8>, CommandParseError> {
+    Ok(args.take_bytes(index)?.to_vec())
+}
+
+fn parse_zrank(name: &str, args: &CommandArgs) -> Result<SortedSetCommand, CommandParseError> {
+    let with_score = args.len() == 3;
+    if with_score && args.take_keyword(2)? != "withscore" {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    Ok(SortedSetCommand::Rank {
+        key: args.take_string(0)?,
+        member: take_member(args, 1)?,
+        reverse: name == "zrevrank",
+        with_score,
+    })
+}
+
+fn parse_zadd(args: &CommandArgs) -> Result<SortedSetCommand, CommandParseError> {
+    // Flags come before the first score
+    let mut flags = Vec::new();
+    let mut index = 1;
+    while index < args.len() {
+        let flag = args.take_keyword(index)?;
+        if !matches!(flag.as_str(), "nx" | "xx" | "gt" | "lt" | "ch" | "incr") {
+            break;
+        }
+        flags.push(flag);
+        index += 1;
+    }
+    let has = |flag: &str| flags.iter().any(|f| f == flag);
+
+    let pairs = args.len() - index;
+    if pairs == 0 || !pairs.is_multiple_of(2) {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    if has("nx") && has("xx") {
+        return Err(CommandParseError::IncompatibleOptions(
+            "XX and NX options at the same time are not compatible".into(),
+        ));
+    }
+    if (has("gt") && has("lt")) || (has("nx") && (has("gt") || has("lt"))) {
+        return Err(CommandParseError::IncompatibleOptions(
+            "GT, LT, and/or NX options at the same time are not compatible".into(),
+        ));
+    }
+    let increment = has("incr");
+    if increment && pairs > 2 {
+        return Err(CommandParseError::IncompatibleOptions(
+            "INCR option supports a single increment-element pair".into(),
+        ));
+    }
+
+    let options = ZAddOptions {
+        condition: if has("nx") {
+            SetCondition::IfNotExists
+        } else if has("xx") {
+            SetCondition::IfExists
+        } else {
+            SetCondition::Always
+        },
+        update: if has("gt") {
+            ScoreUpdate::IfGreater
+        } else if has("lt") {
+            ScoreUpdate::IfLess
+        } else {
+            ScoreUpdate::Always
+        },
+    };
+    let members = (index..args.len())
+        .step_by(2)
+        .map(|i| Ok((args.take_float(i)?, take_member(args, i + 1)?)))
+        .collect::<Result<_, CommandParseError>>()?;
+    Ok(SortedSetCommand::Add {
+        key: args.take_string(0)?,
+        members,
+        options,
+        changed: has("ch"),
+        increment,
+    })
+}
+
+fn parse_zrange(name: &str, args: &CommandArgs) -> Result<SortedSetCommand, CommandParseError> {
+    let mut kind = match name {
+        "zrangebyscore" | "zrevrangebyscore" => RangeKind::Score,
+        "zrangebylex" | "zrevrangebylex" => RangeKind::Lex,
+        _ => RangeKind::Rank,
+    };
+    let mut reverse = name.starts_with("zrev");
+    let mut with_scores = false;
+    let mut limit = None;
+
+    let mut index = 3;
+    while index < args.len() {
+        match args.take_keyword(index)?.as_str() {
+            "byscore" if name == "zrange" => kind = RangeKind::Score,
+            "bylex" if name == "zrange" => kind = RangeKind::Lex,
+            "rev" if name == "zrange" => reverse = true,
+            "withscores" => with_scores = true,
+            "limit" if name != "zrevrange" && index + 2 < args.len() => {
+                limit = Some((args.take_int(index + 1)?, args.take_int(index + 2)?));
+                index += 2;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        index += 1;
+    }
+
+    if limit.is_some() && kind == RangeKind::Rank {
+        return Err(CommandParseError::InvalidArgument(
+            "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+                .into(),
+        ));
+    }
+    if with_scores && kind == RangeKind::Lex {
+        return Err(CommandParseError::InvalidArgument(
+            "syntax error, WITHSCORES not supported in combination with BYLEX".into(),
+        ));
+    }
+
+    // Reversed score and lex ranges are given from max to min
+    let (min, max) = if reverse && kind != RangeKind::Rank {
+        (2, 1)
+    } else {
+        (1, 2)
+    };
+    let mut range = ZRange::new(take_range(args, kind, min, max)?);
+    range.reverse = reverse;
+    if let Some((offset, count)) = limit {
+        // A negative offset selects nothing and a negative count selects the rest
+        match usize::try_from(offset) {
+            Ok(offset) => {
+                range.offset = offset;
+                range.count = usize::try_from(count).ok();
+            }
+            Err(_) => range.count = Some(0),
+        }
+    }
+
+    Ok(SortedSetCommand::Range {
+        key: args.take_string(0)?,
+        range,
+        with_scores,
+    })
+}
+
+fn take_range(
+    args: &CommandArgs,
+    kind: RangeKind,
+    min: usize,
+    max: usize,
+) -> Result<ZRangeBy, CommandParseError> {
+    Ok(match kind {
+        RangeKind::Rank => ZRangeBy::Rank {
+            start: args.take_int(min)?,
+            stop: args.take_int(max)?,
+        },
+        RangeKind::Score => ZRangeBy::Score {
+            min: parse_score_bound(args.take_bytes(min)?)?,
+            max: parse_score_bound(args.take_bytes(max)?)?,
+        },
+        RangeKind::Lex => ZRangeBy::Lex {
+            min: parse_lex_bound(args.take_bytes(min)?)?,
+            max: parse_lex_bound(args.take_bytes(max)?)?,
+        },
+    })
+}
+
+// Parses a score bound, exclusive when prefixed with `(`
+fn parse_score_bound(bytes: &[u8]) -> Result<ScoreBound, CommandParseError> {
+    let invalid = || CommandParseError::InvalidArgument("min or max is not a float".into());
+    let (exclusive, number) = match bytes.strip_prefix(b"(") {
+        Some(rest) => (true, rest),
+        None => (false, bytes),
+    };
+    let score = std::str::from_utf8(number)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| !f.is_nan())
+        .ok_or_else(invalid)?;
+    Ok(if exclusive {
+        ScoreBound::Exclusive(score)
+    } else {
+        ScoreBound::Inclusive(score)
+    })
+}
+
+// Parses a lex bound written `[member`, `(member`, `-` or `+`
+fn parse_lex_bound(bytes: &[u8]) -> Result<LexBound, CommandParseError> {
+    match bytes.split_first() {
+        Some((b'-', [])) => Ok(LexBound::Min),
+        Some((b'+', [])) => Ok(LexBound::Max),
+        Some((b'[', rest)) => Ok(LexBound::Inclusive(rest.to_vec())),
+        Some((b'(', rest)) => Ok(LexBound::Exclusive(rest.to_vec())),
+        _ => Err(CommandParseError::InvalidArgument(
+            "min or max not valid string range item".into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<SortedSetCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::SortedSet(command) => Ok(command),
+            other => panic!("expected a sorted set command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_zadd() {
+        let got = parse(&["ZADD", "z", "XX", "GT", "CH", "1.5", "a", "-inf", "b"]).unwrap();
+        let expected = SortedSetCommand::Add {
+            key: "z".into(),
+            members: vec![(1.5, b"a".to_vec()), (f64::NEG_INFINITY, b"b".to_vec())],
+            options: ZAddOptions {
+                condition: SetCondition::IfExists,
+                update: ScoreUpdate::IfGreater,
+            },
+            changed: true,
+            increment: false,
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["ZADD", "z", "NX", "XX", "1", "a"]).is_err());
+        assert!(parse(&["ZADD", "z", "NX", "GT", "1", "a"]).is_err());
+        assert!(parse(&["ZADD", "z", "INCR", "1", "a", "2", "b"]).is_err());
+        assert!(parse(&["ZADD", "z", "1", "a", "2"]).is_err());
+        let got = parse(&["ZADD", "z", "nan", "a"]);
+        assert!(matches!(got, Err(CommandParseError::NotFloat)));
+    }
+
+    #[test]
+    fn test_zrange() {
+        let got = parse(&[
+            "ZRANGE", "z", "(5", "-inf", "BYSCORE", "REV", "LIMIT", "1", "2",
+        ]);
+        let expected = SortedSetCommand::Range {
+            key: "z".into(),
+            range: ZRange {
+                by: ZRangeBy::Score {
+                    min: ScoreBound::Inclusive(f64::NEG_INFINITY),
+                    max: ScoreBound::Exclusive(5.0),
+                },
+                reverse: true,
+                offset: 1,
+                count: Some(2),
+            },
+            with_scores: false,
+        };
+        assert_eq!(got.unwrap(), expected);
+
+        let got = parse(&["ZREVRANGEBYLEX", "z", "+", "[b"]).unwrap();
+        let expected = SortedSetCommand::Range {
+            key: "z".into(),
+            range: ZRange {
+                by: ZRangeBy::Lex {
+                    min: LexBound::Inclusive(b"b".to_vec()),
+                    max: LexBound::Max,
+                },
+                reverse: true,
+                offset: 0,
+                count: None,
+            },
+            with_scores: false,
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["ZRANGE", "z", "0", "1", "LIMIT", "0", "1"]).is_err());
+        assert!(parse(&["ZRANGE", "z", "a", "b", "BYLEX", "WITHSCORES"]).is_err());
+        assert!(parse(&["ZRANGEBYLEX", "z", "a", "+"]).is_err());
+        assert!(parse(&["ZCOUNT", "z", "x", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_bzpopmin() {
+        let got = parse(&["BZPOPMIN", "a", "b", "0.5"]).unwrap();
+        let expected = SortedSetCommand::BlockingPop {
+            keys: vec!["a".into(), "b".into()],
+            end: ScoreEnd::Min,
+            timeout: Duration::from_millis(500),
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["ZPOPMAX", "z", "-1"]).is_err());
+    }
+}
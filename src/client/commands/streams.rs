@@ -0,0 +1,925 @@
+use super::unknown_subcommand;
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::{
+    ClaimOptions, PendingRange, StreamId, StreamIdSpec, StreamTrim, StreamTrimBy,
+};
+use std::time::Duration;
+
+// Commands that operate on stream values
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamCommand {
+    // XADD, where `create` is cleared by NOMKSTREAM
+    Add {
+        key: String,
+        id: StreamIdSpec,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+        create: bool,
+        trim: Option<StreamTrim>,
+    },
+    // XACK
+    Ack {
+        key: String,
+        group: String,
+        ids: Vec<StreamId>,
+    },
+    AutoClaim {
+        key: String,
+        group: String,
+        consumer: String,
+        start: StreamId,
+        count: usize,
+        options: ClaimOptions,
+    },
+    Claim {
+        key: String,
+        group: String,
+        consumer: String,
+        ids: Vec<StreamId>,
+        options: ClaimOptions,
+    },
+    // XGROUP CREATECONSUMER
+    CreateConsumer {
+        key: String,
+        group: String,
+        consumer: String,
+    },
+    // XGROUP CREATE, where a `None` ID stands for `$` and `create` is set by MKSTREAM
+    CreateGroup {
+        key: String,
+        group: String,
+        id: Option<StreamId>,
+        create: bool,
+    },
+    Del {
+        key: String,
+        ids: Vec<StreamId>,
+    },
+    // XGROUP DELCONSUMER
+    DelConsumer {
+        key: String,
+        group: String,
+        consumer: String,
+    },
+    // XGROUP DESTROY
+    DestroyGroup {
+        key: String,
+        group: String,
+    },
+    // XINFO CONSUMERS
+    InfoConsumers {
+        key: String,
+        group: String,
+    },
+    // XINFO GROUPS
+    InfoGroups(String),
+    // XINFO STREAM
+    InfoStream(String),
+    Len(String),
+    // XPENDING, in its summary form without a range
+    Pending {
+        key: String,
+        group: String,
+        range: Option<PendingRange>,
+    },
+    // XRANGE and XREVRANGE, with exclusive bounds already stepped to inclusive ones
+    Range {
+        key: String,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    },
+    // Streams read with the ID `$` have no ID until the command runs, and a zero
+    // BLOCK timeout waits forever
+    Read {
+        streams: Vec<(String, Option<StreamId>)>,
+        count: Option<usize>,
+        block: Option<Duration>,
+    },
+    // Streams read with the ID `>` have a `None` ID
+    ReadGroup {
+        group: String,
+        consumer: String,
+        streams: Vec<(String, Option<StreamId>)>,
+        count: Option<usize>,
+        block: Option<Duration>,
+        noack: bool,
+    },
+    // XGROUP SETID, where a `None` ID stands for `$`
+    SetGroupId {
+        key: String,
+        group: String,
+        id: Option<StreamId>,
+    },
+    Trim {
+        key: String,
+        trim: StreamTrim,
+    },
+}
+
+impl StreamCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            StreamCommand::Add { .. }
+                | StreamCommand::Ack { .. }
+                | StreamCommand::AutoClaim { .. }
+                | StreamCommand::Claim { .. }
+                | StreamCommand::CreateConsumer { .. }
+                | StreamCommand::CreateGroup { .. }
+                | StreamCommand::Del { .. }
+                | StreamCommand::DelConsumer { .. }
+                | StreamCommand::DestroyGroup { .. }
+                | StreamCommand::ReadGroup { .. }
+                | StreamCommand::SetGroupId { .. }
+                | StreamCommand::Trim { .. }
+        )
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("xadd", -5, &["write", "denyoom", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "INSERT"])])
+        .doc("stream", "5.0.0", "Appends a new message to a stream. Creates the key if it doesn't exist."),
+    CommandSpec::new("xrange", -4, &["readonly"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("stream", "5.0.0", "Returns the messages from a stream within a range of IDs."),
+    CommandSpec::new("xrevrange", -4, &["readonly"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("stream", "5.0.0", "Returns the messages from a stream within a range of IDs in reverse order."),
+    CommandSpec::new("xread", -4, &["readonly", "blocking"], &["stream"])
+        .keys(&[KeySpec::keyword_half("STREAMS", 1, &["RO", "ACCESS"])])
+        .doc("stream", "5.0.0", "Returns messages from multiple streams with IDs greater than the ones requested. Blocks until a message is available otherwise."),
+    CommandSpec::new("xreadgroup", -7, &["write", "blocking"], &["stream"])
+        .keys(&[KeySpec::keyword_half("STREAMS", 4, &["RW", "ACCESS"])])
+        .doc("stream", "5.0.0", "Returns new or historical messages from a stream for a consumer in a group. Blocks until a message is available otherwise."),
+    CommandSpec::new("xack", -4, &["write", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("stream", "5.0.0", "Returns the number of messages that were successfully acknowledged by the consumer group member of a stream."),
+    CommandSpec::new("xpending", -3, &["readonly"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("stream", "5.0.0", "Returns the information and entries from a stream consumer group's pending entries list."),
+    CommandSpec::new("xclaim", -6, &["write", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("stream", "5.0.0", "Changes, or acquires, ownership of a message in a consumer group, as if the message was delivered a consumer group member."),
+    CommandSpec::new("xautoclaim", -6, &["write", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "UPDATE"])])
+        .doc("stream", "6.2.0", "Changes, or acquires, ownership of messages in a consumer group, as if the messages were delivered to as consumer group member."),
+    CommandSpec::new("xlen", 2, &["readonly", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RO"])])
+        .doc("stream", "5.0.0", "Return the number of messages in a stream."),
+    CommandSpec::new("xdel", -3, &["write", "fast"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("stream", "5.0.0", "Returns the number of messages after removing them from a stream."),
+    CommandSpec::new("xtrim", -4, &["write"], &["stream"])
+        .keys(&[KeySpec::key(1, &["RW", "DELETE"])])
+        .doc("stream", "5.0.0", "Deletes messages from the beginning of a stream."),
+    CommandSpec::new("xgroup", -2, &[], &[])
+        .doc("stream", "5.0.0", "A container for consumer groups commands.")
+        .subcommands(&[
+            CommandSpec::new("xgroup|create", -5, &["write", "denyoom"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RW", "INSERT"])])
+                .doc("stream", "5.0.0", "Creates a consumer group."),
+            CommandSpec::new("xgroup|createconsumer", 5, &["write", "denyoom"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RW", "INSERT"])])
+                .doc("stream", "6.2.0", "Creates a consumer in a consumer group."),
+            CommandSpec::new("xgroup|delconsumer", 5, &["write"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RW", "DELETE"])])
+                .doc("stream", "5.0.0", "Deletes a consumer from a consumer group."),
+            CommandSpec::new("xgroup|destroy", 4, &["write"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RW", "DELETE"])])
+                .doc("stream", "5.0.0", "Destroys a consumer group."),
+            CommandSpec::new("xgroup|setid", 5, &["write"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RW", "UPDATE"])])
+                .doc("stream", "5.0.0", "Sets the last-delivered ID of a consumer group."),
+        ]),
+    CommandSpec::new("xinfo", -2, &[], &[])
+        .doc("stream", "5.0.0", "A container for stream introspection commands.")
+        .subcommands(&[
+            CommandSpec::new("xinfo|consumers", 4, &["readonly"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RO", "ACCESS"])])
+                .doc("stream", "5.0.0", "Returns a list of the consumers in a consumer group."),
+            CommandSpec::new("xinfo|groups", 3, &["readonly"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RO"])])
+                .doc("stream", "5.0.0", "Returns a list of the consumer groups of a stream."),
+            CommandSpec::new("xinfo|stream", 3, &["readonly"], &["stream"])
+                .keys(&[KeySpec::key(2, &["RO"])])
+                .doc("stream", "5.0.0", "Returns information about a stream."),
+        ]),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<StreamCommand>, CommandParseError> {
+    let command = match name {
+        // XADD key [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]]
+        //     * | id field value [field value ...]
+        "xadd" => parse_xadd(name, args)?,
+        // XRANGE key start end [COUNT count]
+        // XREVRANGE key end start [COUNT count]
+        "xrange" | "xrevrange" => parse_xrange(name, args)?,
+        // XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+        // XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK]
+        //     STREAMS key [key ...] id [id ...]
+        "xread" | "xreadgroup" => parse_xread(name, args)?,
+        // XGROUP CREATE key group id | $ [MKSTREAM]
+        // XGROUP SETID key group id | $
+        // XGROUP DESTROY key group
+        // XGROUP CREATECONSUMER key group consumer
+        // XGROUP DELCONSUMER key group consumer
+        "xgroup" => parse_xgroup(args)?,
+        // XACK key group id [id ...]
+        "xack" => {
+            let ids = (2..args.len())
+                .map(|i| parse_id(args.take_bytes(i)?))
+                .collect::<Result<_, _>>()?;
+            StreamCommand::Ack {
+                key: args.take_string(0)?,
+                group: args.take_string(1)?,
+                ids,
+            }
+        }
+        // XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+        "xpending" => parse_xpending(args)?,
+        // XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms]
+        //     [TIME unix-time-milliseconds] [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID id]
+        "xclaim" => parse_xclaim(args)?,
+        // XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+        "xautoclaim" => parse_xautoclaim(args)?,
+        // XINFO STREAM key
+        // XINFO GROUPS key
+        // XINFO CONSUMERS key group
+        "xinfo" => parse_xinfo(args)?,
+        // XLEN key
+        "xlen" => StreamCommand::Len(args.take_string(0)?),
+        // XDEL key id [id ...]
+        "xdel" => {
+            let ids = (1..args.len())
+                .map(|i| parse_id(args.take_bytes(i)?))
+                .collect::<Result<_, _>>()?;
+            StreamCommand::Del {
+                key: args.take_string(0)?,
+                ids,
+            }
+        }
+        // XTRIM key MAXLEN | MINID [= | ~] threshold [LIMIT count]
+        "xtrim" => {
+            let (trim, next) = parse_trim(args, 1)?;
+            if next != args.len() {
+                return Err(CommandParseError::InvalidSyntax);
+            }
+            StreamCommand::Trim {
+                key: args.take_string(0)?,
+                trim,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn invalid_id() -> CommandParseError {
+    CommandParseError::InvalidArgument(
+        "Invalid stream ID specified as stream command argument".into(),
+    )
+}
+
+// Parses a full ID, where a bare millisecond time has sequence number 0
+fn parse_id(bytes: &[u8]) -> Result<StreamId, CommandParseError> {
+    StreamId::parse(bytes, 0).ok_or_else(invalid_id)
+}
+
+// Parses an XRANGE bound: `-`, `+`, an ID, or an ID prefixed with `(` to exclude it
+// A bare millisecond time covers every sequence number within it
+fn parse_range_bound(bytes: &[u8], start: bool) -> Result<StreamId, CommandParseError> {
+    match bytes {
+        b"-" => return Ok(StreamId::MIN),
+        b"+" => return Ok(StreamId::MAX),
+        _ => {}
+    }
+    let (exclusive, text) = match bytes.strip_prefix(b"(") {
+        Some(rest) => (true, rest),
+        None => (false, bytes),
+    };
+    let default_seq = if start { 0 } else { StreamId::MAX.seq };
+    let id = StreamId::parse(text, default_seq).ok_or_else(invalid_id)?;
+    if !exclusive {
+        return Ok(id);
+    }
+    let stepped = if start { id.next() } else { id.prev() };
+    stepped.ok_or_else(|| {
+        CommandParseError::InvalidArgument(if start {
+            "invalid start ID for the interval".into()
+        } else {
+            "invalid end ID for the interval".into()
+        })
+    })
+}
+
+fn parse_xrange(name: &str, args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    if args.len() != 3 && args.len() != 5 {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    let reverse = name == "xrevrange";
+    let (start, end) = if reverse { (2, 1) } else { (1, 2) };
+    let count = if args.len() == 5 {
+        if args.take_keyword(3)? != "count" {
+            return Err(CommandParseError::InvalidSyntax);
+        }
+        // A negative count selects nothing
+        Some(usize::try_from(args.take_int(4)?).unwrap_or(0))
+    } else {
+        None
+    };
+    Ok(StreamCommand::Range {
+        key: args.take_string(0)?,
+        start: parse_range_bound(args.take_bytes(start)?, true)?,
+        end: parse_range_bound(args.take_bytes(end)?, false)?,
+        count,
+        reverse,
+    })
+}
+
+fn parse_xadd(name: &str, args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let mut create = true;
+    let mut trim = None;
+    let mut index = 1;
+    loop {
+        match args.take_keyword(index)?.as_str() {
+            "nomkstream" => {
+                create = false;
+                index += 1;
+            }
+            "maxlen" | "minid" => {
+                let (parsed, next) = parse_trim(args, index)?;
+                trim = Some(parsed);
+                index = next;
+            }
+            _ => break,
+        }
+        if index >= args.len() {
+            return Err(CommandParseError::InvalidSyntax);
+        }
+    }
+
+    let id_bytes = args.take_bytes(index)?;
+    let id = match id_bytes {
+        b"*" => StreamIdSpec::Auto,
+        _ => match id_bytes.strip_suffix(b"-*") {
+            Some(ms) => match StreamId::parse(ms, 0) {
+                Some(id) if !ms.contains(&b'-') => StreamIdSpec::AutoSeq(id.ms),
+                _ => return Err(invalid_id()),
+            },
+            None => StreamIdSpec::Explicit(parse_id(id_bytes)?),
+        },
+    };
+
+    let pairs = args.len() - index - 1;
+    if pairs == 0 || !pairs.is_multiple_of(2) {
+        return Err(CommandParseError::ArityMismatch(name.into()));
+    }
+    let fields = (index + 1..args.len())
+        .step_by(2)
+        .map(|i| {
+            Ok((
+                args.take_bytes(i)?.to_vec(),
+                args.take_bytes(i + 1)?.to_vec(),
+            ))
+        })
+        .collect::<Result<_, CommandParseError>>()?;
+    Ok(StreamCommand::Add {
+        key: args.take_string(0)?,
+        id,
+        fields,
+        create,
+        trim,
+    })
+}
+
+// Parses `MAXLEN | MINID [= | ~] threshold [LIMIT count]` starting at the strategy,
+// returning the index after it. Trimming is always exact, so `~` only permits LIMIT
+fn parse_trim(args: &CommandArgs, start: usize) -> Result<(StreamTrim, usize), CommandParseError> {
+    let strategy = args.take_keyword(start)?;
+    let mut index = start + 1;
+    let mut approximate = false;
+    match args.take_bytes(index)? {
+        b"~" => {
+            approximate = true;
+            index += 1;
+        }
+        b"=" => index += 1,
+        _ => {}
+    }
+
+    let by = match strategy.as_str() {
+        "maxlen" => {
+            let max_len = u64::try_from(args.take_int(index)?).map_err(|_| {
+                CommandParseError::InvalidArgument("The MAXLEN argument must be >= 0.".into())
+            })?;
+            StreamTrimBy::MaxLen(max_len)
+        }
+        "minid" => StreamTrimBy::MinId(parse_id(args.take_bytes(index)?)?),
+        _ => return Err(CommandParseError::InvalidSyntax),
+    };
+    index += 1;
+
+    let mut limit = None;
+    if index + 1 < args.len() && args.take_keyword(index)? == "limit" {
+        if !approximate {
+            return Err(CommandParseError::InvalidArgument(
+                "syntax error, LIMIT cannot be used without the special ~ option".into(),
+            ));
+        }
+        let count = usize::try_from(args.take_int(index + 1)?).map_err(|_| {
+            CommandParseError::InvalidArgument("The LIMIT argument must be >= 0.".into())
+        })?;
+        // A limit of zero disables it
+        limit = (count > 0).then_some(count);
+        index += 2;
+    }
+    Ok((StreamTrim { by, limit }, index))
+}
+
+// Parses XREAD and XREADGROUP, whose options come before the list of streams
+fn parse_xread(name: &str, args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let grouped = name == "xreadgroup";
+    let mut group = None;
+    let mut noack = false;
+    let mut count = None;
+    let mut block = None;
+    let mut index = 0;
+    loop {
+        match args.take_keyword(index)?.as_str() {
+            "count" if index + 1 < args.len() => {
+                // A count that is not positive reads every new entry
+                count = usize::try_from(args.take_int(index + 1)?)
+                    .ok()
+                    .filter(|count| *count > 0);
+                index += 2;
+            }
+            "block" if index + 1 < args.len() => {
+                let millis = u64::try_from(args.take_int(index + 1)?).map_err(|_| {
+                    CommandParseError::InvalidArgument("timeout is negative".into())
+                })?;
+                block = Some(Duration::from_millis(millis));
+                index += 2;
+            }
+            "group" if grouped && index + 2 < args.len() => {
+                group = Some((args.take_string(index + 1)?, args.take_string(index + 2)?));
+                index += 3;
+            }
+            "noack" if grouped => {
+                noack = true;
+                index += 1;
+            }
+            "streams" => break,
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        if index >= args.len() {
+            return Err(CommandParseError::InvalidSyntax);
+        }
+    }
+
+    let rest = args.len() - index - 1;
+    if rest == 0 || !rest.is_multiple_of(2) {
+        let expected = if grouped { '>' } else { '$' };
+        return Err(CommandParseError::InvalidArgument(format!(
+            "Unbalanced '{name}' list of streams: for each stream key an ID or '{expected}' \
+             must be specified."
+        )));
+    }
+    let first_key = index + 1;
+    let keys = rest / 2;
+    let streams = (first_key..first_key + keys)
+        .map(|i| {
+            let id = match args.take_bytes(i + keys)? {
+                b"$" if grouped => {
+                    return Err(CommandParseError::InvalidArgument(
+                        "The $ ID is meaningless in the context of XREADGROUP: you want to read \
+                         the history of this consumer by specifying a proper ID, or use the > \
+                         ID to get new messages. The $ ID would just return an empty result set."
+                            .into(),
+                    ));
+                }
+                b">" if grouped => None,
+                b"$" if !grouped => None,
+                bytes => Some(parse_id(bytes)?),
+            };
+            Ok((args.take_string(i)?, id))
+        })
+        .collect::<Result<_, CommandParseError>>()?;
+
+    if !grouped {
+        return Ok(StreamCommand::Read {
+            streams,
+            count,
+            block,
+        });
+    }
+    let Some((group, consumer)) = group else {
+        return Err(CommandParseError::InvalidArgument(
+            "Missing GROUP option for XREADGROUP".into(),
+        ));
+    };
+    Ok(StreamCommand::ReadGroup {
+        group,
+        consumer,
+        streams,
+        count,
+        block,
+        noack,
+    })
+}
+
+// Parses a group's position, where `$` stands for the last entry of the stream
+fn parse_group_id(bytes: &[u8]) -> Result<Option<StreamId>, CommandParseError> {
+    match bytes {
+        b"$" => Ok(None),
+        _ => parse_id(bytes).map(Some),
+    }
+}
+
+fn parse_xgroup(args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    // CREATE only takes MKSTREAM after its ID
+    if subcommand == "create" && args.len() > 5 {
+        return Err(CommandParseError::ArityMismatch("xgroup|create".into()));
+    }
+
+    let key = args.take_string(1)?;
+    let group = args.take_string(2)?;
+    Ok(match subcommand.as_str() {
+        "create" => {
+            let create = args.len() == 5;
+            if create && args.take_keyword(4)? != "mkstream" {
+                return Err(CommandParseError::InvalidSyntax);
+            }
+            StreamCommand::CreateGroup {
+                key,
+                group,
+                id: parse_group_id(args.take_bytes(3)?)?,
+                create,
+            }
+        }
+        "setid" => StreamCommand::SetGroupId {
+            key,
+            group,
+            id: parse_group_id(args.take_bytes(3)?)?,
+        },
+        "destroy" => StreamCommand::DestroyGroup { key, group },
+        "createconsumer" => StreamCommand::CreateConsumer {
+            key,
+            group,
+            consumer: args.take_string(3)?,
+        },
+        "delconsumer" => StreamCommand::DelConsumer {
+            key,
+            group,
+            consumer: args.take_string(3)?,
+        },
+        _ => return Err(unknown_subcommand("xgroup", &subcommand)),
+    })
+}
+
+// Idle times and timestamps below zero count as zero
+fn take_millis(args: &CommandArgs, index: usize) -> Result<u64, CommandParseError> {
+    Ok(u64::try_from(args.take_int(index)?).unwrap_or(0))
+}
+
+fn parse_xpending(args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let group = args.take_string(1)?;
+    if args.len() == 2 {
+        return Ok(StreamCommand::Pending {
+            key,
+            group,
+            range: None,
+        });
+    }
+
+    let mut index = 2;
+    let mut min_idle = 0;
+    if args.take_keyword(index)? == "idle" && index + 1 < args.len() {
+        min_idle = take_millis(args, index + 1)?;
+        index += 2;
+    }
+    let rest = args.len() - index;
+    if rest != 3 && rest != 4 {
+        return Err(CommandParseError::InvalidSyntax);
+    }
+    let range = PendingRange {
+        start: parse_range_bound(args.take_bytes(index)?, true)?,
+        end: parse_range_bound(args.take_bytes(index + 1)?, false)?,
+        // A negative count selects nothing
+        count: usize::try_from(args.take_int(index + 2)?).unwrap_or(0),
+        consumer: args.take_opt_string(index + 3)?,
+        min_idle,
+    };
+    Ok(StreamCommand::Pending {
+        key,
+        group,
+        range: Some(range),
+    })
+}
+
+fn parse_xclaim(args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let mut options = ClaimOptions {
+        min_idle: take_millis(args, 3)?,
+        ..ClaimOptions::default()
+    };
+
+    // IDs run up to the first argument that is not one
+    let mut ids = Vec::new();
+    let mut index = 4;
+    while index < args.len() {
+        let Some(id) = StreamId::parse(args.take_bytes(index)?, 0) else {
+            break;
+        };
+        ids.push(id);
+        index += 1;
+    }
+
+    while index < args.len() {
+        let option = args.take_keyword(index)?;
+        let has_value = index + 1 < args.len();
+        match option.as_str() {
+            "idle" if has_value => options.idle = Some(take_millis(args, index + 1)?),
+            "time" if has_value => {
+                options.time = Some(args.take_int(index + 1)?.max(0));
+            }
+            "retrycount" if has_value => {
+                let count = u64::try_from(args.take_int(index + 1)?).map_err(|_| {
+                    CommandParseError::InvalidArgument(
+                        "Invalid RETRYCOUNT option argument for XCLAIM".into(),
+                    )
+                })?;
+                options.retry_count = Some(count);
+            }
+            "lastid" if has_value => {
+                options.last_id = Some(parse_id(args.take_bytes(index + 1)?)?);
+            }
+            "force" => options.force = true,
+            "justid" => options.just_id = true,
+            _ => {
+                return Err(CommandParseError::InvalidArgument(format!(
+                    "Unrecognized XCLAIM option '{}'",
+                    args.take_string(index)?
+                )));
+            }
+        }
+        index += if matches!(option.as_str(), "force" | "justid") {
+            1
+        } else {
+            2
+        };
+    }
+
+    Ok(StreamCommand::Claim {
+        key: args.take_string(0)?,
+        group: args.take_string(1)?,
+        consumer: args.take_string(2)?,
+        ids,
+        options,
+    })
+}
+
+fn parse_xautoclaim(args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let mut options = ClaimOptions {
+        min_idle: take_millis(args, 3)?,
+        ..ClaimOptions::default()
+    };
+    let mut count = 100;
+    let mut index = 5;
+    while index < args.len() {
+        match args.take_keyword(index)?.as_str() {
+            "count" if index + 1 < args.len() => {
+                count = usize::try_from(args.take_int(index + 1)?)
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        CommandParseError::InvalidArgument("COUNT must be > 0".into())
+                    })?;
+                index += 2;
+            }
+            "justid" => {
+                options.just_id = true;
+                index += 1;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+    }
+
+    Ok(StreamCommand::AutoClaim {
+        key: args.take_string(0)?,
+        group: args.take_string(1)?,
+        consumer: args.take_string(2)?,
+        start: parse_range_bound(args.take_bytes(4)?, true)?,
+        count,
+        options,
+    })
+}
+
+fn parse_xinfo(args: &CommandArgs) -> Result<StreamCommand, CommandParseError> {
+    let subcommand = args.take_keyword(0)?;
+    let key = args.take_string(1)?;
+    Ok(match subcommand.as_str() {
+        "stream" => StreamCommand::InfoStream(key),
+        "groups" => StreamCommand::InfoGroups(key),
+        "consumers" => StreamCommand::InfoConsumers {
+            key,
+            group: args.take_string(2)?,
+        },
+        _ => return Err(unknown_subcommand("xinfo", &subcommand)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<StreamCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::Stream(command) => Ok(command),
+            other => panic!("expected a stream command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_xadd() {
+        let got = parse(&[
+            "XADD",
+            "s",
+            "NOMKSTREAM",
+            "MAXLEN",
+            "~",
+            "10",
+            "LIMIT",
+            "5",
+            "7-*",
+            "f",
+            "v",
+        ]);
+        let expected = StreamCommand::Add {
+            key: "s".into(),
+            id: StreamIdSpec::AutoSeq(7),
+            fields: vec![(b"f".to_vec(), b"v".to_vec())],
+            create: false,
+            trim: Some(StreamTrim {
+                by: StreamTrimBy::MaxLen(10),
+                limit: Some(5),
+            }),
+        };
+        assert_eq!(got.unwrap(), expected);
+
+        let got = parse(&["XADD", "s", "MINID", "5", "*", "f", "v"]).unwrap();
+        let StreamCommand::Add { id, trim, .. } = got else {
+            panic!("expected XADD");
+        };
+        assert_eq!(id, StreamIdSpec::Auto);
+        assert_eq!(trim.unwrap().by, StreamTrimBy::MinId(StreamId::new(5, 0)));
+
+        assert!(parse(&["XADD", "s", "MAXLEN", "10", "LIMIT", "5", "*", "f", "v"]).is_err());
+        assert!(parse(&["XADD", "s", "1-x", "f", "v"]).is_err());
+        assert!(parse(&["XADD", "s", "*", "f", "v", "g"]).is_err());
+    }
+
+    #[test]
+    fn test_xrange() {
+        let got = parse(&["XREVRANGE", "s", "(5-0", "-", "COUNT", "2"]).unwrap();
+        let expected = StreamCommand::Range {
+            key: "s".into(),
+            start: StreamId::MIN,
+            end: StreamId::new(4, StreamId::MAX.seq),
+            count: Some(2),
+            reverse: true,
+        };
+        assert_eq!(got, expected);
+
+        let got = parse(&["XRANGE", "s", "5", "5"]).unwrap();
+        let StreamCommand::Range { start, end, .. } = got else {
+            panic!("expected XRANGE");
+        };
+        assert_eq!(
+            (start, end),
+            (StreamId::new(5, 0), StreamId::new(5, StreamId::MAX.seq))
+        );
+
+        assert!(parse(&["XRANGE", "s", "(+", "+"]).is_err());
+        assert!(parse(&["XRANGE", "s", "-", "+", "LIMIT", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_xread() {
+        let got = parse(&[
+            "XREAD", "COUNT", "2", "BLOCK", "0", "STREAMS", "a", "b", "1-1", "$",
+        ]);
+        let expected = StreamCommand::Read {
+            streams: vec![("a".into(), Some(StreamId::new(1, 1))), ("b".into(), None)],
+            count: Some(2),
+            block: Some(Duration::ZERO),
+        };
+        assert_eq!(got.unwrap(), expected);
+
+        assert!(parse(&["XREAD", "STREAMS", "a", "b", "0"]).is_err());
+        assert!(parse(&["XREAD", "BLOCK", "-1", "STREAMS", "a", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_xreadgroup() {
+        let got = parse(&[
+            "XREADGROUP",
+            "GROUP",
+            "g",
+            "c",
+            "NOACK",
+            "STREAMS",
+            "a",
+            "b",
+            ">",
+            "0",
+        ]);
+        let expected = StreamCommand::ReadGroup {
+            group: "g".into(),
+            consumer: "c".into(),
+            streams: vec![("a".into(), None), ("b".into(), Some(StreamId::MIN))],
+            count: None,
+            block: None,
+            noack: true,
+        };
+        assert_eq!(got.unwrap(), expected);
+
+        assert!(parse(&["XREADGROUP", "GROUP", "g", "c", "STREAMS", "a", "$"]).is_err());
+        assert!(parse(&["XREADGROUP", "COUNT", "1", "STREAMS", "a", ">"]).is_err());
+        assert!(parse(&["XREAD", "NOACK", "STREAMS", "a", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_xgroup() {
+        let got = parse(&["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]).unwrap();
+        let expected = StreamCommand::CreateGroup {
+            key: "s".into(),
+            group: "g".into(),
+            id: None,
+            create: true,
+        };
+        assert_eq!(got, expected);
+
+        assert!(parse(&["XGROUP", "DESTROY", "s"]).is_err());
+        let got = parse(&["XGROUP", "BOGUS", "s"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_xpending() {
+        let got = parse(&["XPENDING", "s", "g", "IDLE", "100", "-", "+", "10", "c"]).unwrap();
+        let expected = StreamCommand::Pending {
+            key: "s".into(),
+            group: "g".into(),
+            range: Some(PendingRange {
+                start: StreamId::MIN,
+                end: StreamId::MAX,
+                count: 10,
+                consumer: Some("c".into()),
+                min_idle: 100,
+            }),
+        };
+        assert_eq!(got, expected);
+        assert!(parse(&["XPENDING", "s", "g", "-", "+"]).is_err());
+    }
+
+    #[test]
+    fn test_xclaim() {
+        let got = parse(&[
+            "XCLAIM",
+            "s",
+            "g",
+            "c",
+            "10",
+            "1-1",
+            "2",
+            "RETRYCOUNT",
+            "3",
+            "JUSTID",
+        ]);
+        let expected = StreamCommand::Claim {
+            key: "s".into(),
+            group: "g".into(),
+            consumer: "c".into(),
+            ids: vec![StreamId::new(1, 1), StreamId::new(2, 0)],
+            options: ClaimOptions {
+                min_idle: 10,
+                retry_count: Some(3),
+                just_id: true,
+                ..ClaimOptions::default()
+            },
+        };
+        assert_eq!(got.unwrap(), expected);
+        assert!(parse(&["XCLAIM", "s", "g", "c", "10", "1-1", "BOGUS"]).is_err());
+
+        let got = parse(&["XAUTOCLAIM", "s", "g", "c", "10", "0", "COUNT", "5"]).unwrap();
+        let StreamCommand::AutoClaim { count, .. } = got else {
+            panic!("expected XAUTOCLAIM");
+        };
+        assert_eq!(count, 5);
+        assert!(parse(&["XAUTOCLAIM", "s", "g", "c", "10", "0", "COUNT", "0"]).is_err());
+    }
+}
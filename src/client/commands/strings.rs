@@ -0,0 +1,178 @@
+use crate::commands::{CommandArgs, CommandParseError, CommandSpec, KeySpec};
+use crate::storage::SetCondition;
+
+// Expiry given to SET or the EXPIRE family, resolved to an absolute time by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    Seconds(i64),
+    Milliseconds(i64),
+    UnixSeconds(i64),
+    UnixMilliseconds(i64),
+    KeepTtl,
+}
+
+// Commands that operate on string values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringCommand {
+    Get(String),
+    Set {
+        key: String,
+        value: Vec<u8>,
+        condition: SetCondition,
+        expiry: Option<Expiry>,
+        get: bool,
+    },
+}
+
+impl StringCommand {
+    pub fn is_write(&self) -> bool {
+        matches!(self, StringCommand::Set { .. })
+    }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("get", 2, &["readonly", "fast"], &["string"])
+        .keys(&[KeySpec::key(1, &["RO", "ACCESS"])])
+        .doc("string", "1.0.0", "Returns the string value of a key."),
+    CommandSpec::new("set", -3, &["write", "denyoom"], &["string"])
+        .keys(&[KeySpec::key(1, &["RW", "ACCESS", "UPDATE", "VARIABLE_FLAGS"])])
+        .doc("string", "1.0.0", "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist."),
+];
+
+pub fn parse(name: &str, args: &CommandArgs) -> Result<Option<StringCommand>, CommandParseError> {
+    let command = match name {
+        // GET key
+        "get" => {
+            let key = args.take_string(0)?;
+            StringCommand::Get(key)
+        }
+        // SET key value [NX | XX] [GET] [EX s | PX ms | EXAT ts | PXAT ts | KEEPTTL]
+        "set" => parse_set(args)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+fn parse_set(args: &CommandArgs) -> Result<StringCommand, CommandParseError> {
+    let key = args.take_string(0)?;
+    let value = args.take_bytes(1)?.to_vec();
+
+    let mut condition = SetCondition::Always;
+    let mut expiry = None;
+    let mut get = false;
+
+    let mut index = 2;
+    while index < args.len() {
+        let option = args.take_keyword(index)?;
+        match option.as_str() {
+            // NX and XX are mutually exclusive
+            "nx" if condition != SetCondition::IfExists => condition = SetCondition::IfNotExists,
+            "xx" if condition != SetCondition::IfNotExists => condition = SetCondition::IfExists,
+            "get" => get = true,
+            // Only one of the expiry options may be given
+            "keepttl" if expiry.is_none() => expiry = Some(Expiry::KeepTtl),
+            "ex" | "px" | "exat" | "pxat" if expiry.is_none() && index + 1 < args.len() => {
+                let time = args.take_int(index + 1)?;
+                if time <= 0 {
+                    return Err(CommandParseError::InvalidExpireTime("set".into()));
+                }
+                expiry = Some(match option.as_str() {
+                    "ex" => Expiry::Seconds(time),
+                    "px" => Expiry::Milliseconds(time),
+                    "exat" => Expiry::UnixSeconds(time),
+                    _ => Expiry::UnixMilliseconds(time),
+                });
+                index += 1;
+            }
+            _ => return Err(CommandParseError::InvalidSyntax),
+        }
+        index += 1;
+    }
+
+    Ok(StringCommand::Set {
+        key,
+        value,
+        condition,
+        expiry,
+        get,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+
+    fn parse(args: &[&str]) -> Result<StringCommand, CommandParseError> {
+        match parse_args(args)? {
+            ClientCommand::String(command) => Ok(command),
+            other => panic!("expected a string command, got {other:?}"),
+        }
+    }
+
+    fn set(condition: SetCondition, expiry: Option<Expiry>, get: bool) -> StringCommand {
+        StringCommand::Set {
+            key: "key".into(),
+            value: b"value".to_vec(),
+            condition,
+            expiry,
+            get,
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let got = parse(&["GET", "key"]).unwrap();
+        assert_eq!(got, StringCommand::Get("key".into()));
+        assert!(parse(&["GET"]).is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let got = parse(&["SET", "key", "value"]).unwrap();
+        assert_eq!(got, set(SetCondition::Always, None, false));
+    }
+
+    #[test]
+    fn test_set_lock_options() {
+        let got = parse(&["set", "key", "value", "NX", "PX", "30000"]).unwrap();
+        let expiry = Some(Expiry::Milliseconds(30000));
+        assert_eq!(got, set(SetCondition::IfNotExists, expiry, false));
+    }
+
+    #[test]
+    fn test_set_all_expiry_options() {
+        let cases = [
+            ("EX", Expiry::Seconds(10)),
+            ("PX", Expiry::Milliseconds(10)),
+            ("EXAT", Expiry::UnixSeconds(10)),
+            ("PXAT", Expiry::UnixMilliseconds(10)),
+        ];
+        for (option, expiry) in cases {
+            let got = parse(&["SET", "key", "value", option, "10"]).unwrap();
+            assert_eq!(got, set(SetCondition::Always, Some(expiry), false));
+        }
+
+        let got = parse(&["SET", "key", "value", "XX", "KEEPTTL", "GET"]).unwrap();
+        let expiry = Some(Expiry::KeepTtl);
+        assert_eq!(got, set(SetCondition::IfExists, expiry, true));
+    }
+
+    #[test]
+    fn test_set_conflicting_options() {
+        assert!(parse(&["SET", "key", "value", "NX", "XX"]).is_err());
+        assert!(parse(&["SET", "key", "value", "EX", "1", "PX", "1"]).is_err());
+        assert!(parse(&["SET", "key", "value", "EX", "1", "KEEPTTL"]).is_err());
+        assert!(parse(&["SET", "key", "value", "EX"]).is_err());
+        assert!(parse(&["SET", "key", "value", "BOGUS"]).is_err());
+    }
+
+    #[test]
+    fn test_set_invalid_expire_time() {
+        let got = parse(&["SET", "key", "value", "EX", "0"]);
+        assert!(matches!(got, Err(CommandParseError::InvalidExpireTime(_))));
+
+        let got = parse(&["SET", "key", "value", "PX", "abc"]);
+        assert!(matches!(got, Err(CommandParseError::NotInteger)));
+    }
+}
@@ -3,12 +3,28 @@ use crate::server::ServerCommand;
 use tokio::sync::mpsc::UnboundedSender;
 
 pub struct ClientEvent {
+    // Identifies the connection the command arrived on
+    pub client_id: u64,
     pub command: ClientCommand,
     pub responder: UnboundedSender<ServerCommand>,
 }
 
 impl ClientEvent {
-    pub fn new(command: ClientCommand, responder: UnboundedSender<ServerCommand>) -> Self {
-        ClientEvent { command, responder }
+    pub fn new(
+        client_id: u64,
+        command: ClientCommand,
+        responder: UnboundedSender<ServerCommand>,
+    ) -> Self {
+        ClientEvent {
+            client_id,
+            command,
+            responder,
+        }
+    }
+
+    // The RESET a reader sends on behalf of a client that disconnected, which has
+    // nowhere to reply to
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self.command, ClientCommand::Reset) && self.responder.is_closed()
     }
 }
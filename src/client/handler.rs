@@ -1,65 +1,110 @@
-use crate::client::commands::ClientCommand;
 use crate::client::ClientEvent;
-use crate::resp::{RespParser, RespValue};
+use crate::client::commands::ClientCommand;
+use crate::log::{Level, log};
+use crate::resp::{CodecError, Protocol, ProtocolLimits, RespCodec, RespValue};
 use crate::server::ServerCommand;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use futures_util::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::sync::CancellationToken;
+
+pub async fn handle_client(
+    stream: TcpStream,
+    client_id: u64,
+    client_event_tx: UnboundedSender<ClientEvent>,
+    limits: ProtocolLimits,
+    closed: CancellationToken,
+) {
+    // The server tells finished connections apart by their token
+    let _closed = closed.clone().drop_guard();
 
-pub async fn handle_client(stream: TcpStream, client_event_tx: UnboundedSender<ClientEvent>) {
     // Create a channel for the server to respond to client events
     let (server_command_tx, mut server_command_rx) = unbounded_channel::<ServerCommand>();
-    let (mut reader, mut writer) = stream.into_split();
+    let (reader, writer) = stream.into_split();
+    let mut requests = FramedRead::new(reader, RespCodec::requests(limits));
+    let mut replies = FramedWrite::new(writer, RespCodec::new(limits));
 
     // Start the background writer (send) loop
     tokio::spawn(async move {
+        let mut protocol = Protocol::Resp2;
         while let Some(command) = server_command_rx.recv().await {
-            // Convert the server command into a serializable RESP response string
-            let resp: RespValue = command.into();
-            let output = resp.to_string();
+            let mut result = replies.feed(reply(command, &mut protocol)).await;
+            // Replies that queued up meanwhile, as when pipelining, go out in one write
+            while let Ok(command) = server_command_rx.try_recv() {
+                if result.is_err() {
+                    break;
+                }
+                result = replies.feed(reply(command, &mut protocol)).await;
+            }
 
-            if let Err(err) = writer.write_all(&output.as_bytes()).await {
-                eprintln!("Failed to send server command: {}", err);
+            if let Err(err) = match result {
+                Ok(()) => replies.flush().await,
+                Err(err) => Err(err),
+            } {
+                log!(Level::Verbose, "Failed to send server command: {err}");
                 break;
             }
         }
     });
 
-    let mut parser = RespParser::new();
-    let mut recv_buffer = [0; 4096];
-
     // Reader (recv) loop
     loop {
-        match reader.read(&mut recv_buffer).await {
-            Ok(0) => {
-                println!("Client disconnected");
+        let frame = tokio::select! {
+            frame = requests.next() => frame,
+            // Closed by the server, as when the client stayed idle too long
+            () = closed.cancelled() => {
+                log!(Level::Verbose, "Client {client_id} closed by the server");
                 break;
             }
-            Ok(n) => {
-                parser.append(&recv_buffer[..n]);
-
-                while let Some(resp) = parser.parse() {
-                    match ClientCommand::try_from(resp) {
-                        // Emit a client event with the command parsed
-                        Ok(command) => {
-                            let event = ClientEvent::new(command, server_command_tx.clone());
-                            client_event_tx.send(event).unwrap();
-                        }
-                        // Queue a server error to be written to the client socket
-                        Err(e) => {
-                            let message = e.to_string();
-                            server_command_tx
-                                .send(ServerCommand::Error(message))
-                                .unwrap();
-                            println!("Client command error: {:?}", e)
-                        }
-                    }
-                }
+        };
+        let resp = match frame {
+            Some(Ok(resp)) => resp,
+            None => {
+                log!(Level::Verbose, "Client {client_id} disconnected");
+                break;
             }
-            Err(e) => {
-                eprintln!("Client error: {:?}", e);
+            // Nothing after a malformed frame can be trusted, so the error is replied to
+            // in order and the connection closed
+            Some(Err(CodecError::Invalid(message))) => {
+                log!(
+                    Level::Verbose,
+                    "Client {client_id} protocol error: {message}"
+                );
+                let command = ClientCommand::Invalid(format!("ERR Protocol error: {message}"));
+                let event = ClientEvent::new(client_id, command, server_command_tx.clone());
+                client_event_tx.send(event).unwrap();
                 break;
             }
-        }
+            Some(Err(CodecError::Io(e))) => {
+                log!(Level::Verbose, "Client {client_id} error: {e:?}");
+                break;
+            }
+        };
+
+        // Parse errors go through the server too, so they are replied to after the
+        // commands before them and can abort a transaction
+        let command = ClientCommand::try_from(resp).unwrap_or_else(|e| {
+            log!(Level::Debug, "Client {client_id} command error: {e:?}");
+            ClientCommand::Invalid(e.to_string())
+        });
+
+        // Emit a client event with the command parsed
+        let event = ClientEvent::new(client_id, command, server_command_tx.clone());
+        client_event_tx.send(event).unwrap();
+    }
+
+    // Release the subscriptions and other state the server holds for the connection,
+    // sending the reply nowhere since the client is gone
+    let (closed_tx, _) = unbounded_channel();
+    let _ = client_event_tx.send(ClientEvent::new(client_id, ClientCommand::Reset, closed_tx));
+}
+
+// Converts a reply for the connection's protocol. The protocol changes in line with the
+// replies, so those to commands sent before HELLO keep the protocol they were sent with.
+fn reply(command: ServerCommand, protocol: &mut Protocol) -> RespValue {
+    if let ServerCommand::SwitchProtocol(switched, _) = &command {
+        *protocol = *switched;
     }
+    command.for_protocol(*protocol)
 }
@@ -2,6 +2,13 @@ mod commands;
 mod event;
 mod handler;
 
-pub use commands::ClientCommand;
+#[cfg(test)]
+pub use commands::parse_args;
+pub use commands::{
+    AdminCommand, ClientCommand, CommandFilter, ConfigCommand, Expiry, HashCommand, KeyCommand,
+    ListCommand, PubSubCommand, RestorePolicy, ScriptCommand, SetCommand, SortedSetCommand,
+    StreamCommand, StringCommand,
+};
+pub use commands::{command_spec, command_specs};
 pub use event::ClientEvent;
 pub use handler::handle_client;
@@ -1,5 +1,6 @@
 use crate::commands::CommandParseError;
 use crate::resp::RespValue;
+use std::time::Duration;
 
 pub struct CommandArgs<'a> {
     args: &'a [RespValue],
@@ -36,21 +37,51 @@ impl<'a> CommandArgs<'a> {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn take_strings(&self, start: usize) -> Result<Vec<String>, CommandParseError> {
+        (start..self.len()).map(|i| self.take_string(i)).collect()
+    }
+
+    // Takes an option or subcommand name, normalized to lowercase for matching
+    pub fn take_keyword(&self, index: usize) -> Result<String, CommandParseError> {
+        Ok(self.take_string(index)?.to_ascii_lowercase())
+    }
+
     pub fn take_int(&self, index: usize) -> Result<i64, CommandParseError> {
         match self.args.get(index) {
             Some(RespValue::Integer(i)) => Ok(*i),
             Some(RespValue::BulkString(bs)) => Ok(String::from_utf8_lossy(bs)
                 .parse()
-                .map_err(|_| CommandParseError::InvalidType)?),
+                .map_err(|_| CommandParseError::NotInteger)?),
             Some(RespValue::SimpleString(s)) => {
-                Ok(s.parse().map_err(|_| CommandParseError::InvalidType)?)
+                Ok(s.parse().map_err(|_| CommandParseError::NotInteger)?)
             }
             _ => Err(CommandParseError::InvalidType),
         }
     }
 
-    #[allow(dead_code)]
+    pub fn take_float(&self, index: usize) -> Result<f64, CommandParseError> {
+        let bs = self.take_bytes(index)?;
+        std::str::from_utf8(bs)
+            .ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|f| !f.is_nan())
+            .ok_or(CommandParseError::NotFloat)
+    }
+
+    // Timeouts of blocking commands are given in (possibly fractional) seconds
+    pub fn take_timeout(&self, index: usize) -> Result<Duration, CommandParseError> {
+        let seconds = self.take_float(index).map_err(|_| {
+            CommandParseError::InvalidArgument("timeout is not a float or out of range".into())
+        })?;
+        if seconds < 0.0 {
+            return Err(CommandParseError::InvalidArgument(
+                "timeout is negative".into(),
+            ));
+        }
+        Duration::try_from_secs_f64(seconds)
+            .map_err(|_| CommandParseError::InvalidArgument("timeout is out of range".into()))
+    }
+
     pub fn take_opt_int(&self, index: usize) -> Result<Option<i64>, CommandParseError> {
         if index < self.len() {
             let int_value = self.take_int(index)?;
@@ -120,6 +151,28 @@ mod tests {
         assert_eq!(cmd_args.take_opt_string(1).unwrap(), None);
     }
 
+    #[test]
+    fn test_take_strings() {
+        let args = vec![
+            RespValue::BulkString(b"a".to_vec()),
+            RespValue::BulkString(b"b".to_vec()),
+            RespValue::SimpleString("c".to_string()),
+        ];
+        let cmd_args = CommandArgs::new(&args);
+
+        assert_eq!(cmd_args.take_strings(1).unwrap(), vec!["b", "c"]);
+        assert!(cmd_args.take_strings(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_take_keyword() {
+        let args = vec![RespValue::BulkString(b"KeepTTL".to_vec())];
+        let cmd_args = CommandArgs::new(&args);
+
+        assert_eq!(cmd_args.take_keyword(0).unwrap(), "keepttl");
+        assert!(cmd_args.take_keyword(1).is_err());
+    }
+
     #[test]
     fn test_take_int() {
         let args = vec![
@@ -136,6 +189,25 @@ mod tests {
         assert!(cmd_args.take_int(3).is_err());
     }
 
+    #[test]
+    fn test_take_float() {
+        let args = vec![
+            RespValue::BulkString(b"1.5".to_vec()),
+            RespValue::BulkString(b"-3".to_vec()),
+            RespValue::BulkString(b"nan".to_vec()),
+            RespValue::BulkString(b"abc".to_vec()),
+        ];
+        let cmd_args = CommandArgs::new(&args);
+
+        assert!((cmd_args.take_float(0).unwrap() - 1.5).abs() < f64::EPSILON);
+        assert!((cmd_args.take_float(1).unwrap() + 3.0).abs() < f64::EPSILON);
+        assert!(matches!(
+            cmd_args.take_float(2),
+            Err(CommandParseError::NotFloat)
+        ));
+        assert!(cmd_args.take_float(3).is_err());
+    }
+
     #[test]
     fn test_take_opt_int() {
         let args = vec![RespValue::Integer(42)];
@@ -5,6 +5,13 @@ pub enum CommandParseError {
     InvalidSyntax,
     InvalidType,
     InvalidUtf8,
+    InvalidCursor,
+    InvalidExpireTime(String),
+    IncompatibleOptions(String),
+    InvalidArgument(String),
+    NotInteger,
+    NotFloat,
+    UnknownTypeName(String),
     UnknownCommand(String),
     ArityMismatch(String),
 }
@@ -12,18 +19,31 @@ pub enum CommandParseError {
 impl fmt::Display for CommandParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CommandParseError::InvalidSyntax => write!(f, "syntax error"),
+            CommandParseError::InvalidSyntax => write!(f, "ERR syntax error"),
             CommandParseError::InvalidType => {
-                write!(f, "invalid data type")
+                write!(f, "ERR invalid data type")
             }
             CommandParseError::InvalidUtf8 => {
-                write!(f, "invalid utf-8 string")
+                write!(f, "ERR invalid utf-8 string")
+            }
+            CommandParseError::InvalidCursor => write!(f, "ERR invalid cursor"),
+            CommandParseError::InvalidExpireTime(command) => {
+                write!(f, "ERR invalid expire time in '{command}' command")
+            }
+            CommandParseError::IncompatibleOptions(message)
+            | CommandParseError::InvalidArgument(message) => write!(f, "ERR {message}"),
+            CommandParseError::NotInteger => {
+                write!(f, "ERR value is not an integer or out of range")
+            }
+            CommandParseError::NotFloat => write!(f, "ERR value is not a valid float"),
+            CommandParseError::UnknownTypeName(name) => {
+                write!(f, "ERR unknown type name '{name}'")
             }
             CommandParseError::UnknownCommand(command) => {
-                write!(f, "unknown command '{}'", command)
+                write!(f, "ERR unknown command '{command}'")
             }
             CommandParseError::ArityMismatch(command) => {
-                write!(f, "wrong number of arguments for '{}' command", command)
+                write!(f, "ERR wrong number of arguments for '{command}' command")
             }
         }
     }
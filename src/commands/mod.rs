@@ -1,5 +1,7 @@
 mod args;
 mod error;
+mod table;
 
 pub use args::CommandArgs;
 pub use error::CommandParseError;
+pub use table::{BeginSearch, CommandSpec, FindKeys, KeySpec};
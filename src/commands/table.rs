@@ -0,0 +1,423 @@
+// Static description of a command: how many arguments it takes, how it behaves and
+// where its keys are, as reported by COMMAND INFO and COMMAND DOCS
+#[derive(Debug)]
+pub struct CommandSpec {
+    // Lowercase, with subcommands named `container|subcommand`
+    pub name: &'static str,
+    // Counts the command name (and the subcommand's), and a negative arity is a minimum
+    pub arity: i64,
+    pub flags: &'static [&'static str],
+    // ACL categories besides those implied by the flags
+    pub categories: &'static [&'static str],
+    pub key_specs: &'static [KeySpec],
+    pub group: &'static str,
+    pub since: &'static str,
+    pub summary: &'static str,
+    pub subcommands: &'static [CommandSpec],
+}
+
+// Where to start looking for keys, and how to find them from there
+#[derive(Debug)]
+pub struct KeySpec {
+    pub flags: &'static [&'static str],
+    pub begin_search: BeginSearch,
+    pub find_keys: FindKeys,
+}
+
+#[derive(Debug)]
+pub enum BeginSearch {
+    Index(usize),
+    // The argument after a keyword, searched for forwards from `start_from` or
+    // backwards when it is negative
+    Keyword {
+        keyword: &'static str,
+        start_from: i64,
+    },
+}
+
+#[derive(Debug)]
+pub enum FindKeys {
+    // Keys up to `last_key` (negative counts from the end), every `step` arguments. A
+    // `limit` above 1 with a `last_key` of -1 only takes that fraction of what remains.
+    Range {
+        last_key: i64,
+        step: usize,
+        limit: usize,
+    },
+    // An argument counting the keys that follow from `first_key`, relative to it
+    KeyNum {
+        key_num_index: usize,
+        first_key: usize,
+        step: usize,
+    },
+}
+
+impl CommandSpec {
+    pub const fn new(
+        name: &'static str,
+        arity: i64,
+        flags: &'static [&'static str],
+        categories: &'static [&'static str],
+    ) -> Self {
+        CommandSpec {
+            name,
+            arity,
+            flags,
+            categories,
+            key_specs: &[],
+            group: "",
+            since: "",
+            summary: "",
+            subcommands: &[],
+        }
+    }
+
+    pub const fn keys(self, key_specs: &'static [KeySpec]) -> Self {
+        CommandSpec { key_specs, ..self }
+    }
+
+    pub const fn doc(
+        self,
+        group: &'static str,
+        since: &'static str,
+        summary: &'static str,
+    ) -> Self {
+        CommandSpec {
+            group,
+            since,
+            summary,
+            ..self
+        }
+    }
+
+    pub const fn subcommands(self, subcommands: &'static [CommandSpec]) -> Self {
+        CommandSpec {
+            subcommands,
+            ..self
+        }
+    }
+
+    // Whether a call with `argc` arguments, the name included, has the right arity
+    pub fn accepts(&self, argc: usize) -> bool {
+        let argc = i64::try_from(argc).unwrap_or(i64::MAX);
+        if self.arity < 0 {
+            argc >= -self.arity
+        } else {
+            argc == self.arity
+        }
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn subcommand(&self, name: &str) -> Option<&CommandSpec> {
+        self.subcommands.iter().find(|spec| {
+            spec.name
+                .split_once('|')
+                .is_some_and(|(_, sub)| sub == name)
+        })
+    }
+
+    // The flags with `movablekeys` added when the keys cannot be found from fixed
+    // positions alone
+    pub fn all_flags(&self) -> Vec<&'static str> {
+        let mut flags = self.flags.to_vec();
+        if self.key_specs.iter().any(|spec| !spec.is_fixed()) {
+            flags.push("movablekeys");
+        }
+        flags
+    }
+
+    // ACL categories, starting with those the flags imply
+    pub fn acl_categories(&self) -> Vec<&'static str> {
+        let mut categories = Vec::new();
+        if self.has_flag("write") {
+            categories.push("write");
+        }
+        if self.has_flag("readonly") {
+            categories.push("read");
+        }
+        if self.has_flag("admin") {
+            categories.extend(["admin", "dangerous"]);
+        }
+        if self.has_flag("pubsub") {
+            categories.push("pubsub");
+        }
+        categories.push(if self.has_flag("fast") {
+            "fast"
+        } else {
+            "slow"
+        });
+        if self.has_flag("blocking") {
+            categories.push("blocking");
+        }
+        categories.extend(self.categories);
+        categories
+    }
+
+    // The first key, last key and step of the legacy key range. Only key specs that
+    // are fixed positions counted from the start (or a tail of all arguments) fit it,
+    // and their ranges are merged.
+    pub fn legacy_key_range(&self) -> (i64, i64, i64) {
+        let mut range: Option<(i64, i64, i64)> = None;
+        for spec in self.key_specs {
+            let (BeginSearch::Index(index), FindKeys::Range { last_key, step, .. }) =
+                (&spec.begin_search, &spec.find_keys)
+            else {
+                continue;
+            };
+            let first = i64::try_from(*index).unwrap_or(i64::MAX);
+            let last = if *last_key < 0 {
+                *last_key
+            } else {
+                first + last_key
+            };
+            let step = i64::try_from(*step).unwrap_or(i64::MAX);
+            range = Some(match range {
+                None => (first, last, step),
+                Some((first_seen, last_seen, step_seen)) => {
+                    let last = if last < 0 || last_seen < 0 {
+                        last.min(last_seen)
+                    } else {
+                        last.max(last_seen)
+                    };
+                    (first_seen.min(first), last, step_seen.max(step))
+                }
+            });
+        }
+        range.unwrap_or((0, 0, 0))
+    }
+
+    pub fn has_keys(&self) -> bool {
+        self.key_specs.iter().any(|spec| !spec.is_channel())
+    }
+
+    // The keys among a call's arguments, the name included, or None when the
+    // arguments are too short for the positions the key specs give
+    pub fn find_keys<'a>(&self, argv: &'a [Vec<u8>]) -> Option<Vec<&'a [u8]>> {
+        let mut keys = Vec::new();
+        for spec in self.key_specs.iter().filter(|spec| !spec.is_channel()) {
+            for index in spec.positions(argv)? {
+                keys.push(argv.get(index)?.as_slice());
+            }
+        }
+        Some(keys)
+    }
+}
+
+impl KeySpec {
+    // A single key at `index`
+    pub const fn key(index: usize, flags: &'static [&'static str]) -> Self {
+        Self::range(index, 0, 1, flags)
+    }
+
+    // Keys from `index` to `last_key` after it, or counting from the end when negative
+    pub const fn range(
+        index: usize,
+        last_key: i64,
+        step: usize,
+        flags: &'static [&'static str],
+    ) -> Self {
+        KeySpec {
+            flags,
+            begin_search: BeginSearch::Index(index),
+            find_keys: FindKeys::Range {
+                last_key,
+                step,
+                limit: 0,
+            },
+        }
+    }
+
+    // A count of keys at `index`, followed by that many keys
+    pub const fn keynum(index: usize, flags: &'static [&'static str]) -> Self {
+        KeySpec {
+            flags,
+            begin_search: BeginSearch::Index(index),
+            find_keys: FindKeys::KeyNum {
+                key_num_index: 0,
+                first_key: 1,
+                step: 1,
+            },
+        }
+    }
+
+    // The first half of what follows a keyword, as keys come before their IDs in
+    // XREAD's STREAMS
+    pub const fn keyword_half(
+        keyword: &'static str,
+        start_from: i64,
+        flags: &'static [&'static str],
+    ) -> Self {
+        KeySpec {
+            flags,
+            begin_search: BeginSearch::Keyword {
+                keyword,
+                start_from,
+            },
+            find_keys: FindKeys::Range {
+                last_key: -1,
+                step: 1,
+                limit: 2,
+            },
+        }
+    }
+
+    fn is_fixed(&self) -> bool {
+        matches!(
+            (&self.begin_search, &self.find_keys),
+            (BeginSearch::Index(_), FindKeys::Range { .. })
+        )
+    }
+
+    // Shard channels are described like keys without being keys
+    fn is_channel(&self) -> bool {
+        self.flags.contains(&"NOT_KEY")
+    }
+
+    fn positions(&self, argv: &[Vec<u8>]) -> Option<Vec<usize>> {
+        let len = argv.len();
+        let begin = match &self.begin_search {
+            BeginSearch::Index(index) => *index,
+            BeginSearch::Keyword {
+                keyword,
+                start_from,
+            } => {
+                let is_keyword = |&i: &usize| argv[i].eq_ignore_ascii_case(keyword.as_bytes());
+                let from = relative(*start_from, len)?;
+                // Searching from the end finds the last occurrence
+                let found = if *start_from < 0 {
+                    (1..=from).rev().find(is_keyword)
+                } else {
+                    (from..len).find(is_keyword)
+                };
+                found? + 1
+            }
+        };
+        if begin >= len {
+            return Some(Vec::new());
+        }
+        match &self.find_keys {
+            FindKeys::Range {
+                last_key,
+                step,
+                limit,
+            } => {
+                let last = if *last_key >= 0 {
+                    begin + usize::try_from(*last_key).ok()?
+                } else if *limit > 1 {
+                    // Only the first 1/limit of the remaining arguments
+                    begin + (len - begin) / limit - 1
+                } else {
+                    relative(*last_key, len)?
+                };
+                Some((begin..=last).step_by(*step).collect())
+            }
+            FindKeys::KeyNum {
+                key_num_index,
+                first_key,
+                step,
+            } => {
+                let count = argv.get(begin + key_num_index)?;
+                let count: usize = std::str::from_utf8(count).ok()?.parse().ok()?;
+                let first = begin + first_key;
+                Some((0..count).map(|i| first + i * step).collect())
+            }
+        }
+    }
+}
+
+// A position from the start, or from the end when negative
+fn relative(position: i64, argc: usize) -> Option<usize> {
+    if position >= 0 {
+        usize::try_from(position).ok()
+    } else {
+        argc.checked_sub(usize::try_from(position.unsigned_abs()).ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SET: CommandSpec = CommandSpec::new("set", -3, &["write", "denyoom"], &["string"])
+        .keys(&[KeySpec::key(1, &["RW"])]);
+    const SMOVE: CommandSpec = CommandSpec::new("smove", 4, &["write", "fast"], &["set"])
+        .keys(&[KeySpec::key(1, &["RW"]), KeySpec::key(2, &["RW"])]);
+    const BLPOP: CommandSpec = CommandSpec::new("blpop", -3, &["write", "blocking"], &["list"])
+        .keys(&[KeySpec::range(1, -2, 1, &["RW"])]);
+    const EVAL: CommandSpec = CommandSpec::new("eval", -3, &["noscript"], &["scripting"])
+        .keys(&[KeySpec::keynum(2, &["RW"])]);
+    const XREAD: CommandSpec = CommandSpec::new("xread", -4, &["readonly", "blocking"], &[])
+        .keys(&[KeySpec::keyword_half("STREAMS", 1, &["RO"])]);
+
+    fn keys(spec: &CommandSpec, args: &[&str]) -> Option<Vec<String>> {
+        let command_line: Vec<_> = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+        let keys = spec.find_keys(&command_line)?;
+        Some(
+            keys.into_iter()
+                .map(|key| String::from_utf8(key.to_vec()).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_arity() {
+        assert!(!SET.accepts(2));
+        assert!(SET.accepts(3));
+        assert!(SET.accepts(6));
+        assert!(!SMOVE.accepts(3));
+        assert!(SMOVE.accepts(4));
+        assert!(!SMOVE.accepts(5));
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(SET.acl_categories(), vec!["write", "slow", "string"]);
+        assert_eq!(
+            BLPOP.acl_categories(),
+            vec!["write", "slow", "blocking", "list"]
+        );
+        assert_eq!(SET.all_flags(), vec!["write", "denyoom"]);
+        assert_eq!(EVAL.all_flags(), vec!["noscript", "movablekeys"]);
+    }
+
+    #[test]
+    fn test_legacy_key_range() {
+        assert_eq!(SET.legacy_key_range(), (1, 1, 1));
+        assert_eq!(SMOVE.legacy_key_range(), (1, 2, 1));
+        assert_eq!(BLPOP.legacy_key_range(), (1, -2, 1));
+        assert_eq!(EVAL.legacy_key_range(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_find_keys() {
+        assert_eq!(keys(&SET, &["set", "k", "v"]).unwrap(), vec!["k"]);
+        assert_eq!(
+            keys(&SMOVE, &["smove", "a", "b", "m"]).unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys(&BLPOP, &["blpop", "a", "b", "0"]).unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys(&EVAL, &["eval", "s", "2", "a", "b", "arg"]).unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            keys(&EVAL, &["eval", "s", "0"]).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(keys(&EVAL, &["eval", "s", "3", "a"]), None);
+        assert_eq!(
+            keys(
+                &XREAD,
+                &["xread", "count", "2", "streams", "a", "b", "0", "0"]
+            )
+            .unwrap(),
+            vec!["a", "b"]
+        );
+    }
+}
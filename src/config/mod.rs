@@ -0,0 +1,498 @@
+mod params;
+mod rewrite;
+
+use crate::glob::glob_match;
+use crate::log::Level;
+use crate::resp::{ProtocolLimits, split_args};
+use params::PARAMS;
+use std::collections::HashSet;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// The storage engine keys are kept in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageEngine {
+    Postgres,
+    Memory,
+}
+
+impl StorageEngine {
+    pub fn name(self) -> &'static str {
+        match self {
+            StorageEngine::Postgres => "postgres",
+            StorageEngine::Memory => "memory",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StorageEngine> {
+        [StorageEngine::Postgres, StorageEngine::Memory]
+            .into_iter()
+            .find(|engine| engine.name().eq_ignore_ascii_case(name))
+    }
+}
+
+// Settings of the server, from a redis.conf-style file, the environment and the command
+// line in that order of precedence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    // The addresses to listen on, at least one
+    pub bind: Vec<IpAddr>,
+    pub port: u16,
+    pub storage: StorageEngine,
+    pub postgres_url: String,
+    // The most connections kept open to Postgres
+    pub pool_size: usize,
+    // How long a script may run before it is aborted
+    pub busy_reply_threshold: Duration,
+    pub maxclients: usize,
+    // How long a client may stay idle before it is disconnected, zero never
+    pub timeout: Duration,
+    pub loglevel: Level,
+    // Whether a failed command rolls back the whole transaction at EXEC
+    pub atomic_exec: bool,
+    pub limits: ProtocolLimits,
+    // The file loaded at startup, which CONFIG REWRITE writes back to
+    pub file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            port: 6379,
+            storage: StorageEngine::Postgres,
+            postgres_url: "host=localhost user=postgres".into(),
+            pool_size: 16,
+            busy_reply_threshold: Duration::from_secs(5),
+            maxclients: 10_000,
+            timeout: Duration::ZERO,
+            loglevel: Level::Notice,
+            atomic_exec: false,
+            limits: ProtocolLimits::default(),
+            file: None,
+        }
+    }
+}
+
+impl Config {
+    // Loads the configuration from the command line, `postgredis [file] [--name value ...]`,
+    // and the environment, where POSTGREDIS_PORT sets port and so on. The error is the
+    // report to print before exiting.
+    pub fn load(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let mut config = Config::default();
+        let (file, options) = match args.first() {
+            Some(first) if !first.starts_with("--") => (Some(first), &args[1..]),
+            _ => (None, args),
+        };
+
+        if let Some(file) = file {
+            let path = std::path::absolute(file).unwrap_or_else(|_| PathBuf::from(file));
+            let text = fs::read_to_string(&path).map_err(|e| {
+                format!(
+                    "Fatal error, can't open config file '{}': {e}",
+                    path.display()
+                )
+            })?;
+            config.apply_file(&text)?;
+            config.file = Some(path);
+        }
+
+        // The URL is also read from where it used to be
+        if let Some(url) = env("POSTGRES_URL") {
+            config.postgres_url = url;
+        }
+        for param in PARAMS {
+            let name = env_name(param.name);
+            if let Some(value) = env(&name) {
+                (param.set)(&mut config, &value).map_err(|e| {
+                    fatal(&format!("Reading the environment, at {name}"), &value, &e)
+                })?;
+            }
+        }
+
+        // Each option takes the arguments up to the next one
+        let mut options = options.iter().peekable();
+        while let Some(option) = options.next() {
+            let Some(name) = option.strip_prefix("--") else {
+                return Err(fatal(
+                    "Reading the command line",
+                    option,
+                    "Unexpected argument",
+                ));
+            };
+            let mut values = Vec::new();
+            while let Some(value) = options.next_if(|value| !value.starts_with("--")) {
+                values.push(value.clone());
+            }
+            config.apply(name, &values).map_err(|e| {
+                fatal(
+                    "Reading the command line",
+                    &format!("{option} {}", values.join(" ")),
+                    &e,
+                )
+            })?;
+        }
+        Ok(config)
+    }
+
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.bind
+            .iter()
+            .map(|&ip| SocketAddr::new(ip, self.port))
+            .collect()
+    }
+
+    // The parameters matching any of the patterns, with their values
+    pub fn get(&self, patterns: &[String]) -> Vec<(&'static str, String)> {
+        let patterns: Vec<_> = patterns.iter().map(|p| p.to_ascii_lowercase()).collect();
+        let matches = |name: &str| {
+            patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+        };
+        let mut values = Vec::new();
+        for param in PARAMS {
+            for name in [Some(param.name), param.alias].into_iter().flatten() {
+                if matches(name) {
+                    let value = (param.get)(self);
+                    let value = if param.secret {
+                        params::redact_password(&value)
+                    } else {
+                        value
+                    };
+                    values.push((name, value));
+                }
+            }
+        }
+        values
+    }
+
+    // Sets every parameter or none of them, returning the error to reply with
+    pub fn set(&mut self, pairs: &[(String, String)]) -> Result<(), String> {
+        let failed = |name: &str, reason: &str| {
+            format!("ERR CONFIG SET failed (possibly related to argument '{name}') - {reason}")
+        };
+        let mut updated = self.clone();
+        let mut seen = HashSet::new();
+        for (name, value) in pairs {
+            let Some(param) = params::find(name) else {
+                return Err(format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{name}'"
+                ));
+            };
+            if !param.mutable {
+                return Err(failed(name, "can't set immutable config"));
+            }
+            if !seen.insert(param.name) {
+                return Err(failed(name, "duplicate parameter"));
+            }
+            (param.set)(&mut updated, value).map_err(|e| failed(name, &e))?;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    // Writes the current values into the file the server was started with
+    pub fn rewrite(&self) -> Result<(), String> {
+        let Some(path) = &self.file else {
+            return Err("ERR The server is running without a config file".into());
+        };
+        rewrite::rewrite(self, path).map_err(|e| format!("ERR Rewriting config file: {e}"))
+    }
+
+    fn apply_file(&mut self, text: &str) -> Result<(), String> {
+        for (number, line) in text.lines().enumerate() {
+            // Blank lines and comments
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let at = format!("Reading the configuration file, at line {}", number + 1);
+            let args = split_args(trimmed.as_bytes())
+                .map_err(|_| fatal(&at, line, "Unbalanced quotes in configuration line"))?;
+            let Some((name, values)) = args.split_first() else {
+                continue;
+            };
+            let values: Vec<_> = values
+                .iter()
+                .map(|v| String::from_utf8_lossy(v).into_owned())
+                .collect();
+            self.apply(&String::from_utf8_lossy(name), &values)
+                .map_err(|e| fatal(&at, line, &e))?;
+        }
+        Ok(())
+    }
+
+    // Parameters taking a list, like bind, take it as several arguments
+    fn apply(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        match (params::find(name), values) {
+            (Some(param), [value]) => (param.set)(self, value),
+            (Some(param), [_, _, ..]) if param.list => (param.set)(self, &values.join(" ")),
+            _ => Err("Bad directive or wrong number of arguments".into()),
+        }
+    }
+}
+
+// The variable a parameter is read from, such as POSTGREDIS_PROTO_MAX_BULK_LEN
+fn env_name(name: &str) -> String {
+    format!("POSTGREDIS_{}", name.to_ascii_uppercase().replace('-', "_"))
+}
+
+// Reports a bad setting the way Redis does, which makes the server exit
+fn fatal(at: &str, line: &str, reason: &str) -> String {
+    format!("\n*** FATAL CONFIG FILE ERROR ***\n{at}\n>>> '{line}'\n{reason}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    // A configuration file in a directory of its own, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, text: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("postgredis-{name}-{}", process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("postgredis.conf");
+            fs::write(&path, text).unwrap();
+            TempFile(path)
+        }
+
+        fn arg(&self) -> String {
+            self.0.display().to_string()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.parent().unwrap());
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    fn get(config: &Config, pattern: &str) -> Vec<(&'static str, String)> {
+        config.get(&[pattern.to_string()])
+    }
+
+    #[test]
+    fn test_load() {
+        assert_eq!(Config::load(&[], no_env), Ok(Config::default()));
+
+        let file = TempFile::new(
+            "load",
+            "# Listen everywhere\n\
+             bind 0.0.0.0\n\
+             port 7000\n\
+             \n\
+             postgres-url \"host=db user=app\"\n\
+             proto-max-inline-len 16kb\n\
+             lua-time-limit 250\n",
+        );
+        let env = |name: &str| match name {
+            "POSTGREDIS_PORT" => Some("7001".to_string()),
+            "POSTGREDIS_STORAGE" => Some("memory".to_string()),
+            _ => None,
+        };
+        let config = Config::load(
+            &args(&[&file.arg(), "--port", "7002", "--loglevel", "warning"]),
+            env,
+        )
+        .unwrap();
+        assert_eq!(config.addrs(), ["0.0.0.0:7002".parse().unwrap()]);
+        assert_eq!(config.storage, StorageEngine::Memory);
+        assert_eq!(config.postgres_url, "host=db user=app");
+        assert_eq!(config.limits.inline_len, 16 * 1024);
+        assert_eq!(config.busy_reply_threshold, Duration::from_millis(250));
+        assert_eq!(config.loglevel, Level::Warning);
+        assert_eq!(config.file.as_deref(), Some(file.0.as_path()));
+
+        // The sample file documents the defaults
+        let sample = concat!(env!("CARGO_MANIFEST_DIR"), "/postgredis.conf");
+        let config = Config::load(&args(&[sample]), no_env).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                file: config.file.clone(),
+                ..Config::default()
+            }
+        );
+
+        // Several addresses may be bound, as separate arguments or in one
+        let config = Config::load(&args(&["--bind", "127.0.0.1", "::1"]), no_env).unwrap();
+        let env = |name: &str| (name == "POSTGREDIS_BIND").then(|| "127.0.0.1 ::1".to_string());
+        assert_eq!(Config::load(&[], env).unwrap(), config);
+        assert_eq!(
+            config.addrs(),
+            [
+                "127.0.0.1:6379".parse().unwrap(),
+                "[::1]:6379".parse().unwrap()
+            ]
+        );
+        assert!(Config::load(&args(&["--port", "7000", "7001"]), no_env).is_err());
+
+        // The old variable still sets the URL, unless the new one is set too
+        let env = |name: &str| (name == "POSTGRES_URL").then(|| "host=old".to_string());
+        assert_eq!(Config::load(&[], env).unwrap().postgres_url, "host=old");
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let error = Config::load(&args(&["--port", "http"]), no_env).unwrap_err();
+        assert!(error.contains(">>> '--port http'"));
+        assert!(error.contains("argument couldn't be parsed into an integer"));
+
+        let error = Config::load(&args(&["--port"]), no_env).unwrap_err();
+        assert!(error.contains("Bad directive or wrong number of arguments"));
+
+        let env = |name: &str| (name == "POSTGREDIS_ATOMIC_EXEC").then(|| "maybe".to_string());
+        let error = Config::load(&[], env).unwrap_err();
+        assert!(error.contains("Reading the environment, at POSTGREDIS_ATOMIC_EXEC"));
+
+        let file = TempFile::new("errors", "port 7000\nsave 900 1\n");
+        let error = Config::load(&args(&[&file.arg()]), no_env).unwrap_err();
+        assert!(error.contains("at line 2\n>>> 'save 900 1'"));
+
+        let error = Config::load(&args(&["/nonexistent/postgredis.conf"]), no_env).unwrap_err();
+        assert!(error.starts_with("Fatal error, can't open config file"));
+    }
+
+    #[test]
+    fn test_get() {
+        let config = Config::default();
+        assert_eq!(get(&config, "PORT"), vec![("port", "6379".to_string())]);
+        assert_eq!(
+            get(&config, "proto-max-*-len")
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            [
+                "proto-max-bulk-len",
+                "proto-max-multibulk-len",
+                "proto-max-inline-len"
+            ]
+        );
+        assert_eq!(
+            config.get(&["*time*".into(), "BUSY-*".into()]),
+            vec![
+                ("busy-reply-threshold", "5000".to_string()),
+                ("lua-time-limit", "5000".to_string()),
+                ("timeout", "0".to_string()),
+            ]
+        );
+        assert!(get(&config, "nothing-like-this").is_empty());
+
+        let config = Config {
+            postgres_url: "postgres://app:s3cret@db/app".into(),
+            ..Config::default()
+        };
+        assert_eq!(
+            get(&config, "postgres-url"),
+            vec![("postgres-url", "postgres://app:***@db/app".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+
+        config
+            .set(&pairs(&[("maxclients", "10"), ("ATOMIC-EXEC", "yes")]))
+            .unwrap();
+        assert_eq!(config.maxclients, 10);
+        assert!(config.atomic_exec);
+
+        // Nothing is set when any of the parameters fails
+        let before = config.clone();
+        assert_eq!(
+            config.set(&pairs(&[("timeout", "30"), ("loglevel", "loud")])),
+            Err(
+                "ERR CONFIG SET failed (possibly related to argument 'loglevel') - \
+                 argument must be one of the following: debug, verbose, notice, warning, nothing"
+                    .into()
+            )
+        );
+        assert_eq!(
+            config.set(&pairs(&[("timeout", "30"), ("port", "7000")])),
+            Err(
+                "ERR CONFIG SET failed (possibly related to argument 'port') - \
+                 can't set immutable config"
+                    .into()
+            )
+        );
+        assert_eq!(
+            config.set(&pairs(&[("timeout", "30"), ("TIMEOUT", "60")])),
+            Err(
+                "ERR CONFIG SET failed (possibly related to argument 'TIMEOUT') - \
+                 duplicate parameter"
+                    .into()
+            )
+        );
+        assert_eq!(
+            config.set(&pairs(&[("save", "")])),
+            Err("ERR Unknown option or number of arguments for CONFIG SET - 'save'".into())
+        );
+        assert_eq!(config, before);
+    }
+
+    #[test]
+    fn test_rewrite() {
+        assert_eq!(
+            Config::default().rewrite(),
+            Err("ERR The server is running without a config file".into())
+        );
+
+        let file = TempFile::new(
+            "rewrite",
+            "# Settings\n\
+             bind 127.0.0.1\n\
+             port 7000\n\
+             timeout 10\n\
+             \n\
+             # Overridden below\n\
+             TIMEOUT 20\n",
+        );
+        let mut config = Config::load(&args(&[&file.arg()]), no_env).unwrap();
+        assert_eq!(config.timeout, Duration::from_secs(20));
+        config
+            .set(&[
+                ("timeout".into(), "0".into()),
+                ("loglevel".into(), "verbose".into()),
+            ])
+            .unwrap();
+        config.bind.push("::1".parse().unwrap());
+        // The file keeps the password that CONFIG GET masks
+        config.postgres_url = "host=db user=app password=s3cret".into();
+        config.rewrite().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&file.0).unwrap(),
+            "# Settings\n\
+             bind 127.0.0.1 ::1\n\
+             port 7000\n\
+             timeout 0\n\
+             \n\
+             # Overridden below\n\
+             # Generated by CONFIG REWRITE\n\
+             postgres-url \"host=db user=app password=s3cret\"\n\
+             loglevel verbose\n"
+        );
+        assert_eq!(Config::load(&args(&[&file.arg()]), no_env).unwrap(), config);
+    }
+}
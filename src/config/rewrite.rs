@@ -0,0 +1,98 @@
+use super::Config;
+use super::params::{self, PARAMS, Param};
+use crate::resp::split_args;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::process;
+
+// Marks the settings CONFIG REWRITE adds at the end of the file
+const GENERATED: &str = "# Generated by CONFIG REWRITE";
+
+// Updates the lines of the file that set a parameter with the current values, keeping
+// comments and everything else in place, and appends the parameters changed from their
+// defaults that the file did not mention
+pub fn rewrite(config: &Config, path: &Path) -> io::Result<()> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    // Only the first line setting a parameter is kept, since the last one used to win
+    let mut written = HashSet::new();
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        match line_param(line) {
+            Some(param) if written.insert(param.name) => lines.push(setting(config, param)),
+            Some(_) => {}
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    let mut missing = PARAMS
+        .iter()
+        .filter(|param| !written.contains(param.name) && !is_default(config, param))
+        .peekable();
+    if missing.peek().is_some() && !lines.iter().any(|line| line == GENERATED) {
+        lines.push(GENERATED.to_string());
+    }
+    lines.extend(missing.map(|param| setting(config, param)));
+
+    // Renaming replaces the file at once, so a crash never leaves it half written
+    let mut text = lines.join("\n");
+    text.push('\n');
+    let temp = path.with_file_name(format!(".postgredis-rewrite-{}.tmp", process::id()));
+    fs::write(&temp, text)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+// The parameter a line of a configuration file sets, if any
+fn line_param(line: &str) -> Option<&'static Param> {
+    let trimmed = line.trim();
+    if trimmed.starts_with('#') {
+        return None;
+    }
+    let args = split_args(trimmed.as_bytes()).ok()?;
+    params::find(&String::from_utf8_lossy(args.first()?))
+}
+
+fn is_default(config: &Config, param: &Param) -> bool {
+    (param.get)(config) == (param.get)(&Config::default())
+}
+
+// A list is written as separate arguments, the way it is read
+fn setting(config: &Config, param: &Param) -> String {
+    let value = (param.get)(config);
+    let value = if param.list { value } else { quote(&value) };
+    format!("{} {value}", param.name)
+}
+
+// Wraps a value in double quotes when it would not read back as a single argument
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b'\'' | b'\\'));
+    if plain {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,151 @@
+// Matches text against a Redis-style glob pattern
+// Supports `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and backslash escapes
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+
+    // Position to resume from when a `*` needs to consume another byte
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    t += 1;
+                    continue;
+                }
+                b'[' => {
+                    if let Some((true, next)) = match_class(pattern, p, text[t]) {
+                        p = next;
+                        t += 1;
+                        continue;
+                    }
+                }
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == text[t] {
+                        p += 2;
+                        t += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == text[t] {
+                        p += 1;
+                        t += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // Mismatch, so let the last star swallow one more byte if possible
+        match backtrack {
+            Some((star_p, star_t)) => {
+                backtrack = Some((star_p, star_t + 1));
+                p = star_p + 1;
+                t = star_t + 1;
+            }
+            None => return false,
+        }
+    }
+
+    // Any trailing stars can match the empty remainder
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// Matches a single byte against the character class starting at `start`
+// Returns whether it matched and the pattern index just past the class
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negate = pattern.get(i) == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    loop {
+        match pattern.get(i)? {
+            b']' => break,
+            b'\\' => {
+                if *pattern.get(i + 1)? == c {
+                    matched = true;
+                }
+                i += 2;
+            }
+            &low if pattern.get(i + 1) == Some(&b'-')
+                && pattern.get(i + 2).is_some_and(|&b| b != b']') =>
+            {
+                let high = pattern[i + 2];
+                let (low, high) = if low <= high {
+                    (low, high)
+                } else {
+                    (high, low)
+                };
+                if (low..=high).contains(&c) {
+                    matched = true;
+                }
+                i += 3;
+            }
+            &other => {
+                if other == c {
+                    matched = true;
+                }
+                i += 1;
+            }
+        }
+    }
+
+    Some((matched != negate, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn test_literal() {
+        assert!(matches("hello", "hello"));
+        assert!(!matches("hello", "hell"));
+        assert!(!matches("hell", "hello"));
+    }
+
+    #[test]
+    fn test_star() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("h*llo", "heeeello"));
+        assert!(matches("user:*:name", "user:42:name"));
+        assert!(!matches("user:*:name", "user:42:email"));
+    }
+
+    #[test]
+    fn test_question_mark() {
+        assert!(matches("h?llo", "hallo"));
+        assert!(!matches("h?llo", "hllo"));
+    }
+
+    #[test]
+    fn test_class() {
+        assert!(matches("h[ae]llo", "hello"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(matches("h[a-c]llo", "hbllo"));
+        assert!(!matches("h[a-c]llo", "hdllo"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert!(matches("h\\*llo", "h*llo"));
+        assert!(!matches("h\\*llo", "hello"));
+    }
+}
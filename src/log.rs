@@ -0,0 +1,93 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// How much the server logs, from most to least verbose like Redis' loglevel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Verbose,
+    Notice,
+    Warning,
+    // Logs nothing at all, no message is ever logged at this level
+    Nothing,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Notice as u8);
+
+impl Level {
+    pub const ALL: [Level; 5] = [
+        Level::Debug,
+        Level::Verbose,
+        Level::Notice,
+        Level::Warning,
+        Level::Nothing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Verbose => "verbose",
+            Level::Notice => "notice",
+            Level::Warning => "warning",
+            Level::Nothing => "nothing",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    // The levels of redis.log, from redis.LOG_DEBUG to redis.LOG_WARNING
+    pub fn from_script(level: i64) -> Level {
+        match level {
+            ..=0 => Level::Debug,
+            1 => Level::Verbose,
+            2 => Level::Notice,
+            _ => Level::Warning,
+        }
+    }
+
+    // The mark Redis puts before a message of the level
+    fn mark(self) -> char {
+        match self {
+            Level::Debug => '.',
+            Level::Verbose => '-',
+            Level::Notice => '*',
+            Level::Warning | Level::Nothing => '#',
+        }
+    }
+}
+
+pub fn level() -> Level {
+    Level::ALL[usize::from(LEVEL.load(Ordering::Relaxed))]
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Nothing && level >= self::level()
+}
+
+// Warnings go to stderr, everything else to stdout
+pub fn write(level: Level, message: fmt::Arguments) {
+    if level >= Level::Warning {
+        eprintln!("{} {message}", level.mark());
+    } else {
+        println!("{} {message}", level.mark());
+    }
+}
+
+// Logs a message if the configured level lets messages of this level through
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($level, format_args!($($arg)*));
+        }
+    };
+}
+
+pub(crate) use log;
@@ -1,19 +1,79 @@
 #![warn(clippy::pedantic)]
 
+use crate::config::{Config, StorageEngine};
+use crate::log::{Level, log};
 use crate::server::Server;
-use std::net::SocketAddr;
+use crate::storage::{MemoryStorage, PostgresStorage, TransactionStorage};
+use std::{env, process};
 
 mod client;
 mod commands;
+mod config;
+mod glob;
+mod log;
 mod resp;
+mod scripting;
 mod server;
+mod storage;
+
+const USAGE: &str = "\
+Usage: postgredis [/path/to/postgredis.conf] [options]
+       postgredis -v or --version
+       postgredis -h or --help
+
+Examples:
+       postgredis (run with the default configuration)
+       postgredis /etc/postgredis/postgredis.conf
+       postgredis --port 7777 --storage memory
+       postgredis /etc/postgredis/postgredis.conf --loglevel verbose
+
+Options are the configuration file's directives, which can also be set in the
+environment as POSTGREDIS_PORT, POSTGREDIS_POSTGRES_URL and so on.";
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 6379));
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            return;
+        }
+        Some("-v" | "--version") => {
+            println!("postgredis v{}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        _ => {}
+    }
+
+    // The file, then the environment, then the command line
+    let config = Config::load(&args, |name| env::var(name).ok()).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
+    log::set_level(config.loglevel);
+
+    match config.storage {
+        StorageEngine::Memory => run(config, MemoryStorage::new()).await,
+        StorageEngine::Postgres => {
+            let storage = PostgresStorage::connect(&config.postgres_url, config.pool_size)
+                .await
+                .expect("Failed to connect to Postgres");
+            run(config, storage).await;
+        }
+    }
+}
 
-    let mut server = Server::new(addr).await;
-    println!("Server listening on {}", addr);
+async fn run<S: TransactionStorage + 'static>(config: Config, storage: S) {
+    let addrs: Vec<_> = config.addrs().iter().map(ToString::to_string).collect();
+    if let Some(file) = &config.file {
+        log!(
+            Level::Notice,
+            "Configuration loaded from {}",
+            file.display()
+        );
+    }
+    let mut server = Server::new(config, storage).await;
+    log!(Level::Notice, "Server listening on {}", addrs.join(", "));
 
     server.run().await;
 }
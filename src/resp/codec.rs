@@ -0,0 +1,181 @@
+use crate::resp::parser::{ParseError, Parser};
+use crate::resp::{ProtocolLimits, RespValue};
+use bytes::{Buf, BytesMut};
+use std::{fmt, io};
+use tokio_util::codec::{Decoder, Encoder};
+
+// Frames RESP values on a byte stream, for use with `Framed` and its halves
+#[derive(Debug, Clone, Default)]
+pub struct RespCodec {
+    limits: ProtocolLimits,
+    // The frame being received, if it has not arrived whole yet
+    parser: Parser,
+}
+
+impl RespCodec {
+    pub fn new(limits: ProtocolLimits) -> Self {
+        RespCodec {
+            limits,
+            parser: Parser::default(),
+        }
+    }
+
+    // Decodes the requests a client sends rather than any value
+    pub fn requests(limits: ProtocolLimits) -> Self {
+        RespCodec {
+            limits,
+            parser: Parser::requests(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    // The peer sent bytes that can never form a valid frame
+    Invalid(String),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodecError::Io(err) => write!(f, "{err}"),
+            CodecError::Invalid(message) => write!(f, "Protocol error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(err: io::Error) -> Self {
+        CodecError::Io(err)
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = RespValue;
+    type Error = CodecError;
+
+    // Consumes the next complete frame, leaving a partial one buffered until the rest
+    // of it arrives
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<RespValue>, CodecError> {
+        match self.parser.parse(src, &self.limits) {
+            Ok((value, len)) => {
+                src.advance(len);
+                Ok(Some(value))
+            }
+            Err(ParseError::Incomplete) => Ok(None),
+            Err(ParseError::Invalid(message)) => Err(CodecError::Invalid(message)),
+        }
+    }
+}
+
+impl Encoder<RespValue> for RespCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: RespValue, dst: &mut BytesMut) -> Result<(), CodecError> {
+        item.encode(dst);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::Framed;
+
+    #[tokio::test]
+    async fn test_framed() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut client = Framed::new(client, RespCodec::default());
+        let mut server = Framed::new(server, RespCodec::default());
+
+        let request = RespValue::Array(vec![
+            RespValue::BulkString(b"ECHO".to_vec()),
+            RespValue::BulkString(vec![b'x'; 1000]),
+        ]);
+        // The request is larger than the pipe, so it is only sent as it is received
+        let (sent, received) = tokio::join!(client.send(request.clone()), server.next());
+        sent.unwrap();
+        assert_eq!(received.unwrap().unwrap(), request);
+
+        client.get_mut().write_all(b"*1\r\n$x\r\n").await.unwrap();
+        let err = server.next().await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Protocol error: invalid bulk length");
+    }
+
+    #[test]
+    fn test_requests() {
+        let ping = RespValue::Array(vec![RespValue::BulkString(b"PING".to_vec())]);
+        let mut codec = RespCodec::requests(ProtocolLimits::default());
+
+        // Empty requests are skipped, even while the one after them is incomplete
+        let mut buffer = BytesMut::from(&b"*0\r\n*-1\r\n*1\r\n$4\r\nPI"[..]);
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(b"NG\r\n*0\r\n");
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(ping.clone()));
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(b"PING\r\n");
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(ping));
+
+        // Arguments can only be bulk strings, which is known from their first byte
+        for (input, got) in [
+            (&b"*1\r\n:1\r\n"[..], ':'),
+            (b"*2\r\n$4\r\nECHO\r\n*1\r\n", '*'),
+            (b"*2\r\n$4\r\nECHO\r\n+", '+'),
+        ] {
+            let mut codec = RespCodec::requests(ProtocolLimits::default());
+            let err = codec.decode(&mut BytesMut::from(input)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Protocol error: expected '$', got '{got}'")
+            );
+        }
+
+        // Any value decodes otherwise
+        let mut buffer = BytesMut::from(&b"*1\r\n:1\r\n*0\r\n"[..]);
+        let mut codec = RespCodec::default();
+        let one = RespValue::Array(vec![RespValue::Integer(1)]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(one));
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(RespValue::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_decode_byte_by_byte() {
+        let items = 100_000;
+        let mut input = format!("*{items}\r\n").into_bytes();
+        for _ in 0..items {
+            input.extend_from_slice(b"$1\r\nx\r\n");
+        }
+        // A long inline command after it, whose line is searched only once
+        input.extend(std::iter::repeat_n(b'a', 60 * 1024));
+        input.extend_from_slice(b"\r\n");
+
+        let mut codec = RespCodec::default();
+        let mut buffer = BytesMut::new();
+        let mut decoded = Vec::new();
+        for &byte in &input {
+            buffer.extend_from_slice(&[byte]);
+            if let Some(value) = codec.decode(&mut buffer).unwrap() {
+                decoded.push(value);
+            }
+        }
+        assert!(buffer.is_empty());
+        let [RespValue::Array(frame), RespValue::Array(inline)] = &decoded[..] else {
+            panic!("expected a frame and an inline command, got {decoded:?}");
+        };
+        assert_eq!(frame.len(), items);
+        assert!(
+            frame
+                .iter()
+                .all(|item| *item == RespValue::BulkString(b"x".to_vec()))
+        );
+        assert_eq!(inline[..], [RespValue::BulkString(vec![b'a'; 60 * 1024])]);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::resp::RespValue;
+use crate::resp::types::format_double;
+use bytes::{BufMut, BytesMut};
+use std::fmt::{Display, Write};
+
+impl RespValue {
+    // Appends the value in its wire format, copying bulk data byte for byte
+    pub fn encode(&self, buf: &mut BytesMut) {
+        match self {
+            RespValue::SimpleString(s) => line(buf, b'+', s),
+            RespValue::Error(e) => line(buf, b'-', e),
+            RespValue::Integer(i) => header(buf, b':', i),
+            RespValue::BulkString(bs) => blob(buf, b'$', bs),
+            RespValue::NullBulkString() => buf.put_slice(b"$-1\r\n"),
+            RespValue::Array(items) => aggregate(buf, b'*', items),
+            RespValue::NullArray() => buf.put_slice(b"*-1\r\n"),
+            RespValue::Null => buf.put_slice(b"_\r\n"),
+            RespValue::Boolean(b) => buf.put_slice(if *b { b"#t\r\n" } else { b"#f\r\n" }),
+            RespValue::Double(d) => header(buf, b',', format_double(*d)),
+            RespValue::BigNumber(n) => header(buf, b'(', n),
+            RespValue::VerbatimString { format, text } => {
+                header(buf, b'=', text.len() + 4);
+                buf.put_slice(format.as_bytes());
+                buf.put_u8(b':');
+                buf.put_slice(text);
+                buf.put_slice(b"\r\n");
+            }
+            RespValue::BlobError(e) => blob(buf, b'!', e),
+            RespValue::Map(pairs) => map(buf, b'%', pairs),
+            RespValue::Set(items) => aggregate(buf, b'~', items),
+            RespValue::Push(items) => aggregate(buf, b'>', items),
+            RespValue::Attribute { attributes, value } => {
+                map(buf, b'|', attributes);
+                value.encode(buf);
+            }
+        }
+    }
+}
+
+// A type byte followed by a number or other text that cannot contain CRLF
+fn header(buf: &mut BytesMut, prefix: u8, value: impl Display) {
+    buf.put_u8(prefix);
+    // Writing to a BytesMut cannot fail
+    let _ = write!(buf, "{value}\r\n");
+}
+
+// Simple strings and errors end at the first CRLF, so line breaks in them (as in
+// some script errors) are sent as spaces
+fn line(buf: &mut BytesMut, prefix: u8, text: &str) {
+    buf.put_u8(prefix);
+    buf.extend(
+        text.bytes()
+            .map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b }),
+    );
+    buf.put_slice(b"\r\n");
+}
+
+fn blob(buf: &mut BytesMut, prefix: u8, data: &[u8]) {
+    header(buf, prefix, data.len());
+    buf.put_slice(data);
+    buf.put_slice(b"\r\n");
+}
+
+fn aggregate(buf: &mut BytesMut, prefix: u8, items: &[RespValue]) {
+    header(buf, prefix, items.len());
+    for item in items {
+        item.encode(buf);
+    }
+}
+
+fn map(buf: &mut BytesMut, prefix: u8, pairs: &[(RespValue, RespValue)]) {
+    header(buf, prefix, pairs.len());
+    for (key, value) in pairs {
+        key.encode(buf);
+        value.encode(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp::RespCodec;
+    use tokio_util::codec::Decoder;
+
+    fn encoded(value: &RespValue) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        value.encode(&mut buf);
+        buf.to_vec()
+    }
+
+    #[test]
+    fn test_simple_types() {
+        let value = RespValue::SimpleString("OK".to_string());
+        assert_eq!(encoded(&value), b"+OK\r\n");
+        let value = RespValue::Error("ERR invalid syntax".to_string());
+        assert_eq!(encoded(&value), b"-ERR invalid syntax\r\n");
+        assert_eq!(encoded(&RespValue::Integer(-42)), b":-42\r\n");
+        assert_eq!(encoded(&RespValue::NullBulkString()), b"$-1\r\n");
+        assert_eq!(encoded(&RespValue::NullArray()), b"*-1\r\n");
+    }
+
+    #[test]
+    fn test_error_line_breaks() {
+        let value = RespValue::Error("ERR line 1\r\nline 2".to_string());
+        assert_eq!(encoded(&value), b"-ERR line 1  line 2\r\n");
+    }
+
+    #[test]
+    fn test_bulk_string() {
+        let value = RespValue::BulkString(b"foobar".to_vec());
+        assert_eq!(encoded(&value), b"$6\r\nfoobar\r\n");
+        let value = RespValue::BulkString(vec![0, 0xff, b'\r', b'\n', 0x80]);
+        assert_eq!(encoded(&value), b"$5\r\n\x00\xff\r\n\x80\r\n");
+    }
+
+    #[test]
+    fn test_array() {
+        let value = RespValue::Array(vec![
+            RespValue::SimpleString("OK".to_string()),
+            RespValue::Integer(42),
+        ]);
+        assert_eq!(encoded(&value), b"*2\r\n+OK\r\n:42\r\n");
+        assert_eq!(encoded(&RespValue::Array(Vec::new())), b"*0\r\n");
+    }
+
+    #[test]
+    fn test_resp3_types() {
+        assert_eq!(encoded(&RespValue::Null), b"_\r\n");
+        assert_eq!(encoded(&RespValue::Boolean(true)), b"#t\r\n");
+        assert_eq!(encoded(&RespValue::Double(1.5)), b",1.5\r\n");
+        assert_eq!(encoded(&RespValue::Double(f64::NEG_INFINITY)), b",-inf\r\n");
+        assert_eq!(encoded(&RespValue::BigNumber("123".into())), b"(123\r\n");
+        let verbatim = RespValue::VerbatimString {
+            format: "txt".into(),
+            text: b"hi".to_vec(),
+        };
+        assert_eq!(encoded(&verbatim), b"=6\r\ntxt:hi\r\n");
+        let map = RespValue::Map(vec![(
+            RespValue::SimpleString("a".into()),
+            RespValue::Integer(1),
+        )]);
+        assert_eq!(encoded(&map), b"%1\r\n+a\r\n:1\r\n");
+        let push = RespValue::Push(vec![RespValue::Integer(1)]);
+        assert_eq!(encoded(&push), b">1\r\n:1\r\n");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = RespValue::Array(vec![
+            RespValue::BulkString((0..=255).collect()),
+            RespValue::Map(vec![(
+                RespValue::BulkString(b"\xc3\x28".to_vec()),
+                RespValue::Set(vec![RespValue::Null, RespValue::Boolean(false)]),
+            )]),
+            RespValue::BlobError(b"ERR \xff".to_vec()),
+        ]);
+        let mut buffer = BytesMut::from(&encoded(&value)[..]);
+        assert_eq!(
+            RespCodec::default().decode(&mut buffer).unwrap(),
+            Some(value)
+        );
+    }
+}
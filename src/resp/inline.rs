@@ -0,0 +1,162 @@
+use crate::resp::parser::ParseError;
+
+// Parses a command typed on a line of its own, as telnet and netcat send them, into
+// its arguments and the number of bytes the line took. A blank line has no arguments.
+// The first `searched` bytes are known not to end the line and are skipped over.
+pub(super) fn parse(
+    buf: &[u8],
+    searched: usize,
+    max_len: usize,
+) -> Result<(Vec<Vec<u8>>, usize), ParseError> {
+    // The line ending does not count towards the length
+    let limited = &buf[..buf.len().min(max_len.saturating_add(2))];
+    let from = searched.min(limited.len());
+    let newline = limited[from..].iter().position(|&b| b == b'\n');
+    let Some(end) = newline.map(|i| from + i) else {
+        if limited.strip_suffix(b"\r").unwrap_or(limited).len() > max_len {
+            return Err(too_big());
+        }
+        return Err(ParseError::Incomplete);
+    };
+    let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+    if line.len() > max_len {
+        return Err(too_big());
+    }
+    Ok((split_args(line)?, end + 1))
+}
+
+// Splits a line on whitespace the way Redis does. Double quoted arguments may contain
+// escapes such as \n or \x41, and single quoted ones only \'.
+pub(crate) fn split_args(line: &[u8]) -> Result<Vec<Vec<u8>>, ParseError> {
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while line.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        match line[i] {
+            b'"' => {
+                i += 1;
+                loop {
+                    match *line.get(i).ok_or_else(unbalanced_quotes)? {
+                        b'"' => break,
+                        b'\\'
+                            if line.get(i + 1) == Some(&b'x')
+                                && let Some(byte) = line.get(i + 2..i + 4).and_then(hex_byte) =>
+                        {
+                            arg.push(byte);
+                            i += 3;
+                        }
+                        b'\\' if i + 1 < line.len() => {
+                            i += 1;
+                            arg.push(match line[i] {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                other => other,
+                            });
+                        }
+                        other => arg.push(other),
+                    }
+                    i += 1;
+                }
+                i = closing_quote(line, i)?;
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match *line.get(i).ok_or_else(unbalanced_quotes)? {
+                        b'\'' => break,
+                        b'\\' if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 1;
+                        }
+                        other => arg.push(other),
+                    }
+                    i += 1;
+                }
+                i = closing_quote(line, i)?;
+            }
+            _ => {
+                while let Some(&b) = line.get(i).filter(|b| !b.is_ascii_whitespace()) {
+                    arg.push(b);
+                    i += 1;
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+// A closing quote must end the argument, so `"a"b` is as unbalanced as `"a`
+fn closing_quote(line: &[u8], quote: usize) -> Result<usize, ParseError> {
+    match line.get(quote + 1) {
+        Some(b) if !b.is_ascii_whitespace() => Err(unbalanced_quotes()),
+        _ => Ok(quote + 1),
+    }
+}
+
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+fn too_big() -> ParseError {
+    ParseError::Invalid("too big inline request".into())
+}
+
+fn unbalanced_quotes() -> ParseError {
+    ParseError::Invalid("unbalanced quotes in request".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        split_args(line.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|arg| String::from_utf8(arg).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(args("  set  key\tvalue "), vec!["set", "key", "value"]);
+        assert_eq!(args(""), Vec::<String>::new());
+        assert_eq!(
+            args(r#"set "a b\n\x41\"" 'it\'s "x"' """#),
+            vec!["set", "a b\nA\"", "it's \"x\"", ""]
+        );
+        // An incomplete hex escape is taken literally
+        assert_eq!(args(r#""\x4" "\q""#), vec!["x4", "q"]);
+
+        for line in [r#"get "key"#, "get 'key", r#"get "a"b"#, "get 'a'b"] {
+            assert_eq!(split_args(line.as_bytes()), Err(unbalanced_quotes()));
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(b"PING\r\nECHO", 0, 64),
+            Ok((vec![b"PING".to_vec()], 6))
+        );
+        assert_eq!(
+            parse(b"ECHO hi\n", 0, 64),
+            Ok((vec![b"ECHO".to_vec(), b"hi".to_vec()], 8))
+        );
+        assert_eq!(parse(b"\r\n", 0, 64), Ok((Vec::new(), 2)));
+        assert_eq!(parse(b"PING", 0, 64), Err(ParseError::Incomplete));
+        assert_eq!(parse(b"PING\r", 0, 4), Err(ParseError::Incomplete));
+        assert_eq!(parse(b"PING\r\n", 0, 4), Ok((vec![b"PING".to_vec()], 6)));
+        assert_eq!(parse(b"PINGS", 0, 4), Err(too_big()));
+    }
+}
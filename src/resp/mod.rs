@@ -1,5 +1,10 @@
+mod codec;
+mod encoder;
+mod inline;
 mod parser;
 mod types;
 
-pub use parser::RespParser;
-pub use types::RespValue;
+pub use codec::{CodecError, RespCodec};
+pub(crate) use inline::split_args;
+pub use parser::ProtocolLimits;
+pub use types::{Protocol, RespValue};
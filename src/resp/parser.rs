@@ -1,137 +1,442 @@
-use crate::resp::RespValue;
+use crate::resp::{RespValue, inline};
+use std::fmt;
 
-pub struct RespParser {
-    buffer: Vec<u8>,
+// The bytes a frame may start with. Input starting with anything else is an inline
+// command.
+const TYPE_BYTES: &[u8] = b"+-:$*_#,(=!%~>|";
+
+// Aggregates announcing more elements than this only reserve room for this many up
+// front, so a huge count cannot allocate memory before the elements arrive
+const MAX_PREALLOCATED_ITEMS: usize = 1024;
+
+// Bounds on what a client may send, checked before anything is allocated for it, so a
+// single client cannot exhaust the server's memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    // The longest bulk string, like Redis' proto-max-bulk-len
+    pub bulk_len: usize,
+    // The most elements an aggregate may have
+    pub multibulk_len: usize,
+    // The longest inline command, or line such as a frame's header
+    pub inline_len: usize,
+    // How deeply aggregates may nest, which also bounds the recursion when the parsed
+    // value is dropped
+    pub depth: usize,
 }
 
-impl RespParser {
-    pub fn new() -> Self {
-        RespParser { buffer: Vec::new() }
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        ProtocolLimits {
+            bulk_len: 512 * 1024 * 1024,
+            multibulk_len: 1024 * 1024,
+            inline_len: 64 * 1024,
+            depth: 128,
+        }
     }
+}
 
-    pub fn append(&mut self, data: &[u8]) {
-        self.buffer.extend_from_slice(data);
-    }
+// Why no value could be parsed from the start of a buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // The buffer holds the start of a valid frame, which needs more bytes
+    Incomplete,
+    // The buffer can never become a valid frame, however many bytes follow
+    Invalid(String),
+}
 
-    pub fn parse(&mut self) -> Option<RespValue> {
-        if self.buffer.is_empty() {
-            return None;
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "incomplete frame"),
+            ParseError::Invalid(message) => write!(f, "Protocol error: {message}"),
         }
+    }
+}
+
+// An aggregate whose elements are still being parsed
+#[derive(Debug, Clone)]
+struct Pending {
+    kind: u8,
+    len: usize,
+    items: Vec<RespValue>,
+}
+
+// Parses the frame or inline command at the start of `buf`, returning it with the
+// number of bytes it took. An inline command becomes an array of bulk strings, just as
+// if it had been sent as a frame, and blank lines before it are skipped.
+#[cfg(test)]
+pub fn parse(buf: &[u8], limits: &ProtocolLimits) -> Result<(RespValue, usize), ParseError> {
+    Parser::default().parse(buf, limits)
+}
+
+// Parses a value that may arrive over several reads. What was parsed of it is kept
+// between calls, so each call only parses the bytes that arrived since instead of
+// starting over, which would take quadratic time for a large frame sent in pieces.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    // Aggregates still waiting for elements
+    stack: Vec<Pending>,
+    // The bytes taken by the elements parsed so far and the blank lines skipped
+    pos: usize,
+    // How far the buffer was searched for the end of a line without finding it
+    searched: usize,
+    // Whether only requests are accepted, as clients send them: arrays of bulk strings
+    requests: bool,
+}
 
-        match self.buffer[0] {
-            b'+' => self.parse_simple_string(),
-            b'-' => self.parse_error(),
-            b':' => self.parse_integer(),
-            b'$' => self.parse_bulk_string(),
-            b'*' => self.parse_array(),
-            _ => None,
+impl Parser {
+    // A parser of the requests a client sends, which like Redis refuses arrays holding
+    // anything but bulk strings and skips empty ones
+    pub fn requests() -> Self {
+        Parser {
+            requests: true,
+            ..Parser::default()
         }
     }
 
-    // A simple string starts with '+' and ends with CRLF
-    fn parse_simple_string(&mut self) -> Option<RespValue> {
-        let end = find_crlf(&self.buffer)?;
-        let text = String::from_utf8(self.buffer[1..end].to_vec()).ok()?;
-        self.buffer.drain(..end + 2);
-        Some(RespValue::SimpleString(text))
-    }
-
-    // An error starts with '-' and ends with CRLF
-    // It is the same as a simple string with a different start character
-    fn parse_error(&mut self) -> Option<RespValue> {
-        let end = find_crlf(&self.buffer)?;
-        let message = String::from_utf8(self.buffer[1..end].to_vec()).ok()?;
-        self.buffer.drain(..end + 2);
-        Some(RespValue::Error(message))
-    }
-
-    // An integer starts with ':' and ends with CRLF
-    // It is the same as a simple string but will be an integer value
-    fn parse_integer(&mut self) -> Option<RespValue> {
-        let end = find_crlf(&self.buffer)?;
-        let payload = String::from_utf8(self.buffer[1..end].to_vec()).ok()?;
-        self.buffer.drain(..end + 2);
-        let int_value = payload.parse::<i64>().ok()?;
-        Some(RespValue::Integer(int_value))
-    }
-
-    // A bulk string starts with '$', length, CRLF, data, and ends with another CRLF
-    // It is used to transmit a binary-safe string up to 512MB
-    fn parse_bulk_string(&mut self) -> Option<RespValue> {
-        let len_end = find_crlf(&self.buffer)?;
-        let len_string = String::from_utf8(self.buffer[1..len_end].to_vec()).ok()?;
-        let len: isize = len_string.parse().ok()?;
-        let header_size = len_end + 2;
-
-        if len < 0 {
-            self.buffer.drain(..header_size);
-            return Some(RespValue::NullBulkString());
+    // Like `parse`, resuming where the previous call stopped if it was incomplete. The
+    // buffer must hold the same bytes as then, with any new ones appended.
+    pub fn parse(
+        &mut self,
+        buf: &[u8],
+        limits: &ProtocolLimits,
+    ) -> Result<(RespValue, usize), ParseError> {
+        loop {
+            let result = self.resume(buf, limits);
+            if result == Err(ParseError::Incomplete) {
+                return result;
+            }
+            *self = Parser {
+                requests: self.requests,
+                ..Parser::default()
+            };
+            match result {
+                Ok((RespValue::Array(items), len)) if self.requests && items.is_empty() => {
+                    self.pos = len;
+                }
+                Ok((RespValue::NullArray(), len)) if self.requests => self.pos = len,
+                result => return result,
+            }
         }
+    }
 
-        let total_size = header_size + (len as usize) + 2;
-        if self.buffer.len() < total_size {
-            return None;
+    fn resume(
+        &mut self,
+        buf: &[u8],
+        limits: &ProtocolLimits,
+    ) -> Result<(RespValue, usize), ParseError> {
+        while self.stack.is_empty() {
+            let rest = &buf[self.pos..];
+            match rest.first() {
+                None => return Err(ParseError::Incomplete),
+                Some(b) if TYPE_BYTES.contains(b) => break,
+                Some(_) => {}
+            }
+            let searched = self.searched.saturating_sub(self.pos);
+            let (args, len) = match inline::parse(rest, searched, limits.inline_len) {
+                Err(ParseError::Incomplete) => {
+                    self.searched = buf.len();
+                    return Err(ParseError::Incomplete);
+                }
+                parsed => parsed?,
+            };
+            self.pos += len;
+            if !args.is_empty() {
+                let args = args.into_iter().map(RespValue::BulkString).collect();
+                return Ok((RespValue::Array(args), self.pos));
+            }
         }
+        self.parse_frame(buf, limits)
+    }
 
-        let data = self.buffer[header_size..header_size + (len as usize)].to_vec();
-        if &self.buffer[header_size + (len as usize)..total_size] != b"\r\n" {
-            return None;
+    // Aggregates are kept on a stack rather than parsed recursively, so deeply nested
+    // frames cannot overflow the call stack
+    fn parse_frame(
+        &mut self,
+        buf: &[u8],
+        limits: &ProtocolLimits,
+    ) -> Result<(RespValue, usize), ParseError> {
+        let mut cursor = Cursor {
+            buf,
+            pos: self.pos,
+            searched: self.searched,
+            limits,
+        };
+        loop {
+            if self.requests
+                && !self.stack.is_empty()
+                && let Some(&kind) = buf.get(cursor.pos)
+                && kind != b'$'
+            {
+                return Err(invalid(&format!(
+                    "expected '$', got '{}'",
+                    kind.escape_ascii()
+                )));
+            }
+            // An incomplete element is parsed again from its start next time
+            let frame = match cursor.next_frame() {
+                Err(ParseError::Incomplete) => {
+                    self.searched = cursor.searched;
+                    return Err(ParseError::Incomplete);
+                }
+                frame => frame?,
+            };
+            let mut value = match frame {
+                Frame::Value(value) => value,
+                Frame::Aggregate { kind, len } => {
+                    if self.stack.len() >= limits.depth {
+                        return Err(invalid("aggregates nested too deeply"));
+                    }
+                    self.stack.push(Pending {
+                        kind,
+                        len,
+                        items: Vec::with_capacity(len.min(MAX_PREALLOCATED_ITEMS)),
+                    });
+                    self.pos = cursor.pos;
+                    continue;
+                }
+            };
+            // A value may complete the aggregates it closes, innermost first
+            loop {
+                let Some(pending) = self.stack.last_mut() else {
+                    return Ok((value, cursor.pos));
+                };
+                pending.items.push(value);
+                if pending.items.len() < pending.len {
+                    break;
+                }
+                let pending = self.stack.pop().expect("the aggregate was just completed");
+                value = aggregate(pending.kind, pending.items);
+            }
+            self.pos = cursor.pos;
         }
-        self.buffer.drain(..total_size);
-        Some(RespValue::BulkString(data))
     }
+}
+
+// What the header of a frame introduced
+enum Frame {
+    Value(RespValue),
+    // An aggregate of `len` values (twice its count for maps), none of them parsed yet
+    Aggregate { kind: u8, len: usize },
+}
 
-    // An array starts with '*', count, CRLF, and then each RESP type serialized for count
-    fn parse_array(&mut self) -> Option<RespValue> {
-        let header_size = find_crlf(&self.buffer)?;
-        let count_string = String::from_utf8(self.buffer[1..header_size].to_vec()).ok()?;
-        let count: isize = count_string.parse().ok()?;
-        let mut offset = header_size + 2;
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    // How far the buffer is known to have no line end, from an earlier incomplete parse
+    searched: usize,
+    limits: &'a ProtocolLimits,
+}
 
-        if count < 0 {
-            self.buffer.drain(..offset);
-            return Some(RespValue::NullArray());
+impl Cursor<'_> {
+    fn next_frame(&mut self) -> Result<Frame, ParseError> {
+        let kind = *self.buf.get(self.pos).ok_or(ParseError::Incomplete)?;
+        self.pos += 1;
+        let value = match kind {
+            // A simple string or error runs to the CRLF
+            b'+' => RespValue::SimpleString(self.line_text()?),
+            b'-' => RespValue::Error(self.line_text()?),
+            b':' => RespValue::Integer(self.line_number("integer")?),
+            b'$' => match self.length("bulk", self.limits.bulk_len)? {
+                Some(len) => RespValue::BulkString(self.blob(len)?.to_vec()),
+                None => RespValue::NullBulkString(),
+            },
+            b'*' => match self.length("multibulk", self.limits.multibulk_len)? {
+                Some(0) => RespValue::Array(Vec::new()),
+                Some(len) => return Ok(Frame::Aggregate { kind, len }),
+                None => RespValue::NullArray(),
+            },
+            b'_' => {
+                if !self.line()?.is_empty() {
+                    return Err(invalid("invalid null"));
+                }
+                RespValue::Null
+            }
+            b'#' => match self.line()? {
+                b"t" => RespValue::Boolean(true),
+                b"f" => RespValue::Boolean(false),
+                _ => return Err(invalid("invalid boolean")),
+            },
+            b',' => RespValue::Double(match self.line_text()?.as_str() {
+                "inf" => f64::INFINITY,
+                "-inf" => f64::NEG_INFINITY,
+                "nan" => f64::NAN,
+                text => text.parse().map_err(|_| invalid("invalid double"))?,
+            }),
+            b'(' => {
+                let text = self.line_text()?;
+                let digits = text.strip_prefix('-').unwrap_or(&text);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid("invalid big number"));
+                }
+                RespValue::BigNumber(text)
+            }
+            // A verbatim string's data starts with its three letter format and ':'
+            b'=' => {
+                let len = self.required_length("verbatim string", self.limits.bulk_len)?;
+                let data = self.blob(len)?;
+                if data.len() < 4 || data[3] != b':' {
+                    return Err(invalid("invalid verbatim string format"));
+                }
+                RespValue::VerbatimString {
+                    format: utf8(&data[..3])?,
+                    text: data[4..].to_vec(),
+                }
+            }
+            b'!' => {
+                let len = self.required_length("blob error", self.limits.bulk_len)?;
+                RespValue::BlobError(self.blob(len)?.to_vec())
+            }
+            // Maps and attributes count pairs, and an attribute precedes the value it
+            // describes
+            b'%' | b'~' | b'>' | b'|' => {
+                let count = self.required_length("aggregate", self.limits.multibulk_len)?;
+                let len = match kind {
+                    b'%' => count.checked_mul(2),
+                    b'|' => count.checked_mul(2).and_then(|n| n.checked_add(1)),
+                    _ => Some(count),
+                }
+                .ok_or_else(|| invalid("invalid aggregate length"))?;
+                if len == 0 {
+                    aggregate(kind, Vec::new())
+                } else {
+                    return Ok(Frame::Aggregate { kind, len });
+                }
+            }
+            other => {
+                return Err(invalid(&format!(
+                    "unknown type byte '{}'",
+                    other.escape_ascii()
+                )));
+            }
+        };
+        Ok(Frame::Value(value))
+    }
+
+    // The bytes up to the next CRLF, which is consumed along with them. Only as far as
+    // the longest line allowed is searched, so a line that never ends is not buffered.
+    fn line(&mut self) -> Result<&[u8], ParseError> {
+        let rest = &self.buf[self.pos..];
+        let limited = &rest[..rest.len().min(self.limits.inline_len.saturating_add(2))];
+        let from = self.searched.saturating_sub(self.pos).min(limited.len());
+        let crlf = limited[from..]
+            .windows(2)
+            .position(|window| window == b"\r\n");
+        let Some(end) = crlf.map(|i| from + i) else {
+            if limited.strip_suffix(b"\r").unwrap_or(limited).len() > self.limits.inline_len {
+                return Err(invalid("too big line"));
+            }
+            // A trailing CR may yet be followed by its LF
+            self.searched = self.pos + limited.len().saturating_sub(1);
+            return Err(ParseError::Incomplete);
+        };
+        self.pos += end + 2;
+        Ok(&rest[..end])
+    }
+
+    fn line_text(&mut self) -> Result<String, ParseError> {
+        utf8(self.line()?)
+    }
+
+    fn line_number(&mut self, what: &str) -> Result<i64, ParseError> {
+        let line = self.line()?;
+        std::str::from_utf8(line)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| invalid(&format!("invalid {what}")))
+    }
+
+    // A length of at most `max`, or -1 for null as bulk strings and arrays allow
+    fn length(&mut self, what: &str, max: usize) -> Result<Option<usize>, ParseError> {
+        match self.line_number(&format!("{what} length"))? {
+            -1 => Ok(None),
+            len => usize::try_from(len)
+                .ok()
+                .filter(|&len| len <= max)
+                .map(Some)
+                .ok_or_else(|| invalid(&format!("invalid {what} length"))),
         }
+    }
 
-        let mut items = Vec::with_capacity(count as usize);
-        for _ in 0..count {
-            let slice = self.buffer[offset..].to_vec();
-            let slice_len = slice.len();
+    fn required_length(&mut self, what: &str, max: usize) -> Result<usize, ParseError> {
+        self.length(what, max)?
+            .ok_or_else(|| invalid(&format!("invalid {what} length")))
+    }
 
-            let mut item_parser = RespParser { buffer: slice };
+    // `len` bytes of data followed by CRLF
+    fn blob(&mut self, len: usize) -> Result<&[u8], ParseError> {
+        let start = self.pos;
+        let end = start.checked_add(len).ok_or(ParseError::Incomplete)?;
+        let terminator = self.buf.get(end..end + 2).ok_or(ParseError::Incomplete)?;
+        if terminator != b"\r\n" {
+            return Err(invalid("bulk data not terminated by CRLF"));
+        }
+        self.pos = end + 2;
+        Ok(&self.buf[start..end])
+    }
+}
 
-            if let Some(item) = item_parser.parse() {
-                let consumed = slice_len - item_parser.buffer.len();
-                offset += consumed;
-                items.push(item);
+fn aggregate(kind: u8, items: Vec<RespValue>) -> RespValue {
+    match kind {
+        b'*' => RespValue::Array(items),
+        b'~' => RespValue::Set(items),
+        b'>' => RespValue::Push(items),
+        b'%' => RespValue::Map(pairs(items)),
+        _ => {
+            let mut items = items;
+            let value = Box::new(items.pop().unwrap_or(RespValue::Null));
+            RespValue::Attribute {
+                attributes: pairs(items),
+                value,
             }
         }
+    }
+}
 
-        self.buffer.drain(..offset);
-        Some(RespValue::Array(items))
+// Pairs up the keys and values of a map, which alternate
+fn pairs(items: Vec<RespValue>) -> Vec<(RespValue, RespValue)> {
+    let mut items = items.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+        pairs.push((key, value));
     }
+    pairs
 }
 
-fn find_crlf(buffer: &[u8]) -> Option<usize> {
-    buffer.windows(2).position(|window| window == b"\r\n")
+fn utf8(bytes: &[u8]) -> Result<String, ParseError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid utf-8"))
+}
+
+fn invalid(message: &str) -> ParseError {
+    ParseError::Invalid(message.into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::resp::RespCodec;
+    use bytes::BytesMut;
+    use proptest::prelude::*;
+    use tokio_util::codec::Decoder;
 
     // Parses all possible values from an input buffer
     fn parse_all(buffer: &[u8]) -> Vec<RespValue> {
-        let mut parser = RespParser::new();
-        parser.append(buffer);
+        let mut codec = RespCodec::default();
+        let mut buffer = BytesMut::from(buffer);
 
         let mut results = Vec::new();
-        while let Some(item) = parser.parse() {
+        while let Ok(Some(item)) = codec.decode(&mut buffer) {
             results.push(item);
         }
         results
     }
 
+    fn encoded(value: &RespValue) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        value.encode(&mut buf);
+        buf.to_vec()
+    }
+
     #[test]
     fn test_simple_string() {
         let got = parse_all(b"+OK\r\n");
@@ -194,4 +499,249 @@ mod tests {
         let got = parse_all(b"*-1\r\n");
         assert_eq!(got, vec![RespValue::NullArray()]);
     }
+
+    #[test]
+    fn test_array_incomplete() {
+        let mut codec = RespCodec::default();
+        let mut buffer = BytesMut::from(&b"*2\r\n$3\r\nfoo\r\n$3\r\nb"[..]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        assert_eq!(buffer.len(), 18);
+        buffer.extend_from_slice(b"ar\r\n");
+        assert_eq!(
+            codec.decode(&mut buffer).unwrap(),
+            Some(RespValue::Array(vec![
+                RespValue::BulkString(b"foo".to_vec()),
+                RespValue::BulkString(b"bar".to_vec()),
+            ]))
+        );
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for input in [
+            &b"*1\r\n?\r\n"[..],
+            b":12a\r\n",
+            b"$-2\r\n",
+            b"$3\r\nfoobar\r\n",
+            b"*-5\r\n",
+            b"*1\r\n$x\r\n",
+            b"#x\r\n",
+            b"=3\r\ntxt\r\n",
+            b"+\xff\r\n",
+        ] {
+            let got = parse(input, &ProtocolLimits::default());
+            assert!(
+                matches!(got, Err(ParseError::Invalid(_))),
+                "{input:?} parsed as {got:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_inline() {
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        let got = parse_all(b"PING\r\n\r\n\nset k \"a b\"\n*1\r\n$4\r\nPING\r\nECHO");
+        assert_eq!(
+            got,
+            vec![
+                RespValue::Array(vec![bulk("PING")]),
+                RespValue::Array(vec![bulk("set"), bulk("k"), bulk("a b")]),
+                RespValue::Array(vec![bulk("PING")]),
+            ]
+        );
+        assert_eq!(
+            parse(b"get \"k\r\n", &ProtocolLimits::default()),
+            Err(ParseError::Invalid("unbalanced quotes in request".into()))
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = ProtocolLimits {
+            bulk_len: 3,
+            multibulk_len: 2,
+            inline_len: 8,
+            depth: 2,
+        };
+        let parse = |input: &[u8]| parse(input, &limits);
+        let invalid = |message: &str| Err(ParseError::Invalid(message.into()));
+
+        assert!(parse(b"$3\r\nfoo\r\n").is_ok());
+        assert_eq!(parse(b"$4\r\n"), invalid("invalid bulk length"));
+        assert_eq!(parse(b"!4\r\n"), invalid("invalid blob error length"));
+        assert_eq!(parse(b"*3\r\n"), invalid("invalid multibulk length"));
+        assert_eq!(parse(b"%3\r\n"), invalid("invalid aggregate length"));
+        assert_eq!(
+            parse(b"*1\r\n*1\r\n*1\r\n"),
+            invalid("aggregates nested too deeply")
+        );
+        assert!(parse(b"*1\r\n*1\r\n:1\r\n").is_ok());
+
+        // Lines are rejected as soon as they are too long to end in time
+        assert_eq!(parse(b"+12345678\r"), Err(ParseError::Incomplete));
+        assert!(parse(b"+12345678\r\n").is_ok());
+        assert_eq!(parse(b"+123456789"), invalid("too big line"));
+        assert_eq!(parse(b"$00000000000"), invalid("too big line"));
+        assert_eq!(parse(b"GET 1234"), Err(ParseError::Incomplete));
+        assert_eq!(parse(b"GET 12345"), invalid("too big inline request"));
+        assert_eq!(parse(b"GET 12345\n"), invalid("too big inline request"));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 5_000;
+        let mut input = b"*1\r\n".repeat(depth);
+        input.extend_from_slice(b":1\r\n");
+        let limits = ProtocolLimits {
+            depth,
+            ..ProtocolLimits::default()
+        };
+        let (mut value, len) = parse(&input, &limits).unwrap();
+        assert_eq!(len, input.len());
+
+        // Unwrapped level by level, since dropping it whole would recurse as deep
+        let mut levels = 0;
+        while let RespValue::Array(mut items) = value {
+            value = items.pop().unwrap();
+            levels += 1;
+        }
+        assert_eq!(levels, depth);
+        assert_eq!(value, RespValue::Integer(1));
+    }
+
+    #[test]
+    fn test_resp3_simple_types() {
+        let got = parse_all(b"_\r\n#t\r\n#f\r\n,1.5\r\n,-inf\r\n(-12345678901234567890\r\n");
+        assert_eq!(
+            got,
+            vec![
+                RespValue::Null,
+                RespValue::Boolean(true),
+                RespValue::Boolean(false),
+                RespValue::Double(1.5),
+                RespValue::Double(f64::NEG_INFINITY),
+                RespValue::BigNumber("-12345678901234567890".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resp3_blobs() {
+        let got = parse_all(b"=8\r\ntxt:Some\r\n!10\r\nERR failed\r\n");
+        assert_eq!(
+            got,
+            vec![
+                RespValue::VerbatimString {
+                    format: "txt".into(),
+                    text: b"Some".to_vec(),
+                },
+                RespValue::BlobError(b"ERR failed".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resp3_aggregates() {
+        let got = parse_all(b"%1\r\n+a\r\n:1\r\n~2\r\n:1\r\n:2\r\n>1\r\n+hi\r\n%0\r\n");
+        assert_eq!(
+            got,
+            vec![
+                RespValue::Map(vec![(
+                    RespValue::SimpleString("a".into()),
+                    RespValue::Integer(1)
+                )]),
+                RespValue::Set(vec![RespValue::Integer(1), RespValue::Integer(2)]),
+                RespValue::Push(vec![RespValue::SimpleString("hi".into())]),
+                RespValue::Map(Vec::new()),
+            ]
+        );
+
+        let got = parse_all(b"|1\r\n+ttl\r\n:3\r\n$2\r\nok\r\n");
+        assert_eq!(
+            got,
+            vec![RespValue::Attribute {
+                attributes: vec![(RespValue::SimpleString("ttl".into()), RespValue::Integer(3))],
+                value: Box::new(RespValue::BulkString(b"ok".to_vec())),
+            }]
+        );
+    }
+
+    // Any value the encoder can produce, with text that survives it unchanged and
+    // doubles that compare equal to themselves
+    fn any_value() -> impl Strategy<Value = RespValue> {
+        let text = "[a-zA-Z0-9 :_-]{0,12}";
+        let leaf = prop_oneof![
+            text.prop_map(RespValue::SimpleString),
+            text.prop_map(RespValue::Error),
+            any::<i64>().prop_map(RespValue::Integer),
+            proptest::collection::vec(any::<u8>(), 0..24).prop_map(RespValue::BulkString),
+            Just(RespValue::NullBulkString()),
+            Just(RespValue::NullArray()),
+            Just(RespValue::Null),
+            any::<bool>().prop_map(RespValue::Boolean),
+            prop_oneof![
+                any::<f64>().prop_filter("NaN", |d| !d.is_nan()),
+                Just(f64::INFINITY)
+            ]
+            .prop_map(RespValue::Double),
+            "-?[0-9]{1,30}".prop_map(RespValue::BigNumber),
+            ("[a-z]{3}", proptest::collection::vec(any::<u8>(), 0..16))
+                .prop_map(|(format, text)| RespValue::VerbatimString { format, text }),
+            proptest::collection::vec(any::<u8>(), 0..16).prop_map(RespValue::BlobError),
+        ];
+        leaf.prop_recursive(4, 32, 6, |inner| {
+            let items = proptest::collection::vec(inner.clone(), 0..6);
+            let pairs = proptest::collection::vec((inner.clone(), inner.clone()), 0..4);
+            prop_oneof![
+                items.clone().prop_map(RespValue::Array),
+                items.clone().prop_map(RespValue::Set),
+                items.prop_map(RespValue::Push),
+                pairs.clone().prop_map(RespValue::Map),
+                (pairs, inner).prop_map(|(attributes, value)| RespValue::Attribute {
+                    attributes,
+                    value: Box::new(value),
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        // However the bytes of pipelined frames are split across reads, nothing is
+        // consumed until a frame is complete and every frame comes out whole
+        #[test]
+        fn prop_split_anywhere(first in any_value(), second in any_value()) {
+            let mut input = encoded(&first);
+            let first_len = input.len();
+            input.extend(encoded(&second));
+
+            for split in 0..=input.len() {
+                let mut codec = RespCodec::default();
+                let mut buffer = BytesMut::from(&input[..split]);
+                let mut parsed = Vec::new();
+                while let Some(value) = codec.decode(&mut buffer).unwrap() {
+                    parsed.push(value);
+                }
+                let complete = usize::from(split >= first_len) + usize::from(split == input.len());
+                prop_assert_eq!(parsed.len(), complete);
+                let consumed = [0, first_len, input.len()][complete];
+                prop_assert_eq!(buffer.len(), split - consumed);
+
+                buffer.extend_from_slice(&input[split..]);
+                while let Some(value) = codec.decode(&mut buffer).unwrap() {
+                    parsed.push(value);
+                }
+                prop_assert_eq!(&parsed, &vec![first.clone(), second.clone()]);
+                prop_assert!(buffer.is_empty());
+            }
+        }
+
+        // Arbitrary bytes never panic the parser, and a parsed frame fits in them
+        #[test]
+        fn prop_garbage(input in proptest::collection::vec(any::<u8>(), 0..64)) {
+            if let Ok((_, len)) = parse(&input, &ProtocolLimits::default()) {
+                prop_assert!(len <= input.len());
+            }
+        }
+    }
 }
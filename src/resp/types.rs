@@ -1,6 +1,23 @@
 use std::fmt;
 
-#[derive(Clone, PartialEq, Eq)]
+// The protocol version a connection speaks, negotiated with HELLO
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    pub fn version(self) -> i64 {
+        match self {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        }
+    }
+}
+
+// Doubles make values only partially comparable (NaN is not equal to itself)
+#[derive(Clone, PartialEq)]
 pub enum RespValue {
     SimpleString(String),
     Error(String),
@@ -9,15 +26,101 @@ pub enum RespValue {
     NullBulkString(),
     Array(Vec<RespValue>),
     NullArray(),
+    // The types below were added by RESP3
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(String),
+    // Text with a three letter format such as `txt` or `mkd`
+    VerbatimString {
+        format: String,
+        text: Vec<u8>,
+    },
+    BlobError(Vec<u8>),
+    Map(Vec<(RespValue, RespValue)>),
+    Set(Vec<RespValue>),
+    // Out-of-band data such as pub/sub messages
+    Push(Vec<RespValue>),
+    // Auxiliary data describing the value that follows it
+    Attribute {
+        attributes: Vec<(RespValue, RespValue)>,
+        value: Box<RespValue>,
+    },
+}
+
+impl RespValue {
+    // Shapes the value for a connection. RESP2 has no RESP3 types, so they are sent as
+    // the RESP2 type that Redis replies with instead, while RESP3 has a single null.
+    pub fn for_protocol(self, protocol: Protocol) -> RespValue {
+        let convert = |items: Vec<RespValue>| {
+            items
+                .into_iter()
+                .map(|item| item.for_protocol(protocol))
+                .collect()
+        };
+        match (self, protocol) {
+            (RespValue::Array(items), _) => RespValue::Array(convert(items)),
+            (RespValue::NullBulkString() | RespValue::NullArray(), Protocol::Resp3) => {
+                RespValue::Null
+            }
+            (RespValue::Map(pairs), Protocol::Resp3) => RespValue::Map(
+                pairs
+                    .into_iter()
+                    .map(|(k, v)| (k.for_protocol(protocol), v.for_protocol(protocol)))
+                    .collect(),
+            ),
+            (RespValue::Set(items), Protocol::Resp3) => RespValue::Set(convert(items)),
+            (RespValue::Push(items), Protocol::Resp3) => RespValue::Push(convert(items)),
+            (RespValue::Attribute { attributes, value }, Protocol::Resp3) => RespValue::Attribute {
+                attributes,
+                value: Box::new(value.for_protocol(protocol)),
+            },
+            (RespValue::Null, Protocol::Resp2) => RespValue::NullBulkString(),
+            (RespValue::Boolean(b), Protocol::Resp2) => RespValue::Integer(i64::from(b)),
+            (RespValue::Double(d), Protocol::Resp2) => {
+                RespValue::BulkString(format_double(d).into_bytes())
+            }
+            (RespValue::BigNumber(n), Protocol::Resp2) => RespValue::BulkString(n.into_bytes()),
+            (RespValue::VerbatimString { text, .. }, Protocol::Resp2) => {
+                RespValue::BulkString(text)
+            }
+            (RespValue::BlobError(e), Protocol::Resp2) => {
+                RespValue::Error(String::from_utf8_lossy(&e).into_owned())
+            }
+            (RespValue::Map(pairs), Protocol::Resp2) => RespValue::Array(
+                pairs
+                    .into_iter()
+                    .flat_map(|(k, v)| [k.for_protocol(protocol), v.for_protocol(protocol)])
+                    .collect(),
+            ),
+            (RespValue::Set(items) | RespValue::Push(items), Protocol::Resp2) => {
+                RespValue::Array(convert(items))
+            }
+            // RESP2 clients do not expect attributes, so only the value is sent
+            (RespValue::Attribute { value, .. }, Protocol::Resp2) => value.for_protocol(protocol),
+            (value, _) => value,
+        }
+    }
+}
+
+// Infinities and NaN are spelled the way RESP3 expects them
+pub(super) fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        "nan".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.into()
+    } else {
+        format!("{value}")
+    }
 }
 
 // Displays the value as a comma-separated list of values (useful for debug output)
 impl fmt::Debug for RespValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            RespValue::SimpleString(s) => write!(f, "+{}", s),
-            RespValue::Error(e) => write!(f, "-{}", e),
-            RespValue::Integer(i) => write!(f, ":{}", i),
+            RespValue::SimpleString(s) => write!(f, "+{s}"),
+            RespValue::Error(e) => write!(f, "-{e}"),
+            RespValue::Integer(i) => write!(f, ":{i}"),
             RespValue::BulkString(bs) => {
                 write!(f, "${},{}", bs.len(), String::from_utf8_lossy(bs))
             }
@@ -25,36 +128,53 @@ impl fmt::Debug for RespValue {
             RespValue::Array(array) => {
                 write!(f, "*{}", array.len())?;
                 for item in array {
-                    write!(f, ",{:?}", item)?;
+                    write!(f, ",{item:?}")?;
                 }
                 Ok(())
             }
             RespValue::NullArray() => write!(f, "*-1"),
+            RespValue::Null => write!(f, "_"),
+            RespValue::Boolean(b) => write!(f, "#{}", if *b { 't' } else { 'f' }),
+            RespValue::Double(d) => write!(f, ",{}", format_double(*d)),
+            RespValue::BigNumber(n) => write!(f, "({n}"),
+            RespValue::VerbatimString { format, text } => {
+                write!(
+                    f,
+                    "={},{format}:{}",
+                    text.len() + 4,
+                    String::from_utf8_lossy(text)
+                )
+            }
+            RespValue::BlobError(e) => write!(f, "!{},{}", e.len(), String::from_utf8_lossy(e)),
+            RespValue::Map(pairs) => debug_pairs(f, '%', pairs),
+            RespValue::Set(items) => debug_items(f, '~', items),
+            RespValue::Push(items) => debug_items(f, '>', items),
+            RespValue::Attribute { attributes, value } => {
+                debug_pairs(f, '|', attributes)?;
+                write!(f, ",{value:?}")
+            }
         }
     }
 }
 
-// Displays the value as the RESP protocol string representation
-impl fmt::Display for RespValue {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            RespValue::SimpleString(s) => write!(f, "+{}\r\n", s),
-            RespValue::Error(e) => write!(f, "-{}\r\n", e),
-            RespValue::Integer(i) => write!(f, ":{}\r\n", i),
-            RespValue::BulkString(bs) => {
-                write!(f, "${}\r\n{}\r\n", bs.len(), String::from_utf8_lossy(bs))
-            }
-            RespValue::NullBulkString() => write!(f, "$-1\r\n"),
-            RespValue::Array(array) => {
-                write!(f, "*{}\r\n", array.len())?;
-                for item in array {
-                    write!(f, " {}", item)?;
-                }
-                Ok(())
-            }
-            RespValue::NullArray() => write!(f, "*-1\r\n"),
-        }
+fn debug_items(f: &mut fmt::Formatter, prefix: char, items: &[RespValue]) -> fmt::Result {
+    write!(f, "{prefix}{}", items.len())?;
+    for item in items {
+        write!(f, ",{item:?}")?;
     }
+    Ok(())
+}
+
+fn debug_pairs(
+    f: &mut fmt::Formatter,
+    prefix: char,
+    pairs: &[(RespValue, RespValue)],
+) -> fmt::Result {
+    write!(f, "{prefix}{}", pairs.len())?;
+    for (key, value) in pairs {
+        write!(f, ",{key:?},{value:?}")?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -63,61 +183,31 @@ pub mod tests {
     #[test]
     fn test_simple_string_debug() {
         let value = RespValue::SimpleString("OK".to_string());
-        assert_eq!(format!("{:?}", value), "+OK");
-    }
-
-    #[test]
-    fn test_simple_string_display() {
-        let value = RespValue::SimpleString("OK".to_string());
-        assert_eq!(format!("{}", value), "+OK\r\n");
+        assert_eq!(format!("{value:?}"), "+OK");
     }
 
     #[test]
     fn test_error_debug() {
         let value = RespValue::Error("ERR invalid syntax".to_string());
-        assert_eq!(format!("{:?}", value), "-ERR invalid syntax");
-    }
-
-    #[test]
-    fn test_error_display() {
-        let value = RespValue::Error("ERR invalid syntax".to_string());
-        assert_eq!(format!("{}", value), "-ERR invalid syntax\r\n");
+        assert_eq!(format!("{value:?}"), "-ERR invalid syntax");
     }
 
     #[test]
     fn test_integer_debug() {
         let value = RespValue::Integer(42);
-        assert_eq!(format!("{:?}", value), ":42");
-    }
-
-    #[test]
-    fn test_integer_display() {
-        let value = RespValue::Integer(42);
-        assert_eq!(format!("{}", value), ":42\r\n");
+        assert_eq!(format!("{value:?}"), ":42");
     }
 
     #[test]
     fn test_bulk_string_debug() {
         let value = RespValue::BulkString(b"foobar".to_vec());
-        assert_eq!(format!("{:?}", value), "$6,foobar");
-    }
-
-    #[test]
-    fn test_bulk_string_display() {
-        let value = RespValue::BulkString(b"foobar".to_vec());
-        assert_eq!(format!("{}", value), "$6\r\nfoobar\r\n");
+        assert_eq!(format!("{value:?}"), "$6,foobar");
     }
 
     #[test]
     fn test_null_bulk_string_debug() {
         let value = RespValue::NullBulkString();
-        assert_eq!(format!("{:?}", value), "$-1");
-    }
-
-    #[test]
-    fn test_null_bulk_string_display() {
-        let value = RespValue::NullBulkString();
-        assert_eq!(format!("{}", value), "$-1\r\n");
+        assert_eq!(format!("{value:?}"), "$-1");
     }
 
     #[test]
@@ -126,27 +216,39 @@ pub mod tests {
             RespValue::SimpleString("OK".to_string()),
             RespValue::Integer(42),
         ]);
-        assert_eq!(format!("{:?}", value), "*2,+OK,:42");
-    }
-
-    #[test]
-    fn test_array_display() {
-        let value = RespValue::Array(vec![
-            RespValue::SimpleString("OK".to_string()),
-            RespValue::Integer(42),
-        ]);
-        assert_eq!(format!("{}", value), "*2\r\n +OK\r\n :42\r\n");
+        assert_eq!(format!("{value:?}"), "*2,+OK,:42");
     }
 
     #[test]
     fn test_null_array_debug() {
         let value = RespValue::NullArray();
-        assert_eq!(format!("{:?}", value), "*-1");
+        assert_eq!(format!("{value:?}"), "*-1");
     }
 
     #[test]
-    fn test_null_array_display() {
-        let value = RespValue::NullArray();
-        assert_eq!(format!("{}", value), "*-1\r\n");
+    fn test_for_protocol() {
+        let map = RespValue::Map(vec![(
+            RespValue::BulkString(b"a".to_vec()),
+            RespValue::Set(vec![RespValue::Boolean(true), RespValue::Double(0.5)]),
+        )]);
+        assert_eq!(
+            map.clone().for_protocol(Protocol::Resp2),
+            RespValue::Array(vec![
+                RespValue::BulkString(b"a".to_vec()),
+                RespValue::Array(vec![
+                    RespValue::Integer(1),
+                    RespValue::BulkString(b"0.5".to_vec())
+                ]),
+            ])
+        );
+        assert_eq!(map.clone().for_protocol(Protocol::Resp3), map);
+        assert_eq!(
+            RespValue::Array(vec![RespValue::NullBulkString()]).for_protocol(Protocol::Resp3),
+            RespValue::Array(vec![RespValue::Null])
+        );
+        assert_eq!(
+            RespValue::Null.for_protocol(Protocol::Resp2),
+            RespValue::NullBulkString()
+        );
     }
 }
@@ -0,0 +1,110 @@
+use crate::resp::{Protocol, RespValue};
+use mlua::{Lua, Table, Value};
+
+// Converts a command reply into the Lua value a script sees, with nulls as false and
+// status and error replies as tables holding an `ok` or `err` field
+pub fn to_lua(lua: &Lua, value: RespValue) -> mlua::Result<Value> {
+    Ok(match value {
+        RespValue::Integer(i) => Value::Integer(i),
+        RespValue::BulkString(bs) => Value::String(lua.create_string(bs)?),
+        RespValue::NullBulkString() | RespValue::NullArray() => Value::Boolean(false),
+        RespValue::SimpleString(s) => Value::Table(lua.create_table_from([("ok", s)])?),
+        RespValue::Error(e) => Value::Table(lua.create_table_from([("err", e)])?),
+        RespValue::Array(values) => {
+            let table = lua.create_table_with_capacity(values.len(), 0)?;
+            for value in values {
+                table.raw_push(to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+        // Scripts see replies the way RESP2 clients do
+        value => to_lua(lua, value.for_protocol(Protocol::Resp2))?,
+    })
+}
+
+// Converts a value returned by a script into its reply, truncating numbers to integers
+// and ending arrays at their first nil
+pub fn to_resp(value: &Value) -> mlua::Result<RespValue> {
+    Ok(match value {
+        Value::Boolean(true) => RespValue::Integer(1),
+        Value::Integer(i) => RespValue::Integer(*i),
+        #[allow(clippy::cast_possible_truncation)]
+        Value::Number(n) => RespValue::Integer(*n as i64),
+        Value::String(s) => RespValue::BulkString(s.as_bytes().to_vec()),
+        Value::Table(table) => table_to_resp(table)?,
+        _ => RespValue::NullBulkString(),
+    })
+}
+
+fn table_to_resp(table: &Table) -> mlua::Result<RespValue> {
+    if let Value::String(e) = table.raw_get("err")? {
+        return Ok(RespValue::Error(e.to_string_lossy()));
+    }
+    if let Value::String(s) = table.raw_get("ok")? {
+        return Ok(RespValue::SimpleString(s.to_string_lossy()));
+    }
+    let mut values = Vec::new();
+    for i in 1.. {
+        let value: Value = table.raw_get(i)?;
+        if value.is_nil() {
+            break;
+        }
+        values.push(to_resp(&value)?);
+    }
+    Ok(RespValue::Array(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(lua: &Lua, script: &str) -> RespValue {
+        to_resp(&lua.load(script).eval().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_to_resp() {
+        let lua = Lua::new();
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        assert_eq!(eval(&lua, "return 3.99"), RespValue::Integer(3));
+        assert_eq!(eval(&lua, "return -2"), RespValue::Integer(-2));
+        assert_eq!(eval(&lua, "return true"), RespValue::Integer(1));
+        assert_eq!(eval(&lua, "return false"), RespValue::NullBulkString());
+        assert_eq!(eval(&lua, "return nil"), RespValue::NullBulkString());
+        assert_eq!(eval(&lua, "return 'a'"), bulk("a"));
+        assert_eq!(
+            eval(&lua, "return {1, 'b', {'c'}, nil, 5}"),
+            RespValue::Array(vec![
+                RespValue::Integer(1),
+                bulk("b"),
+                RespValue::Array(vec![bulk("c")]),
+            ])
+        );
+        assert_eq!(
+            eval(&lua, "return {ok = 'FINE'}"),
+            RespValue::SimpleString("FINE".into())
+        );
+        assert_eq!(
+            eval(&lua, "return {err = 'ERR bad'}"),
+            RespValue::Error("ERR bad".into())
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let lua = Lua::new();
+        let value = RespValue::Array(vec![
+            RespValue::Integer(7),
+            RespValue::BulkString(b"\x00bin".to_vec()),
+            RespValue::SimpleString("OK".into()),
+            RespValue::Error("ERR no".into()),
+            RespValue::Array(Vec::new()),
+        ]);
+        let lua_value = to_lua(&lua, value.clone()).unwrap();
+        assert_eq!(to_resp(&lua_value).unwrap(), value);
+
+        // Nulls become false, which converts back to a null bulk string
+        let lua_value = to_lua(&lua, RespValue::NullArray()).unwrap();
+        assert_eq!(lua_value, Value::Boolean(false));
+    }
+}
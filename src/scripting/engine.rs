@@ -0,0 +1,526 @@
+use super::convert::{to_lua, to_resp};
+use super::library::{is_valid_flag, is_valid_name};
+use super::{Event, Request, sha1_hex};
+use crate::log::{Level, log};
+use crate::resp::RespValue;
+use crate::storage::LibraryFunction;
+use mlua::{
+    Function, HookTriggers, IntoLuaMulti, Lua, LuaOptions, LuaString, MultiValue, StdLib, Table,
+    Value, Variadic, VmState,
+};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+// How many instructions a script runs between checks of its deadline
+const HOOK_INTERVAL: u32 = 1000;
+
+const ARGUMENT_ERROR: &str = "ERR Lua redis lib command arguments must be strings or integers";
+
+const NAME_ERROR: &str = "ERR Function names can only contain letters, numbers, or \
+                          underscores(_) and must be at least one character long";
+
+// Defines the `redis` library around the functions passed in. Returns the function
+// scripts are run through so that errors they raise come back as values, and the one
+// that runs a function library, collecting the functions it registers.
+const PRELUDE: &str = r"
+local call, sha1hex, log = ...
+
+-- The functions registered so far, only set while a library loads
+local registered
+
+local function checked_call(...)
+    if registered then
+        error('redis.call can not be used while loading a library')
+    end
+    return call(...)
+end
+
+redis = {
+    LOG_DEBUG = 0,
+    LOG_VERBOSE = 1,
+    LOG_NOTICE = 2,
+    LOG_WARNING = 3,
+    sha1hex = sha1hex,
+    log = log,
+    pcall = checked_call,
+}
+
+function redis.call(...)
+    local reply = checked_call(...)
+    if type(reply) == 'table' and reply.err then
+        error(reply)
+    end
+    return reply
+end
+
+function redis.error_reply(message)
+    return { err = message }
+end
+
+function redis.status_reply(message)
+    return { ok = message }
+end
+
+-- Takes the name and callback, then optionally flags, or a table of named arguments
+function redis.register_function(...)
+    if not registered then
+        error('redis.register_function can only be called on FUNCTION LOAD command')
+    end
+    local args = { ... }
+    if type(args[1]) == 'table' then
+        local named = args[1]
+        args = { named.function_name, named.callback, named.flags, named.description }
+    end
+    registered[#registered + 1] = args
+end
+
+dofile = nil
+loadfile = nil
+
+-- Precompiled chunks can be crafted to break out of the sandbox, so only source code
+-- is loaded. The pieces that load reads are joined to be checked the same way.
+local loadsource, concat = loadstring, table.concat
+
+function loadstring(chunk, name)
+    if type(chunk) == 'string' and chunk:byte(1) == 27 then
+        error('Attempt to load a precompiled chunk', 2)
+    end
+    return loadsource(chunk, name)
+end
+
+function load(reader, name)
+    local pieces = {}
+    local piece = reader()
+    while piece ~= nil and piece ~= '' do
+        if type(piece) ~= 'string' then
+            error('reader function must return a string', 2)
+        end
+        pieces[#pieces + 1] = piece
+        piece = reader()
+    end
+    return loadstring(concat(pieces), name)
+end
+
+local pcall, error, type, tostring, pairs = pcall, error, type, tostring, pairs
+local rawset, getmetatable, setmetatable = rawset, getmetatable, setmetatable
+
+-- Every script shares these globals, so like Redis they are made read-only for one
+-- script not to change what the next sees. The libraries are reached through empty
+-- tables that refuse writes, as are the globals in _G, and rawset refuses them too.
+local readonly = setmetatable({}, { __mode = 'k' })
+
+local function refuse()
+    error('Attempt to modify a readonly table', 2)
+end
+
+local function protect(target)
+    local proxy = setmetatable({}, { __index = target, __newindex = refuse, __metatable = false })
+    readonly[proxy] = true
+    return proxy
+end
+
+local G = _G
+local globals = {}
+for name, value in pairs(G) do
+    if type(value) == 'table' and value ~= G then
+        value = protect(value)
+    end
+    globals[name] = value
+    G[name] = nil
+end
+globals._G = G
+globals.rawset = function(t, k, v)
+    if readonly[t] then
+        refuse()
+    end
+    return rawset(t, k, v)
+end
+getmetatable('').__metatable = false
+
+setmetatable(G, {
+    __index = function(_, name)
+        local value = globals[name]
+        if value == nil then
+            error('Script attempted to access nonexistent global variable \'' .. tostring(name) .. '\'', 2)
+        end
+        return value
+    end,
+    __newindex = refuse,
+    __metatable = false,
+})
+readonly[G] = true
+
+local function run(script, ...)
+    return pcall(script, ...)
+end
+
+local function load(library)
+    registered = {}
+    local ok, err = pcall(library)
+    local functions = registered
+    registered = nil
+    return ok, err, functions
+end
+
+return run, load
+";
+
+// A loaded function library and the callbacks it registered
+struct Library {
+    code: String,
+    functions: HashMap<String, Function>,
+}
+
+struct Engine {
+    lua: Lua,
+    runner: Function,
+    loader: Function,
+    // Compiled scripts by their SHA1 digest
+    scripts: HashMap<String, Function>,
+    // Function libraries by name
+    libraries: HashMap<String, Library>,
+    time_limit: Duration,
+    deadline: Rc<Cell<Option<Instant>>>,
+    timed_out: Rc<Cell<bool>>,
+}
+
+// Serves requests until the server drops its end of the channel
+pub(super) fn run(
+    requests: &Receiver<Request>,
+    events: &UnboundedSender<Event>,
+    replies: Receiver<RespValue>,
+    time_limit: Duration,
+) {
+    let mut engine = Engine::new(events.clone(), replies, time_limit).expect("Failed to start Lua");
+    while let Ok(request) = requests.recv() {
+        let event = match request {
+            Request::Run {
+                sha,
+                body,
+                keys,
+                args,
+            } => engine.run(&sha, &body, keys, args),
+            Request::Load { sha, body } => match engine.compile(&sha, &body) {
+                Ok(_) => Event::Done(RespValue::BulkString(sha.into_bytes())),
+                Err(message) => Event::Invalid(message),
+            },
+            Request::Flush => {
+                engine.scripts.clear();
+                continue;
+            }
+            Request::LoadLibrary { name, code } => match engine.load_library(&name, &code) {
+                Ok(functions) => Event::Library(functions),
+                Err(message) => Event::Invalid(message),
+            },
+            Request::Call {
+                library,
+                code,
+                function,
+                keys,
+                args,
+            } => engine.call_function(&library, &code, &function, keys, args),
+        };
+        let _ = events.send(event);
+    }
+}
+
+impl Engine {
+    fn new(
+        events: UnboundedSender<Event>,
+        replies: Receiver<RespValue>,
+        time_limit: Duration,
+    ) -> mlua::Result<Self> {
+        let lua = Lua::new_with(
+            StdLib::TABLE | StdLib::STRING | StdLib::MATH,
+            LuaOptions::default(),
+        )?;
+        let deadline = Rc::new(Cell::new(None));
+        let timed_out = Rc::new(Cell::new(false));
+
+        // Once past its deadline a script fails at every check, even if it catches the
+        // error, and the commands it calls are refused
+        let (hook_deadline, hook_timed_out) = (Rc::clone(&deadline), Rc::clone(&timed_out));
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+            move |_, _| {
+                if hook_deadline.get().is_some_and(|d| Instant::now() >= d) {
+                    hook_timed_out.set(true);
+                    return Err(mlua::Error::runtime("Script timed out"));
+                }
+                Ok(VmState::Continue)
+            },
+        )?;
+
+        let call_timed_out = Rc::clone(&timed_out);
+        let call = lua.create_function(move |lua, args: Variadic<Value>| {
+            if call_timed_out.get() {
+                return Err(mlua::Error::runtime("Script timed out"));
+            }
+            let reply = match command_args(lua, args) {
+                Ok(args) => {
+                    events
+                        .send(Event::Call(args))
+                        .map_err(mlua::Error::external)?;
+                    replies.recv().map_err(mlua::Error::external)?
+                }
+                Err(message) => RespValue::Error(message.into()),
+            };
+            to_lua(lua, reply)
+        })?;
+        let sha1hex = lua.create_function(|_, data: LuaString| Ok(sha1_hex(&data.as_bytes())))?;
+        let log = lua.create_function(|_, (level, message): (i64, Variadic<LuaString>)| {
+            let message: Vec<_> = message.iter().map(LuaString::to_string_lossy).collect();
+            log!(
+                Level::from_script(level),
+                "Script log: {}",
+                message.join(" ")
+            );
+            Ok(())
+        })?;
+        let (runner, loader) = lua
+            .load(PRELUDE)
+            .set_name("@prelude")
+            .call((call, sha1hex, log))?;
+
+        Ok(Engine {
+            lua,
+            runner,
+            loader,
+            scripts: HashMap::new(),
+            libraries: HashMap::new(),
+            time_limit,
+            deadline,
+            timed_out,
+        })
+    }
+
+    fn compile(&mut self, sha: &str, body: &str) -> Result<Function, String> {
+        if let Some(function) = self.scripts.get(sha) {
+            return Ok(function.clone());
+        }
+        let function = self
+            .lua
+            .load(body)
+            .set_name("@user_script")
+            .into_function()
+            .map_err(|e| {
+                format!(
+                    "ERR Error compiling script (new function): {}",
+                    syntax_error(e)
+                )
+            })?;
+        self.scripts.insert(sha.into(), function.clone());
+        Ok(function)
+    }
+
+    fn run(&mut self, sha: &str, body: &str, keys: Vec<String>, args: Vec<Vec<u8>>) -> Event {
+        let function = match self.compile(sha, body) {
+            Ok(function) => function,
+            Err(message) => return Event::Invalid(message),
+        };
+        let globals = self.lua.globals();
+        let set = self
+            .strings(keys.into_iter().map(String::into_bytes))
+            .and_then(|keys| globals.raw_set("KEYS", keys))
+            .and_then(|()| self.strings(args))
+            .and_then(|args| globals.raw_set("ARGV", args));
+        if let Err(e) = set {
+            return Event::Done(RespValue::Error(format!("ERR {e}")));
+        }
+        self.execute(sha, &function, ())
+    }
+
+    // Runs library code, which registers its functions, keeping it only if it succeeds
+    fn load_library(&mut self, name: &str, code: &str) -> Result<Vec<LibraryFunction>, String> {
+        // Commenting out the metadata line keeps the line numbers of errors intact
+        let body = self
+            .lua
+            .load(format!("--{code}"))
+            .set_name("@user_function")
+            .into_function()
+            .map_err(|e| format!("ERR Error compiling function: {}", syntax_error(e)))?;
+
+        self.deadline.set(Some(Instant::now() + self.time_limit));
+        let loaded: mlua::Result<(bool, Value, Table)> = self.loader.call(body);
+        self.deadline.set(None);
+        if self.timed_out.replace(false) {
+            return Err("ERR FUNCTION LOAD timed out".into());
+        }
+        let (ok, error, registered) = loaded.map_err(|e| format!("ERR {e}"))?;
+        if !ok {
+            let message = error_message(&error).map_err(|e| format!("ERR {e}"))?;
+            return Err(format!("ERR Error registering functions: {message}"));
+        }
+
+        let registered = registered_functions(&registered)?;
+        let functions = registered.iter().map(|(f, _)| f.clone()).collect();
+        let library = Library {
+            code: code.into(),
+            functions: registered
+                .into_iter()
+                .map(|(f, callback)| (f.name, callback))
+                .collect(),
+        };
+        self.libraries.insert(name.into(), library);
+        Ok(functions)
+    }
+
+    // Runs a function, first loading its library if this server has not seen the code
+    // it is stored with, as when another server loaded it
+    fn call_function(
+        &mut self,
+        library: &str,
+        code: &str,
+        function: &str,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+    ) -> Event {
+        if self.libraries.get(library).is_none_or(|l| l.code != code)
+            && let Err(message) = self.load_library(library, code)
+        {
+            return Event::Invalid(message);
+        }
+        let Some(callback) = self.libraries[library].functions.get(function).cloned() else {
+            return Event::Invalid("ERR Function not found".into());
+        };
+        let args = self
+            .strings(keys.into_iter().map(String::into_bytes))
+            .and_then(|keys| Ok((keys, self.strings(args)?)));
+        match args {
+            Ok(args) => self.execute(function, &callback, args),
+            Err(e) => Event::Done(RespValue::Error(format!("ERR {e}"))),
+        }
+    }
+
+    // Calls a script or function under the time limit, naming it in the errors it raises
+    fn execute(&self, name: &str, function: &Function, args: impl IntoLuaMulti) -> Event {
+        self.deadline.set(Some(Instant::now() + self.time_limit));
+        let reply = self
+            .call(name, function, args)
+            .unwrap_or_else(|e| RespValue::Error(format!("ERR {e} script: {name}")));
+        self.deadline.set(None);
+        if self.timed_out.replace(false) {
+            return Event::TimedOut;
+        }
+        Event::Done(reply)
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        function: &Function,
+        args: impl IntoLuaMulti,
+    ) -> mlua::Result<RespValue> {
+        let mut args = args.into_lua_multi(&self.lua)?;
+        args.push_front(Value::Function(function.clone()));
+        let results: MultiValue = self.runner.call(args)?;
+        let mut results = results.into_iter();
+        let ok = matches!(results.next(), Some(Value::Boolean(true)));
+        let value = results.next().unwrap_or(Value::Nil);
+        if ok {
+            return to_resp(&value);
+        }
+        // Errors raised by redis.call are tables holding the command's error
+        let message = match &value {
+            Value::Table(_) => error_message(&value)?,
+            value => format!("ERR {}", error_message(value)?),
+        };
+        Ok(RespValue::Error(format!("{message} script: {name}")))
+    }
+
+    fn strings(&self, items: impl IntoIterator<Item = Vec<u8>>) -> mlua::Result<Table> {
+        let items = items
+            .into_iter()
+            .map(|item| self.lua.create_string(item))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        self.lua.create_sequence_from(items)
+    }
+}
+
+fn error_message(error: &Value) -> mlua::Result<String> {
+    match error {
+        Value::Table(table) => match table.raw_get("err")? {
+            Value::String(e) => Ok(e.to_string_lossy()),
+            _ => Ok("ERR unknown error".into()),
+        },
+        error => error.to_string(),
+    }
+}
+
+// The message of a syntax error, without the prefix mlua gives it
+fn syntax_error(error: mlua::Error) -> String {
+    match error {
+        mlua::Error::SyntaxError { message, .. } => message,
+        e => e.to_string(),
+    }
+}
+
+// Validates the `{name, callback, flags, description}` entries a library registered
+fn registered_functions(registered: &Table) -> Result<Vec<(LibraryFunction, Function)>, String> {
+    let mut functions: Vec<(LibraryFunction, Function)> = Vec::new();
+    for entry in registered.sequence_values::<Table>() {
+        let entry = entry.map_err(|e| format!("ERR {e}"))?;
+        let (Ok(Value::String(name)), Ok(Value::Function(callback))) =
+            (entry.raw_get(1), entry.raw_get(2))
+        else {
+            return Err("ERR wrong arguments given to redis.register_function".into());
+        };
+        let name = name.to_string_lossy();
+        if !is_valid_name(&name) {
+            return Err(NAME_ERROR.into());
+        }
+        if functions.iter().any(|(f, _)| f.name == name) {
+            return Err(format!("ERR Function {name} already exists"));
+        }
+        let flags = match entry.raw_get(3) {
+            Ok(Value::Nil) => Vec::new(),
+            Ok(Value::Table(flags)) => flags
+                .sequence_values::<String>()
+                .map(|flag| match flag {
+                    Ok(flag) if is_valid_flag(&flag) => Ok(flag),
+                    _ => Err("ERR unknown flag given".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Err("ERR flags argument to redis.register_function must be a table".into());
+            }
+        };
+        let description = match entry.raw_get(4) {
+            Ok(Value::Nil) => None,
+            Ok(Value::String(description)) => Some(description.to_string_lossy()),
+            _ => return Err("ERR function description must be a string".into()),
+        };
+        let function = LibraryFunction {
+            name,
+            description,
+            flags,
+        };
+        functions.push((function, callback));
+    }
+    if functions.is_empty() {
+        return Err("ERR No functions registered".into());
+    }
+    Ok(functions)
+}
+
+// Converts the arguments of redis.call into a command, formatting numbers the way Lua does
+fn command_args(lua: &Lua, args: Variadic<Value>) -> Result<Vec<Vec<u8>>, &'static str> {
+    if args.is_empty() {
+        return Err("ERR Please specify at least one argument for this redis lib call");
+    }
+    args.into_iter()
+        .map(|arg| match arg {
+            Value::String(_) | Value::Integer(_) | Value::Number(_) => {
+                match lua.coerce_string(arg) {
+                    Ok(Some(s)) => Ok(s.as_bytes().to_vec()),
+                    _ => Err(ARGUMENT_ERROR),
+                }
+            }
+            _ => Err(ARGUMENT_ERROR),
+        })
+        .collect()
+}
@@ -0,0 +1,129 @@
+// Flags a function may be registered with, of which only `no-writes` changes anything
+const FUNCTION_FLAGS: [&str; 5] = [
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+// FUNCTION DUMP payloads start with this line, followed by the code of each library
+// as its length in bytes on a line of its own and then the code itself
+const DUMP_HEADER: &[u8] = b"postgredis-functions 1\n";
+
+// Reads the library name from the `#!lua name=<name>` line that code starts with
+pub fn library_name(code: &str) -> Result<String, String> {
+    let Some(header) = code.lines().next().and_then(|line| line.strip_prefix("#!")) else {
+        return Err("ERR Missing library metadata".into());
+    };
+    let mut parts = header.split_whitespace();
+    let engine = parts.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(format!("ERR Engine '{engine}' not found"));
+    }
+    let mut name = None;
+    for part in parts {
+        match part.strip_prefix("name=") {
+            Some(value) if name.is_none() => name = Some(value),
+            _ => return Err(format!("ERR Invalid metadata value given: {part}")),
+        }
+    }
+    match name {
+        Some(name) if is_valid_name(name) => Ok(name.into()),
+        Some(_) => Err(
+            "ERR Library names can only contain letters, numbers, or underscores(_) and must \
+             be at least one character long"
+                .into(),
+        ),
+        None => Err("ERR Library name was not given".into()),
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+pub fn is_valid_flag(flag: &str) -> bool {
+    FUNCTION_FLAGS.contains(&flag)
+}
+
+pub fn dump<'a>(codes: impl IntoIterator<Item = &'a str>) -> Vec<u8> {
+    let mut payload = DUMP_HEADER.to_vec();
+    for code in codes {
+        payload.extend_from_slice(format!("{}\n", code.len()).as_bytes());
+        payload.extend_from_slice(code.as_bytes());
+    }
+    payload
+}
+
+// Returns the code of each library in a payload, or `None` if it is malformed
+pub fn restore(payload: &[u8]) -> Option<Vec<String>> {
+    let mut rest = payload.strip_prefix(DUMP_HEADER)?;
+    let mut codes = Vec::new();
+    while !rest.is_empty() {
+        let newline = rest.iter().position(|&b| b == b'\n')?;
+        let len: usize = std::str::from_utf8(&rest[..newline]).ok()?.parse().ok()?;
+        rest = &rest[newline + 1..];
+        if len > rest.len() {
+            return None;
+        }
+        let (code, remaining) = rest.split_at(len);
+        codes.push(String::from_utf8(code.to_vec()).ok()?);
+        rest = remaining;
+    }
+    Some(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_name() {
+        assert_eq!(
+            library_name("#!lua name=mylib\nreturn 1"),
+            Ok("mylib".into())
+        );
+        assert_eq!(library_name("#!LUA  name=my_lib2"), Ok("my_lib2".into()));
+        assert!(
+            library_name("return 1")
+                .unwrap_err()
+                .contains("Missing library metadata")
+        );
+        assert!(
+            library_name("#!js name=a")
+                .unwrap_err()
+                .contains("Engine 'js'")
+        );
+        assert!(
+            library_name("#!lua")
+                .unwrap_err()
+                .contains("name was not given")
+        );
+        assert!(
+            library_name("#!lua name=a-b")
+                .unwrap_err()
+                .contains("names can only")
+        );
+        assert!(
+            library_name("#!lua name=a x=1")
+                .unwrap_err()
+                .contains("x=1")
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let payload = dump(["#!lua name=a\n", "#!lua name=b\nreturn 'é'"]);
+        assert_eq!(
+            restore(&payload),
+            Some(vec![
+                "#!lua name=a\n".to_string(),
+                "#!lua name=b\nreturn 'é'".to_string()
+            ])
+        );
+        assert_eq!(restore(&dump([])), Some(Vec::new()));
+        assert_eq!(restore(b"garbage"), None);
+        assert_eq!(restore(&payload[..payload.len() - 1]), None);
+    }
+}
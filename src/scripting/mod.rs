@@ -0,0 +1,456 @@
+mod convert;
+mod engine;
+pub mod library;
+
+use crate::resp::RespValue;
+use crate::storage::{FunctionLibrary, LibraryFunction, StorageError, StorageResult};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+// Work sent to the thread running the Lua interpreter
+enum Request {
+    Run {
+        sha: String,
+        body: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+    },
+    Load {
+        sha: String,
+        body: String,
+    },
+    Flush,
+    LoadLibrary {
+        name: String,
+        code: String,
+    },
+    // Runs a function, loading its library from `code` if needed
+    Call {
+        library: String,
+        code: String,
+        function: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+    },
+}
+
+// What the Lua thread reports back while running a request
+enum Event {
+    // A command called through redis.call or redis.pcall, waiting for its reply
+    Call(Vec<Vec<u8>>),
+    // The script compiled, and ran to this reply if it was run
+    Done(RespValue),
+    // A library loaded, registering these functions
+    Library(Vec<LibraryFunction>),
+    // The script or library failed to compile or load
+    Invalid(String),
+    // The script ran past the time limit and was aborted
+    TimedOut,
+}
+
+// Runs Lua scripts on a dedicated thread, which blocks while the server executes the
+// commands they call
+pub struct Scripting {
+    requests: mpsc::Sender<Request>,
+    events: UnboundedReceiver<Event>,
+    replies: mpsc::Sender<RespValue>,
+    // The bodies of the scripts that compiled, by their SHA1 digest
+    scripts: HashMap<String, String>,
+}
+
+impl Scripting {
+    // Scripts running longer than the time limit are aborted
+    pub fn new(time_limit: Duration) -> Self {
+        let (requests_tx, requests_rx) = mpsc::channel();
+        let (events_tx, events_rx) = unbounded_channel();
+        let (replies_tx, replies_rx) = mpsc::channel();
+        thread::spawn(move || engine::run(&requests_rx, &events_tx, replies_rx, time_limit));
+
+        Scripting {
+            requests: requests_tx,
+            events: events_rx,
+            replies: replies_tx,
+            scripts: HashMap::new(),
+        }
+    }
+
+    pub fn exists(&self, sha: &str) -> bool {
+        self.scripts.contains_key(sha)
+    }
+
+    pub fn body(&self, sha: &str) -> Option<String> {
+        self.scripts.get(sha).cloned()
+    }
+
+    pub fn flush(&mut self) {
+        self.scripts.clear();
+        let _ = self.requests.send(Request::Flush);
+    }
+
+    // Compiles and caches a script, replying with its digest or the compile error
+    pub async fn load(&mut self, body: String) -> RespValue {
+        let sha = sha1_hex(body.as_bytes());
+        let request = Request::Load {
+            sha: sha.clone(),
+            body: body.clone(),
+        };
+        match self.request(request).await {
+            Some(Event::Done(_)) => {
+                self.scripts.insert(sha.clone(), body);
+                RespValue::BulkString(sha.into_bytes())
+            }
+            Some(Event::Invalid(message)) => RespValue::Error(message),
+            _ => engine_stopped(),
+        }
+    }
+
+    // Runs a script, answering the commands it calls with `call` until it replies. A
+    // script that ran too long fails, so the caller can undo what it wrote.
+    pub async fn eval(
+        &mut self,
+        body: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+        call: impl AsyncFnMut(Vec<Vec<u8>>) -> RespValue,
+    ) -> StorageResult<RespValue> {
+        let sha = sha1_hex(body.as_bytes());
+        let request = Request::Run {
+            sha: sha.clone(),
+            body: body.clone(),
+            keys,
+            args,
+        };
+        let reply = self.run(request, call).await?;
+        if matches!(reply, Event::Done(_)) {
+            self.scripts.insert(sha, body);
+        }
+        Ok(reply_of(reply))
+    }
+
+    // Checks that library code loads, returning the library it defines or the error
+    pub async fn load_library(&mut self, code: String) -> Result<FunctionLibrary, String> {
+        let name = library::library_name(&code)?;
+        let request = Request::LoadLibrary {
+            name: name.clone(),
+            code: code.clone(),
+        };
+        match self.request(request).await {
+            Some(Event::Library(functions)) => Ok(FunctionLibrary {
+                name,
+                code,
+                functions,
+            }),
+            Some(Event::Invalid(message)) => Err(message),
+            _ => Err("ERR scripting engine stopped".into()),
+        }
+    }
+
+    // Runs a function of a library the way `eval` runs a script
+    pub async fn fcall(
+        &mut self,
+        library: &FunctionLibrary,
+        function: String,
+        keys: Vec<String>,
+        args: Vec<Vec<u8>>,
+        call: impl AsyncFnMut(Vec<Vec<u8>>) -> RespValue,
+    ) -> StorageResult<RespValue> {
+        let request = Request::Call {
+            library: library.name.clone(),
+            code: library.code.clone(),
+            function,
+            keys,
+            args,
+        };
+        Ok(reply_of(self.run(request, call).await?))
+    }
+
+    // Sends a request, answering the calls made while it runs, and returns how it ended
+    async fn run(
+        &mut self,
+        request: Request,
+        mut call: impl AsyncFnMut(Vec<Vec<u8>>) -> RespValue,
+    ) -> StorageResult<Event> {
+        let mut event = self.request(request).await;
+        while let Some(Event::Call(args)) = event {
+            let reply = call(args).await;
+            if self.replies.send(reply).is_err() {
+                return Ok(Event::Done(engine_stopped()));
+            }
+            event = self.events.recv().await;
+        }
+        match event {
+            Some(Event::TimedOut) => Err(StorageError::ScriptTimeout),
+            Some(event) => Ok(event),
+            None => Ok(Event::Done(engine_stopped())),
+        }
+    }
+
+    async fn request(&mut self, request: Request) -> Option<Event> {
+        self.requests.send(request).ok()?;
+        self.events.recv().await
+    }
+}
+
+fn reply_of(event: Event) -> RespValue {
+    match event {
+        Event::Done(reply) => reply,
+        Event::Invalid(message) => RespValue::Error(message),
+        _ => engine_stopped(),
+    }
+}
+
+// Only reached if the Lua thread panicked
+fn engine_stopped() -> RespValue {
+    RespValue::Error("ERR scripting engine stopped".into())
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk(s: &str) -> RespValue {
+        RespValue::BulkString(s.as_bytes().to_vec())
+    }
+
+    // Runs a script whose calls are answered with the command name they called
+    async fn eval(
+        scripting: &mut Scripting,
+        body: &str,
+        keys: &[&str],
+        args: &[&str],
+    ) -> RespValue {
+        let keys = keys.iter().map(ToString::to_string).collect();
+        let args = args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+        scripting
+            .eval(body.into(), keys, args, async |args| {
+                match args[0].as_slice() {
+                    b"fail" => RespValue::Error("ERR failed".into()),
+                    name => RespValue::BulkString(name.to_vec()),
+                }
+            })
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sha1_hex() {
+        assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[tokio::test]
+    async fn test_eval() {
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        assert_eq!(
+            eval(
+                &mut scripting,
+                "return {KEYS[1], ARGV[2]}",
+                &["k"],
+                &["a", "b"]
+            )
+            .await,
+            RespValue::Array(vec![bulk("k"), bulk("b")])
+        );
+        assert!(scripting.exists(&sha1_hex(b"return {KEYS[1], ARGV[2]}")));
+        assert_eq!(
+            eval(&mut scripting, "return redis.call('get', 1.5)", &[], &[]).await,
+            bulk("get")
+        );
+
+        // redis.call raises errors that redis.pcall returns
+        let reply = eval(&mut scripting, "return redis.call('fail')", &[], &[]).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.starts_with("ERR failed script: ")));
+        assert_eq!(
+            eval(&mut scripting, "return redis.pcall('fail')", &[], &[]).await,
+            RespValue::Error("ERR failed".into())
+        );
+        let reply = eval(&mut scripting, "return redis.call({})", &[], &[]).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("must be strings or integers")));
+        let reply = eval(&mut scripting, "error('boom')", &[], &[]).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.starts_with("ERR user_script:1: boom")));
+
+        let reply = eval(&mut scripting, "return (", &[], &[]).await;
+        assert!(
+            matches!(reply, RespValue::Error(e) if e.starts_with("ERR Error compiling script"))
+        );
+        assert!(!scripting.exists(&sha1_hex(b"return (")));
+    }
+
+    #[tokio::test]
+    async fn test_readonly_globals() {
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let readonly = "Attempt to modify a readonly table";
+        for script in [
+            "x = 1",
+            "redis = nil",
+            "redis.call = nil",
+            "string.rep = nil",
+            "rawset(_G, 'x', 1)",
+            "rawset(redis, 'call', 1)",
+        ] {
+            let reply = eval(&mut scripting, script, &[], &[]).await;
+            assert!(
+                matches!(&reply, RespValue::Error(e) if e.contains(readonly)),
+                "{script} replied {reply:?}"
+            );
+        }
+        let reply = eval(&mut scripting, "setmetatable(_G, nil)", &[], &[]).await;
+        assert!(matches!(reply, RespValue::Error(e) if e.contains("protected metatable")));
+        let reply = eval(&mut scripting, "return x", &[], &[]).await;
+        assert!(
+            matches!(reply, RespValue::Error(e) if e.contains("nonexistent global variable 'x'"))
+        );
+
+        // Nothing the scripts above tried is seen by the next one
+        assert_eq!(
+            eval(
+                &mut scripting,
+                "local t = {} rawset(t, 1, string.rep('a', 2)) return {t[1], type(redis.call)}",
+                &[],
+                &[]
+            )
+            .await,
+            RespValue::Array(vec![bulk("aa"), bulk("function")])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_precompiled_chunks() {
+        // Only source code loads, not the chunks string.dump makes
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let dumped = "string.dump(function() return 1 end)";
+        for script in [
+            format!("return loadstring({dumped})"),
+            format!("local s = {dumped} return load(function() local c = s s = nil return c end)"),
+        ] {
+            let reply = eval(&mut scripting, &script, &[], &[]).await;
+            assert!(
+                matches!(&reply, RespValue::Error(e) if e.contains("precompiled chunk")),
+                "{script} replied {reply:?}"
+            );
+        }
+        assert_eq!(
+            eval(&mut scripting, "return loadstring('return 1')()", &[], &[]).await,
+            RespValue::Integer(1)
+        );
+        let reply = eval(
+            &mut scripting,
+            "local s = 'return 2' return load(function() local c = s s = nil return c end)()",
+            &[],
+            &[],
+        )
+        .await;
+        assert_eq!(reply, RespValue::Integer(2));
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let sha = sha1_hex(b"return 1");
+        assert_eq!(scripting.load("return 1".into()).await, bulk(&sha));
+        assert_eq!(scripting.body(&sha).as_deref(), Some("return 1"));
+        scripting.flush();
+        assert!(!scripting.exists(&sha));
+    }
+
+    #[tokio::test]
+    async fn test_time_limit() {
+        let mut scripting = Scripting::new(Duration::from_millis(50));
+        let result = scripting
+            .eval(
+                "pcall(function() while true do end end) return 1".into(),
+                Vec::new(),
+                Vec::new(),
+                async |_| RespValue::NullBulkString(),
+            )
+            .await;
+        assert!(matches!(result, Err(StorageError::ScriptTimeout)));
+
+        // The interpreter is still usable afterwards
+        assert_eq!(
+            eval(&mut scripting, "return 2", &[], &[]).await,
+            RespValue::Integer(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_library() {
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let code = "#!lua name=lib\n\
+            redis.register_function('echo', function(keys, args) return {keys[1], args[1]} end)\n\
+            redis.register_function{function_name='ro', callback=function() return 1 end, \
+                flags={'no-writes'}, description='read only'}";
+        let library = scripting.load_library(code.into()).await.unwrap();
+        assert_eq!(library.name, "lib");
+        assert_eq!(
+            library.functions,
+            vec![
+                LibraryFunction {
+                    name: "echo".into(),
+                    description: None,
+                    flags: Vec::new(),
+                },
+                LibraryFunction {
+                    name: "ro".into(),
+                    description: Some("read only".into()),
+                    flags: vec!["no-writes".into()],
+                },
+            ]
+        );
+
+        // A fresh engine loads the library from its code on the first call
+        let mut other = Scripting::new(Duration::from_secs(5));
+        let reply = other
+            .fcall(
+                &library,
+                "echo".into(),
+                vec!["k".into()],
+                vec![b"a".to_vec()],
+                async |_| RespValue::NullBulkString(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply, RespValue::Array(vec![bulk("k"), bulk("a")]));
+
+        let error = async |code: &str| {
+            Scripting::new(Duration::from_secs(5))
+                .load_library(code.into())
+                .await
+                .unwrap_err()
+        };
+        assert!(error("return 1").await.contains("Missing library metadata"));
+        assert!(
+            error("#!lua name=a\nreturn 1")
+                .await
+                .contains("No functions registered")
+        );
+        assert!(
+            error("#!lua name=a\nredis.call('GET', 'k')")
+                .await
+                .contains("while loading")
+        );
+        assert!(
+            error("#!lua name=a\nredis.register_function('f', function() end, {'x'})")
+                .await
+                .contains("unknown flag")
+        );
+        assert!(
+            error("#!lua name=a\n(")
+                .await
+                .contains("Error compiling function")
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::client::ClientEvent;
+use crate::server::handler::{ready_keys, timeout_reply, try_blocking};
+use crate::storage::Storage;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// How often blocked clients are retried regardless of local writes, so data pushed by
+// other servers sharing the database is picked up
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+struct BlockedClient {
+    event: ClientEvent,
+    keys: Vec<String>,
+    deadline: Option<Instant>,
+    // Commands the client sent while blocked, run once it is served
+    deferred: VecDeque<ClientEvent>,
+}
+
+// Clients parked by blocking commands, served in the order they blocked
+pub struct BlockedClients {
+    clients: Vec<BlockedClient>,
+    next_poll: Instant,
+}
+
+impl BlockedClients {
+    pub fn new() -> Self {
+        BlockedClients {
+            clients: Vec::new(),
+            next_poll: Instant::now(),
+        }
+    }
+
+    pub fn block(&mut self, event: ClientEvent, keys: Vec<String>, deadline: Option<Instant>) {
+        if self.clients.is_empty() {
+            self.next_poll = Instant::now() + POLL_INTERVAL;
+        }
+        self.clients.push(BlockedClient {
+            event,
+            keys,
+            deadline,
+            deferred: VecDeque::new(),
+        });
+    }
+
+    pub fn is_blocked(&self, client_id: u64) -> bool {
+        self.clients.iter().any(|c| c.event.client_id == client_id)
+    }
+
+    // Holds on to an event from a blocked client, handing it back otherwise. A client
+    // that disconnected stops waiting, and what it sent meanwhile is dropped with it.
+    pub fn defer(&mut self, event: ClientEvent) -> Option<ClientEvent> {
+        if event.is_disconnect() {
+            self.clients
+                .retain(|c| c.event.client_id != event.client_id);
+            return Some(event);
+        }
+        match self
+            .clients
+            .iter_mut()
+            .find(|c| c.event.client_id == event.client_id)
+        {
+            Some(client) => {
+                client.deferred.push_back(event);
+                None
+            }
+            None => Some(event),
+        }
+    }
+
+    // Completes once blocked clients should be polled or timed out
+    pub async fn wait(&self) {
+        if self.clients.is_empty() {
+            return std::future::pending().await;
+        }
+        let deadline = self.clients.iter().filter_map(|c| c.deadline).min();
+        let wake_at = deadline.map_or(self.next_poll, |deadline| deadline.min(self.next_poll));
+        tokio::time::sleep_until(wake_at).await;
+    }
+
+    // Retries clients waiting on any of the keys, returning the events deferred by the
+    // clients that were served so they can be processed next
+    pub async fn wake<S: Storage>(&mut self, storage: &S, keys: Vec<String>) -> Vec<ClientEvent> {
+        let mut ready: HashSet<String> = keys.into_iter().collect();
+        let mut resumed = Vec::new();
+
+        // Serving a client can make more keys ready (e.g. BLMOVE), so keep going until
+        // a full pass serves nobody
+        loop {
+            let Some(index) = self.serve_next(storage, &ready).await else {
+                break;
+            };
+            let client = self.clients.remove(index);
+            ready.extend(ready_keys(&client.event.command));
+            resumed.extend(client.deferred);
+        }
+        resumed
+    }
+
+    // Retries every blocked client and replies to the ones whose timeout has elapsed
+    pub async fn poll<S: Storage>(&mut self, storage: &S) -> Vec<ClientEvent> {
+        self.next_poll = Instant::now() + POLL_INTERVAL;
+        let keys = self.clients.iter().flat_map(|c| c.keys.clone()).collect();
+        let mut resumed = self.wake(storage, keys).await;
+
+        let now = Instant::now();
+        let (expired, waiting) = std::mem::take(&mut self.clients)
+            .into_iter()
+            .partition(|c| c.deadline.is_some_and(|deadline| deadline <= now));
+        self.clients = waiting;
+        for client in expired {
+            let _ = client
+                .event
+                .responder
+                .send(timeout_reply(&client.event.command));
+            resumed.extend(client.deferred);
+        }
+        resumed
+    }
+
+    // Serves the first client (in blocking order) waiting on a ready key, returning
+    // its index once it has been replied to
+    async fn serve_next<S: Storage>(
+        &mut self,
+        storage: &S,
+        ready: &HashSet<String>,
+    ) -> Option<usize> {
+        for (index, client) in self.clients.iter().enumerate() {
+            if !client.keys.iter().any(|key| ready.contains(key)) {
+                continue;
+            }
+            if let Some(response) = try_blocking(storage, &client.event.command).await {
+                let _ = client.event.responder.send(response);
+                return Some(index);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+    use crate::resp::{Protocol, RespValue};
+    use crate::server::ServerCommand;
+    use crate::storage::{ListEnd, ListStorage, MemoryStorage};
+    use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+    fn event(client_id: u64, args: &[&str]) -> (ClientEvent, UnboundedReceiver<ServerCommand>) {
+        let (tx, rx) = unbounded_channel();
+        let command = parse_args(args).unwrap();
+        (ClientEvent::new(client_id, command, tx), rx)
+    }
+
+    #[tokio::test]
+    async fn test_wake_serves_in_order() {
+        let storage = MemoryStorage::new();
+        let mut blocked = BlockedClients::new();
+        let (first, mut first_rx) = event(1, &["BLPOP", "queue", "0"]);
+        let (second, mut second_rx) = event(2, &["BLPOP", "queue", "0"]);
+        blocked.block(first, vec!["queue".into()], None);
+        blocked.block(second, vec!["queue".into()], None);
+
+        // Commands sent while blocked are handed back once the client is served
+        let (later, _) = event(1, &["PING"]);
+        assert!(blocked.defer(later).is_none());
+
+        storage
+            .push("queue", &[b"job".to_vec()], ListEnd::Right, false)
+            .await
+            .unwrap();
+        let resumed = blocked.wake(&storage, vec!["queue".into()]).await;
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].client_id, 1);
+
+        let reply = first_rx.try_recv().unwrap().layout(Protocol::Resp3);
+        let expected = RespValue::Array(vec![
+            RespValue::BulkString(b"queue".to_vec()),
+            RespValue::BulkString(b"job".to_vec()),
+        ]);
+        assert_eq!(reply, expected);
+        assert!(second_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_poll_times_out() {
+        let storage = MemoryStorage::new();
+        let mut blocked = BlockedClients::new();
+        let (client, mut rx) = event(1, &["BLMOVE", "a", "b", "LEFT", "LEFT", "0.01"]);
+        blocked.block(client, vec!["a".into()], Some(Instant::now()));
+
+        assert!(blocked.poll(&storage).await.is_empty());
+        let reply = rx.try_recv().unwrap().layout(Protocol::Resp3);
+        assert_eq!(reply, RespValue::NullBulkString());
+
+        let (client, _) = event(1, &["PING"]);
+        assert!(blocked.defer(client).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_while_blocked() {
+        let storage = MemoryStorage::new();
+        let mut blocked = BlockedClients::new();
+        let (client, rx) = event(1, &["BLPOP", "queue", "0"]);
+        blocked.block(client, vec!["queue".into()], None);
+        drop(rx);
+
+        // The reader's RESET keeps a clone of the responder alive, but replies nowhere
+        let (pipelined, _) = event(1, &["PING"]);
+        assert!(blocked.defer(pipelined).is_none());
+        let (closed_tx, _) = unbounded_channel();
+        let reset = ClientEvent::new(1, ClientCommand::Reset, closed_tx);
+        assert!(blocked.defer(reset).is_some());
+        assert!(!blocked.is_blocked(1));
+
+        storage
+            .push("queue", &[b"job".to_vec()], ListEnd::Right, false)
+            .await
+            .unwrap();
+        assert!(
+            blocked
+                .wake(&storage, vec!["queue".into()])
+                .await
+                .is_empty()
+        );
+        assert_eq!(storage.llen("queue").await.unwrap(), 1);
+    }
+}
@@ -1,21 +1,37 @@
-use crate::resp::RespValue;
+use crate::resp::{Protocol, RespValue};
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ServerCommand {
     Pong(Option<String>),
     Response(RespValue),
     Ok,
     Error(String),
+    // Switches the connection to a protocol, starting with this reply
+    SwitchProtocol(Protocol, RespValue),
+    // Pairs such as members and their scores, replied as nested two element arrays in
+    // RESP3 and as one flat array in RESP2
+    Pairs(Vec<(RespValue, RespValue)>),
+    // A map in RESP3 that RESP2 replies as nested pairs rather than flattened, like the
+    // streams read by XREAD
+    NestedMap(Vec<(RespValue, RespValue)>),
+    // The replies of the commands EXEC ran, each laid out as it would be on its own
+    Replies(Vec<ServerCommand>),
 }
 
-#[allow(unreachable_patterns)]
-impl Into<RespValue> for ServerCommand {
-    fn into(self) -> RespValue {
+impl ServerCommand {
+    // The reply as sent to a connection speaking the protocol
+    pub fn for_protocol(self, protocol: Protocol) -> RespValue {
+        self.layout(protocol).for_protocol(protocol)
+    }
+
+    // Lays out the pairs and maps whose layout depends on the protocol, leaving the
+    // RESP3 types within to `RespValue::for_protocol`
+    pub fn layout(self, protocol: Protocol) -> RespValue {
         match self {
             ServerCommand::Pong(message) => match message {
                 Some(msg) => {
-                    let output = format!("PONG {}", msg);
+                    let output = format!("PONG {msg}");
                     RespValue::BulkString(output.into_bytes())
                 }
                 None => RespValue::SimpleString("PONG".into()),
@@ -23,7 +39,68 @@ impl Into<RespValue> for ServerCommand {
             ServerCommand::Response(value) => value,
             ServerCommand::Ok => RespValue::SimpleString("OK".into()),
             ServerCommand::Error(message) => RespValue::Error(message),
-            _ => RespValue::SimpleString("OK".into()),
+            ServerCommand::SwitchProtocol(_, reply) => reply,
+            ServerCommand::Pairs(pairs) => RespValue::Array(match protocol {
+                Protocol::Resp2 => pairs.into_iter().flat_map(|(k, v)| [k, v]).collect(),
+                Protocol::Resp3 => nested(pairs),
+            }),
+            ServerCommand::NestedMap(pairs) => match protocol {
+                Protocol::Resp2 => RespValue::Array(nested(pairs)),
+                Protocol::Resp3 => RespValue::Map(pairs),
+            },
+            ServerCommand::Replies(replies) => RespValue::Array(
+                replies
+                    .into_iter()
+                    .map(|reply| reply.layout(protocol))
+                    .collect(),
+            ),
         }
     }
 }
+
+fn nested(pairs: Vec<(RespValue, RespValue)>) -> Vec<RespValue> {
+    pairs
+        .into_iter()
+        .map(|(k, v)| RespValue::Array(vec![k, v]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let a = || RespValue::BulkString(b"a".to_vec());
+        let pairs = vec![(a(), RespValue::Double(1.0))];
+        assert_eq!(
+            ServerCommand::Pairs(pairs.clone()).for_protocol(Protocol::Resp2),
+            RespValue::Array(vec![a(), RespValue::BulkString(b"1".to_vec())])
+        );
+        assert_eq!(
+            ServerCommand::Pairs(pairs.clone()).for_protocol(Protocol::Resp3),
+            RespValue::Array(vec![RespValue::Array(vec![a(), RespValue::Double(1.0)])])
+        );
+        assert_eq!(
+            ServerCommand::NestedMap(pairs.clone()).for_protocol(Protocol::Resp2),
+            RespValue::Array(vec![RespValue::Array(vec![
+                a(),
+                RespValue::BulkString(b"1".to_vec())
+            ])])
+        );
+        assert_eq!(
+            ServerCommand::NestedMap(pairs.clone()).for_protocol(Protocol::Resp3),
+            RespValue::Map(pairs.clone())
+        );
+
+        // Replies within EXEC are laid out the same
+        let replies = ServerCommand::Replies(vec![ServerCommand::Ok, ServerCommand::Pairs(pairs)]);
+        assert_eq!(
+            replies.for_protocol(Protocol::Resp2),
+            RespValue::Array(vec![
+                RespValue::SimpleString("OK".into()),
+                RespValue::Array(vec![a(), RespValue::BulkString(b"1".to_vec())]),
+            ])
+        );
+    }
+}
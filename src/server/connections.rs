@@ -0,0 +1,57 @@
+use crate::resp::Protocol;
+use std::collections::HashMap;
+
+// Settings a client made for its connection with HELLO or CLIENT SETNAME
+struct Connection {
+    protocol: Protocol,
+    name: Option<String>,
+}
+
+// The connection settings of every client that changed them from the defaults
+#[derive(Default)]
+pub struct Connections {
+    clients: HashMap<u64, Connection>,
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Connections::default()
+    }
+
+    // Connections speak RESP2 until they negotiate otherwise
+    pub fn protocol(&self, client_id: u64) -> Protocol {
+        self.clients
+            .get(&client_id)
+            .map_or(Protocol::Resp2, |c| c.protocol)
+    }
+
+    pub fn set_protocol(&mut self, client_id: u64, protocol: Protocol) {
+        self.connection(client_id).protocol = protocol;
+    }
+
+    pub fn name(&self, client_id: u64) -> Option<&str> {
+        self.clients.get(&client_id)?.name.as_deref()
+    }
+
+    // An empty name clears it
+    pub fn set_name(&mut self, client_id: u64, name: &str) {
+        self.connection(client_id).name = (!name.is_empty()).then(|| name.to_string());
+    }
+
+    // Restores the defaults, as RESET does and a disconnect requires
+    pub fn reset(&mut self, client_id: u64) {
+        self.clients.remove(&client_id);
+    }
+
+    fn connection(&mut self, client_id: u64) -> &mut Connection {
+        self.clients.entry(client_id).or_insert(Connection {
+            protocol: Protocol::Resp2,
+            name: None,
+        })
+    }
+}
+
+// Names are shown by CLIENT LIST in Redis, so they may not contain spaces or newlines
+pub fn is_valid_name(name: &str) -> bool {
+    name.bytes().all(|b| (b'!'..=b'~').contains(&b))
+}
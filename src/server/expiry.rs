@@ -0,0 +1,35 @@
+use crate::log::{Level, log};
+use crate::storage::Storage;
+use std::sync::Arc;
+use std::time::Duration;
+
+// How often the sweeper wakes up to look for expired keys
+const SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+
+// Maximum number of keys deleted by a single storage call
+const SWEEP_BATCH_SIZE: usize = 200;
+
+// Maximum number of batches per wake-up, so a backlog of expired keys is worked
+// through gradually instead of monopolizing the storage engine
+const SWEEP_MAX_BATCHES: usize = 10;
+
+// Actively deletes expired keys in the background, complementing lazy expiry on access
+pub async fn run_expiry_sweeper<S: Storage>(storage: Arc<S>) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for _ in 0..SWEEP_MAX_BATCHES {
+            match storage.purge_expired(SWEEP_BATCH_SIZE).await {
+                // Keep going only while full batches suggest more keys are waiting
+                Ok(purged) if purged == SWEEP_BATCH_SIZE => {}
+                Ok(_) => break,
+                Err(e) => {
+                    log!(Level::Warning, "Expiry sweep failed: {e:?}");
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use crate::client::{AdminCommand, CommandFilter, command_spec, command_specs};
+use crate::commands::{BeginSearch, CommandSpec, FindKeys, KeySpec};
+use crate::glob::glob_match;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use std::iter;
+
+// Answers COMMAND and its subcommands from the command table, without storage
+pub fn execute(command: &AdminCommand) -> ServerCommand {
+    let reply = match command {
+        AdminCommand::CommandCount => int(command_specs().count()),
+        AdminCommand::CommandDocs(names) if names.is_empty() => {
+            RespValue::Map(command_specs().map(docs).collect())
+        }
+        AdminCommand::CommandDocs(names) => RespValue::Map(
+            names
+                .iter()
+                .filter_map(|name| command_spec(name))
+                .map(docs)
+                .collect(),
+        ),
+        AdminCommand::CommandGetKeys(argv) => return get_keys(argv),
+        AdminCommand::CommandInfo(names) if names.is_empty() => {
+            RespValue::Array(command_specs().map(info).collect())
+        }
+        AdminCommand::CommandInfo(names) => RespValue::Array(
+            names
+                .iter()
+                .map(|name| command_spec(name).map_or(RespValue::NullBulkString(), info))
+                .collect(),
+        ),
+        AdminCommand::CommandList(filter) => RespValue::Array(
+            command_specs()
+                .flat_map(|spec| iter::once(spec).chain(spec.subcommands))
+                .filter(|spec| filter.as_ref().is_none_or(|filter| matches(spec, filter)))
+                .map(|spec| bulk(spec.name))
+                .collect(),
+        ),
+    };
+    ServerCommand::Response(reply)
+}
+
+fn matches(spec: &CommandSpec, filter: &CommandFilter) -> bool {
+    match filter {
+        CommandFilter::AclCategory(category) => spec
+            .acl_categories()
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(category)),
+        // No modules are ever loaded
+        CommandFilter::Module(_) => false,
+        CommandFilter::Pattern(pattern) => glob_match(
+            pattern.to_ascii_lowercase().as_bytes(),
+            spec.name.as_bytes(),
+        ),
+    }
+}
+
+// The reply COMMAND INFO gives for a command, with its subcommands nested in it
+fn info(spec: &CommandSpec) -> RespValue {
+    let (first, last, step) = spec.legacy_key_range();
+    RespValue::Array(vec![
+        bulk(spec.name),
+        RespValue::Integer(spec.arity),
+        statuses(spec.all_flags()),
+        RespValue::Integer(first),
+        RespValue::Integer(last),
+        RespValue::Integer(step),
+        statuses(spec.acl_categories().iter().map(|c| format!("@{c}"))),
+        RespValue::Array(Vec::new()),
+        RespValue::Array(spec.key_specs.iter().map(key_spec).collect()),
+        RespValue::Array(spec.subcommands.iter().map(info).collect()),
+    ])
+}
+
+fn key_spec(spec: &KeySpec) -> RespValue {
+    let begin_search = match &spec.begin_search {
+        BeginSearch::Index(index) => search("index", vec![("index", int(*index))]),
+        BeginSearch::Keyword {
+            keyword,
+            start_from,
+        } => search(
+            "keyword",
+            vec![
+                ("keyword", bulk(keyword)),
+                ("startfrom", RespValue::Integer(*start_from)),
+            ],
+        ),
+    };
+    let find_keys = match &spec.find_keys {
+        FindKeys::Range {
+            last_key,
+            step,
+            limit,
+        } => search(
+            "range",
+            vec![
+                ("lastkey", RespValue::Integer(*last_key)),
+                ("keystep", int(*step)),
+                ("limit", int(*limit)),
+            ],
+        ),
+        FindKeys::KeyNum {
+            key_num_index,
+            first_key,
+            step,
+        } => search(
+            "keynum",
+            vec![
+                ("keynumidx", int(*key_num_index)),
+                ("firstkey", int(*first_key)),
+                ("keystep", int(*step)),
+            ],
+        ),
+    };
+    RespValue::Map(vec![
+        (bulk("flags"), statuses(spec.flags.iter())),
+        (bulk("begin_search"), begin_search),
+        (bulk("find_keys"), find_keys),
+    ])
+}
+
+fn search(kind: &str, spec: Vec<(&str, RespValue)>) -> RespValue {
+    RespValue::Map(vec![
+        (bulk("type"), bulk(kind)),
+        (
+            bulk("spec"),
+            RespValue::Map(spec.into_iter().map(|(k, v)| (bulk(k), v)).collect()),
+        ),
+    ])
+}
+
+// A command's entry in the COMMAND DOCS map
+fn docs(spec: &CommandSpec) -> (RespValue, RespValue) {
+    let mut fields = vec![
+        (bulk("summary"), bulk(spec.summary)),
+        (bulk("since"), bulk(spec.since)),
+        (bulk("group"), bulk(spec.group)),
+    ];
+    if !spec.subcommands.is_empty() {
+        fields.push((
+            bulk("subcommands"),
+            RespValue::Map(spec.subcommands.iter().map(docs).collect()),
+        ));
+    }
+    (bulk(spec.name), RespValue::Map(fields))
+}
+
+// Finds the keys of a command line the way the server would, checking its arity first
+fn get_keys(argv: &[Vec<u8>]) -> ServerCommand {
+    let name = String::from_utf8_lossy(&argv[0]);
+    let Some(mut spec) = command_spec(&name).filter(|_| !name.contains('|')) else {
+        return ServerCommand::Error("ERR Invalid command specified".into());
+    };
+    if let Some(subcommand) = argv.get(1)
+        && let Some(sub) = spec.subcommand(&String::from_utf8_lossy(subcommand).to_lowercase())
+    {
+        spec = sub;
+    }
+    if !spec.accepts(argv.len()) {
+        return ServerCommand::Error(
+            "ERR Invalid number of arguments specified for command".into(),
+        );
+    }
+    if !spec.has_keys() {
+        return ServerCommand::Error("ERR The command has no key arguments".into());
+    }
+    match spec.find_keys(argv) {
+        Some(keys) if !keys.is_empty() || spec.has_flag("no_mandatory_keys") => {
+            ServerCommand::Response(RespValue::Array(
+                keys.into_iter()
+                    .map(|key| RespValue::BulkString(key.to_vec()))
+                    .collect(),
+            ))
+        }
+        _ => ServerCommand::Error("ERR Invalid arguments specified for command".into()),
+    }
+}
+
+fn statuses<T: ToString>(items: impl IntoIterator<Item = T>) -> RespValue {
+    RespValue::Array(
+        items
+            .into_iter()
+            .map(|item| RespValue::SimpleString(item.to_string()))
+            .collect(),
+    )
+}
+
+fn int(n: usize) -> RespValue {
+    RespValue::Integer(i64::try_from(n).unwrap_or(i64::MAX))
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(s.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::RespValue;
+    use crate::server::handler::run;
+    use crate::storage::MemoryStorage;
+
+    fn bulks(items: &[&str]) -> RespValue {
+        RespValue::Array(
+            items
+                .iter()
+                .map(|item| RespValue::BulkString(item.as_bytes().to_vec()))
+                .collect(),
+        )
+    }
+
+    fn error(message: &str) -> RespValue {
+        RespValue::Error(message.into())
+    }
+
+    #[tokio::test]
+    async fn test_command_info() {
+        let storage = MemoryStorage::new();
+        let RespValue::Array(info) = run(&storage, &["COMMAND", "INFO", "get", "bogus"]).await
+        else {
+            panic!("expected an array");
+        };
+        let RespValue::Array(get) = &info[0] else {
+            panic!("expected an array");
+        };
+        assert_eq!(get[0], RespValue::BulkString(b"get".to_vec()));
+        assert_eq!(
+            get[1..6],
+            [
+                RespValue::Integer(2),
+                RespValue::Array(vec![
+                    RespValue::SimpleString("readonly".into()),
+                    RespValue::SimpleString("fast".into()),
+                ]),
+                RespValue::Integer(1),
+                RespValue::Integer(1),
+                RespValue::Integer(1),
+            ]
+        );
+        assert_eq!(
+            get[6],
+            RespValue::Array(
+                ["@read", "@fast", "@string"]
+                    .map(|c| RespValue::SimpleString(c.into()))
+                    .to_vec()
+            )
+        );
+        assert_eq!(info[1], RespValue::NullBulkString());
+
+        let RespValue::Array(all) = run(&storage, &["COMMAND"]).await else {
+            panic!("expected an array");
+        };
+        assert_eq!(
+            run(&storage, &["COMMAND", "COUNT"]).await,
+            RespValue::Integer(i64::try_from(all.len()).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_getkeys() {
+        let storage = MemoryStorage::new();
+        let getkeys = async |args: &[&str]| {
+            let args = [&["COMMAND", "GETKEYS"], args].concat();
+            run(&storage, &args).await
+        };
+        assert_eq!(getkeys(&["SET", "k", "v"]).await, bulks(&["k"]));
+        assert_eq!(getkeys(&["smove", "a", "b", "m"]).await, bulks(&["a", "b"]));
+        assert_eq!(
+            getkeys(&["EVAL", "return 1", "2", "a", "b", "x"]).await,
+            bulks(&["a", "b"])
+        );
+        assert_eq!(getkeys(&["EVAL", "return 1", "0"]).await, bulks(&[]));
+        assert_eq!(
+            getkeys(&["XREAD", "COUNT", "1", "STREAMS", "a", "b", "0", "0"]).await,
+            bulks(&["a", "b"])
+        );
+        assert_eq!(
+            getkeys(&["XGROUP", "DESTROY", "s", "g"]).await,
+            bulks(&["s"])
+        );
+
+        assert_eq!(
+            getkeys(&["BOGUS", "k"]).await,
+            error("ERR Invalid command specified")
+        );
+        assert_eq!(
+            getkeys(&["GET"]).await,
+            error("ERR Invalid number of arguments specified for command")
+        );
+        assert_eq!(
+            getkeys(&["PING", "x"]).await,
+            error("ERR The command has no key arguments")
+        );
+        assert_eq!(
+            getkeys(&["EVAL", "return 1", "3", "a"]).await,
+            error("ERR Invalid arguments specified for command")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_list_and_docs() {
+        let storage = MemoryStorage::new();
+        assert_eq!(
+            run(
+                &storage,
+                &["COMMAND", "LIST", "FILTERBY", "PATTERN", "XINFO*"]
+            )
+            .await,
+            bulks(&["xinfo", "xinfo|consumers", "xinfo|groups", "xinfo|stream"])
+        );
+        let RespValue::Array(blocking) = run(
+            &storage,
+            &["COMMAND", "LIST", "FILTERBY", "ACLCAT", "blocking"],
+        )
+        .await
+        else {
+            panic!("expected an array");
+        };
+        assert!(blocking.contains(&RespValue::BulkString(b"blpop".to_vec())));
+        assert!(!blocking.contains(&RespValue::BulkString(b"lpop".to_vec())));
+        assert_eq!(
+            run(&storage, &["COMMAND", "LIST", "FILTERBY", "MODULE", "x"]).await,
+            bulks(&[])
+        );
+
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        assert_eq!(
+            run(&storage, &["COMMAND", "DOCS", "TYPE", "bogus"]).await,
+            RespValue::Map(vec![(
+                bulk("type"),
+                RespValue::Map(vec![
+                    (
+                        bulk("summary"),
+                        bulk("Determines the type of value stored at a key.")
+                    ),
+                    (bulk("since"), bulk("1.0.0")),
+                    (bulk("group"), bulk("generic")),
+                ])
+            )])
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::client::ConfigCommand;
+use crate::config::Config;
+use crate::log;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::server::transactions::Transactions;
+
+// Runs CONFIG. Parameters read where they are used take effect with the next use, the
+// others are handed on here.
+pub fn execute<V>(
+    config: &mut Config,
+    transactions: &mut Transactions<V>,
+    command: &ConfigCommand,
+) -> ServerCommand {
+    match command {
+        ConfigCommand::Get(patterns) => ServerCommand::Response(RespValue::Map(
+            config
+                .get(patterns)
+                .into_iter()
+                .map(|(name, value)| (bulk(name), bulk(&value)))
+                .collect(),
+        )),
+        ConfigCommand::Set(pairs) => match config.set(pairs) {
+            Ok(()) => {
+                log::set_level(config.loglevel);
+                transactions.set_atomic(config.atomic_exec);
+                ServerCommand::Ok
+            }
+            Err(message) => ServerCommand::Error(message),
+        },
+        // No statistics are kept yet
+        ConfigCommand::ResetStat => ServerCommand::Ok,
+        ConfigCommand::Rewrite => match config.rewrite() {
+            Ok(()) => ServerCommand::Ok,
+            Err(message) => ServerCommand::Error(message),
+        },
+    }
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(s.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ClientCommand, parse_args};
+    use crate::resp::Protocol;
+
+    fn config(
+        config: &mut Config,
+        transactions: &mut Transactions<i64>,
+        args: &[&str],
+    ) -> RespValue {
+        let ClientCommand::Config(command) = parse_args(args).unwrap() else {
+            panic!("expected a config command");
+        };
+        execute(config, transactions, &command).layout(Protocol::Resp3)
+    }
+
+    #[test]
+    fn test_config() {
+        let mut settings = Config::default();
+        let mut transactions = Transactions::new(false);
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+
+        assert_eq!(
+            config(
+                &mut settings,
+                &mut transactions,
+                &["CONFIG", "SET", "atomic-exec", "yes", "maxclients", "2"]
+            ),
+            RespValue::SimpleString("OK".into())
+        );
+        assert!(transactions.is_atomic());
+        assert_eq!(
+            config(
+                &mut settings,
+                &mut transactions,
+                &["CONFIG", "GET", "max*", "atomic-exec"]
+            ),
+            RespValue::Map(vec![
+                (bulk("maxclients"), bulk("2")),
+                (bulk("atomic-exec"), bulk("yes")),
+            ])
+        );
+        assert!(matches!(
+            config(&mut settings, &mut transactions, &["CONFIG", "SET", "bind", "::"]),
+            RespValue::Error(e) if e.ends_with("can't set immutable config")
+        ));
+        assert!(matches!(
+            config(&mut settings, &mut transactions, &["CONFIG", "REWRITE"]),
+            RespValue::Error(e) if e == "ERR The server is running without a config file"
+        ));
+    }
+}
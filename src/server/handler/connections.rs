@@ -0,0 +1,92 @@
+use crate::client::ClientCommand;
+use crate::resp::{Protocol, RespValue};
+use crate::server::ServerCommand;
+use crate::server::connections::{Connections, is_valid_name};
+
+// Runs HELLO and CLIENT, returning `None` for other commands
+pub fn execute(
+    connections: &mut Connections,
+    client_id: u64,
+    command: &ClientCommand,
+) -> Option<ServerCommand> {
+    let response = match command {
+        ClientCommand::Hello {
+            protocol,
+            auth,
+            name,
+        } => hello(
+            connections,
+            client_id,
+            *protocol,
+            auth.as_ref(),
+            name.as_deref(),
+        ),
+        ClientCommand::ClientGetName => ServerCommand::Response(
+            connections
+                .name(client_id)
+                .map_or(RespValue::NullBulkString(), bulk),
+        ),
+        ClientCommand::ClientSetName(name) if !is_valid_name(name) => name_error(),
+        ClientCommand::ClientSetName(name) => {
+            connections.set_name(client_id, name);
+            ServerCommand::Ok
+        }
+        _ => return None,
+    };
+    Some(response)
+}
+
+// Applies the options only once all of them are valid, replying with the server's
+// details in the negotiated protocol
+fn hello(
+    connections: &mut Connections,
+    client_id: u64,
+    protocol: Option<i64>,
+    auth: Option<&(String, String)>,
+    name: Option<&str>,
+) -> ServerCommand {
+    let protocol = match protocol {
+        None => connections.protocol(client_id),
+        Some(2) => Protocol::Resp2,
+        Some(3) => Protocol::Resp3,
+        Some(_) => return ServerCommand::Error("NOPROTO unsupported protocol version".into()),
+    };
+    // No passwords are configured, so only the default user exists and any password
+    // authenticates it
+    if auth.is_some_and(|(username, _)| username != "default") {
+        return ServerCommand::Error(
+            "WRONGPASS invalid username-password pair or user is disabled.".into(),
+        );
+    }
+    if let Some(name) = name {
+        if !is_valid_name(name) {
+            return name_error();
+        }
+        connections.set_name(client_id, name);
+    }
+    connections.set_protocol(client_id, protocol);
+
+    let reply = RespValue::Map(vec![
+        (bulk("server"), bulk("postgredis")),
+        (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+        (bulk("proto"), RespValue::Integer(protocol.version())),
+        (
+            bulk("id"),
+            RespValue::Integer(i64::try_from(client_id).unwrap_or(i64::MAX)),
+        ),
+        (bulk("mode"), bulk("standalone")),
+        (bulk("role"), bulk("master")),
+        (bulk("modules"), RespValue::Array(Vec::new())),
+    ]);
+    ServerCommand::SwitchProtocol(protocol, reply)
+}
+
+fn name_error() -> ServerCommand {
+    ServerCommand::Error(
+        "ERR Client names cannot contain spaces, newlines or special characters.".into(),
+    )
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(s.as_bytes().to_vec())
+}
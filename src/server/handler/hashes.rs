@@ -0,0 +1,295 @@
+use crate::client::HashCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::storage::{Storage, StorageResult, format_float};
+use rand::seq::IndexedRandom;
+
+// Number of fields HSCAN examines per call when no COUNT is given
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &HashCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        HashCommand::Del { key, fields } => {
+            let deleted = storage.hdel(key, fields).await?;
+            ServerCommand::Response(RespValue::Integer(deleted))
+        }
+        HashCommand::Exists { key, field } => {
+            let value = get_field(storage, key, field).await?;
+            ServerCommand::Response(RespValue::Integer(i64::from(value.is_some())))
+        }
+        HashCommand::Get { key, field } => {
+            let value = get_field(storage, key, field).await?;
+            ServerCommand::Response(bulk_or_null(value))
+        }
+        HashCommand::GetAll(key) => {
+            let pairs = storage.hgetall(key).await?;
+            let pairs = pairs
+                .into_iter()
+                .map(|(f, v)| (RespValue::BulkString(f), RespValue::BulkString(v)));
+            ServerCommand::Response(RespValue::Map(pairs.collect()))
+        }
+        HashCommand::IncrBy { key, field, delta } => {
+            let value = storage.hincrby(key, field, *delta).await?;
+            ServerCommand::Response(RespValue::Integer(value))
+        }
+        HashCommand::IncrByFloat { key, field, delta } => {
+            let value = storage.hincrbyfloat(key, field, *delta).await?;
+            ServerCommand::Response(RespValue::BulkString(format_float(value).into_bytes()))
+        }
+        HashCommand::Keys(key) => {
+            let pairs = storage.hgetall(key).await?;
+            let fields = pairs.into_iter().map(|(f, _)| RespValue::BulkString(f));
+            ServerCommand::Response(RespValue::Array(fields.collect()))
+        }
+        HashCommand::Len(key) => {
+            let len = storage.hlen(key).await?;
+            ServerCommand::Response(RespValue::Integer(len))
+        }
+        HashCommand::MGet { key, fields } => {
+            let values = storage.hmget(key, fields).await?;
+            let values = values.into_iter().map(bulk_or_null).collect();
+            ServerCommand::Response(RespValue::Array(values))
+        }
+        HashCommand::RandField {
+            key,
+            count,
+            with_values,
+        } => {
+            let pairs = storage.hgetall(key).await?;
+            rand_field_reply(&pairs, *count, *with_values)
+        }
+        HashCommand::Scan {
+            key,
+            cursor,
+            pattern,
+            count,
+            no_values,
+        } => {
+            let count = count.unwrap_or(DEFAULT_SCAN_COUNT);
+            let scan = storage.hscan(key, *cursor, pattern.as_deref(), count);
+            let (next_cursor, pairs) = scan.await?;
+            ServerCommand::Response(RespValue::Array(vec![
+                RespValue::BulkString(next_cursor.to_string().into_bytes()),
+                pairs_reply(pairs, !no_values),
+            ]))
+        }
+        HashCommand::Set { key, pairs, legacy } => {
+            let added = storage.hset(key, pairs).await?;
+            if *legacy {
+                ServerCommand::Ok
+            } else {
+                ServerCommand::Response(RespValue::Integer(added))
+            }
+        }
+        HashCommand::SetNx { key, field, value } => {
+            let set = storage.hsetnx(key, field, value).await?;
+            ServerCommand::Response(RespValue::Integer(i64::from(set)))
+        }
+        HashCommand::StrLen { key, field } => {
+            let value = get_field(storage, key, field).await?;
+            let len = value.map_or(0, |v| v.len());
+            ServerCommand::Response(RespValue::Integer(i64::try_from(len).unwrap_or(i64::MAX)))
+        }
+        HashCommand::Vals(key) => {
+            let pairs = storage.hgetall(key).await?;
+            let values = pairs.into_iter().map(|(_, v)| RespValue::BulkString(v));
+            ServerCommand::Response(RespValue::Array(values.collect()))
+        }
+    };
+    Ok(response)
+}
+
+async fn get_field<S: Storage>(
+    storage: &S,
+    key: &str,
+    field: &[u8],
+) -> StorageResult<Option<Vec<u8>>> {
+    let mut values = storage.hmget(key, &[field.to_vec()]).await?;
+    Ok(values.pop().flatten())
+}
+
+fn bulk_or_null(value: Option<Vec<u8>>) -> RespValue {
+    match value {
+        Some(value) => RespValue::BulkString(value),
+        None => RespValue::NullBulkString(),
+    }
+}
+
+// Flattens field/value pairs into a single array, optionally leaving out the values
+fn pairs_reply(pairs: Vec<(Vec<u8>, Vec<u8>)>, with_values: bool) -> RespValue {
+    let mut items = Vec::with_capacity(pairs.len() * 2);
+    for (field, value) in pairs {
+        items.push(RespValue::BulkString(field));
+        if with_values {
+            items.push(RespValue::BulkString(value));
+        }
+    }
+    RespValue::Array(items)
+}
+
+// HRANDFIELD pairs fields with their values, nested in RESP3
+fn rand_pairs_reply(pairs: Vec<(Vec<u8>, Vec<u8>)>, with_values: bool) -> ServerCommand {
+    if !with_values {
+        return ServerCommand::Response(pairs_reply(pairs, false));
+    }
+    ServerCommand::Pairs(
+        pairs
+            .into_iter()
+            .map(|(field, value)| (RespValue::BulkString(field), RespValue::BulkString(value)))
+            .collect(),
+    )
+}
+
+// A positive count picks distinct fields, a negative count may repeat them
+fn rand_field_reply(
+    pairs: &[(Vec<u8>, Vec<u8>)],
+    count: Option<i64>,
+    with_values: bool,
+) -> ServerCommand {
+    let mut rng = rand::rng();
+    let Some(count) = count else {
+        let field = pairs.choose(&mut rng).map(|(f, _)| f.clone());
+        return ServerCommand::Response(bulk_or_null(field));
+    };
+
+    let amount = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+    let picked: Vec<(Vec<u8>, Vec<u8>)> = if count >= 0 {
+        pairs.sample(&mut rng, amount).cloned().collect()
+    } else if pairs.is_empty() {
+        Vec::new()
+    } else {
+        (0..amount)
+            .filter_map(|_| pairs.choose(&mut rng).cloned())
+            .collect()
+    };
+    rand_pairs_reply(picked, with_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::{Protocol, RespValue};
+    use crate::server::handler::{run, run_as};
+    use crate::storage::MemoryStorage;
+
+    fn bulk(value: &str) -> RespValue {
+        RespValue::BulkString(value.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_hset_hget() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["HSET", "user:1", "name", "ada", "age", "36"]).await;
+        assert_eq!(got, RespValue::Integer(2));
+        let got = run(&storage, &["HMSET", "user:1", "name", "grace"]).await;
+        assert_eq!(got, RespValue::SimpleString("OK".into()));
+
+        assert_eq!(
+            run(&storage, &["HGET", "user:1", "name"]).await,
+            bulk("grace")
+        );
+        assert_eq!(
+            run(&storage, &["HGET", "user:1", "email"]).await,
+            RespValue::NullBulkString()
+        );
+        assert_eq!(
+            run(&storage, &["HMGET", "user:1", "age", "email"]).await,
+            RespValue::Array(vec![bulk("36"), RespValue::NullBulkString()])
+        );
+        assert_eq!(
+            run(&storage, &["HGETALL", "user:1"]).await,
+            RespValue::Map(vec![
+                (bulk("age"), bulk("36")),
+                (bulk("name"), bulk("grace"))
+            ])
+        );
+        assert_eq!(
+            run(&storage, &["HKEYS", "user:1"]).await,
+            RespValue::Array(vec![bulk("age"), bulk("name")])
+        );
+        assert_eq!(
+            run(&storage, &["HSTRLEN", "user:1", "name"]).await,
+            RespValue::Integer(5)
+        );
+        assert_eq!(
+            run(&storage, &["HEXISTS", "user:1", "age"]).await,
+            RespValue::Integer(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hdel_hlen() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["HSET", "h", "a", "1", "b", "2"]).await;
+
+        assert_eq!(
+            run(&storage, &["HDEL", "h", "a", "c"]).await,
+            RespValue::Integer(1)
+        );
+        assert_eq!(run(&storage, &["HLEN", "h"]).await, RespValue::Integer(1));
+        run(&storage, &["HDEL", "h", "b"]).await;
+        assert_eq!(run(&storage, &["EXISTS", "h"]).await, RespValue::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn test_hincrby() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["HINCRBY", "h", "visits", "3"]).await;
+        assert_eq!(got, RespValue::Integer(3));
+        let got = run(&storage, &["HINCRBYFLOAT", "h", "score", "10.5"]).await;
+        assert_eq!(got, bulk("10.5"));
+        let got = run(&storage, &["HINCRBYFLOAT", "h", "score", "-0.5"]).await;
+        assert_eq!(got, bulk("10"));
+    }
+
+    #[tokio::test]
+    async fn test_hrandfield() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["HSET", "h", "a", "1", "b", "2"]).await;
+
+        let got = run(&storage, &["HRANDFIELD", "h", "5"]).await;
+        assert!(matches!(got, RespValue::Array(items) if items.len() == 2));
+        let got = run_as(
+            &storage,
+            Protocol::Resp2,
+            &["HRANDFIELD", "h", "-5", "WITHVALUES"],
+        )
+        .await;
+        assert!(matches!(got, RespValue::Array(items) if items.len() == 10));
+        // RESP3 nests each field with its value
+        let got = run_as(
+            &storage,
+            Protocol::Resp3,
+            &["HRANDFIELD", "h", "-5", "WITHVALUES"],
+        )
+        .await;
+        let RespValue::Array(pairs) = got else {
+            panic!("expected an array of pairs");
+        };
+        assert_eq!(pairs.len(), 5);
+        assert!(
+            pairs
+                .iter()
+                .all(|pair| matches!(pair, RespValue::Array(items) if items.len() == 2))
+        );
+        let got = run(&storage, &["HRANDFIELD", "missing"]).await;
+        assert_eq!(got, RespValue::NullBulkString());
+    }
+
+    #[tokio::test]
+    async fn test_hscan() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["HSET", "h", "a1", "1", "a2", "2", "b1", "3"]).await;
+
+        let got = run(&storage, &["HSCAN", "h", "0", "MATCH", "a*", "NOVALUES"]).await;
+        let expected = RespValue::Array(vec![
+            bulk("0"),
+            RespValue::Array(vec![bulk("a1"), bulk("a2")]),
+        ]);
+        assert_eq!(got, expected);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::client::{Expiry, KeyCommand};
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::server::handler::resolve_expiry;
+use crate::storage::{
+    ExpireCondition, KeyExpiry, KeyKind, Storage, StorageError, StorageResult, now_millis,
+};
+
+// Number of keys SCAN examines per call when no COUNT is given
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &KeyCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        KeyCommand::Del(keys) => {
+            let deleted = storage.del(keys).await?;
+            ServerCommand::Response(RespValue::Integer(deleted))
+        }
+        KeyCommand::Exists(keys) => {
+            let found = storage.exists(keys).await?;
+            ServerCommand::Response(RespValue::Integer(found))
+        }
+        KeyCommand::Expire {
+            key,
+            expiry,
+            condition,
+        } => {
+            let Some(expires_at) = resolve_expiry(*expiry) else {
+                return Err(StorageError::InvalidExpireTime(expire_command_name(
+                    *expiry,
+                )));
+            };
+            let updated = storage.expire(key, Some(expires_at), *condition).await?;
+            ServerCommand::Response(RespValue::Integer(i64::from(updated)))
+        }
+        KeyCommand::ExpireTime(key) => {
+            let expiry = storage.expire_time(key).await?;
+            let reply = expiry_reply(expiry, round_to_seconds);
+            ServerCommand::Response(RespValue::Integer(reply))
+        }
+        KeyCommand::Persist(key) => {
+            let cleared = storage
+                .expire(key, None, ExpireCondition::IfHasExpiry)
+                .await?;
+            ServerCommand::Response(RespValue::Integer(i64::from(cleared)))
+        }
+        KeyCommand::PexpireTime(key) => {
+            let expiry = storage.expire_time(key).await?;
+            let reply = expiry_reply(expiry, |at| at);
+            ServerCommand::Response(RespValue::Integer(reply))
+        }
+        KeyCommand::Pttl(key) => {
+            let expiry = storage.expire_time(key).await?;
+            let reply = expiry_reply(expiry, |at| (at - now_millis()).max(0));
+            ServerCommand::Response(RespValue::Integer(reply))
+        }
+        KeyCommand::Scan {
+            cursor,
+            pattern,
+            count,
+            kind,
+        } => {
+            let count = count.unwrap_or(DEFAULT_SCAN_COUNT);
+            let scan = storage.scan(*cursor, pattern.as_deref(), *kind, count);
+            let (next_cursor, keys) = scan.await?;
+            let keys = keys
+                .into_iter()
+                .map(|k| RespValue::BulkString(k.into_bytes()))
+                .collect();
+            ServerCommand::Response(RespValue::Array(vec![
+                RespValue::BulkString(next_cursor.to_string().into_bytes()),
+                RespValue::Array(keys),
+            ]))
+        }
+        KeyCommand::Ttl(key) => {
+            let expiry = storage.expire_time(key).await?;
+            let reply = expiry_reply(expiry, |at| round_to_seconds((at - now_millis()).max(0)));
+            ServerCommand::Response(RespValue::Integer(reply))
+        }
+        KeyCommand::Type(key) => {
+            let name = storage.key_type(key).await?.map_or("none", KeyKind::as_str);
+            ServerCommand::Response(RespValue::SimpleString(name.into()))
+        }
+    };
+    Ok(response)
+}
+
+fn expire_command_name(expiry: Expiry) -> &'static str {
+    match expiry {
+        Expiry::Seconds(_) | Expiry::KeepTtl => "expire",
+        Expiry::Milliseconds(_) => "pexpire",
+        Expiry::UnixSeconds(_) => "expireat",
+        Expiry::UnixMilliseconds(_) => "pexpireat",
+    }
+}
+
+// Replies -2 for a missing key, -1 for a persistent key, otherwise the formatted expiry
+fn expiry_reply(expiry: KeyExpiry, format: impl Fn(i64) -> i64) -> i64 {
+    match expiry {
+        KeyExpiry::Missing => -2,
+        KeyExpiry::Persistent => -1,
+        KeyExpiry::At(at) => format(at),
+    }
+}
+
+fn round_to_seconds(ms: i64) -> i64 {
+    (ms + 500) / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::RespValue;
+    use crate::server::handler::run;
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_del_exists() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SET", "foo", "bar"]).await;
+
+        let got = run(&storage, &["EXISTS", "foo", "foo", "baz"]).await;
+        assert_eq!(got, RespValue::Integer(2));
+
+        let got = run(&storage, &["DEL", "foo", "baz"]).await;
+        assert_eq!(got, RespValue::Integer(1));
+
+        let got = run(&storage, &["EXISTS", "foo"]).await;
+        assert_eq!(got, RespValue::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn test_expire_ttl_persist() {
+        let storage = MemoryStorage::new();
+        assert_eq!(run(&storage, &["TTL", "foo"]).await, RespValue::Integer(-2));
+
+        run(&storage, &["SET", "foo", "bar"]).await;
+        assert_eq!(run(&storage, &["TTL", "foo"]).await, RespValue::Integer(-1));
+
+        let got = run(&storage, &["EXPIRE", "foo", "100"]).await;
+        assert_eq!(got, RespValue::Integer(1));
+        assert_eq!(
+            run(&storage, &["TTL", "foo"]).await,
+            RespValue::Integer(100)
+        );
+
+        // NX is rejected because the key already has an expiry
+        let got = run(&storage, &["EXPIRE", "foo", "200", "NX"]).await;
+        assert_eq!(got, RespValue::Integer(0));
+
+        let got = run(&storage, &["PERSIST", "foo"]).await;
+        assert_eq!(got, RespValue::Integer(1));
+        assert_eq!(
+            run(&storage, &["PTTL", "foo"]).await,
+            RespValue::Integer(-1)
+        );
+
+        let got = run(&storage, &["PEXPIREAT", "foo", "4102444800000"]).await;
+        assert_eq!(got, RespValue::Integer(1));
+        let got = run(&storage, &["EXPIRETIME", "foo"]).await;
+        assert_eq!(got, RespValue::Integer(4_102_444_800));
+    }
+
+    #[tokio::test]
+    async fn test_expire_in_past_deletes_key() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SET", "foo", "bar"]).await;
+
+        let got = run(&storage, &["EXPIRE", "foo", "-1"]).await;
+        assert_eq!(got, RespValue::Integer(1));
+        assert_eq!(run(&storage, &["TTL", "foo"]).await, RespValue::Integer(-2));
+    }
+
+    #[tokio::test]
+    async fn test_expire_overflow() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SET", "foo", "bar"]).await;
+
+        let got = run(&storage, &["EXPIRE", "foo", "9223372036854775807"]).await;
+        assert_eq!(
+            got,
+            RespValue::Error("ERR invalid expire time in 'expire' command".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan() {
+        let storage = MemoryStorage::new();
+        for key in ["user:1", "user:2", "session:1"] {
+            run(&storage, &["SET", key, "x"]).await;
+        }
+
+        let got = run(&storage, &["SCAN", "0", "MATCH", "user:*"]).await;
+        let expected = RespValue::Array(vec![
+            RespValue::BulkString(b"0".to_vec()),
+            RespValue::Array(vec![
+                RespValue::BulkString(b"user:1".to_vec()),
+                RespValue::BulkString(b"user:2".to_vec()),
+            ]),
+        ]);
+        assert_eq!(got, expected);
+    }
+
+    #[tokio::test]
+    async fn test_type() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SET", "s", "x"]).await;
+        run(&storage, &["HSET", "h", "f", "v"]).await;
+
+        let string = RespValue::SimpleString("string".into());
+        assert_eq!(run(&storage, &["TYPE", "s"]).await, string);
+        let hash = RespValue::SimpleString("hash".into());
+        assert_eq!(run(&storage, &["TYPE", "h"]).await, hash);
+        let none = RespValue::SimpleString("none".into());
+        assert_eq!(run(&storage, &["TYPE", "x"]).await, none);
+
+        let got = run(&storage, &["SCAN", "0", "TYPE", "hash"]).await;
+        let expected = RespValue::Array(vec![
+            RespValue::BulkString(b"0".to_vec()),
+            RespValue::Array(vec![RespValue::BulkString(b"h".to_vec())]),
+        ]);
+        assert_eq!(got, expected);
+    }
+}
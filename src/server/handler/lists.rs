@@ -0,0 +1,311 @@
+use crate::client::ListCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::storage::{Storage, StorageResult};
+use std::time::Duration;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &ListCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        // Outside of the event loop (e.g. in a transaction) blocking commands never wait
+        ListCommand::BlockingPop { .. } | ListCommand::BlockingMove { .. } => {
+            match try_blocking(storage, command).await? {
+                Some(response) => response,
+                None => timeout_reply(command),
+            }
+        }
+        ListCommand::Index { key, index } => {
+            let mut values = storage.lrange(key, *index, *index).await?;
+            ServerCommand::Response(bulk_or_null(values.pop()))
+        }
+        ListCommand::Insert {
+            key,
+            before,
+            pivot,
+            value,
+        } => {
+            let len = storage.linsert(key, *before, pivot, value).await?;
+            ServerCommand::Response(RespValue::Integer(len))
+        }
+        ListCommand::Len(key) => {
+            let len = storage.llen(key).await?;
+            ServerCommand::Response(RespValue::Integer(len))
+        }
+        ListCommand::Move {
+            source,
+            destination,
+            from,
+            to,
+        } => {
+            let value = storage.lmove(source, destination, *from, *to).await?;
+            ServerCommand::Response(bulk_or_null(value))
+        }
+        ListCommand::Pop { key, end, count } => {
+            let popped = storage.pop(key, *end, count.unwrap_or(1)).await?;
+            ServerCommand::Response(match (popped, count) {
+                (None, Some(_)) => RespValue::NullArray(),
+                (popped, None) => bulk_or_null(popped.and_then(|mut v| v.pop())),
+                (Some(values), Some(_)) => bulk_array(values),
+            })
+        }
+        ListCommand::Pos {
+            key,
+            element,
+            rank,
+            count,
+            max_len,
+        } => {
+            let values = storage.lrange(key, 0, -1).await?;
+            let positions = find_positions(&values, element, *rank, *count, *max_len);
+            match count {
+                Some(_) => {
+                    let positions = positions.into_iter().map(RespValue::Integer).collect();
+                    ServerCommand::Response(RespValue::Array(positions))
+                }
+                None => ServerCommand::Response(match positions.first() {
+                    Some(position) => RespValue::Integer(*position),
+                    None => RespValue::NullBulkString(),
+                }),
+            }
+        }
+        ListCommand::Push {
+            key,
+            values,
+            end,
+            only_existing,
+        } => {
+            let len = storage.push(key, values, *end, *only_existing).await?;
+            ServerCommand::Response(RespValue::Integer(len))
+        }
+        ListCommand::Range { key, start, stop } => {
+            let values = storage.lrange(key, *start, *stop).await?;
+            ServerCommand::Response(bulk_array(values))
+        }
+        ListCommand::Rem { key, count, value } => {
+            let removed = storage.lrem(key, *count, value).await?;
+            ServerCommand::Response(RespValue::Integer(removed))
+        }
+        ListCommand::Set { key, index, value } => {
+            storage.lset(key, *index, value).await?;
+            ServerCommand::Ok
+        }
+        ListCommand::Trim { key, start, stop } => {
+            storage.ltrim(key, *start, *stop).await?;
+            ServerCommand::Ok
+        }
+    };
+    Ok(response)
+}
+
+// Returns the keys a blocking command waits on and its timeout (zero waits forever)
+pub fn blocking_keys(command: &ListCommand) -> Option<(Vec<String>, Duration)> {
+    match command {
+        ListCommand::BlockingPop { keys, timeout, .. } => Some((keys.clone(), *timeout)),
+        ListCommand::BlockingMove {
+            source, timeout, ..
+        } => Some((vec![source.clone()], *timeout)),
+        _ => None,
+    }
+}
+
+// Returns the keys that may have gained elements after the command ran, so clients
+// blocked on them can be served
+pub fn ready_keys(command: &ListCommand) -> Vec<String> {
+    match command {
+        ListCommand::Push { key, .. } | ListCommand::Insert { key, .. } => vec![key.clone()],
+        ListCommand::Move { destination, .. } | ListCommand::BlockingMove { destination, .. } => {
+            vec![destination.clone()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Attempts a blocking command without waiting, returning `None` if it would block
+pub async fn try_blocking<S: Storage>(
+    storage: &S,
+    command: &ListCommand,
+) -> StorageResult<Option<ServerCommand>> {
+    match command {
+        ListCommand::BlockingPop { keys, end, .. } => {
+            // Keys are tried in the order given, the first non-empty list wins
+            for key in keys {
+                let popped = storage.pop(key, *end, 1).await?;
+                if let Some(value) = popped.and_then(|mut values| values.pop()) {
+                    return Ok(Some(ServerCommand::Response(RespValue::Array(vec![
+                        RespValue::BulkString(key.clone().into_bytes()),
+                        RespValue::BulkString(value),
+                    ]))));
+                }
+            }
+            Ok(None)
+        }
+        ListCommand::BlockingMove {
+            source,
+            destination,
+            from,
+            to,
+            ..
+        } => {
+            let value = storage.lmove(source, destination, *from, *to).await?;
+            Ok(value.map(|value| ServerCommand::Response(RespValue::BulkString(value))))
+        }
+        _ => Ok(None),
+    }
+}
+
+// The reply sent to a blocked client whose timeout elapsed
+pub fn timeout_reply(command: &ListCommand) -> ServerCommand {
+    match command {
+        ListCommand::BlockingPop { .. } => ServerCommand::Response(RespValue::NullArray()),
+        _ => ServerCommand::Response(RespValue::NullBulkString()),
+    }
+}
+
+// Finds the indexes of matching elements for LPOS, where a negative rank searches from
+// the tail, a count of zero returns every match and a max length of zero scans the
+// whole list
+fn find_positions(
+    values: &[Vec<u8>],
+    element: &[u8],
+    rank: i64,
+    count: Option<usize>,
+    max_len: usize,
+) -> Vec<i64> {
+    let limit = if max_len == 0 { values.len() } else { max_len };
+    let skip = usize::try_from(rank.unsigned_abs() - 1).unwrap_or(usize::MAX);
+    let wanted = match count {
+        Some(0) => usize::MAX,
+        Some(count) => count,
+        None => 1,
+    };
+
+    let indexes: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+        Box::new(0..values.len())
+    } else {
+        Box::new((0..values.len()).rev())
+    };
+    indexes
+        .take(limit)
+        .filter(|i| values[*i] == element)
+        .skip(skip)
+        .take(wanted)
+        .map(|i| i64::try_from(i).unwrap_or(i64::MAX))
+        .collect()
+}
+
+fn bulk_or_null(value: Option<Vec<u8>>) -> RespValue {
+    match value {
+        Some(value) => RespValue::BulkString(value),
+        None => RespValue::NullBulkString(),
+    }
+}
+
+fn bulk_array(values: Vec<Vec<u8>>) -> RespValue {
+    RespValue::Array(values.into_iter().map(RespValue::BulkString).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::RespValue;
+    use crate::server::handler::run;
+    use crate::storage::MemoryStorage;
+
+    fn bulk(value: &str) -> RespValue {
+        RespValue::BulkString(value.as_bytes().to_vec())
+    }
+
+    fn bulks(values: &[&str]) -> RespValue {
+        RespValue::Array(values.iter().map(|v| bulk(v)).collect())
+    }
+
+    #[tokio::test]
+    async fn test_push_pop_range() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["RPUSH", "l", "a", "b", "c"]).await;
+        assert_eq!(got, RespValue::Integer(3));
+        let got = run(&storage, &["LPUSH", "l", "z"]).await;
+        assert_eq!(got, RespValue::Integer(4));
+
+        assert_eq!(
+            run(&storage, &["LRANGE", "l", "0", "-1"]).await,
+            bulks(&["z", "a", "b", "c"])
+        );
+        assert_eq!(run(&storage, &["LINDEX", "l", "-2"]).await, bulk("b"));
+        assert_eq!(run(&storage, &["LPOP", "l"]).await, bulk("z"));
+        assert_eq!(run(&storage, &["RPOP", "l", "2"]).await, bulks(&["c", "b"]));
+        assert_eq!(run(&storage, &["LLEN", "l"]).await, RespValue::Integer(1));
+
+        assert_eq!(
+            run(&storage, &["LPOP", "missing"]).await,
+            RespValue::NullBulkString()
+        );
+        assert_eq!(
+            run(&storage, &["LPOP", "missing", "2"]).await,
+            RespValue::NullArray()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lpos() {
+        let storage = MemoryStorage::new();
+        run(
+            &storage,
+            &["RPUSH", "l", "a", "b", "c", "1", "2", "3", "c", "c"],
+        )
+        .await;
+
+        assert_eq!(
+            run(&storage, &["LPOS", "l", "c"]).await,
+            RespValue::Integer(2)
+        );
+        assert_eq!(
+            run(&storage, &["LPOS", "l", "c", "RANK", "-1"]).await,
+            RespValue::Integer(7)
+        );
+        assert_eq!(
+            run(&storage, &["LPOS", "l", "c", "COUNT", "0", "RANK", "2"]).await,
+            RespValue::Array(vec![RespValue::Integer(6), RespValue::Integer(7)])
+        );
+        assert_eq!(
+            run(&storage, &["LPOS", "l", "c", "MAXLEN", "2"]).await,
+            RespValue::NullBulkString()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lmove() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["RPUSH", "src", "a", "b"]).await;
+
+        let got = run(&storage, &["LMOVE", "src", "dst", "RIGHT", "LEFT"]).await;
+        assert_eq!(got, bulk("b"));
+        let got = run(&storage, &["RPOPLPUSH", "src", "dst"]).await;
+        assert_eq!(got, bulk("a"));
+        assert_eq!(
+            run(&storage, &["LRANGE", "dst", "0", "-1"]).await,
+            bulks(&["a", "b"])
+        );
+        assert_eq!(
+            run(&storage, &["EXISTS", "src"]).await,
+            RespValue::Integer(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocking_without_wait() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["RPUSH", "b", "x"]).await;
+
+        assert_eq!(
+            run(&storage, &["BLPOP", "a", "b", "0"]).await,
+            bulks(&["b", "x"])
+        );
+        assert_eq!(
+            run(&storage, &["BLPOP", "a", "b", "0"]).await,
+            RespValue::NullArray()
+        );
+    }
+}
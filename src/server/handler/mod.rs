@@ -0,0 +1,790 @@
+mod admin;
+mod config;
+mod connections;
+mod hashes;
+mod keys;
+mod lists;
+mod pubsub;
+mod scripting;
+mod sets;
+mod sorted_sets;
+mod streams;
+mod strings;
+mod transactions;
+
+use crate::client::{ClientCommand, ClientEvent, Expiry};
+use crate::config::Config;
+use crate::log::{Level, log};
+use crate::resp::{Protocol, RespValue};
+use crate::scripting::Scripting;
+use crate::server::ServerCommand;
+use crate::server::connections::Connections;
+use crate::server::pubsub::{PubSub, Published};
+use crate::server::transactions::Transactions;
+use crate::storage::{Storage, StorageError, StorageResult, TransactionStorage, now_millis};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Whether a command was answered or left its client waiting for data
+pub enum EventOutcome {
+    Replied,
+    // The command to retry while blocked, which may differ from the one received
+    Blocked {
+        keys: Vec<String>,
+        deadline: Option<Instant>,
+        command: Box<ClientCommand>,
+    },
+    // A transaction ran, whose commands may have readied these keys
+    Executed {
+        ready_keys: Vec<String>,
+    },
+}
+
+pub async fn handle_client_event<S: TransactionStorage>(
+    storage: &S,
+    pubsub: &mut PubSub,
+    scripting: &mut Scripting,
+    transactions: &mut Transactions<S::Version>,
+    connections: &mut Connections,
+    config: &mut Config,
+    event: &ClientEvent,
+) -> EventOutcome {
+    let tx = &event.responder;
+
+    // Commands sent after MULTI are queued rather than run
+    if let Some(outcome) =
+        transactions::handle(storage, pubsub, scripting, transactions, event).await
+    {
+        return outcome;
+    }
+
+    // Commands acting on the connection itself never reach storage
+    if handle_connection_command(storage, pubsub, connections, event).await {
+        return EventOutcome::Replied;
+    }
+
+    // The configuration is held by the server, and some of it by the transactions
+    if let ClientCommand::Config(command) = &event.command {
+        let _ = tx.send(config::execute(config, transactions, command));
+        return EventOutcome::Replied;
+    }
+
+    // Scripts may call commands that ready keys for blocked clients, and the messages
+    // they publish are only delivered if they did not fail and roll back
+    if let ClientCommand::Script(command) = &event.command {
+        let mut ready_keys = Vec::new();
+        let mut published = Vec::new();
+        let handled = scripting::handle(
+            storage,
+            pubsub,
+            scripting,
+            command,
+            &mut ready_keys,
+            &mut published,
+        );
+        let response = match handled.await {
+            Ok(response) => {
+                pubsub.deliver(published);
+                response
+            }
+            Err(e) => error_reply(&e),
+        };
+        let _ = tx.send(response);
+        return EventOutcome::Executed { ready_keys };
+    }
+
+    // Blocking commands that cannot be served yet park the client instead of replying
+    if let Some((keys, timeout)) = blocking_keys(&event.command) {
+        let command = match prepare_blocking(storage, &event.command).await {
+            Ok(command) => command,
+            Err(e) => {
+                let _ = tx.send(error_reply(&e));
+                return EventOutcome::Replied;
+            }
+        };
+        if let Some(response) = try_blocking(storage, &command).await {
+            let _ = tx.send(response);
+            return EventOutcome::Replied;
+        }
+        let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout);
+        return EventOutcome::Blocked {
+            keys,
+            deadline,
+            command: Box::new(command),
+        };
+    }
+
+    let response = execute_command(storage, &event.command)
+        .await
+        .unwrap_or_else(|e| error_reply(&e));
+
+    // The client may have disconnected while the command was executing
+    let _ = tx.send(response);
+    EventOutcome::Replied
+}
+
+// Handles pub/sub, RESET, HELLO, CLIENT and the commands restricted in subscriber mode,
+// returning whether the command was replied to rather than left to run against storage
+async fn handle_connection_command<S: Storage>(
+    storage: &S,
+    pubsub: &mut PubSub,
+    connections: &mut Connections,
+    event: &ClientEvent,
+) -> bool {
+    let tx = &event.responder;
+    let subscribed = pubsub.is_subscribed(event.client_id);
+    let response = match &event.command {
+        ClientCommand::Invalid(message) => ServerCommand::Error(message.clone()),
+        ClientCommand::PubSub(command) if subscribed && !command.allowed_when_subscribed() => {
+            subscriber_mode_error()
+        }
+        ClientCommand::PubSub(command) => {
+            match pubsub::execute(storage, pubsub, event.client_id, tx, command).await {
+                Ok(()) => return true,
+                Err(e) => error_reply(&e),
+            }
+        }
+        // Any transaction was already discarded
+        ClientCommand::Reset => {
+            pubsub.reset(event.client_id);
+            connections.reset(event.client_id);
+            ServerCommand::SwitchProtocol(Protocol::Resp2, RespValue::SimpleString("RESET".into()))
+        }
+        // Subscribed clients get their pings as a message-shaped array
+        ClientCommand::Ping(message) if subscribed => {
+            let message = message.as_deref().unwrap_or_default();
+            ServerCommand::Response(RespValue::Array(vec![
+                RespValue::BulkString(b"pong".to_vec()),
+                RespValue::BulkString(message.as_bytes().to_vec()),
+            ]))
+        }
+        _ if subscribed => subscriber_mode_error(),
+        command => match connections::execute(connections, event.client_id, command) {
+            Some(response) => response,
+            None => return false,
+        },
+    };
+    let _ = tx.send(response);
+    true
+}
+
+fn subscriber_mode_error() -> ServerCommand {
+    ServerCommand::Error(
+        "ERR only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / RESET are allowed in this context"
+            .into(),
+    )
+}
+
+// Returns the keys a blocking command waits on and its timeout (zero waits forever)
+fn blocking_keys(command: &ClientCommand) -> Option<(Vec<String>, Duration)> {
+    match command {
+        ClientCommand::List(command) => lists::blocking_keys(command),
+        ClientCommand::SortedSet(command) => sorted_sets::blocking_keys(command),
+        ClientCommand::Stream(command) => streams::blocking_keys(command),
+        _ => None,
+    }
+}
+
+// Captures the state a blocking command waits relative to, returning the command
+// to retry until it is served
+async fn prepare_blocking<S: Storage>(
+    storage: &S,
+    command: &ClientCommand,
+) -> StorageResult<ClientCommand> {
+    match command {
+        ClientCommand::Stream(command) => Ok(ClientCommand::Stream(
+            streams::resolve_ids(storage, command).await?,
+        )),
+        _ => Ok(command.clone()),
+    }
+}
+
+// Attempts a blocking command without waiting, returning `None` if it would still block
+pub async fn try_blocking<S: Storage>(
+    storage: &S,
+    command: &ClientCommand,
+) -> Option<ServerCommand> {
+    poll_blocking(storage, command)
+        .await
+        .unwrap_or_else(|e| Some(error_reply(&e)))
+}
+
+async fn poll_blocking<S: Storage>(
+    storage: &S,
+    command: &ClientCommand,
+) -> StorageResult<Option<ServerCommand>> {
+    match command {
+        ClientCommand::List(command) => lists::try_blocking(storage, command).await,
+        ClientCommand::SortedSet(command) => sorted_sets::try_blocking(storage, command).await,
+        ClientCommand::Stream(command) => streams::try_blocking(storage, command).await,
+        _ => Ok(None),
+    }
+}
+
+// The reply sent to a blocked client whose timeout elapsed
+pub fn timeout_reply(command: &ClientCommand) -> ServerCommand {
+    match command {
+        ClientCommand::List(command) => lists::timeout_reply(command),
+        ClientCommand::SortedSet(command) => sorted_sets::timeout_reply(command),
+        ClientCommand::Stream(command) => streams::timeout_reply(command),
+        _ => ServerCommand::Response(RespValue::NullArray()),
+    }
+}
+
+// Returns the keys that may now satisfy blocked clients after the command ran
+pub fn ready_keys(command: &ClientCommand) -> Vec<String> {
+    match command {
+        ClientCommand::List(command) => lists::ready_keys(command),
+        ClientCommand::SortedSet(command) => sorted_sets::ready_keys(command),
+        ClientCommand::Stream(command) => streams::ready_keys(command),
+        _ => Vec::new(),
+    }
+}
+
+// Blocking commands never wait within a transaction or script, replying as if they timed
+// out, and messages published are held in `published` until it commits
+async fn run_queued<S: Storage>(
+    storage: &S,
+    pubsub: &PubSub,
+    command: &ClientCommand,
+    published: &mut Vec<Published>,
+) -> StorageResult<ServerCommand> {
+    if blocking_keys(command).is_some() {
+        let command = prepare_blocking(storage, command).await?;
+        let response = poll_blocking(storage, &command).await?;
+        return Ok(response.unwrap_or_else(|| timeout_reply(&command)));
+    }
+    match command {
+        ClientCommand::PubSub(command) => Ok(ServerCommand::Response(
+            pubsub::publish(storage, pubsub, command, published).await?,
+        )),
+        command => execute_command(storage, command).await,
+    }
+}
+
+fn error_reply(e: &StorageError) -> ServerCommand {
+    // Type and value errors are the client's concern, only failures are logged
+    if matches!(e, StorageError::Pool(_) | StorageError::Postgres(_)) {
+        log!(Level::Warning, "Storage error: {e:?}");
+    }
+    ServerCommand::Error(e.to_string())
+}
+
+async fn execute_command<S: Storage>(
+    storage: &S,
+    command: &ClientCommand,
+) -> StorageResult<ServerCommand> {
+    match command {
+        ClientCommand::Admin(command) => Ok(admin::execute(command)),
+        ClientCommand::Hash(command) => hashes::execute(storage, command).await,
+        ClientCommand::Key(command) => keys::execute(storage, command).await,
+        ClientCommand::List(command) => lists::execute(storage, command).await,
+        ClientCommand::Ping(message) => Ok(ServerCommand::Pong(message.clone())),
+        ClientCommand::ClientGetName
+        | ClientCommand::ClientSetName(_)
+        | ClientCommand::Config(_)
+        | ClientCommand::Discard
+        | ClientCommand::Exec
+        | ClientCommand::Hello { .. }
+        | ClientCommand::Invalid(_)
+        | ClientCommand::Multi
+        | ClientCommand::PubSub(_)
+        | ClientCommand::Reset
+        | ClientCommand::Script(_)
+        | ClientCommand::Watch(_) => {
+            unreachable!("connection commands are handled before storage")
+        }
+        ClientCommand::Set(command) => sets::execute(storage, command).await,
+        ClientCommand::SortedSet(command) => sorted_sets::execute(storage, command).await,
+        ClientCommand::Stream(command) => streams::execute(storage, command).await,
+        ClientCommand::String(command) => strings::execute(storage, command).await,
+        // Only reached within EXEC, which drops the client's watches itself
+        ClientCommand::Unwatch => Ok(ServerCommand::Ok),
+    }
+}
+
+// Converts an expiry into an absolute timestamp, or `None` if it overflows
+fn resolve_expiry(expiry: Expiry) -> Option<i64> {
+    match expiry {
+        Expiry::Seconds(secs) => now_millis().checked_add(secs.checked_mul(1000)?),
+        Expiry::Milliseconds(ms) => now_millis().checked_add(ms),
+        Expiry::UnixSeconds(secs) => secs.checked_mul(1000),
+        Expiry::UnixMilliseconds(ms) => Some(ms),
+        Expiry::KeepTtl => None,
+    }
+}
+
+// Runs a command given as redis-cli style arguments, returning the RESP reply
+#[cfg(test)]
+async fn run<S: Storage>(storage: &S, args: &[&str]) -> RespValue {
+    let command = crate::client::parse_args(args).unwrap();
+    execute_command(storage, &command)
+        .await
+        .unwrap_or_else(|e| error_reply(&e))
+        .layout(Protocol::Resp3)
+}
+
+// Like `run`, with the reply shaped for a protocol as the client would see it
+#[cfg(test)]
+async fn run_as<S: Storage>(storage: &S, protocol: Protocol, args: &[&str]) -> RespValue {
+    let command = crate::client::parse_args(args).unwrap();
+    execute_command(storage, &command)
+        .await
+        .unwrap_or_else(|e| error_reply(&e))
+        .for_protocol(protocol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_ping() {
+        let storage = MemoryStorage::new();
+        assert_eq!(
+            run(&storage, &["PING"]).await,
+            RespValue::SimpleString("PONG".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_mode() {
+        let storage = MemoryStorage::new();
+        let mut pubsub = PubSub::new();
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let mut transactions = Transactions::new(false);
+        let mut connections = Connections::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut send = async |args: &[&str]| {
+            let command = crate::client::parse_args(args).unwrap();
+            let event = ClientEvent::new(1, command, tx.clone());
+            handle_client_event(
+                &storage,
+                &mut pubsub,
+                &mut scripting,
+                &mut transactions,
+                &mut connections,
+                &mut Config::default(),
+                &event,
+            )
+            .await;
+        };
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+
+        send(&["SUBSCRIBE", "a", "b"]).await;
+        send(&["GET", "k"]).await;
+        send(&["PING"]).await;
+        send(&["UNSUBSCRIBE"]).await;
+        send(&["PING"]).await;
+
+        let mut replies = Vec::new();
+        while let Ok(reply) = rx.try_recv() {
+            replies.push(reply.layout(Protocol::Resp3));
+        }
+        let ack = |action: &str, channel: &str, count| {
+            RespValue::Push(vec![bulk(action), bulk(channel), RespValue::Integer(count)])
+        };
+        assert_eq!(replies[0], ack("subscribe", "a", 1));
+        assert_eq!(replies[1], ack("subscribe", "b", 2));
+        assert!(
+            matches!(&replies[2], RespValue::Error(e) if e.contains("allowed in this context"))
+        );
+        assert_eq!(replies[3], RespValue::Array(vec![bulk("pong"), bulk("")]));
+        assert_eq!(replies[4], ack("unsubscribe", "a", 1));
+        assert_eq!(replies[5], ack("unsubscribe", "b", 0));
+        assert_eq!(replies[6], RespValue::SimpleString("PONG".into()));
+    }
+
+    #[tokio::test]
+    async fn test_publish_after_commit() {
+        let storage = MemoryStorage::new();
+        let mut pubsub = PubSub::new();
+        let mut scripting = Scripting::new(Duration::from_millis(50));
+        let mut transactions = Transactions::new(true);
+        let mut connections = Connections::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (subscriber_tx, mut subscriber_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut send = async |client_id, args: &[&str]| {
+            let command = crate::client::parse_args(args).unwrap();
+            let responder = if client_id == 1 { &tx } else { &subscriber_tx };
+            let event = ClientEvent::new(client_id, command, responder.clone());
+            handle_client_event(
+                &storage,
+                &mut pubsub,
+                &mut scripting,
+                &mut transactions,
+                &mut connections,
+                &mut Config::default(),
+                &event,
+            )
+            .await;
+        };
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        send(2, &["SUBSCRIBE", "news"]).await;
+        subscriber_rx.try_recv().unwrap();
+
+        // Nothing is delivered from a transaction or script that rolled back
+        for args in [
+            &["MULTI"][..],
+            &["PUBLISH", "news", "a"],
+            &["SET", "k", "v", "EX", "9223372036854775807"],
+            &["EXEC"],
+            &[
+                "EVAL",
+                "redis.call('publish', 'news', 'b') while true do end",
+                "0",
+            ],
+        ] {
+            send(1, args).await;
+        }
+        let replies: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|reply| reply.layout(Protocol::Resp2))
+            .collect();
+        assert!(matches!(&replies[3], RespValue::Error(e) if e.starts_with("EXECABORT")));
+        assert!(matches!(&replies[4], RespValue::Error(e) if e.contains("timed out")));
+        assert!(subscriber_rx.try_recv().is_err());
+
+        // Once committed the messages are delivered, having counted their receivers
+        for args in [
+            &["MULTI"][..],
+            &["PUBLISH", "news", "c"],
+            &["EXEC"],
+            &["EVAL", "return redis.call('publish', 'news', 'd')", "0"],
+        ] {
+            send(1, args).await;
+        }
+        let replies: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|reply| reply.layout(Protocol::Resp2))
+            .collect();
+        assert_eq!(replies[2], RespValue::Array(vec![RespValue::Integer(1)]));
+        assert_eq!(replies[3], RespValue::Integer(1));
+        for message in ["c", "d"] {
+            assert_eq!(
+                subscriber_rx.try_recv().unwrap().layout(Protocol::Resp3),
+                RespValue::Push(vec![bulk("message"), bulk("news"), bulk(message)])
+            );
+        }
+    }
+
+    // Runs each command as an event from one client, returning the replies shaped for
+    // the protocol the client speaks at the time, as they are sent
+    async fn send_all<S: TransactionStorage>(
+        storage: &S,
+        transactions: &mut Transactions<S::Version>,
+        commands: &[&[&str]],
+    ) -> Vec<RespValue> {
+        let mut pubsub = PubSub::new();
+        let mut scripting = Scripting::new(Duration::from_secs(5));
+        let mut connections = Connections::new();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for args in commands {
+            let command = crate::client::parse_args(args)
+                .unwrap_or_else(|e| ClientCommand::Invalid(e.to_string()));
+            let event = ClientEvent::new(1, command, tx.clone());
+            handle_client_event(
+                storage,
+                &mut pubsub,
+                &mut scripting,
+                transactions,
+                &mut connections,
+                &mut Config::default(),
+                &event,
+            )
+            .await;
+        }
+        let mut replies = Vec::new();
+        let mut protocol = Protocol::Resp2;
+        while let Ok(reply) = rx.try_recv() {
+            if let ServerCommand::SwitchProtocol(switched, _) = &reply {
+                protocol = *switched;
+            }
+            replies.push(reply.for_protocol(protocol));
+        }
+        replies
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let storage = MemoryStorage::new();
+        let mut transactions = Transactions::new(false);
+        let ok = || RespValue::SimpleString("OK".into());
+        let queued = || RespValue::SimpleString("QUEUED".into());
+        let is_error = |reply: &RespValue, prefix: &str| matches!(reply, RespValue::Error(e) if e.starts_with(prefix));
+
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["MULTI"],
+                &["SET", "k", "v"],
+                &["LPUSH", "k", "x"],
+                &["GET", "k"],
+                &["EXEC"],
+                &["EXEC"],
+            ],
+        )
+        .await;
+        assert_eq!(replies[..4], [ok(), queued(), queued(), queued()]);
+        let RespValue::Array(results) = &replies[4] else {
+            panic!("expected an array of results, got {:?}", replies[4]);
+        };
+        assert_eq!(results[0], ok());
+        assert!(is_error(&results[1], "WRONGTYPE"));
+        assert_eq!(results[2], RespValue::BulkString(b"v".to_vec()));
+        assert!(is_error(&replies[5], "ERR EXEC without MULTI"));
+
+        // A command that fails to queue discards the transaction
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["MULTI"],
+                &["MULTI"],
+                &["SET", "k", "w"],
+                &["BOGUS"],
+                &["EXEC"],
+                &["GET", "k"],
+            ],
+        )
+        .await;
+        assert!(is_error(&replies[1], "ERR MULTI calls can not be nested"));
+        assert!(is_error(&replies[3], "ERR unknown command"));
+        assert!(is_error(&replies[4], "EXECABORT"));
+        assert_eq!(replies[5], RespValue::BulkString(b"v".to_vec()));
+
+        // An atomic transaction is rolled back as a whole
+        let mut transactions = Transactions::new(true);
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["MULTI"],
+                &["SET", "k", "w"],
+                &["LPUSH", "k", "x"],
+                &["EXEC"],
+                &["GET", "k"],
+            ],
+        )
+        .await;
+        assert!(is_error(&replies[3], "EXECABORT Transaction rolled back"));
+        assert_eq!(replies[4], RespValue::BulkString(b"v".to_vec()));
+
+        // So is one whose expiry is only found invalid when it runs, while an
+        // expiry rejected when queued discards the transaction
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["MULTI"],
+                &["SET", "k", "w"],
+                &["SET", "k2", "v", "EX", "9223372036854775807"],
+                &["EXEC"],
+                &["MULTI"],
+                &["SET", "k", "w"],
+                &["SET", "k2", "v", "EX", "0"],
+                &["EXEC"],
+                &["GET", "k"],
+                &["EXISTS", "k2"],
+            ],
+        )
+        .await;
+        assert!(is_error(&replies[3], "EXECABORT Transaction rolled back"));
+        assert!(is_error(&replies[6], "ERR invalid expire time in 'set'"));
+        assert!(is_error(&replies[7], "EXECABORT"));
+        assert_eq!(replies[8], RespValue::BulkString(b"v".to_vec()));
+        assert_eq!(replies[9], RespValue::Integer(0));
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let storage = MemoryStorage::new();
+        let mut transactions = Transactions::new(false);
+        let ok = || RespValue::SimpleString("OK".into());
+
+        // A write between WATCH and EXEC, even by the watching client, fails the EXEC
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["WATCH", "k", "other"],
+                &["SET", "k", "v"],
+                &["MULTI"],
+                &["WATCH", "k"],
+                &["SET", "k", "w"],
+                &["EXEC"],
+                &["GET", "k"],
+            ],
+        )
+        .await;
+        assert_eq!(replies[0], ok());
+        assert!(matches!(&replies[3], RespValue::Error(e) if e.contains("WATCH inside MULTI")));
+        assert_eq!(replies[5], RespValue::NullArray());
+        assert_eq!(replies[6], RespValue::BulkString(b"v".to_vec()));
+
+        // EXEC dropped the watches, and UNWATCH drops them without a transaction
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["WATCH", "k"],
+                &["UNWATCH"],
+                &["SET", "k", "w"],
+                &["MULTI"],
+                &["GET", "k"],
+                &["EXEC"],
+            ],
+        )
+        .await;
+        assert_eq!(
+            replies[5],
+            RespValue::Array(vec![RespValue::BulkString(b"w".to_vec())])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_script() {
+        let storage = MemoryStorage::new();
+        let mut transactions = Transactions::new(false);
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        let script = "redis.call('SET', KEYS[1], ARGV[1]) return redis.call('GET', KEYS[1])";
+        let sha = crate::scripting::sha1_hex(script.as_bytes());
+
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["EVALSHA", &sha, "0"],
+                &["EVAL", script, "1", "k", "v"],
+                &["EVALSHA", &sha.to_uppercase(), "1", "k", "w"],
+                &["SCRIPT", "EXISTS", &sha, "ffff"],
+                &["EVAL", "return redis.call('MULTI')", "0"],
+                &["EVAL", "return redis.pcall('BOGUS')", "0"],
+                &["MULTI"],
+                &["EVAL", "return redis.call('GET', 'k')", "0"],
+                &["EXEC"],
+            ],
+        )
+        .await;
+        assert!(matches!(&replies[0], RespValue::Error(e) if e.starts_with("NOSCRIPT")));
+        assert_eq!(replies[1], bulk("v"));
+        assert_eq!(replies[2], bulk("w"));
+        assert_eq!(
+            replies[3],
+            RespValue::Array(vec![RespValue::Integer(1), RespValue::Integer(0)])
+        );
+        assert!(
+            matches!(&replies[4], RespValue::Error(e) if e.contains("not allowed from script"))
+        );
+        assert_eq!(
+            replies[5],
+            RespValue::Error("ERR Unknown Redis command called from script".into())
+        );
+        assert_eq!(replies[8], RespValue::Array(vec![bulk("w")]));
+    }
+
+    #[tokio::test]
+    async fn test_function() {
+        let storage = MemoryStorage::new();
+        let mut transactions = Transactions::new(false);
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        let library = "#!lua name=lib
+            redis.register_function('set', function(keys, args)
+                return redis.call('SET', keys[1], args[1])
+            end)
+            redis.register_function{
+                function_name = 'get',
+                callback = function(keys) return redis.call('GET', keys[1]) end,
+                flags = {'no-writes'},
+            }
+            redis.register_function{
+                function_name = 'sneaky',
+                callback = function(keys) return redis.pcall('DEL', keys[1]) end,
+                flags = {'no-writes'},
+            }";
+
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["FUNCTION", "LOAD", library],
+                &["FUNCTION", "LOAD", library],
+                &["FCALL", "set", "1", "k", "v"],
+                &["FCALL_RO", "get", "1", "k"],
+                &["FCALL_RO", "set", "1", "k", "w"],
+                &["FCALL", "sneaky", "1", "k"],
+                &["FCALL", "missing", "0"],
+                &["FUNCTION", "LIST", "LIBRARYNAME", "l*"],
+                &["FUNCTION", "LOAD", "#!lua name=other\nreturn 1"],
+                &["FUNCTION", "DELETE", "lib"],
+                &["FCALL", "get", "1", "k"],
+            ],
+        )
+        .await;
+        assert_eq!(replies[0], bulk("lib"));
+        assert_eq!(
+            replies[1],
+            RespValue::Error("ERR Library 'lib' already exists".into())
+        );
+        assert_eq!(replies[2], RespValue::SimpleString("OK".into()));
+        assert_eq!(replies[3], bulk("v"));
+        assert!(matches!(&replies[4], RespValue::Error(e) if e.contains("write flag")));
+        assert!(matches!(&replies[5], RespValue::Error(e) if e.contains("read-only scripts")));
+        assert_eq!(
+            replies[6],
+            RespValue::Error("ERR Function not found".into())
+        );
+        let RespValue::Array(libraries) = &replies[7] else {
+            panic!("expected an array, got {:?}", replies[7]);
+        };
+        assert_eq!(libraries.len(), 1);
+        assert!(
+            matches!(&replies[8], RespValue::Error(e) if e.contains("No functions registered"))
+        );
+        assert_eq!(replies[9], RespValue::SimpleString("OK".into()));
+        assert_eq!(
+            replies[10],
+            RespValue::Error("ERR Function not found".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hello() {
+        let storage = MemoryStorage::new();
+        let mut transactions = Transactions::new(false);
+        let bulk = |s: &str| RespValue::BulkString(s.as_bytes().to_vec());
+        let replies = send_all(
+            &storage,
+            &mut transactions,
+            &[
+                &["HSET", "h", "f", "v"],
+                &["HGETALL", "h"],
+                &["HELLO", "4"],
+                &["HELLO", "3", "AUTH", "admin", "secret"],
+                &["HELLO", "3", "SETNAME", "my app"],
+                &["HELLO", "3", "AUTH", "default", "any", "SETNAME", "app"],
+                &["HGETALL", "h"],
+                &["GET", "missing"],
+                &["CLIENT", "GETNAME"],
+                &["RESET"],
+                &["HGETALL", "h"],
+                &["CLIENT", "GETNAME"],
+            ],
+        )
+        .await;
+        assert_eq!(replies[1], RespValue::Array(vec![bulk("f"), bulk("v")]));
+        assert!(matches!(&replies[2], RespValue::Error(e) if e.starts_with("NOPROTO")));
+        assert!(matches!(&replies[3], RespValue::Error(e) if e.starts_with("WRONGPASS")));
+        assert!(matches!(&replies[4], RespValue::Error(e) if e.contains("cannot contain spaces")));
+        let RespValue::Map(hello) = &replies[5] else {
+            panic!("expected a map, got {:?}", replies[5]);
+        };
+        assert!(hello.contains(&(bulk("proto"), RespValue::Integer(3))));
+        assert_eq!(replies[6], RespValue::Map(vec![(bulk("f"), bulk("v"))]));
+        assert_eq!(replies[7], RespValue::Null);
+        assert_eq!(replies[8], bulk("app"));
+        assert_eq!(replies[9], RespValue::SimpleString("RESET".into()));
+        assert_eq!(replies[10], RespValue::Array(vec![bulk("f"), bulk("v")]));
+        assert_eq!(replies[11], RespValue::NullBulkString());
+    }
+}
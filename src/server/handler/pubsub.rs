@@ -0,0 +1,162 @@
+use crate::client::PubSubCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::server::pubsub::{PubSub, Published, SubscriptionKind};
+use crate::storage::{Storage, StorageResult};
+use tokio::sync::mpsc::UnboundedSender;
+
+// Pub/sub commands act on the server's subscriptions, with storage only relaying
+// published messages to other servers. (Un)subscribing acknowledges every channel
+// with its own reply
+pub async fn execute<S: Storage>(
+    storage: &S,
+    pubsub: &mut PubSub,
+    client_id: u64,
+    tx: &UnboundedSender<ServerCommand>,
+    command: &PubSubCommand,
+) -> StorageResult<()> {
+    match command {
+        PubSubCommand::Subscribe(channels) => {
+            subscribe(pubsub, client_id, tx, SubscriptionKind::Channel, channels);
+        }
+        PubSubCommand::PSubscribe(patterns) => {
+            subscribe(pubsub, client_id, tx, SubscriptionKind::Pattern, patterns);
+        }
+        PubSubCommand::Unsubscribe(channels) => {
+            unsubscribe(pubsub, client_id, tx, SubscriptionKind::Channel, channels);
+        }
+        PubSubCommand::PUnsubscribe(patterns) => {
+            unsubscribe(pubsub, client_id, tx, SubscriptionKind::Pattern, patterns);
+        }
+        PubSubCommand::SSubscribe(channels) => {
+            subscribe(pubsub, client_id, tx, SubscriptionKind::Shard, channels);
+        }
+        PubSubCommand::SUnsubscribe(channels) => {
+            unsubscribe(pubsub, client_id, tx, SubscriptionKind::Shard, channels);
+        }
+        command => {
+            let mut published = Vec::new();
+            let response = publish(storage, pubsub, command, &mut published).await?;
+            pubsub.deliver(published);
+            let _ = tx.send(ServerCommand::Response(response));
+        }
+    }
+    Ok(())
+}
+
+// Publishes or inspects channels, which unlike (un)subscribing has a single reply and
+// may be queued in a transaction. Messages are added to `published` for the caller to
+// deliver here once they are committed, while storage relays them on commit itself.
+pub async fn publish<S: Storage>(
+    storage: &S,
+    pubsub: &PubSub,
+    command: &PubSubCommand,
+    published: &mut Vec<Published>,
+) -> StorageResult<RespValue> {
+    let response = match command {
+        // Only local receivers are counted, like PUBLISH in a Redis cluster
+        PubSubCommand::Publish { channel, message }
+        | PubSubCommand::SPublish { channel, message } => {
+            let shard = matches!(command, PubSubCommand::SPublish { .. });
+            storage.publish(channel, message, shard).await?;
+            published.push(Published {
+                channel: channel.clone(),
+                message: message.clone(),
+                shard,
+            });
+            count(pubsub.receivers(channel, shard))
+        }
+        PubSubCommand::Channels(pattern) => {
+            channels_reply(pubsub, SubscriptionKind::Channel, pattern.as_deref())
+        }
+        PubSubCommand::ShardChannels(pattern) => {
+            channels_reply(pubsub, SubscriptionKind::Shard, pattern.as_deref())
+        }
+        PubSubCommand::NumSub(channels) => {
+            numsub_reply(pubsub, SubscriptionKind::Channel, channels)
+        }
+        PubSubCommand::ShardNumSub(channels) => {
+            numsub_reply(pubsub, SubscriptionKind::Shard, channels)
+        }
+        PubSubCommand::NumPat => count(pubsub.pattern_count()),
+        _ => unreachable!("(un)subscribing is acknowledged per channel"),
+    };
+    Ok(response)
+}
+
+fn subscribe(
+    pubsub: &mut PubSub,
+    client_id: u64,
+    tx: &UnboundedSender<ServerCommand>,
+    kind: SubscriptionKind,
+    names: &[String],
+) {
+    for name in names {
+        let total = pubsub.subscribe(client_id, tx, kind, name);
+        let _ = tx.send(ack(kind, true, Some(name), total));
+    }
+}
+
+// Unsubscribing from nothing drops every subscription of the kind, and still
+// acknowledges once when there were none
+fn unsubscribe(
+    pubsub: &mut PubSub,
+    client_id: u64,
+    tx: &UnboundedSender<ServerCommand>,
+    kind: SubscriptionKind,
+    names: &[String],
+) {
+    let names = if names.is_empty() {
+        pubsub.subscriptions(client_id, kind)
+    } else {
+        names.to_vec()
+    };
+    if names.is_empty() {
+        let _ = tx.send(ack(kind, false, None, pubsub.count(client_id, kind)));
+    }
+    for name in &names {
+        let total = pubsub.unsubscribe(client_id, kind, name);
+        let _ = tx.send(ack(kind, false, Some(name), total));
+    }
+}
+
+// The reply confirming a change of subscription, carrying the client's new total. RESP3
+// clients receive it as a push, since it may arrive amid messages
+fn ack(
+    kind: SubscriptionKind,
+    subscribed: bool,
+    name: Option<&str>,
+    total: usize,
+) -> ServerCommand {
+    let action = match (kind, subscribed) {
+        (SubscriptionKind::Channel, true) => "subscribe",
+        (SubscriptionKind::Channel, false) => "unsubscribe",
+        (SubscriptionKind::Pattern, true) => "psubscribe",
+        (SubscriptionKind::Pattern, false) => "punsubscribe",
+        (SubscriptionKind::Shard, true) => "ssubscribe",
+        (SubscriptionKind::Shard, false) => "sunsubscribe",
+    };
+    let name = name.map_or(RespValue::NullBulkString(), bulk);
+    ServerCommand::Response(RespValue::Push(vec![bulk(action), name, count(total)]))
+}
+
+fn channels_reply(pubsub: &PubSub, kind: SubscriptionKind, pattern: Option<&str>) -> RespValue {
+    let channels = pubsub.active_channels(kind, pattern);
+    RespValue::Array(channels.iter().map(|c| bulk(c)).collect())
+}
+
+// Pairs each channel with its number of subscribers
+fn numsub_reply(pubsub: &PubSub, kind: SubscriptionKind, channels: &[String]) -> RespValue {
+    let counts = channels
+        .iter()
+        .flat_map(|c| [bulk(c), count(pubsub.subscriber_count(kind, c))]);
+    RespValue::Array(counts.collect())
+}
+
+fn bulk(s: &str) -> RespValue {
+    RespValue::BulkString(s.as_bytes().to_vec())
+}
+
+fn count(n: usize) -> RespValue {
+    RespValue::Integer(i64::try_from(n).unwrap_or(i64::MAX))
+}
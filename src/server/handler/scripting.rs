@@ -0,0 +1,301 @@
+use super::{error_reply, ready_keys, run_queued};
+use crate::client::{ClientCommand, RestorePolicy, ScriptCommand};
+use crate::commands::CommandParseError;
+use crate::glob::glob_match;
+use crate::resp::{Protocol, RespValue};
+use crate::scripting::{Scripting, library};
+use crate::server::ServerCommand;
+use crate::server::pubsub::{PubSub, Published};
+use crate::storage::{FunctionLibrary, Storage, StorageResult, TransactionStorage};
+
+// Runs a script or function on a storage transaction of its own, so that one aborted
+// for running too long leaves nothing it wrote behind. FUNCTION RESTORE does too, so
+// that it restores every library or none.
+pub async fn handle<S: TransactionStorage>(
+    storage: &S,
+    pubsub: &PubSub,
+    scripting: &mut Scripting,
+    command: &ScriptCommand,
+    ready: &mut Vec<String>,
+    published: &mut Vec<Published>,
+) -> StorageResult<ServerCommand> {
+    if !matches!(
+        command,
+        ScriptCommand::Eval { .. }
+            | ScriptCommand::EvalSha { .. }
+            | ScriptCommand::FCall { .. }
+            | ScriptCommand::FunctionRestore { .. }
+    ) {
+        return execute(storage, pubsub, scripting, command, ready, published).await;
+    }
+    let results = storage
+        .exec(&[], [command], true, async |storage, command| {
+            execute(storage, pubsub, scripting, command, ready, published).await
+        })
+        .await?;
+    match results.and_then(|mut results| results.pop()) {
+        Some(result) => result,
+        None => unreachable!("no keys are watched and one command runs"),
+    }
+}
+
+// Runs a script or function command, adding the keys its calls may have readied to `ready`
+// and the messages they published to `published`
+pub async fn execute<S: Storage>(
+    storage: &S,
+    pubsub: &PubSub,
+    scripting: &mut Scripting,
+    command: &ScriptCommand,
+    ready: &mut Vec<String>,
+    published: &mut Vec<Published>,
+) -> StorageResult<ServerCommand> {
+    let (body, keys, args) = match command {
+        ScriptCommand::Eval { script, keys, args } => (script.clone(), keys, args),
+        ScriptCommand::EvalSha { sha, keys, args } => match scripting.body(sha) {
+            Some(body) => (body, keys, args),
+            None => {
+                return Ok(ServerCommand::Error(
+                    "NOSCRIPT No matching script. Please use EVAL.".into(),
+                ));
+            }
+        },
+        ScriptCommand::Exists(shas) => {
+            let exists = shas
+                .iter()
+                .map(|sha| RespValue::Integer(i64::from(scripting.exists(sha))))
+                .collect();
+            return Ok(ServerCommand::Response(RespValue::Array(exists)));
+        }
+        ScriptCommand::Flush => {
+            scripting.flush();
+            return Ok(ServerCommand::Ok);
+        }
+        ScriptCommand::Load(body) => {
+            return Ok(ServerCommand::Response(scripting.load(body.clone()).await));
+        }
+        // FCALL_RO may only run functions flagged `no-writes`, which may not call write
+        // commands however they are called
+        ScriptCommand::FCall {
+            function,
+            keys,
+            args,
+            read_only,
+        } => {
+            let Some(library) = storage.function_library(function).await? else {
+                return Ok(ServerCommand::Error("ERR Function not found".into()));
+            };
+            let no_writes = library
+                .functions
+                .iter()
+                .find(|f| &f.name == function)
+                .is_some_and(|f| f.flags.iter().any(|flag| flag == "no-writes"));
+            if *read_only && !no_writes {
+                return Ok(ServerCommand::Error(
+                    "ERR Can not execute a script with write flag using *_ro command.".into(),
+                ));
+            }
+            let reply = scripting
+                .fcall(
+                    &library,
+                    function.clone(),
+                    keys.clone(),
+                    args.clone(),
+                    async |args| call(storage, pubsub, args, no_writes, ready, published).await,
+                )
+                .await?;
+            return Ok(ServerCommand::Response(reply));
+        }
+        ScriptCommand::FunctionDelete(_)
+        | ScriptCommand::FunctionDump
+        | ScriptCommand::FunctionFlush
+        | ScriptCommand::FunctionList { .. }
+        | ScriptCommand::FunctionLoad { .. }
+        | ScriptCommand::FunctionRestore { .. } => {
+            return manage_functions(storage, scripting, command).await;
+        }
+    };
+    let reply = scripting
+        .eval(body, keys.clone(), args.clone(), async |args| {
+            call(storage, pubsub, args, false, ready, published).await
+        })
+        .await?;
+    Ok(ServerCommand::Response(reply))
+}
+
+// Runs the FUNCTION subcommands, which manage the libraries kept in storage
+async fn manage_functions<S: Storage>(
+    storage: &S,
+    scripting: &mut Scripting,
+    command: &ScriptCommand,
+) -> StorageResult<ServerCommand> {
+    match command {
+        ScriptCommand::FunctionDelete(name) => {
+            if !storage.delete_library(name).await? {
+                return Ok(ServerCommand::Error("ERR Library not found".into()));
+            }
+            Ok(ServerCommand::Ok)
+        }
+        ScriptCommand::FunctionDump => {
+            let libraries = storage.libraries().await?;
+            let payload = library::dump(libraries.iter().map(|library| library.code.as_str()));
+            Ok(ServerCommand::Response(RespValue::BulkString(payload)))
+        }
+        ScriptCommand::FunctionFlush => {
+            storage.flush_libraries().await?;
+            Ok(ServerCommand::Ok)
+        }
+        ScriptCommand::FunctionList { pattern, with_code } => {
+            let libraries = storage
+                .libraries()
+                .await?
+                .iter()
+                .filter(|library| {
+                    pattern
+                        .as_ref()
+                        .is_none_or(|p| glob_match(p.as_bytes(), library.name.as_bytes()))
+                })
+                .map(|library| list_entry(library, *with_code))
+                .collect();
+            Ok(ServerCommand::Response(RespValue::Array(libraries)))
+        }
+        ScriptCommand::FunctionLoad { code, replace } => {
+            let library = match scripting.load_library(code.clone()).await {
+                Ok(library) => library,
+                Err(message) => return Ok(ServerCommand::Error(message)),
+            };
+            storage.load_library(&library, *replace).await?;
+            Ok(ServerCommand::Response(bulk(&library.name)))
+        }
+        ScriptCommand::FunctionRestore { payload, policy } => {
+            restore(storage, scripting, payload, *policy).await
+        }
+        _ => unreachable!("not a FUNCTION subcommand"),
+    }
+}
+
+// Libraries are loaded by the engine before any is stored, so a payload with code that
+// fails to load restores nothing
+async fn restore<S: Storage>(
+    storage: &S,
+    scripting: &mut Scripting,
+    payload: &[u8],
+    policy: RestorePolicy,
+) -> StorageResult<ServerCommand> {
+    let Some(codes) = library::restore(payload) else {
+        return Ok(ServerCommand::Error(
+            "ERR payload version or checksum are wrong".into(),
+        ));
+    };
+    let mut libraries = Vec::new();
+    for code in codes {
+        match scripting.load_library(code).await {
+            Ok(library) => libraries.push(library),
+            Err(message) => return Ok(ServerCommand::Error(message)),
+        }
+    }
+    if policy == RestorePolicy::Flush {
+        storage.flush_libraries().await?;
+    }
+    for library in &libraries {
+        storage
+            .load_library(library, policy == RestorePolicy::Replace)
+            .await?;
+    }
+    Ok(ServerCommand::Ok)
+}
+
+// A library as FUNCTION LIST describes it
+fn list_entry(library: &FunctionLibrary, with_code: bool) -> RespValue {
+    let functions = library
+        .functions
+        .iter()
+        .map(|function| {
+            let flags = function.flags.iter().map(|flag| bulk(flag)).collect();
+            RespValue::Array(vec![
+                bulk("name"),
+                bulk(&function.name),
+                bulk("description"),
+                function
+                    .description
+                    .as_deref()
+                    .map_or(RespValue::NullBulkString(), bulk),
+                bulk("flags"),
+                RespValue::Array(flags),
+            ])
+        })
+        .collect();
+    let mut entry = vec![
+        bulk("library_name"),
+        bulk(&library.name),
+        bulk("engine"),
+        bulk("LUA"),
+        bulk("functions"),
+        RespValue::Array(functions),
+    ];
+    if with_code {
+        entry.extend([bulk("library_code"), bulk(&library.code)]);
+    }
+    RespValue::Array(entry)
+}
+
+fn bulk(value: &str) -> RespValue {
+    RespValue::BulkString(value.as_bytes().to_vec())
+}
+
+// Runs a command called by redis.call or redis.pcall, refusing writes if `read_only`
+async fn call<S: Storage>(
+    storage: &S,
+    pubsub: &PubSub,
+    args: Vec<Vec<u8>>,
+    read_only: bool,
+    ready: &mut Vec<String>,
+    published: &mut Vec<Published>,
+) -> RespValue {
+    let args = args.into_iter().map(RespValue::BulkString).collect();
+    let command = match ClientCommand::try_from(RespValue::Array(args)) {
+        Ok(command) => command,
+        Err(CommandParseError::UnknownCommand(_)) => {
+            return RespValue::Error("ERR Unknown Redis command called from script".into());
+        }
+        Err(CommandParseError::ArityMismatch(_)) => {
+            return RespValue::Error(
+                "ERR Wrong number of args calling Redis command from script".into(),
+            );
+        }
+        Err(e) => return RespValue::Error(e.to_string()),
+    };
+    if !allowed_in_script(&command) {
+        return RespValue::Error("ERR This Redis command is not allowed from script".into());
+    }
+    if read_only && command.is_write() {
+        return RespValue::Error(
+            "ERR Write commands are not allowed from read-only scripts.".into(),
+        );
+    }
+    let reply = run_queued(storage, pubsub, &command, published)
+        .await
+        .unwrap_or_else(|e| error_reply(&e));
+    ready.extend(ready_keys(&command));
+    // Laid out for RESP2, which the types within are converted to for the script too
+    reply.layout(Protocol::Resp2)
+}
+
+// Scripts run like a transaction, so they may not manage one or the connection
+fn allowed_in_script(command: &ClientCommand) -> bool {
+    match command {
+        ClientCommand::ClientGetName
+        | ClientCommand::ClientSetName(_)
+        | ClientCommand::Config(_)
+        | ClientCommand::Discard
+        | ClientCommand::Exec
+        | ClientCommand::Hello { .. }
+        | ClientCommand::Invalid(_)
+        | ClientCommand::Multi
+        | ClientCommand::Reset
+        | ClientCommand::Script(_)
+        | ClientCommand::Unwatch
+        | ClientCommand::Watch(_) => false,
+        ClientCommand::PubSub(command) => !command.allowed_when_subscribed(),
+        _ => true,
+    }
+}
@@ -0,0 +1,169 @@
+use crate::client::SetCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::storage::{Storage, StorageResult};
+
+// Number of members SSCAN examines per call when no COUNT is given
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &SetCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        SetCommand::Add { key, members } => {
+            let added = storage.sadd(key, members).await?;
+            RespValue::Integer(added)
+        }
+        SetCommand::Card(key) => RespValue::Integer(storage.scard(key).await?),
+        SetCommand::Combine { op, keys } => set_reply(storage.combine(*op, keys).await?),
+        SetCommand::CombineStore {
+            op,
+            destination,
+            keys,
+        } => {
+            let len = storage.combine_store(*op, destination, keys).await?;
+            RespValue::Integer(len)
+        }
+        SetCommand::InterCard { keys, limit } => {
+            RespValue::Integer(storage.intercard(keys, *limit).await?)
+        }
+        SetCommand::IsMember { key, member } => {
+            let found = storage
+                .smismember(key, std::slice::from_ref(member))
+                .await?;
+            RespValue::Integer(i64::from(found.first().copied().unwrap_or(false)))
+        }
+        SetCommand::MIsMember { key, members } => {
+            let found = storage.smismember(key, members).await?;
+            let found = found.into_iter().map(|f| RespValue::Integer(i64::from(f)));
+            RespValue::Array(found.collect())
+        }
+        SetCommand::Members(key) => set_reply(storage.smembers(key).await?),
+        SetCommand::Move {
+            source,
+            destination,
+            member,
+        } => {
+            let moved = storage.smove(source, destination, member).await?;
+            RespValue::Integer(i64::from(moved))
+        }
+        SetCommand::Pop { key, count } => match count {
+            Some(count) => members_reply(storage.spop(key, *count).await?),
+            None => bulk_or_null(storage.spop(key, 1).await?.pop()),
+        },
+        SetCommand::RandMember { key, count } => match count {
+            Some(count) => members_reply(storage.srandmember(key, *count).await?),
+            None => bulk_or_null(storage.srandmember(key, 1).await?.pop()),
+        },
+        SetCommand::Rem { key, members } => RespValue::Integer(storage.srem(key, members).await?),
+        SetCommand::Scan {
+            key,
+            cursor,
+            pattern,
+            count,
+        } => {
+            let count = count.unwrap_or(DEFAULT_SCAN_COUNT);
+            let scan = storage.sscan(key, *cursor, pattern.as_deref(), count);
+            let (next_cursor, members) = scan.await?;
+            RespValue::Array(vec![
+                RespValue::BulkString(next_cursor.to_string().into_bytes()),
+                members_reply(members),
+            ])
+        }
+    };
+    Ok(ServerCommand::Response(response))
+}
+
+fn members_reply(members: Vec<Vec<u8>>) -> RespValue {
+    RespValue::Array(members.into_iter().map(RespValue::BulkString).collect())
+}
+
+// Sent as a set to RESP3 clients
+fn set_reply(members: Vec<Vec<u8>>) -> RespValue {
+    RespValue::Set(members.into_iter().map(RespValue::BulkString).collect())
+}
+
+fn bulk_or_null(value: Option<Vec<u8>>) -> RespValue {
+    match value {
+        Some(value) => RespValue::BulkString(value),
+        None => RespValue::NullBulkString(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::RespValue;
+    use crate::server::handler::run;
+    use crate::storage::MemoryStorage;
+
+    fn bulk(value: &str) -> RespValue {
+        RespValue::BulkString(value.as_bytes().to_vec())
+    }
+
+    fn bulks(values: &[&str]) -> RespValue {
+        RespValue::Array(values.iter().map(|v| bulk(v)).collect())
+    }
+
+    fn set(values: &[&str]) -> RespValue {
+        RespValue::Set(values.iter().map(|v| bulk(v)).collect())
+    }
+
+    #[tokio::test]
+    async fn test_sadd_smembers() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["SADD", "s", "b", "a", "b"]).await;
+        assert_eq!(got, RespValue::Integer(2));
+        assert_eq!(run(&storage, &["SMEMBERS", "s"]).await, set(&["a", "b"]));
+        assert_eq!(
+            run(&storage, &["SISMEMBER", "s", "a"]).await,
+            RespValue::Integer(1)
+        );
+        assert_eq!(
+            run(&storage, &["SMISMEMBER", "s", "a", "c"]).await,
+            RespValue::Array(vec![RespValue::Integer(1), RespValue::Integer(0)])
+        );
+        assert_eq!(run(&storage, &["SCARD", "s"]).await, RespValue::Integer(2));
+        assert_eq!(
+            run(&storage, &["TYPE", "s"]).await,
+            RespValue::SimpleString("set".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spop_srandmember() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SADD", "s", "a"]).await;
+
+        assert_eq!(run(&storage, &["SRANDMEMBER", "s"]).await, bulk("a"));
+        assert_eq!(
+            run(&storage, &["SRANDMEMBER", "s", "-2"]).await,
+            bulks(&["a", "a"])
+        );
+        assert_eq!(run(&storage, &["SPOP", "s"]).await, bulk("a"));
+        assert_eq!(
+            run(&storage, &["SPOP", "s"]).await,
+            RespValue::NullBulkString()
+        );
+        assert_eq!(run(&storage, &["SPOP", "s", "3"]).await, bulks(&[]));
+    }
+
+    #[tokio::test]
+    async fn test_set_algebra() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["SADD", "a", "1", "2", "3"]).await;
+        run(&storage, &["SADD", "b", "2", "3", "4"]).await;
+
+        assert_eq!(run(&storage, &["SINTER", "a", "b"]).await, set(&["2", "3"]));
+        assert_eq!(run(&storage, &["SDIFF", "a", "b"]).await, set(&["1"]));
+        assert_eq!(
+            run(&storage, &["SUNIONSTORE", "u", "a", "b"]).await,
+            RespValue::Integer(4)
+        );
+        assert_eq!(
+            run(&storage, &["SINTERCARD", "2", "a", "u", "LIMIT", "2"]).await,
+            RespValue::Integer(2)
+        );
+    }
+}
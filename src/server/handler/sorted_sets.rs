@@ -0,0 +1,308 @@
+use crate::client::SortedSetCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::storage::{ScoredMembers, Storage, StorageResult, ZAddOptions};
+use std::time::Duration;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &SortedSetCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        SortedSetCommand::Add {
+            key,
+            members,
+            options,
+            changed,
+            increment,
+        } => {
+            if *increment {
+                // The parser guarantees INCR comes with exactly one pair
+                let (delta, member) = &members[0];
+                let score = storage.zincrby(key, member, *delta, *options).await?;
+                score_or_null(score)
+            } else {
+                let outcome = storage.zadd(key, members, *options).await?;
+                let changed = if *changed { outcome.changed } else { 0 };
+                RespValue::Integer(outcome.added + changed)
+            }
+        }
+        // Outside of the event loop (e.g. in a transaction) blocking commands never wait
+        SortedSetCommand::BlockingPop { .. } => {
+            return Ok(match try_blocking(storage, command).await? {
+                Some(response) => response,
+                None => timeout_reply(command),
+            });
+        }
+        SortedSetCommand::Card(key) => RespValue::Integer(storage.zcard(key).await?),
+        SortedSetCommand::Count { key, range } => {
+            RespValue::Integer(storage.zcount(key, range).await?)
+        }
+        SortedSetCommand::IncrBy { key, member, delta } => {
+            let options = ZAddOptions::default();
+            score_or_null(storage.zincrby(key, member, *delta, options).await?)
+        }
+        SortedSetCommand::MScore { key, members } => {
+            let scores = storage.zmscore(key, members).await?;
+            RespValue::Array(scores.into_iter().map(score_or_null).collect())
+        }
+        SortedSetCommand::Pop { key, end, count } => {
+            let popped = storage.zpop(key, *end, count.unwrap_or(1)).await?;
+            // Without a count the single pair is not nested
+            match count {
+                Some(_) => return Ok(scored_reply(popped, true)),
+                None => RespValue::Array(
+                    popped
+                        .into_iter()
+                        .flat_map(|(member, score)| {
+                            [RespValue::BulkString(member), score_reply(score)]
+                        })
+                        .collect(),
+                ),
+            }
+        }
+        SortedSetCommand::Range {
+            key,
+            range,
+            with_scores,
+        } => {
+            return Ok(scored_reply(
+                storage.zrange(key, range).await?,
+                *with_scores,
+            ));
+        }
+        SortedSetCommand::Rank {
+            key,
+            member,
+            reverse,
+            with_score,
+        } => match storage.zrank(key, member, *reverse).await? {
+            Some((rank, score)) if *with_score => {
+                RespValue::Array(vec![RespValue::Integer(rank), score_reply(score)])
+            }
+            Some((rank, _)) => RespValue::Integer(rank),
+            None => RespValue::NullBulkString(),
+        },
+        SortedSetCommand::Rem { key, members } => {
+            RespValue::Integer(storage.zrem(key, members).await?)
+        }
+        SortedSetCommand::RemRange { key, range } => {
+            RespValue::Integer(storage.zremrange(key, range).await?)
+        }
+        SortedSetCommand::Score { key, member } => {
+            let mut scores = storage.zmscore(key, std::slice::from_ref(member)).await?;
+            score_or_null(scores.pop().flatten())
+        }
+    };
+    Ok(ServerCommand::Response(response))
+}
+
+// Returns the keys a blocking command waits on and its timeout
+pub fn blocking_keys(command: &SortedSetCommand) -> Option<(Vec<String>, Duration)> {
+    match command {
+        SortedSetCommand::BlockingPop { keys, timeout, .. } => Some((keys.clone(), *timeout)),
+        _ => None,
+    }
+}
+
+// Returns the keys that may have gained members after the command ran
+pub fn ready_keys(command: &SortedSetCommand) -> Vec<String> {
+    match command {
+        SortedSetCommand::Add { key, .. } | SortedSetCommand::IncrBy { key, .. } => {
+            vec![key.clone()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Attempts a blocking command without waiting, returning `None` if it would block
+pub async fn try_blocking<S: Storage>(
+    storage: &S,
+    command: &SortedSetCommand,
+) -> StorageResult<Option<ServerCommand>> {
+    let SortedSetCommand::BlockingPop { keys, end, .. } = command else {
+        return Ok(None);
+    };
+    // Keys are tried in the order given, the first non-empty sorted set wins
+    for key in keys {
+        if let Some((member, score)) = storage.zpop(key, *end, 1).await?.pop() {
+            return Ok(Some(ServerCommand::Response(RespValue::Array(vec![
+                RespValue::BulkString(key.clone().into_bytes()),
+                RespValue::BulkString(member),
+                score_reply(score),
+            ]))));
+        }
+    }
+    Ok(None)
+}
+
+// The reply sent to a blocked client whose timeout elapsed
+pub fn timeout_reply(_command: &SortedSetCommand) -> ServerCommand {
+    ServerCommand::Response(RespValue::NullArray())
+}
+
+// Scores are doubles in RESP3 and strings in RESP2
+fn score_reply(score: f64) -> RespValue {
+    RespValue::Double(score)
+}
+
+fn score_or_null(score: Option<f64>) -> RespValue {
+    match score {
+        Some(score) => score_reply(score),
+        None => RespValue::NullBulkString(),
+    }
+}
+
+// Pairs each member with its score if requested, or lists the members alone
+fn scored_reply(members: ScoredMembers, with_scores: bool) -> ServerCommand {
+    if with_scores {
+        ServerCommand::Pairs(
+            members
+                .into_iter()
+                .map(|(member, score)| (RespValue::BulkString(member), score_reply(score)))
+                .collect(),
+        )
+    } else {
+        ServerCommand::Response(RespValue::Array(
+            members
+                .into_iter()
+                .map(|(member, _)| RespValue::BulkString(member))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::{Protocol, RespValue};
+    use crate::server::handler::run_as;
+    use crate::storage::MemoryStorage;
+
+    // Replies as a RESP2 client sees them
+    async fn run(storage: &MemoryStorage, args: &[&str]) -> RespValue {
+        run_as(storage, Protocol::Resp2, args).await
+    }
+
+    fn bulks(values: &[&str]) -> RespValue {
+        RespValue::Array(
+            values
+                .iter()
+                .map(|v| RespValue::BulkString(v.as_bytes().to_vec()))
+                .collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_zadd_flags() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["ZADD", "z", "1", "a", "2", "b"]).await;
+        assert_eq!(got, RespValue::Integer(2));
+        let got = run(
+            &storage,
+            &["ZADD", "z", "CH", "GT", "3", "a", "1", "b", "1", "c"],
+        )
+        .await;
+        assert_eq!(got, RespValue::Integer(2));
+        let got = run(&storage, &["ZADD", "z", "NX", "INCR", "1", "a"]).await;
+        assert_eq!(got, RespValue::NullBulkString());
+        let got = run(&storage, &["ZADD", "z", "INCR", "1.5", "a"]).await;
+        assert_eq!(got, RespValue::BulkString(b"4.5".to_vec()));
+        assert_eq!(
+            run(&storage, &["ZMSCORE", "z", "a", "b", "x"]).await,
+            RespValue::Array(vec![
+                RespValue::BulkString(b"4.5".to_vec()),
+                RespValue::BulkString(b"2".to_vec()),
+                RespValue::NullBulkString(),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zrange() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["ZADD", "z", "1", "a", "2", "b", "3", "c"]).await;
+
+        assert_eq!(
+            run(&storage, &["ZRANGE", "z", "0", "-1", "WITHSCORES"]).await,
+            bulks(&["a", "1", "b", "2", "c", "3"])
+        );
+        assert_eq!(
+            run(&storage, &["ZRANGE", "z", "+inf", "(1", "BYSCORE", "REV"]).await,
+            bulks(&["c", "b"])
+        );
+        assert_eq!(
+            run(
+                &storage,
+                &["ZRANGEBYSCORE", "z", "-inf", "+inf", "LIMIT", "1", "-1"]
+            )
+            .await,
+            bulks(&["b", "c"])
+        );
+        assert_eq!(
+            run(&storage, &["ZREVRANK", "z", "a", "WITHSCORE"]).await,
+            RespValue::Array(vec![
+                RespValue::Integer(2),
+                RespValue::BulkString(b"1".to_vec())
+            ])
+        );
+        assert_eq!(
+            run(&storage, &["ZCOUNT", "z", "(1", "3"]).await,
+            RespValue::Integer(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zpop() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["ZADD", "z", "1", "a", "2", "b", "3", "c"]).await;
+
+        assert_eq!(run(&storage, &["ZPOPMAX", "z"]).await, bulks(&["c", "3"]));
+        assert_eq!(
+            run(&storage, &["BZPOPMIN", "missing", "z", "0"]).await,
+            bulks(&["z", "a", "1"])
+        );
+        assert_eq!(
+            run(&storage, &["ZREMRANGEBYRANK", "z", "0", "-1"]).await,
+            RespValue::Integer(1)
+        );
+        assert_eq!(
+            run(&storage, &["BZPOPMAX", "z", "1"]).await,
+            RespValue::NullArray()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resp3_replies() {
+        let storage = MemoryStorage::new();
+        let resp3 = |args| run_as(&storage, Protocol::Resp3, args);
+        let pair = |member: &str, score| {
+            RespValue::Array(vec![
+                RespValue::BulkString(member.as_bytes().to_vec()),
+                RespValue::Double(score),
+            ])
+        };
+        run(&storage, &["ZADD", "z", "1", "a", "2.5", "b"]).await;
+
+        assert_eq!(resp3(&["ZSCORE", "z", "b"]).await, RespValue::Double(2.5));
+        assert_eq!(
+            resp3(&["ZMSCORE", "z", "a", "x"]).await,
+            RespValue::Array(vec![RespValue::Double(1.0), RespValue::Null])
+        );
+        assert_eq!(
+            resp3(&["ZINCRBY", "z", "1", "a"]).await,
+            RespValue::Double(2.0)
+        );
+        assert_eq!(
+            resp3(&["ZRANGE", "z", "0", "-1", "WITHSCORES"]).await,
+            RespValue::Array(vec![pair("a", 2.0), pair("b", 2.5)])
+        );
+        assert_eq!(resp3(&["ZRANGE", "z", "0", "-1"]).await, bulks(&["a", "b"]));
+        // A pop without a count replies the pair itself
+        assert_eq!(resp3(&["ZPOPMIN", "z"]).await, pair("a", 2.0));
+        assert_eq!(
+            resp3(&["ZPOPMAX", "z", "1"]).await,
+            RespValue::Array(vec![pair("b", 2.5)])
+        );
+    }
+}
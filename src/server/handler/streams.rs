@@ -0,0 +1,554 @@
+use crate::client::StreamCommand;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::storage::{
+    AutoClaim, ConsumerInfo, DeliveredEntry, GroupInfo, PendingRange, Storage, StorageError,
+    StorageResult, StreamEntry, StreamId, now_millis,
+};
+use std::time::Duration;
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &StreamCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        StreamCommand::Add {
+            key,
+            id,
+            fields,
+            create,
+            trim,
+        } => match storage.xadd(key, *id, fields, *create, *trim).await? {
+            Some(id) => RespValue::BulkString(id.to_string().into_bytes()),
+            None => RespValue::NullBulkString(),
+        },
+        StreamCommand::Ack { key, group, ids } => {
+            RespValue::Integer(storage.xack(key, group, ids).await?)
+        }
+        StreamCommand::AutoClaim {
+            key,
+            group,
+            consumer,
+            start,
+            count,
+            options,
+        } => {
+            let claimed = storage.xautoclaim(key, group, consumer, *start, *count, options);
+            auto_claim_reply(claimed.await?, options.just_id)
+        }
+        StreamCommand::Claim {
+            key,
+            group,
+            consumer,
+            ids,
+            options,
+        } => {
+            let claimed = storage.xclaim(key, group, consumer, ids, options).await?;
+            delivered_reply(claimed, options.just_id)
+        }
+        StreamCommand::CreateConsumer {
+            key,
+            group,
+            consumer,
+        } => {
+            let created = storage.xgroup_createconsumer(key, group, consumer).await?;
+            RespValue::Integer(i64::from(created))
+        }
+        StreamCommand::CreateGroup {
+            key,
+            group,
+            id,
+            create,
+        } => {
+            storage.xgroup_create(key, group, *id, *create).await?;
+            return Ok(ServerCommand::Ok);
+        }
+        StreamCommand::Del { key, ids } => RespValue::Integer(storage.xdel(key, ids).await?),
+        StreamCommand::DelConsumer {
+            key,
+            group,
+            consumer,
+        } => RespValue::Integer(storage.xgroup_delconsumer(key, group, consumer).await?),
+        StreamCommand::DestroyGroup { key, group } => {
+            RespValue::Integer(i64::from(storage.xgroup_destroy(key, group).await?))
+        }
+        StreamCommand::InfoConsumers { key, group } => {
+            consumers_reply(storage.xinfo_consumers(key, group).await?)
+        }
+        StreamCommand::InfoGroups(key) => groups_reply(storage.xinfo_groups(key).await?),
+        StreamCommand::InfoStream(key) => info_stream_reply(storage, key).await?,
+        StreamCommand::Len(key) => RespValue::Integer(storage.xlen(key).await?),
+        StreamCommand::Pending { key, group, range } => {
+            pending_reply(storage, key, group, range.as_ref()).await?
+        }
+        StreamCommand::Range {
+            key,
+            start,
+            end,
+            count,
+            reverse,
+        } => {
+            let entries = storage.xrange(key, *start, *end, *count, *reverse).await?;
+            entries_reply(entries)
+        }
+        // Outside of the event loop (e.g. in a transaction) XREAD never waits
+        StreamCommand::Read { .. } | StreamCommand::ReadGroup { .. } => {
+            let command = resolve_ids(storage, command).await?;
+            return Ok(match try_blocking(storage, &command).await? {
+                Some(response) => response,
+                None => timeout_reply(&command),
+            });
+        }
+        StreamCommand::SetGroupId { key, group, id } => {
+            storage.xgroup_setid(key, group, *id).await?;
+            return Ok(ServerCommand::Ok);
+        }
+        StreamCommand::Trim { key, trim } => RespValue::Integer(storage.xtrim(key, *trim).await?),
+    };
+    Ok(ServerCommand::Response(response))
+}
+
+// Replaces the `$` IDs of XREAD with the last ID of each stream, so only entries
+// added after the command first ran are read
+pub async fn resolve_ids<S: Storage>(
+    storage: &S,
+    command: &StreamCommand,
+) -> StorageResult<StreamCommand> {
+    let StreamCommand::Read {
+        streams,
+        count,
+        block,
+    } = command
+    else {
+        return Ok(command.clone());
+    };
+    let mut resolved = Vec::with_capacity(streams.len());
+    for (key, id) in streams {
+        let id = match id {
+            Some(id) => *id,
+            None => storage
+                .xinfo(key)
+                .await?
+                .map_or(StreamId::MIN, |info| info.last_id),
+        };
+        resolved.push((key.clone(), Some(id)));
+    }
+    Ok(StreamCommand::Read {
+        streams: resolved,
+        count: *count,
+        block: *block,
+    })
+}
+
+// Returns the keys XREAD BLOCK and XREADGROUP BLOCK wait on and their timeout
+pub fn blocking_keys(command: &StreamCommand) -> Option<(Vec<String>, Duration)> {
+    match command {
+        StreamCommand::Read {
+            streams,
+            block: Some(timeout),
+            ..
+        }
+        | StreamCommand::ReadGroup {
+            streams,
+            block: Some(timeout),
+            ..
+        } => Some((
+            streams.iter().map(|(key, _)| key.clone()).collect(),
+            *timeout,
+        )),
+        _ => None,
+    }
+}
+
+// Returns the keys that may have gained entries after the command ran
+pub fn ready_keys(command: &StreamCommand) -> Vec<String> {
+    match command {
+        StreamCommand::Add { key, .. } => vec![key.clone()],
+        _ => Vec::new(),
+    }
+}
+
+// Reads from the streams, returning `None` if none of them has new entries
+// Reads of a consumer's history are always served
+pub async fn try_blocking<S: Storage>(
+    storage: &S,
+    command: &StreamCommand,
+) -> StorageResult<Option<ServerCommand>> {
+    let read: Vec<(String, RespValue)> = match command {
+        StreamCommand::Read { streams, count, .. } => {
+            // Unresolved `$` IDs have nothing to read yet
+            let streams: Vec<(String, StreamId)> = streams
+                .iter()
+                .map(|(key, id)| (key.clone(), id.unwrap_or(StreamId::MAX)))
+                .collect();
+            let read = storage.xread(&streams, *count).await?;
+            read.into_iter()
+                .map(|(key, entries)| (key, entries_reply(entries)))
+                .collect()
+        }
+        StreamCommand::ReadGroup {
+            group,
+            consumer,
+            streams,
+            count,
+            noack,
+            ..
+        } => {
+            let read = storage.xreadgroup(group, consumer, streams, *count, *noack);
+            read.await?
+                .into_iter()
+                .map(|(key, entries)| (key, delivered_reply(entries, false)))
+                .collect()
+        }
+        _ => return Ok(None),
+    };
+    if read.is_empty() {
+        return Ok(None);
+    }
+    // Keyed by stream in RESP3
+    let items = read
+        .into_iter()
+        .map(|(key, entries)| (RespValue::BulkString(key.into_bytes()), entries))
+        .collect();
+    Ok(Some(ServerCommand::NestedMap(items)))
+}
+
+// The reply sent to a blocked client whose timeout elapsed
+pub fn timeout_reply(_command: &StreamCommand) -> ServerCommand {
+    ServerCommand::Response(RespValue::NullArray())
+}
+
+fn id_reply(id: StreamId) -> RespValue {
+    RespValue::BulkString(id.to_string().into_bytes())
+}
+
+fn name_reply(name: &str) -> RespValue {
+    RespValue::BulkString(name.as_bytes().to_vec())
+}
+
+fn count(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn fields_reply(fields: Vec<(Vec<u8>, Vec<u8>)>) -> RespValue {
+    let fields = fields
+        .into_iter()
+        .flat_map(|(field, value)| [RespValue::BulkString(field), RespValue::BulkString(value)])
+        .collect();
+    RespValue::Array(fields)
+}
+
+fn entry_reply(entry: StreamEntry) -> RespValue {
+    RespValue::Array(vec![id_reply(entry.id), fields_reply(entry.fields)])
+}
+
+// Each entry is replied as its ID followed by its flattened fields
+fn entries_reply(entries: Vec<StreamEntry>) -> RespValue {
+    RespValue::Array(entries.into_iter().map(entry_reply).collect())
+}
+
+// Deleted entries have null fields, and only IDs are replied with JUSTID
+fn delivered_reply(entries: Vec<DeliveredEntry>, just_id: bool) -> RespValue {
+    let entries = entries
+        .into_iter()
+        .map(|entry| {
+            if just_id {
+                return id_reply(entry.id);
+            }
+            let fields = entry.fields.map_or(RespValue::NullArray(), fields_reply);
+            RespValue::Array(vec![id_reply(entry.id), fields])
+        })
+        .collect();
+    RespValue::Array(entries)
+}
+
+fn auto_claim_reply(claim: AutoClaim, just_id: bool) -> RespValue {
+    RespValue::Array(vec![
+        id_reply(claim.next),
+        delivered_reply(claim.claimed, just_id),
+        RespValue::Array(claim.deleted.into_iter().map(id_reply).collect()),
+    ])
+}
+
+// The summary form of XPENDING without a range, otherwise one array per entry
+async fn pending_reply<S: Storage>(
+    storage: &S,
+    key: &str,
+    group: &str,
+    range: Option<&PendingRange>,
+) -> StorageResult<RespValue> {
+    let Some(range) = range else {
+        let summary = storage.xpending_summary(key, group).await?;
+        let Some((first, last)) = summary.bounds else {
+            return Ok(RespValue::Array(vec![
+                RespValue::Integer(0),
+                RespValue::NullBulkString(),
+                RespValue::NullBulkString(),
+                RespValue::NullArray(),
+            ]));
+        };
+        let consumers = summary
+            .consumers
+            .into_iter()
+            .map(|(name, count)| {
+                RespValue::Array(vec![
+                    RespValue::BulkString(name.into_bytes()),
+                    RespValue::BulkString(count.to_string().into_bytes()),
+                ])
+            })
+            .collect();
+        return Ok(RespValue::Array(vec![
+            RespValue::Integer(count(summary.count)),
+            id_reply(first),
+            id_reply(last),
+            RespValue::Array(consumers),
+        ]));
+    };
+
+    let now = now_millis();
+    let entries = storage.xpending(key, group, range).await?;
+    let entries = entries
+        .into_iter()
+        .map(|entry| {
+            RespValue::Array(vec![
+                id_reply(entry.id),
+                RespValue::BulkString(entry.consumer.into_bytes()),
+                RespValue::Integer(now.saturating_sub(entry.delivered_at).max(0)),
+                RespValue::Integer(count(entry.deliveries)),
+            ])
+        })
+        .collect();
+    Ok(RespValue::Array(entries))
+}
+
+async fn info_stream_reply<S: Storage>(storage: &S, key: &str) -> StorageResult<RespValue> {
+    let Some(info) = storage.xinfo(key).await? else {
+        return Err(StorageError::InvalidValue("no such key"));
+    };
+    let first = storage.xrange(key, StreamId::MIN, StreamId::MAX, Some(1), false);
+    let first = first.await?.pop();
+    let last = storage.xrange(key, StreamId::MIN, StreamId::MAX, Some(1), true);
+    let last = last.await?.pop();
+
+    let first_id = first.as_ref().map_or(StreamId::MIN, |entry| entry.id);
+    let entry_or_null =
+        |entry: Option<StreamEntry>| entry.map_or(RespValue::NullBulkString(), entry_reply);
+    Ok(RespValue::Map(vec![
+        (name_reply("length"), RespValue::Integer(count(info.length))),
+        (name_reply("last-generated-id"), id_reply(info.last_id)),
+        (
+            name_reply("max-deleted-entry-id"),
+            id_reply(info.max_deleted_id),
+        ),
+        (
+            name_reply("entries-added"),
+            RespValue::Integer(count(info.entries_added)),
+        ),
+        (name_reply("recorded-first-entry-id"), id_reply(first_id)),
+        (name_reply("groups"), RespValue::Integer(count(info.groups))),
+        (name_reply("first-entry"), entry_or_null(first)),
+        (name_reply("last-entry"), entry_or_null(last)),
+    ]))
+}
+
+fn groups_reply(groups: Vec<GroupInfo>) -> RespValue {
+    let groups = groups
+        .into_iter()
+        .map(|group| {
+            let entries_read = group.entries_read.map_or(RespValue::NullBulkString(), |n| {
+                RespValue::Integer(count(n))
+            });
+            RespValue::Map(vec![
+                (
+                    name_reply("name"),
+                    RespValue::BulkString(group.name.into_bytes()),
+                ),
+                (
+                    name_reply("consumers"),
+                    RespValue::Integer(count(group.consumers)),
+                ),
+                (
+                    name_reply("pending"),
+                    RespValue::Integer(count(group.pending)),
+                ),
+                (name_reply("last-delivered-id"), id_reply(group.last_id)),
+                (name_reply("entries-read"), entries_read),
+                (name_reply("lag"), RespValue::Integer(count(group.lag))),
+            ])
+        })
+        .collect();
+    RespValue::Array(groups)
+}
+
+// Idle time counts from the last time the consumer was seen, and inactive time from
+// the last time it was delivered entries (-1 if never)
+fn consumers_reply(consumers: Vec<ConsumerInfo>) -> RespValue {
+    let now = now_millis();
+    let consumers = consumers
+        .into_iter()
+        .map(|consumer| {
+            let inactive = consumer
+                .active_at
+                .map_or(-1, |at| now.saturating_sub(at).max(0));
+            RespValue::Map(vec![
+                (
+                    name_reply("name"),
+                    RespValue::BulkString(consumer.name.into_bytes()),
+                ),
+                (
+                    name_reply("pending"),
+                    RespValue::Integer(count(consumer.pending)),
+                ),
+                (
+                    name_reply("idle"),
+                    RespValue::Integer(now.saturating_sub(consumer.seen_at).max(0)),
+                ),
+                (name_reply("inactive"), RespValue::Integer(inactive)),
+            ])
+        })
+        .collect();
+    RespValue::Array(consumers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resp::{Protocol, RespValue};
+    use crate::server::handler::run_as;
+    use crate::storage::MemoryStorage;
+
+    // Replies as a RESP2 client sees them
+    async fn run(storage: &MemoryStorage, args: &[&str]) -> RespValue {
+        run_as(storage, Protocol::Resp2, args).await
+    }
+
+    fn bulk(s: &str) -> RespValue {
+        RespValue::BulkString(s.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_xadd_xrange() {
+        let storage = MemoryStorage::new();
+        assert_eq!(
+            run(&storage, &["XADD", "s", "1-1", "a", "1"]).await,
+            bulk("1-1")
+        );
+        assert_eq!(
+            run(&storage, &["XADD", "s", "1-*", "b", "2"]).await,
+            bulk("1-2")
+        );
+        assert_eq!(
+            run(&storage, &["XADD", "missing", "NOMKSTREAM", "*", "a", "1"]).await,
+            RespValue::NullBulkString()
+        );
+
+        let expected = RespValue::Array(vec![RespValue::Array(vec![
+            bulk("1-2"),
+            RespValue::Array(vec![bulk("b"), bulk("2")]),
+        ])]);
+        assert_eq!(
+            run(&storage, &["XREVRANGE", "s", "+", "-", "COUNT", "1"]).await,
+            expected
+        );
+        assert_eq!(run(&storage, &["XLEN", "s"]).await, RespValue::Integer(2));
+    }
+
+    #[tokio::test]
+    async fn test_xread() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["XADD", "s", "1-1", "a", "1"]).await;
+
+        let expected = RespValue::Array(vec![RespValue::Array(vec![
+            bulk("s"),
+            RespValue::Array(vec![RespValue::Array(vec![
+                bulk("1-1"),
+                RespValue::Array(vec![bulk("a"), bulk("1")]),
+            ])]),
+        ])]);
+        assert_eq!(
+            run(&storage, &["XREAD", "STREAMS", "s", "0"]).await,
+            expected
+        );
+        assert_eq!(
+            run(&storage, &["XREAD", "STREAMS", "s", "$"]).await,
+            RespValue::NullArray()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_xpending() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["XGROUP", "CREATE", "s", "g", "0", "MKSTREAM"]).await;
+        run(&storage, &["XADD", "s", "1-1", "a", "1"]).await;
+
+        let read = run(
+            &storage,
+            &["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"],
+        )
+        .await;
+        let expected = RespValue::Array(vec![RespValue::Array(vec![
+            bulk("s"),
+            RespValue::Array(vec![RespValue::Array(vec![
+                bulk("1-1"),
+                RespValue::Array(vec![bulk("a"), bulk("1")]),
+            ])]),
+        ])]);
+        assert_eq!(read, expected);
+
+        let expected = RespValue::Array(vec![
+            RespValue::Integer(1),
+            bulk("1-1"),
+            bulk("1-1"),
+            RespValue::Array(vec![RespValue::Array(vec![bulk("c"), bulk("1")])]),
+        ]);
+        assert_eq!(run(&storage, &["XPENDING", "s", "g"]).await, expected);
+        assert_eq!(
+            run(&storage, &["XACK", "s", "g", "1-1"]).await,
+            RespValue::Integer(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resp3_replies() {
+        let storage = MemoryStorage::new();
+        let resp3 = |args| run_as(&storage, Protocol::Resp3, args);
+        run(&storage, &["XGROUP", "CREATE", "s", "g", "0", "MKSTREAM"]).await;
+        run(&storage, &["XADD", "s", "1-1", "a", "1"]).await;
+
+        let entries = RespValue::Array(vec![RespValue::Array(vec![
+            bulk("1-1"),
+            RespValue::Array(vec![bulk("a"), bulk("1")]),
+        ])]);
+        let expected = RespValue::Map(vec![(bulk("s"), entries)]);
+        assert_eq!(resp3(&["XREAD", "STREAMS", "s", "0"]).await, expected);
+        let read = resp3(&["XREADGROUP", "GROUP", "g", "c", "STREAMS", "s", ">"]);
+        assert_eq!(read.await, expected);
+        assert_eq!(
+            resp3(&["XREAD", "STREAMS", "s", "$"]).await,
+            RespValue::Null
+        );
+
+        let RespValue::Map(info) = resp3(&["XINFO", "STREAM", "s"]).await else {
+            panic!("expected XINFO STREAM to reply a map");
+        };
+        assert_eq!(info[0], (bulk("length"), RespValue::Integer(1)));
+        assert_eq!(info.len(), 8);
+        let RespValue::Array(groups) = resp3(&["XINFO", "GROUPS", "s"]).await else {
+            panic!("expected XINFO GROUPS to reply an array");
+        };
+        assert!(
+            matches!(&groups[..], [RespValue::Map(group)] if group[0] == (bulk("name"), bulk("g")))
+        );
+        let RespValue::Array(consumers) = resp3(&["XINFO", "CONSUMERS", "s", "g"]).await else {
+            panic!("expected XINFO CONSUMERS to reply an array");
+        };
+        assert!(
+            matches!(&consumers[..], [RespValue::Map(consumer)] if consumer[0] == (bulk("name"), bulk("c")))
+        );
+
+        // RESP2 clients get the same replies flattened
+        let RespValue::Array(info) = run(&storage, &["XINFO", "STREAM", "s"]).await else {
+            panic!("expected XINFO STREAM to reply an array");
+        };
+        assert_eq!(info[..2], [bulk("length"), RespValue::Integer(1)]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::client::{Expiry, StringCommand};
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use crate::server::handler::resolve_expiry;
+use crate::storage::{KeyKind, SetExpiry, SetOptions, Storage, StorageError, StorageResult};
+
+pub async fn execute<S: Storage>(
+    storage: &S,
+    command: &StringCommand,
+) -> StorageResult<ServerCommand> {
+    let response = match command {
+        StringCommand::Get(key) => match storage.get(key).await? {
+            Some(value) => ServerCommand::Response(RespValue::BulkString(value)),
+            None => ServerCommand::Response(RespValue::NullBulkString()),
+        },
+        StringCommand::Set {
+            key,
+            value,
+            condition,
+            expiry,
+            get,
+        } => {
+            let Some(expiry) = resolve_set_expiry(*expiry) else {
+                return Err(StorageError::InvalidExpireTime("set"));
+            };
+            let options = SetOptions {
+                condition: *condition,
+                expiry,
+            };
+
+            // GET fails on a key holding another type, without writing the new value
+            if *get
+                && storage
+                    .key_type(key)
+                    .await?
+                    .is_some_and(|k| k != KeyKind::String)
+            {
+                return Err(StorageError::WrongType);
+            }
+
+            let outcome = storage.set(key, value, options).await?;
+            if *get {
+                // GET replies with the previous value whether or not the write happened
+                match outcome.previous {
+                    Some(value) => ServerCommand::Response(RespValue::BulkString(value)),
+                    None => ServerCommand::Response(RespValue::NullBulkString()),
+                }
+            } else if outcome.written {
+                ServerCommand::Ok
+            } else {
+                ServerCommand::Response(RespValue::NullBulkString())
+            }
+        }
+    };
+    Ok(response)
+}
+
+// Converts a SET expiry option into how the stored expiry changes, or `None` if it overflows
+fn resolve_set_expiry(expiry: Option<Expiry>) -> Option<SetExpiry> {
+    match expiry {
+        None => Some(SetExpiry::Clear),
+        Some(Expiry::KeepTtl) => Some(SetExpiry::Keep),
+        Some(expiry) => resolve_expiry(expiry).map(SetExpiry::At),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::parse_args;
+    use crate::resp::RespValue;
+    use crate::server::handler::{execute_command, run};
+    use crate::storage::{MemoryStorage, StorageError};
+
+    #[tokio::test]
+    async fn test_set_get() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["SET", "foo", "bar"]).await;
+        assert_eq!(got, RespValue::SimpleString("OK".into()));
+
+        let got = run(&storage, &["GET", "foo"]).await;
+        assert_eq!(got, RespValue::BulkString(b"bar".to_vec()));
+
+        let got = run(&storage, &["GET", "baz"]).await;
+        assert_eq!(got, RespValue::NullBulkString());
+    }
+
+    #[tokio::test]
+    async fn test_set_nx_get() {
+        let storage = MemoryStorage::new();
+
+        let got = run(&storage, &["SET", "lock", "a", "NX", "PX", "30000"]).await;
+        assert_eq!(got, RespValue::SimpleString("OK".into()));
+
+        let got = run(&storage, &["SET", "lock", "b", "NX", "PX", "30000"]).await;
+        assert_eq!(got, RespValue::NullBulkString());
+
+        let got = run(&storage, &["SET", "lock", "c", "GET", "KEEPTTL"]).await;
+        assert_eq!(got, RespValue::BulkString(b"a".to_vec()));
+
+        let got = run(&storage, &["PTTL", "lock"]).await;
+        assert!(matches!(got, RespValue::Integer(ms) if ms > 29_000));
+    }
+
+    #[tokio::test]
+    async fn test_set_replaces_hash() {
+        let storage = MemoryStorage::new();
+        run(&storage, &["HSET", "h", "f", "v"]).await;
+
+        let command = parse_args(&["SET", "h", "x", "GET"]).unwrap();
+        let got = execute_command(&storage, &command).await;
+        assert!(matches!(got, Err(StorageError::WrongType)));
+
+        let got = run(&storage, &["SET", "h", "x"]).await;
+        assert_eq!(got, RespValue::SimpleString("OK".into()));
+        assert_eq!(
+            run(&storage, &["GET", "h"]).await,
+            RespValue::BulkString(b"x".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_expiry_overflow() {
+        let storage = MemoryStorage::new();
+
+        let got = run(
+            &storage,
+            &["SET", "foo", "bar", "EX", "9223372036854775807"],
+        )
+        .await;
+        assert_eq!(
+            got,
+            RespValue::Error("ERR invalid expire time in 'set' command".into())
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use super::{EventOutcome, error_reply, ready_keys, run_queued, scripting};
+use crate::client::{ClientCommand, ClientEvent};
+use crate::resp::RespValue;
+use crate::scripting::Scripting;
+use crate::server::ServerCommand;
+use crate::server::pubsub::PubSub;
+use crate::server::transactions::Transactions;
+use crate::storage::TransactionStorage;
+
+// Handles MULTI, EXEC, DISCARD and (UN)WATCH and queues the commands sent in between,
+// returning `None` for commands that run right away
+pub async fn handle<S: TransactionStorage>(
+    storage: &S,
+    pubsub: &PubSub,
+    scripting: &mut Scripting,
+    transactions: &mut Transactions<S::Version>,
+    event: &ClientEvent,
+) -> Option<EventOutcome> {
+    let client_id = event.client_id;
+    let queuing = transactions.is_queuing(client_id);
+    let response = match &event.command {
+        // Subscribed clients are refused along with the other commands
+        ClientCommand::Multi | ClientCommand::Watch(_) if pubsub.is_subscribed(client_id) => {
+            return None;
+        }
+        ClientCommand::Multi if queuing => {
+            ServerCommand::Error("ERR MULTI calls can not be nested".into())
+        }
+        ClientCommand::Multi => {
+            transactions.begin(client_id);
+            ServerCommand::Ok
+        }
+        ClientCommand::Exec => {
+            return Some(exec(storage, pubsub, scripting, transactions, event).await);
+        }
+        ClientCommand::Discard => match transactions.take(client_id) {
+            Some(_) => {
+                transactions.unwatch(client_id);
+                ServerCommand::Ok
+            }
+            None => ServerCommand::Error("ERR DISCARD without MULTI".into()),
+        },
+        ClientCommand::Watch(_) if queuing => {
+            ServerCommand::Error("ERR WATCH inside MULTI is not allowed".into())
+        }
+        ClientCommand::Watch(keys) => match storage.versions(keys).await {
+            Ok(versions) => {
+                transactions.watch(client_id, keys.iter().cloned().zip(versions));
+                ServerCommand::Ok
+            }
+            Err(e) => error_reply(&e),
+        },
+        // Within a transaction UNWATCH is queued, and EXEC unwatches anyway
+        ClientCommand::Unwatch if !queuing => {
+            transactions.unwatch(client_id);
+            ServerCommand::Ok
+        }
+        ClientCommand::Reset => {
+            transactions.take(client_id);
+            transactions.unwatch(client_id);
+            return None;
+        }
+        _ if !queuing => return None,
+        ClientCommand::Invalid(message) => {
+            transactions.abort(client_id);
+            ServerCommand::Error(message.clone())
+        }
+        ClientCommand::PubSub(command) if command.allowed_when_subscribed() => {
+            transactions.abort(client_id);
+            ServerCommand::Error("ERR Command not allowed inside a transaction".into())
+        }
+        ClientCommand::ClientGetName
+        | ClientCommand::ClientSetName(_)
+        | ClientCommand::Config(_)
+        | ClientCommand::Hello { .. } => {
+            transactions.abort(client_id);
+            ServerCommand::Error("ERR Command not allowed inside a transaction".into())
+        }
+        command => {
+            transactions.queue(client_id, command.clone());
+            ServerCommand::Response(RespValue::SimpleString("QUEUED".into()))
+        }
+    };
+    let _ = event.responder.send(response);
+    Some(EventOutcome::Replied)
+}
+
+// Runs the queued commands on a single storage transaction, replying with an array of
+// their results, or a null array if a watched key was written since it was watched
+async fn exec<S: TransactionStorage>(
+    storage: &S,
+    pubsub: &PubSub,
+    scripting: &mut Scripting,
+    transactions: &mut Transactions<S::Version>,
+    event: &ClientEvent,
+) -> EventOutcome {
+    let tx = &event.responder;
+    let Some(queued) = transactions.take(event.client_id) else {
+        let _ = tx.send(ServerCommand::Error("ERR EXEC without MULTI".into()));
+        return EventOutcome::Replied;
+    };
+    let watched = transactions.unwatch(event.client_id);
+    if queued.aborted {
+        let _ = tx.send(ServerCommand::Error(
+            "EXECABORT Transaction discarded because of previous errors.".into(),
+        ));
+        return EventOutcome::Replied;
+    }
+
+    // Scripts only know the keys they readied once they ran
+    let mut ready: Vec<_> = queued.commands.iter().flat_map(ready_keys).collect();
+    let mut published = Vec::new();
+    let atomic = transactions.is_atomic();
+    let results = storage
+        .exec(
+            &watched,
+            &queued.commands,
+            atomic,
+            async |storage, command| {
+                let held = published.len();
+                let result = match command {
+                    ClientCommand::Script(command) => {
+                        let execute = scripting::execute(
+                            storage,
+                            pubsub,
+                            scripting,
+                            command,
+                            &mut ready,
+                            &mut published,
+                        );
+                        execute.await
+                    }
+                    command => run_queued(storage, pubsub, command, &mut published).await,
+                };
+                // A failed command is rolled back, along with the messages it published
+                if result.is_err() {
+                    published.truncate(held);
+                }
+                result
+            },
+        )
+        .await;
+    let response = match results {
+        Ok(None) => {
+            let _ = tx.send(ServerCommand::Response(RespValue::NullArray()));
+            return EventOutcome::Replied;
+        }
+        Ok(Some(results)) => {
+            pubsub.deliver(published);
+            let replies = results
+                .into_iter()
+                .map(|result| result.unwrap_or_else(|e| error_reply(&e)))
+                .collect();
+            ServerCommand::Replies(replies)
+        }
+        Err(e) if atomic => ServerCommand::Error(format!("EXECABORT Transaction rolled back: {e}")),
+        Err(e) => error_reply(&e),
+    };
+    let _ = tx.send(response);
+
+    EventOutcome::Executed { ready_keys: ready }
+}
@@ -1,6 +1,12 @@
+mod blocking;
 mod commands;
+mod connections;
+mod expiry;
 mod handler;
+mod pubsub;
+#[allow(clippy::module_inception)]
 mod server;
+mod transactions;
 
 pub use commands::ServerCommand;
 pub use server::Server;
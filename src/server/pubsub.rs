@@ -0,0 +1,338 @@
+use crate::glob::glob_match;
+use crate::resp::RespValue;
+use crate::server::ServerCommand;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::mpsc::UnboundedSender;
+
+type Subscribers = BTreeMap<u64, UnboundedSender<ServerCommand>>;
+
+// Whether a subscription names a channel exactly, matches channels by a glob pattern
+// or names a shard channel, which lives in a namespace of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionKind {
+    Channel,
+    Pattern,
+    Shard,
+}
+
+const KINDS: [SubscriptionKind; 3] = [
+    SubscriptionKind::Channel,
+    SubscriptionKind::Pattern,
+    SubscriptionKind::Shard,
+];
+
+// A message published by a script or transaction, held back from the subscribers here
+// until what published it commits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    pub channel: String,
+    pub message: Vec<u8>,
+    // Published to a shard channel with SPUBLISH
+    pub shard: bool,
+}
+
+// The subscriptions of a single client, in the order they were made
+#[derive(Default)]
+struct Subscriptions {
+    channels: Vec<String>,
+    patterns: Vec<String>,
+    shard_channels: Vec<String>,
+}
+
+impl Subscriptions {
+    fn of(&self, kind: SubscriptionKind) -> &Vec<String> {
+        match kind {
+            SubscriptionKind::Channel => &self.channels,
+            SubscriptionKind::Pattern => &self.patterns,
+            SubscriptionKind::Shard => &self.shard_channels,
+        }
+    }
+
+    fn of_mut(&mut self, kind: SubscriptionKind) -> &mut Vec<String> {
+        match kind {
+            SubscriptionKind::Channel => &mut self.channels,
+            SubscriptionKind::Pattern => &mut self.patterns,
+            SubscriptionKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    // Classic and sharded subscriptions are counted separately in acknowledgements
+    fn count(&self, kind: SubscriptionKind) -> usize {
+        match kind {
+            SubscriptionKind::Channel | SubscriptionKind::Pattern => {
+                self.channels.len() + self.patterns.len()
+            }
+            SubscriptionKind::Shard => self.shard_channels.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        KINDS.iter().all(|&kind| self.of(kind).is_empty())
+    }
+}
+
+// Channel, pattern and shard channel subscriptions of every connected client
+#[derive(Default)]
+pub struct PubSub {
+    channels: HashMap<String, Subscribers>,
+    patterns: HashMap<String, Subscribers>,
+    shard_channels: HashMap<String, Subscribers>,
+    clients: HashMap<u64, Subscriptions>,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        PubSub::default()
+    }
+
+    fn table(&self, kind: SubscriptionKind) -> &HashMap<String, Subscribers> {
+        match kind {
+            SubscriptionKind::Channel => &self.channels,
+            SubscriptionKind::Pattern => &self.patterns,
+            SubscriptionKind::Shard => &self.shard_channels,
+        }
+    }
+
+    fn table_mut(&mut self, kind: SubscriptionKind) -> &mut HashMap<String, Subscribers> {
+        match kind {
+            SubscriptionKind::Channel => &mut self.channels,
+            SubscriptionKind::Pattern => &mut self.patterns,
+            SubscriptionKind::Shard => &mut self.shard_channels,
+        }
+    }
+
+    // A client with any subscription is in subscriber mode
+    pub fn is_subscribed(&self, client_id: u64) -> bool {
+        self.clients.contains_key(&client_id)
+    }
+
+    // The number of subscriptions the client holds, as reported in acknowledgements
+    pub fn count(&self, client_id: u64, kind: SubscriptionKind) -> usize {
+        self.clients.get(&client_id).map_or(0, |s| s.count(kind))
+    }
+
+    pub fn subscriptions(&self, client_id: u64, kind: SubscriptionKind) -> Vec<String> {
+        self.clients
+            .get(&client_id)
+            .map_or_else(Vec::new, |s| s.of(kind).clone())
+    }
+
+    // Subscribes the client, returning its subscription count afterwards
+    pub fn subscribe(
+        &mut self,
+        client_id: u64,
+        responder: &UnboundedSender<ServerCommand>,
+        kind: SubscriptionKind,
+        name: &str,
+    ) -> usize {
+        let subscribers = self.table_mut(kind).entry(name.to_string()).or_default();
+        if subscribers.insert(client_id, responder.clone()).is_none() {
+            let client = self.clients.entry(client_id).or_default();
+            client.of_mut(kind).push(name.to_string());
+        }
+        self.count(client_id, kind)
+    }
+
+    // Unsubscribes the client, returning its subscription count afterwards
+    pub fn unsubscribe(&mut self, client_id: u64, kind: SubscriptionKind, name: &str) -> usize {
+        let table = self.table_mut(kind);
+        if let Some(subscribers) = table.get_mut(name) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                table.remove(name);
+            }
+        }
+        if let Some(client) = self.clients.get_mut(&client_id) {
+            client.of_mut(kind).retain(|n| n != name);
+            if client.is_empty() {
+                self.clients.remove(&client_id);
+            }
+        }
+        self.count(client_id, kind)
+    }
+
+    // Drops every subscription of the client, leaving subscriber mode
+    pub fn reset(&mut self, client_id: u64) {
+        for kind in KINDS {
+            for name in self.subscriptions(client_id, kind) {
+                self.unsubscribe(client_id, kind, &name);
+            }
+        }
+    }
+
+    // Delivers a message to the shard channel's subscribers, returning how many there were
+    pub fn spublish(&self, channel: &str, message: &[u8]) -> usize {
+        let Some(subscribers) = self.shard_channels.get(channel) else {
+            return 0;
+        };
+        let push = push_reply(&["smessage", channel], message);
+        for responder in subscribers.values() {
+            let _ = responder.send(push.clone());
+        }
+        subscribers.len()
+    }
+
+    // Delivers a message to the channel's subscribers and the clients whose patterns
+    // match it, returning how many deliveries were made
+    pub fn publish(&self, channel: &str, message: &[u8]) -> usize {
+        let mut receivers = 0;
+        if let Some(subscribers) = self.channels.get(channel) {
+            let push = push_reply(&["message", channel], message);
+            for responder in subscribers.values() {
+                let _ = responder.send(push.clone());
+                receivers += 1;
+            }
+        }
+        for (pattern, subscribers) in &self.patterns {
+            if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let push = push_reply(&["pmessage", pattern, channel], message);
+            for responder in subscribers.values() {
+                let _ = responder.send(push.clone());
+                receivers += 1;
+            }
+        }
+        receivers
+    }
+
+    // How many deliveries publishing to the channel would make
+    pub fn receivers(&self, channel: &str, shard: bool) -> usize {
+        if shard {
+            return self.shard_channels.get(channel).map_or(0, BTreeMap::len);
+        }
+        let patterns = self
+            .patterns
+            .iter()
+            .filter(|(pattern, _)| glob_match(pattern.as_bytes(), channel.as_bytes()))
+            .map(|(_, subscribers)| subscribers.len());
+        self.channels.get(channel).map_or(0, BTreeMap::len) + patterns.sum::<usize>()
+    }
+
+    // Delivers the messages held back while they were published
+    pub fn deliver(&self, published: Vec<Published>) {
+        for Published {
+            channel,
+            message,
+            shard,
+        } in published
+        {
+            if shard {
+                self.spublish(&channel, &message);
+            } else {
+                self.publish(&channel, &message);
+            }
+        }
+    }
+
+    // Channels (or shard channels) with at least one subscriber, optionally filtered
+    // by a glob pattern
+    pub fn active_channels(&self, kind: SubscriptionKind, pattern: Option<&str>) -> Vec<String> {
+        let mut channels: Vec<String> = self
+            .table(kind)
+            .keys()
+            .filter(|c| pattern.is_none_or(|p| glob_match(p.as_bytes(), c.as_bytes())))
+            .cloned()
+            .collect();
+        channels.sort_unstable();
+        channels
+    }
+
+    pub fn subscriber_count(&self, kind: SubscriptionKind, channel: &str) -> usize {
+        self.table(kind).get(channel).map_or(0, BTreeMap::len)
+    }
+
+    // The number of distinct patterns subscribed to by any client
+    pub fn pattern_count(&self) -> usize {
+        self.patterns.len()
+    }
+}
+
+// Builds a pushed message from its header fields and payload
+fn push_reply(header: &[&str], message: &[u8]) -> ServerCommand {
+    let mut items: Vec<RespValue> = header
+        .iter()
+        .map(|s| RespValue::BulkString(s.as_bytes().to_vec()))
+        .collect();
+    items.push(RespValue::BulkString(message.to_vec()));
+    ServerCommand::Response(RespValue::Push(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp::Protocol;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn bulk(s: &str) -> RespValue {
+        RespValue::BulkString(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_publish() {
+        let mut pubsub = PubSub::new();
+        let (tx, mut rx) = unbounded_channel();
+        assert_eq!(
+            pubsub.subscribe(1, &tx, SubscriptionKind::Channel, "news"),
+            1
+        );
+        assert_eq!(pubsub.subscribe(1, &tx, SubscriptionKind::Pattern, "n*"), 2);
+        assert_eq!(
+            pubsub.subscribe(1, &tx, SubscriptionKind::Channel, "news"),
+            2
+        );
+
+        assert_eq!(pubsub.publish("news", b"hi"), 2);
+        let message = rx.try_recv().unwrap().layout(Protocol::Resp3);
+        assert_eq!(
+            message,
+            RespValue::Push(vec![bulk("message"), bulk("news"), bulk("hi")])
+        );
+        let message = rx.try_recv().unwrap().layout(Protocol::Resp3);
+        assert_eq!(
+            message,
+            RespValue::Push(vec![bulk("pmessage"), bulk("n*"), bulk("news"), bulk("hi")])
+        );
+        assert_eq!(pubsub.publish("other", b"hi"), 0);
+
+        // Shard channels are a separate namespace
+        assert_eq!(pubsub.spublish("news", b"hi"), 0);
+        assert_eq!(pubsub.subscribe(1, &tx, SubscriptionKind::Shard, "news"), 1);
+        assert_eq!(pubsub.spublish("news", b"yo"), 1);
+        let message = rx.try_recv().unwrap().layout(Protocol::Resp3);
+        assert_eq!(
+            message,
+            RespValue::Push(vec![bulk("smessage"), bulk("news"), bulk("yo")])
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut pubsub = PubSub::new();
+        let (tx, _rx) = unbounded_channel();
+        pubsub.subscribe(1, &tx, SubscriptionKind::Channel, "a");
+        pubsub.subscribe(2, &tx, SubscriptionKind::Channel, "a");
+        pubsub.subscribe(1, &tx, SubscriptionKind::Pattern, "b*");
+        pubsub.subscribe(1, &tx, SubscriptionKind::Shard, "a");
+        assert_eq!(
+            pubsub.active_channels(SubscriptionKind::Channel, None),
+            vec!["a".to_string()]
+        );
+        assert_eq!(pubsub.subscriber_count(SubscriptionKind::Channel, "a"), 2);
+        assert_eq!(pubsub.subscriber_count(SubscriptionKind::Shard, "a"), 1);
+        assert_eq!(pubsub.count(1, SubscriptionKind::Shard), 1);
+
+        pubsub.reset(1);
+        assert!(!pubsub.is_subscribed(1));
+        assert_eq!(pubsub.subscriber_count(SubscriptionKind::Channel, "a"), 1);
+        assert_eq!(pubsub.subscriber_count(SubscriptionKind::Shard, "a"), 0);
+        assert_eq!(pubsub.pattern_count(), 0);
+        assert_eq!(pubsub.unsubscribe(2, SubscriptionKind::Channel, "a"), 0);
+        assert!(
+            pubsub
+                .active_channels(SubscriptionKind::Channel, None)
+                .is_empty()
+        );
+    }
+}
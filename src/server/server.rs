@@ -1,57 +1,254 @@
-use crate::client::{handle_client, ClientEvent};
-use crate::server::handler::handle_client_event;
-use std::collections::VecDeque;
+use crate::client::{ClientEvent, handle_client};
+use crate::config::Config;
+use crate::log::{Level, log};
+use crate::scripting::Scripting;
+use crate::server::blocking::BlockedClients;
+use crate::server::connections::Connections;
+use crate::server::expiry::run_expiry_sweeper;
+use crate::server::handler::{EventOutcome, handle_client_event, ready_keys};
+use crate::server::pubsub::PubSub;
+use crate::server::transactions::Transactions;
+use crate::storage::TransactionStorage;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 
-pub struct Server {
-    listener: TcpListener,
+// How often clients are checked for having been idle longer than the timeout
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// A connected client, as far as the server keeps track of it
+struct Client {
+    last_active: Instant,
+    // Cancelled by the server to close the connection, and by the connection when done
+    closed: CancellationToken,
+}
+
+pub struct Server<S: TransactionStorage + 'static> {
+    // Connections accepted on any of the bound addresses
+    accepted: UnboundedReceiver<(TcpStream, SocketAddr)>,
+    storage: Arc<S>,
     client_event_tx: UnboundedSender<ClientEvent>,
     client_event_rx: UnboundedReceiver<ClientEvent>,
+    blocked: BlockedClients,
+    connections: Connections,
+    pubsub: PubSub,
+    scripting: Scripting,
+    transactions: Transactions<S::Version>,
+    clients: HashMap<u64, Client>,
+    config: Config,
+    next_client_id: u64,
 }
 
-impl Server {
-    pub async fn new(addr: SocketAddr) -> Self {
-        let listener = TcpListener::bind(addr)
-            .await
-            .expect("Failed to bind to address");
+impl<S: TransactionStorage + 'static> Server<S> {
+    pub async fn new(config: Config, storage: S) -> Self {
+        let (accepted_tx, accepted) = unbounded_channel();
+        for addr in config.addrs() {
+            let listener = TcpListener::bind(addr)
+                .await
+                .unwrap_or_else(|e| panic!("Failed to bind to {addr}: {e}"));
+            tokio::spawn(accept_connections(listener, accepted_tx.clone()));
+        }
 
         let (tx, rx) = unbounded_channel();
 
         Server {
-            listener,
+            accepted,
+            storage: Arc::new(storage),
             client_event_tx: tx,
             client_event_rx: rx,
+            blocked: BlockedClients::new(),
+            connections: Connections::new(),
+            pubsub: PubSub::new(),
+            scripting: Scripting::new(config.busy_reply_threshold),
+            transactions: Transactions::new(config.atomic_exec),
+            clients: HashMap::new(),
+            config,
+            next_client_id: 1,
         }
     }
 
     pub async fn run(&mut self) {
         let mut event_queue: VecDeque<ClientEvent> = VecDeque::new();
 
+        // Start the background task that actively deletes expired keys
+        tokio::spawn(run_expiry_sweeper(Arc::clone(&self.storage)));
+
+        // Messages published by other servers sharing the storage
+        let mut published = self
+            .storage
+            .listen()
+            .await
+            .expect("Failed to listen for published messages");
+
+        let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+
         loop {
             tokio::select! {
                 // Accept new client connections
-                Ok((stream, addr)) = self.listener.accept() => {
-                    println!("Client connected {}", addr);
-                    let tx = self.client_event_tx.clone();
-
-                    // Start a new task for the client to handle send/recv loop
-                    tokio::spawn(async move {
-                        handle_client(stream, tx).await
-                    });
+                Some((stream, addr)) = self.accepted.recv() => {
+                    self.accept(stream, addr);
                 }
 
                 // Receive the next available event from clients
                 Some(event) = self.client_event_rx.recv() => {
+                    if let Some(client) = self.clients.get_mut(&event.client_id) {
+                        client.last_active = Instant::now();
+                    }
                     event_queue.push_back(event);
                 }
+
+                // Disconnect clients that stayed idle for too long
+                _ = idle_check.tick() => {
+                    self.close_idle_clients();
+                }
+
+                // Deliver messages published elsewhere to local subscribers
+                Some(message) = published.recv() => {
+                    if message.sharded {
+                        self.pubsub.spublish(&message.channel, &message.message);
+                    } else {
+                        self.pubsub.publish(&message.channel, &message.message);
+                    }
+                }
+
+                // Retry blocked clients and time out the ones that waited long enough
+                () = self.blocked.wait() => {
+                    let resumed = self.blocked.poll(self.storage.as_ref()).await;
+                    requeue(&mut event_queue, resumed);
+                }
             }
 
             // Process the event queue
             while let Some(event) = event_queue.pop_front() {
-                handle_client_event(&event);
+                // Commands from a blocked client wait until it has been served
+                let Some(event) = self.blocked.defer(event) else {
+                    continue;
+                };
+
+                let outcome = handle_client_event(
+                    self.storage.as_ref(),
+                    &mut self.pubsub,
+                    &mut self.scripting,
+                    &mut self.transactions,
+                    &mut self.connections,
+                    &mut self.config,
+                    &event,
+                )
+                .await;
+                let keys = match outcome {
+                    EventOutcome::Replied => ready_keys(&event.command),
+                    EventOutcome::Executed { ready_keys } => ready_keys,
+                    EventOutcome::Blocked {
+                        keys,
+                        deadline,
+                        command,
+                    } => {
+                        self.blocked.block(
+                            ClientEvent {
+                                command: *command,
+                                ..event
+                            },
+                            keys,
+                            deadline,
+                        );
+                        continue;
+                    }
+                };
+                if !keys.is_empty() {
+                    let resumed = self.blocked.wake(self.storage.as_ref(), keys).await;
+                    requeue(&mut event_queue, resumed);
+                }
             }
         }
     }
+
+    fn accept(&mut self, mut stream: TcpStream, addr: SocketAddr) {
+        self.forget_closed_clients();
+        if self.clients.len() >= self.config.maxclients {
+            log!(
+                Level::Verbose,
+                "Rejected client {addr}, max number of clients reached"
+            );
+            tokio::spawn(async move {
+                let _ = stream
+                    .write_all(b"-ERR max number of clients reached\r\n")
+                    .await;
+            });
+            return;
+        }
+
+        let client_id = self.next_client_id;
+        self.next_client_id += 1;
+        log!(Level::Verbose, "Client {client_id} connected from {addr}");
+
+        let closed = CancellationToken::new();
+        self.clients.insert(
+            client_id,
+            Client {
+                last_active: Instant::now(),
+                closed: closed.clone(),
+            },
+        );
+
+        // Start a new task for the client to handle send/recv loop, with the limits
+        // configured at the time it connected
+        let tx = self.client_event_tx.clone();
+        let limits = self.config.limits;
+        tokio::spawn(async move {
+            handle_client(stream, client_id, tx, limits, closed).await;
+        });
+    }
+
+    // Blocked clients wait on their own timeout and subscribers on messages, so only
+    // the others are closed for being idle
+    fn close_idle_clients(&mut self) {
+        self.forget_closed_clients();
+        let timeout = self.config.timeout;
+        if timeout.is_zero() {
+            return;
+        }
+        for (&client_id, client) in &self.clients {
+            if client.last_active.elapsed() > timeout
+                && !self.blocked.is_blocked(client_id)
+                && !self.pubsub.is_subscribed(client_id)
+            {
+                log!(Level::Verbose, "Closing idle client {client_id}");
+                client.closed.cancel();
+            }
+        }
+    }
+
+    // Connections cancel their token once they finish
+    fn forget_closed_clients(&mut self) {
+        self.clients
+            .retain(|_, client| !client.closed.is_cancelled());
+    }
+}
+
+// Hands the connections accepted on a listener to the server, until it is dropped
+async fn accept_connections(
+    listener: TcpListener,
+    accepted: UnboundedSender<(TcpStream, SocketAddr)>,
+) {
+    loop {
+        if let Ok(connection) = listener.accept().await
+            && accepted.send(connection).is_err()
+        {
+            return;
+        }
+    }
+}
+
+// Puts events deferred by newly unblocked clients at the front of the queue, since
+// they were sent before anything still waiting in it
+fn requeue(event_queue: &mut VecDeque<ClientEvent>, events: Vec<ClientEvent>) {
+    for event in events.into_iter().rev() {
+        event_queue.push_front(event);
+    }
 }
@@ -0,0 +1,122 @@
+use crate::client::ClientCommand;
+use std::collections::HashMap;
+
+// The commands a client queued since MULTI
+#[derive(Default)]
+pub struct Queued {
+    pub commands: Vec<ClientCommand>,
+    // Set when a command failed to queue, making EXEC discard the transaction
+    pub aborted: bool,
+}
+
+// Transactions opened by MULTI and keys watched by WATCH on every connected client
+pub struct Transactions<V> {
+    queued: HashMap<u64, Queued>,
+    // The watched keys with their versions when first watched, in the order watched
+    watched: HashMap<u64, Vec<(String, V)>>,
+    // Whether a failed command rolls back the whole transaction at EXEC
+    atomic: bool,
+}
+
+impl<V> Transactions<V> {
+    pub fn new(atomic: bool) -> Self {
+        Transactions {
+            queued: HashMap::new(),
+            watched: HashMap::new(),
+            atomic,
+        }
+    }
+
+    pub fn is_atomic(&self) -> bool {
+        self.atomic
+    }
+
+    // Applies to transactions executed from now on
+    pub fn set_atomic(&mut self, atomic: bool) {
+        self.atomic = atomic;
+    }
+
+    // A client that sent MULTI queues its commands until EXEC or DISCARD
+    pub fn is_queuing(&self, client_id: u64) -> bool {
+        self.queued.contains_key(&client_id)
+    }
+
+    pub fn begin(&mut self, client_id: u64) {
+        self.queued.entry(client_id).or_default();
+    }
+
+    pub fn queue(&mut self, client_id: u64, command: ClientCommand) {
+        if let Some(queued) = self.queued.get_mut(&client_id) {
+            queued.commands.push(command);
+        }
+    }
+
+    pub fn abort(&mut self, client_id: u64) {
+        if let Some(queued) = self.queued.get_mut(&client_id) {
+            queued.aborted = true;
+        }
+    }
+
+    // Ends the client's transaction, returning what it queued if there was one
+    pub fn take(&mut self, client_id: u64) -> Option<Queued> {
+        self.queued.remove(&client_id)
+    }
+
+    // Watching a key again keeps the version it was first watched at
+    pub fn watch(&mut self, client_id: u64, versions: impl IntoIterator<Item = (String, V)>) {
+        let watched = self.watched.entry(client_id).or_default();
+        for (key, version) in versions {
+            if !watched.iter().any(|(k, _)| *k == key) {
+                watched.push((key, version));
+            }
+        }
+    }
+
+    // Stops watching every key of the client, returning them with their versions
+    pub fn unwatch(&mut self, client_id: u64) -> Vec<(String, V)> {
+        self.watched.remove(&client_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transactions() {
+        let mut transactions = Transactions::<i64>::new(false);
+        transactions.queue(1, ClientCommand::Reset);
+        assert!(!transactions.is_queuing(1));
+
+        transactions.begin(1);
+        transactions.queue(1, ClientCommand::Ping(None));
+        transactions.begin(1);
+        assert!(transactions.is_queuing(1));
+        assert!(!transactions.is_queuing(2));
+
+        let queued = transactions.take(1).unwrap();
+        assert_eq!(queued.commands, vec![ClientCommand::Ping(None)]);
+        assert!(!queued.aborted);
+        assert!(transactions.take(1).is_none());
+
+        transactions.begin(2);
+        transactions.abort(2);
+        assert!(transactions.take(2).unwrap().aborted);
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut transactions = Transactions::new(false);
+        transactions.watch(1, [("a".to_string(), 1), ("b".to_string(), 2)]);
+        transactions.watch(1, [("a".to_string(), 3), ("c".to_string(), 4)]);
+        assert_eq!(
+            transactions.unwatch(1),
+            vec![
+                ("a".to_string(), 1),
+                ("b".to_string(), 2),
+                ("c".to_string(), 4)
+            ]
+        );
+        assert!(transactions.unwatch(1).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Pool(deadpool_postgres::PoolError),
+    Postgres(tokio_postgres::Error),
+    WrongType,
+    InvalidValue(&'static str),
+    // An expiry out of range, named by the command it was given to
+    InvalidExpireTime(&'static str),
+    NoGroup { key: String, group: String },
+    BusyGroup,
+    LibraryExists(String),
+    FunctionExists(String),
+    // A script ran past its time limit, so what it wrote is rolled back
+    ScriptTimeout,
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Pool(err) => write!(f, "ERR storage unavailable: {err}"),
+            StorageError::Postgres(err) => write!(f, "ERR storage failure: {err}"),
+            StorageError::WrongType => write!(
+                f,
+                "WRONGTYPE Operation against a key holding the wrong kind of value"
+            ),
+            StorageError::InvalidValue(message) => write!(f, "ERR {message}"),
+            StorageError::InvalidExpireTime(command) => {
+                write!(f, "ERR invalid expire time in '{command}' command")
+            }
+            StorageError::NoGroup { key, group } => {
+                write!(f, "NOGROUP No such key '{key}' or consumer group '{group}'")
+            }
+            StorageError::BusyGroup => write!(f, "BUSYGROUP Consumer Group name already exists"),
+            StorageError::LibraryExists(name) => write!(f, "ERR Library '{name}' already exists"),
+            StorageError::FunctionExists(name) => write!(f, "ERR Function {name} already exists"),
+            StorageError::ScriptTimeout => write!(f, "ERR Script timed out and was aborted"),
+        }
+    }
+}
+
+impl From<deadpool_postgres::PoolError> for StorageError {
+    fn from(err: deadpool_postgres::PoolError) -> Self {
+        StorageError::Pool(err)
+    }
+}
+
+impl From<tokio_postgres::Error> for StorageError {
+    fn from(err: tokio_postgres::Error) -> Self {
+        StorageError::Postgres(err)
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+// The type of value held by a key, as reported by TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyKind {
+    String,
+    Hash,
+    List,
+    Set,
+    SortedSet,
+    Stream,
+}
+
+impl KeyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyKind::String => "string",
+            KeyKind::Hash => "hash",
+            KeyKind::List => "list",
+            KeyKind::Set => "set",
+            KeyKind::SortedSet => "zset",
+            KeyKind::Stream => "stream",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyKind> {
+        match name {
+            "string" => Some(KeyKind::String),
+            "hash" => Some(KeyKind::Hash),
+            "list" => Some(KeyKind::List),
+            "set" => Some(KeyKind::Set),
+            "zset" => Some(KeyKind::SortedSet),
+            "stream" => Some(KeyKind::Stream),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
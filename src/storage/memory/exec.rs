@@ -0,0 +1,140 @@
+use crate::storage::memory::MemoryStorage;
+use crate::storage::{StorageResult, TransactionStorage, watched_changed};
+use std::collections::HashMap;
+
+// Commands already run one at a time, so an atomic transaction only needs to be able
+// to undo its writes if it fails. The keyspace and libraries log them as they happen.
+impl TransactionStorage for MemoryStorage {
+    type Transaction<'a> = MemoryStorage;
+
+    async fn exec<I: IntoIterator, T>(
+        &self,
+        watched: &[(String, Option<u64>)],
+        items: I,
+        atomic: bool,
+        mut run: impl AsyncFnMut(&MemoryStorage, I::Item) -> StorageResult<T>,
+    ) -> StorageResult<Option<Vec<StorageResult<T>>>> {
+        if watched_changed(self, watched).await? {
+            return Ok(None);
+        }
+
+        if atomic {
+            self.entries.lock().unwrap().undo.begin();
+            self.libraries.lock().unwrap().undo.begin();
+        }
+        let mut results = Vec::new();
+        for item in items {
+            let result = run(self, item).await;
+            if atomic && let Err(e) = result {
+                self.entries.lock().unwrap().rollback();
+                self.libraries.lock().unwrap().rollback();
+                return Err(e);
+            }
+            results.push(result);
+        }
+        if atomic {
+            self.entries.lock().unwrap().undo.commit();
+            self.libraries.lock().unwrap().undo.commit();
+        }
+        Ok(Some(results))
+    }
+}
+
+// The value each key had before a transaction first wrote it, so that only the keys
+// it touched are copied. Transactions may nest, each keeping its own log.
+pub(super) struct UndoLog<V> {
+    logs: Vec<HashMap<String, Option<V>>>,
+}
+
+impl<V> Default for UndoLog<V> {
+    fn default() -> Self {
+        UndoLog { logs: Vec::new() }
+    }
+}
+
+impl<V: Clone> UndoLog<V> {
+    fn begin(&mut self) {
+        self.logs.push(HashMap::new());
+    }
+
+    // Called before every write with the key's current value
+    pub(super) fn save(&mut self, key: &str, current: Option<&V>) {
+        if let Some(log) = self.logs.last_mut()
+            && !log.contains_key(key)
+        {
+            log.insert(key.to_string(), current.cloned());
+        }
+    }
+
+    // An enclosing transaction still needs the values from before this one wrote
+    fn commit(&mut self) {
+        let Some(log) = self.logs.pop() else {
+            return;
+        };
+        if let Some(outer) = self.logs.last_mut() {
+            for (key, value) in log {
+                outer.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    // Returns the values to put back, removing the keys logged as missing
+    pub(super) fn rollback(&mut self) -> HashMap<String, Option<V>> {
+        self.logs.pop().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{
+        FunctionLibrary, FunctionStorage, KeyStorage, MemoryStorage, SetOptions, StorageError,
+        StringStorage, TransactionStorage,
+    };
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn test_atomic_rollback() {
+        let storage = MemoryStorage::new();
+        let options = SetOptions::default();
+        storage.set("kept", b"1", options).await.unwrap();
+        storage.set("deleted", b"2", options).await.unwrap();
+        let watched = keys(&["kept", "deleted", "created"]);
+        let versions = storage.versions(&watched).await.unwrap();
+        let library = FunctionLibrary {
+            name: "lib".into(),
+            code: String::new(),
+            functions: Vec::new(),
+        };
+
+        // Writes made in a nested transaction are undone with the outer one
+        let failed = storage
+            .exec(&[], [()], true, async |storage, ()| {
+                storage.set("kept", b"3", options).await?;
+                storage.set("kept", b"4", options).await?;
+                storage.del(&keys(&["deleted"])).await?;
+                storage.load_library(&library, false).await?;
+                let inner = storage.exec(&[], [()], true, async |storage, ()| {
+                    storage.set("created", b"5", options).await
+                });
+                inner.await?;
+                Err::<(), _>(StorageError::WrongType)
+            })
+            .await;
+        assert!(matches!(failed, Err(StorageError::WrongType)));
+        assert_eq!(storage.get("kept").await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(storage.get("deleted").await.unwrap(), Some(b"2".to_vec()));
+        assert_eq!(storage.get("created").await.unwrap(), None);
+        assert_eq!(storage.versions(&watched).await.unwrap(), versions);
+        assert!(storage.libraries().await.unwrap().is_empty());
+
+        // A committed transaction keeps its writes
+        let done = storage.exec(&[], [()], true, async |storage, ()| {
+            storage.set("kept", b"6", options).await
+        });
+        assert!(done.await.unwrap().is_some());
+        assert_eq!(storage.get("kept").await.unwrap(), Some(b"6".to_vec()));
+    }
+}
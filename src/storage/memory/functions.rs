@@ -0,0 +1,105 @@
+use crate::storage::memory::MemoryStorage;
+use crate::storage::{FunctionLibrary, FunctionStorage, StorageError, StorageResult};
+
+impl FunctionStorage for MemoryStorage {
+    async fn load_library(&self, library: &FunctionLibrary, replace: bool) -> StorageResult<()> {
+        let mut libraries = self.libraries.lock().unwrap();
+        if !replace && libraries.contains_key(&library.name) {
+            return Err(StorageError::LibraryExists(library.name.clone()));
+        }
+        // Function names are shared by every library except the one being replaced
+        let taken = libraries
+            .values()
+            .filter(|other| other.name != library.name)
+            .flat_map(|other| &other.functions)
+            .find(|function| library.functions.iter().any(|f| f.name == function.name));
+        if let Some(function) = taken {
+            return Err(StorageError::FunctionExists(function.name.clone()));
+        }
+        libraries.insert(library.clone());
+        Ok(())
+    }
+
+    async fn delete_library(&self, name: &str) -> StorageResult<bool> {
+        Ok(self.libraries.lock().unwrap().remove(name).is_some())
+    }
+
+    async fn flush_libraries(&self) -> StorageResult<()> {
+        self.libraries.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn libraries(&self) -> StorageResult<Vec<FunctionLibrary>> {
+        Ok(self.libraries.lock().unwrap().values().cloned().collect())
+    }
+
+    async fn function_library(&self, function: &str) -> StorageResult<Option<FunctionLibrary>> {
+        let libraries = self.libraries.lock().unwrap();
+        Ok(libraries
+            .values()
+            .find(|library| library.functions.iter().any(|f| f.name == function))
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LibraryFunction;
+
+    fn library(name: &str, functions: &[&str]) -> FunctionLibrary {
+        FunctionLibrary {
+            name: name.into(),
+            code: format!("#!lua name={name}"),
+            functions: functions
+                .iter()
+                .map(|f| LibraryFunction {
+                    name: (*f).into(),
+                    description: None,
+                    flags: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_libraries() {
+        let storage = MemoryStorage::new();
+        storage
+            .load_library(&library("a", &["f", "g"]), false)
+            .await
+            .unwrap();
+        assert!(matches!(
+            storage.load_library(&library("a", &["h"]), false).await,
+            Err(StorageError::LibraryExists(_))
+        ));
+        assert!(matches!(
+            storage.load_library(&library("b", &["g"]), false).await,
+            Err(StorageError::FunctionExists(f)) if f == "g"
+        ));
+
+        // Replacing a library drops the functions it no longer registers
+        storage
+            .load_library(&library("a", &["g"]), true)
+            .await
+            .unwrap();
+        assert!(storage.function_library("f").await.unwrap().is_none());
+        storage
+            .load_library(&library("b", &["f"]), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.function_library("f").await.unwrap().unwrap().name,
+            "b"
+        );
+
+        assert!(storage.delete_library("a").await.unwrap());
+        assert!(!storage.delete_library("a").await.unwrap());
+        assert_eq!(
+            storage.libraries().await.unwrap(),
+            vec![library("b", &["f"])]
+        );
+        storage.flush_libraries().await.unwrap();
+        assert!(storage.libraries().await.unwrap().is_empty());
+    }
+}
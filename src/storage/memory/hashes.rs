@@ -0,0 +1,286 @@
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value, pattern_matches, scan_page};
+use crate::storage::numeric::{format_float, increment_float, increment_int};
+use crate::storage::{FieldPairs, HashStorage, StorageError, StorageResult};
+use std::collections::HashMap;
+
+type Hash = HashMap<Vec<u8>, Vec<u8>>;
+
+fn get_hash<'a>(entries: &'a Keyspace, key: &str) -> StorageResult<Option<&'a Hash>> {
+    match entries.get(key).map(|e| &e.value) {
+        None => Ok(None),
+        Some(Value::Hash(hash)) => Ok(Some(hash)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Returns the hash stored at `key`, creating an empty one if the key does not exist
+fn get_or_create_hash<'a>(entries: &'a mut Keyspace, key: &str) -> StorageResult<&'a mut Hash> {
+    let entry = entries.get_or_insert_with(key, || Entry::new(Value::Hash(HashMap::new())));
+    let Value::Hash(hash) = &mut entry.value else {
+        return Err(StorageError::WrongType);
+    };
+    Ok(hash)
+}
+
+impl HashStorage for MemoryStorage {
+    async fn hset(&self, key: &str, pairs: &[(Vec<u8>, Vec<u8>)]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let hash = get_or_create_hash(&mut entries, key)?;
+
+        let added = pairs
+            .iter()
+            .filter(|(field, value)| hash.insert(field.clone(), value.clone()).is_none())
+            .count();
+        Ok(i64::try_from(added).unwrap_or(i64::MAX))
+    }
+
+    async fn hsetnx(&self, key: &str, field: &[u8], value: &[u8]) -> StorageResult<bool> {
+        let mut entries = self.lock_key(key);
+        let hash = get_or_create_hash(&mut entries, key)?;
+        if hash.contains_key(field) {
+            return Ok(false);
+        }
+        hash.insert(field.to_vec(), value.to_vec());
+        Ok(true)
+    }
+
+    async fn hmget(&self, key: &str, fields: &[Vec<u8>]) -> StorageResult<Vec<Option<Vec<u8>>>> {
+        let entries = self.lock_key(key);
+        let hash = get_hash(&entries, key)?;
+        Ok(fields
+            .iter()
+            .map(|field| hash.and_then(|h| h.get(field).cloned()))
+            .collect())
+    }
+
+    async fn hgetall(&self, key: &str) -> StorageResult<FieldPairs> {
+        let entries = self.lock_key(key);
+        let mut pairs: Vec<(Vec<u8>, Vec<u8>)> = get_hash(&entries, key)?
+            .map(|h| h.iter().map(|(f, v)| (f.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        pairs.sort();
+        Ok(pairs)
+    }
+
+    async fn hlen(&self, key: &str) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        let len = get_hash(&entries, key)?.map_or(0, HashMap::len);
+        Ok(i64::try_from(len).unwrap_or(i64::MAX))
+    }
+
+    async fn hdel(&self, key: &str, fields: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(entry) = entries.get_mut(key) else {
+            return Ok(0);
+        };
+        let Value::Hash(hash) = &mut entry.value else {
+            return Err(StorageError::WrongType);
+        };
+
+        let deleted = fields.iter().filter(|f| hash.remove(*f).is_some()).count();
+        if hash.is_empty() {
+            entries.remove(key);
+        }
+        Ok(i64::try_from(deleted).unwrap_or(i64::MAX))
+    }
+
+    async fn hincrby(&self, key: &str, field: &[u8], delta: i64) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let created = !entries.contains_key(key);
+        let hash = get_or_create_hash(&mut entries, key)?;
+
+        let result = increment_int(
+            hash.get(field).map(Vec::as_slice),
+            delta,
+            "hash value is not an integer",
+        );
+        match result {
+            Ok(value) => {
+                hash.insert(field.to_vec(), value.to_string().into_bytes());
+                Ok(value)
+            }
+            Err(err) => {
+                if created {
+                    entries.remove(key);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn hincrbyfloat(&self, key: &str, field: &[u8], delta: f64) -> StorageResult<f64> {
+        let mut entries = self.lock_key(key);
+        let created = !entries.contains_key(key);
+        let hash = get_or_create_hash(&mut entries, key)?;
+
+        let result = increment_float(
+            hash.get(field).map(Vec::as_slice),
+            delta,
+            "hash value is not a float",
+        );
+        match result {
+            Ok(value) => {
+                hash.insert(field.to_vec(), format_float(value).into_bytes());
+                Ok(value)
+            }
+            Err(err) => {
+                if created {
+                    entries.remove(key);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> StorageResult<(u64, FieldPairs)> {
+        let entries = self.lock_key(key);
+        let Some(hash) = get_hash(&entries, key)? else {
+            return Ok((0, Vec::new()));
+        };
+
+        // Fields are visited in sorted order so the cursor is a stable offset
+        let mut fields: Vec<(&Vec<u8>, &Vec<u8>)> = hash.iter().collect();
+        fields.sort();
+
+        let (next_cursor, page) = scan_page(&fields, cursor, count);
+        let matched = page
+            .into_iter()
+            .filter(|(field, _)| pattern_matches(pattern, field))
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        Ok((next_cursor, matched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{KeyStorage, SetOptions, StringStorage};
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        items
+            .iter()
+            .map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn fields(names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|n| n.as_bytes().to_vec()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_hset_hmget() {
+        let storage = MemoryStorage::new();
+        let first = pairs(&[("a", "1"), ("b", "2")]);
+        assert_eq!(storage.hset("h", &first).await.unwrap(), 2);
+        let second = pairs(&[("a", "3"), ("c", "4")]);
+        assert_eq!(storage.hset("h", &second).await.unwrap(), 1);
+
+        let values = storage.hmget("h", &fields(&["a", "x", "c"])).await.unwrap();
+        assert_eq!(values, vec![Some(b"3".to_vec()), None, Some(b"4".to_vec())]);
+        assert_eq!(storage.hlen("h").await.unwrap(), 3);
+        assert_eq!(
+            storage.hgetall("h").await.unwrap(),
+            pairs(&[("a", "3"), ("b", "2"), ("c", "4")])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hsetnx() {
+        let storage = MemoryStorage::new();
+        assert!(storage.hsetnx("h", b"a", b"1").await.unwrap());
+        assert!(!storage.hsetnx("h", b"a", b"2").await.unwrap());
+        assert_eq!(storage.hgetall("h").await.unwrap(), pairs(&[("a", "1")]));
+    }
+
+    #[tokio::test]
+    async fn test_hdel_removes_empty_hash() {
+        let storage = MemoryStorage::new();
+        storage
+            .hset("h", &pairs(&[("a", "1"), ("b", "2")]))
+            .await
+            .unwrap();
+
+        assert_eq!(storage.hdel("h", &fields(&["a", "x"])).await.unwrap(), 1);
+        assert_eq!(storage.hdel("h", &fields(&["b"])).await.unwrap(), 1);
+        assert_eq!(storage.exists(&["h".to_string()]).await.unwrap(), 0);
+        assert_eq!(storage.hdel("h", &fields(&["b"])).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hincrby() {
+        let storage = MemoryStorage::new();
+        assert_eq!(storage.hincrby("h", b"n", 5).await.unwrap(), 5);
+        assert_eq!(storage.hincrby("h", b"n", -7).await.unwrap(), -2);
+
+        storage.hset("h", &pairs(&[("s", "abc")])).await.unwrap();
+        let err = storage.hincrby("h", b"s", 1).await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR hash value is not an integer");
+
+        storage
+            .hset("h", &pairs(&[("max", "9223372036854775807")]))
+            .await
+            .unwrap();
+        let err = storage.hincrby("h", b"max", 1).await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR increment or decrement would overflow");
+    }
+
+    #[tokio::test]
+    async fn test_hincrbyfloat() {
+        let storage = MemoryStorage::new();
+        assert!((storage.hincrbyfloat("h", b"f", 1.5).await.unwrap() - 1.5).abs() < f64::EPSILON);
+        assert!((storage.hincrbyfloat("h", b"f", 0.25).await.unwrap() - 1.75).abs() < f64::EPSILON);
+        assert_eq!(
+            storage.hmget("h", &fields(&["f"])).await.unwrap(),
+            vec![Some(b"1.75".to_vec())]
+        );
+
+        // A failed increment does not leave an empty hash behind
+        let err = storage
+            .hincrbyfloat("g", b"f", f64::INFINITY)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR increment would produce NaN or Infinity"
+        );
+        assert_eq!(storage.exists(&["g".to_string()]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_hscan() {
+        let storage = MemoryStorage::new();
+        let items = pairs(&[("a1", "1"), ("a2", "2"), ("b1", "3")]);
+        storage.hset("h", &items).await.unwrap();
+
+        let (cursor, page) = storage.hscan("h", 0, None, 2).await.unwrap();
+        assert_eq!(cursor, 2);
+        assert_eq!(page, pairs(&[("a1", "1"), ("a2", "2")]));
+
+        let (cursor, page) = storage.hscan("h", 0, Some(b"b*"), 10).await.unwrap();
+        assert_eq!(cursor, 0);
+        assert_eq!(page, pairs(&[("b1", "3")]));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_type() {
+        let storage = MemoryStorage::new();
+        storage.set("s", b"x", SetOptions::default()).await.unwrap();
+
+        assert!(matches!(
+            storage.hset("s", &pairs(&[("a", "1")])).await,
+            Err(StorageError::WrongType)
+        ));
+        assert!(matches!(
+            storage.hlen("s").await,
+            Err(StorageError::WrongType)
+        ));
+        assert_eq!(storage.get("s").await.unwrap(), Some(b"x".to_vec()));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::storage::memory::{Entry, MemoryStorage, pattern_matches, scan_page};
+use crate::storage::{ExpireCondition, KeyExpiry, KeyKind, KeyStorage, StorageResult, now_millis};
+
+impl KeyStorage for MemoryStorage {
+    type Version = Option<u64>;
+
+    async fn del(&self, keys: &[String]) -> StorageResult<i64> {
+        let mut entries = self.lock_keys(keys);
+        let deleted = keys.iter().filter(|k| entries.remove(k).is_some()).count();
+        Ok(i64::try_from(deleted).unwrap_or(i64::MAX))
+    }
+
+    async fn exists(&self, keys: &[String]) -> StorageResult<i64> {
+        let entries = self.lock_keys(keys);
+        let found = keys.iter().filter(|k| entries.contains_key(*k)).count();
+        Ok(i64::try_from(found).unwrap_or(i64::MAX))
+    }
+
+    async fn key_type(&self, key: &str) -> StorageResult<Option<KeyKind>> {
+        let entries = self.lock_key(key);
+        Ok(entries.get(key).map(|e| e.value.kind()))
+    }
+
+    async fn expire(
+        &self,
+        key: &str,
+        expires_at: Option<i64>,
+        condition: ExpireCondition,
+    ) -> StorageResult<bool> {
+        let mut entries = self.lock_key(key);
+        let Some(entry) = entries.get(key) else {
+            return Ok(false);
+        };
+        if !condition.allows(entry.expires_at, expires_at) {
+            return Ok(false);
+        }
+
+        if expires_at.is_some_and(|at| at <= now_millis()) {
+            entries.remove(key);
+        } else {
+            entries.set_expiry(key, expires_at);
+        }
+        Ok(true)
+    }
+
+    async fn expire_time(&self, key: &str) -> StorageResult<KeyExpiry> {
+        let entries = self.lock_key(key);
+        Ok(match entries.get(key) {
+            None => KeyExpiry::Missing,
+            Some(Entry {
+                expires_at: None, ..
+            }) => KeyExpiry::Persistent,
+            Some(Entry {
+                expires_at: Some(at),
+                ..
+            }) => KeyExpiry::At(*at),
+        })
+    }
+
+    async fn purge_expired(&self, limit: usize) -> StorageResult<usize> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = now_millis();
+
+        let expired = entries.expired(now, limit);
+        for key in &expired {
+            entries.remove(key);
+        }
+        Ok(expired.len())
+    }
+
+    async fn versions(&self, keys: &[String]) -> StorageResult<Vec<Option<u64>>> {
+        let entries = self.lock_keys(keys);
+        Ok(keys
+            .iter()
+            .map(|k| entries.get(k).map(|e| e.version))
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        kind: Option<KeyKind>,
+        count: usize,
+    ) -> StorageResult<(u64, Vec<String>)> {
+        let entries = self.entries.lock().unwrap();
+        let now = now_millis();
+
+        // Keys are visited in sorted order so the cursor is a stable offset
+        let mut keys: Vec<&String> = entries
+            .iter()
+            .filter(|(_, e)| !e.is_expired(now))
+            .map(|(k, _)| k)
+            .collect();
+        keys.sort();
+
+        let (next_cursor, page) = scan_page(&keys, cursor, count);
+        let matched = page
+            .into_iter()
+            .filter(|k| pattern_matches(pattern.map(str::as_bytes), k.as_bytes()))
+            .filter(|k| kind.is_none_or(|kind| entries[*k].value.kind() == kind))
+            .cloned()
+            .collect();
+        Ok((next_cursor, matched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{
+        HashStorage, MemoryStorage, SetExpiry, SetOptions, StringStorage, TransactionStorage,
+    };
+    use std::time::Duration;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    async fn set(storage: &MemoryStorage, key: &str, value: &[u8]) {
+        storage
+            .set(key, value, SetOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_del_exists() {
+        let storage = MemoryStorage::new();
+        set(&storage, "a", b"1").await;
+        set(&storage, "b", b"2").await;
+
+        assert_eq!(storage.exists(&keys(&["a", "a", "c"])).await.unwrap(), 2);
+        assert_eq!(storage.del(&keys(&["a", "c"])).await.unwrap(), 1);
+        assert_eq!(storage.exists(&keys(&["a", "b"])).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_key_type() {
+        let storage = MemoryStorage::new();
+        set(&storage, "s", b"1").await;
+        let pairs = vec![(b"f".to_vec(), b"v".to_vec())];
+        storage.hset("h", &pairs).await.unwrap();
+
+        assert_eq!(storage.key_type("s").await.unwrap(), Some(KeyKind::String));
+        assert_eq!(storage.key_type("h").await.unwrap(), Some(KeyKind::Hash));
+        assert_eq!(storage.key_type("x").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_expire() {
+        let storage = MemoryStorage::new();
+        let always = ExpireCondition::Always;
+        assert!(!storage.expire("foo", Some(0), always).await.unwrap());
+
+        set(&storage, "foo", b"bar").await;
+        let past = Some(now_millis() - 1);
+        assert!(storage.expire("foo", past, always).await.unwrap());
+        assert_eq!(storage.get("foo").await.unwrap(), None);
+        assert_eq!(storage.exists(&keys(&["foo"])).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_expire_conditions() {
+        let storage = MemoryStorage::new();
+        set(&storage, "foo", b"bar").await;
+        let later = now_millis() + 60_000;
+
+        // GT never applies to a persistent key, LT always does
+        let expire = |at: i64, condition| storage.expire("foo", Some(at), condition);
+        assert!(!expire(later, ExpireCondition::IfGreater).await.unwrap());
+        assert!(!expire(later, ExpireCondition::IfHasExpiry).await.unwrap());
+        assert!(expire(later, ExpireCondition::IfLess).await.unwrap());
+        assert_eq!(
+            storage.expire_time("foo").await.unwrap(),
+            KeyExpiry::At(later)
+        );
+
+        assert!(!expire(later, ExpireCondition::IfNoExpiry).await.unwrap());
+        assert!(expire(later + 1, ExpireCondition::IfGreater).await.unwrap());
+
+        // PERSIST clears an existing expiry
+        let persist = storage.expire("foo", None, ExpireCondition::IfHasExpiry);
+        assert!(persist.await.unwrap());
+        assert_eq!(
+            storage.expire_time("foo").await.unwrap(),
+            KeyExpiry::Persistent
+        );
+        assert_eq!(
+            storage.expire_time("bar").await.unwrap(),
+            KeyExpiry::Missing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_purge_expired() {
+        let storage = MemoryStorage::new();
+        let expiring = SetOptions {
+            expiry: SetExpiry::At(now_millis() + 10),
+            ..SetOptions::default()
+        };
+        storage.set("a", b"x", expiring).await.unwrap();
+        storage.set("b", b"x", expiring).await.unwrap();
+        set(&storage, "c", b"x").await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(storage.purge_expired(1).await.unwrap(), 1);
+        assert_eq!(storage.purge_expired(10).await.unwrap(), 1);
+        assert_eq!(storage.purge_expired(10).await.unwrap(), 0);
+        assert_eq!(storage.entries.lock().unwrap().len(), 1);
+
+        // The index follows keys that are persisted, overwritten, re-expired or deleted
+        let at = now_millis() + 60_000;
+        let always = ExpireCondition::Always;
+        for key in ["d", "e", "f", "g", "h"] {
+            set(&storage, key, b"x").await;
+            storage.expire(key, Some(at), always).await.unwrap();
+        }
+        storage.expire("d", None, always).await.unwrap();
+        set(&storage, "e", b"y").await;
+        storage.expire("f", Some(at + 1), always).await.unwrap();
+        storage.del(&keys(&["g"])).await.unwrap();
+        let keep = SetOptions {
+            expiry: SetExpiry::Keep,
+            ..SetOptions::default()
+        };
+        storage.set("h", b"y", keep).await.unwrap();
+        let entries = storage.entries.lock().unwrap();
+        let expiring: Vec<_> = entries.expiring.iter().cloned().collect();
+        let expected = vec![(at, "h".to_string()), (at + 1, "f".to_string())];
+        assert_eq!(expiring, expected);
+    }
+
+    #[tokio::test]
+    async fn test_scan() {
+        let storage = MemoryStorage::new();
+        for key in ["user:1", "user:2", "session:1"] {
+            set(&storage, key, b"x").await;
+        }
+        let pairs = vec![(b"f".to_vec(), b"v".to_vec())];
+        storage.hset("user:3", &pairs).await.unwrap();
+
+        let (cursor, page) = storage.scan(0, None, None, 2).await.unwrap();
+        assert_eq!(cursor, 2);
+        assert_eq!(page, keys(&["session:1", "user:1"]));
+
+        let (cursor, page) = storage.scan(cursor, None, None, 2).await.unwrap();
+        assert_eq!(cursor, 0);
+        assert_eq!(page, keys(&["user:2", "user:3"]));
+
+        let scan = storage.scan(0, Some("user:*"), Some(KeyKind::String), 10);
+        let (cursor, page) = scan.await.unwrap();
+        assert_eq!(cursor, 0);
+        assert_eq!(page, keys(&["user:1", "user:2"]));
+    }
+
+    #[tokio::test]
+    async fn test_versions() {
+        let storage = MemoryStorage::new();
+        let watched = keys(&["inv", "missing"]);
+        set(&storage, "inv", b"5").await;
+        let before = storage.versions(&watched).await.unwrap();
+        assert!(before[0].is_some());
+        assert_eq!(before[1], None);
+
+        // Reads leave the version alone
+        storage.get("inv").await.unwrap();
+        assert_eq!(storage.versions(&watched).await.unwrap(), before);
+
+        // Writing the same value is still a write
+        set(&storage, "inv", b"5").await;
+        let same = storage.versions(&watched).await.unwrap();
+        assert_ne!(same, before);
+
+        // As is changing it and changing it back
+        set(&storage, "inv", b"6").await;
+        set(&storage, "inv", b"5").await;
+        let back = storage.versions(&watched).await.unwrap();
+        assert_ne!(back, same);
+
+        let watched: Vec<_> = watched.into_iter().zip(before).collect();
+        let exec = storage.exec(&watched, [()], false, async |_, ()| Ok(()));
+        assert!(exec.await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,331 @@
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value};
+use crate::storage::range::{normalize_index, normalize_range};
+use crate::storage::{ListEnd, ListStorage, StorageError, StorageResult};
+use std::collections::VecDeque;
+
+type List = VecDeque<Vec<u8>>;
+
+fn get_list<'a>(entries: &'a Keyspace, key: &str) -> StorageResult<Option<&'a List>> {
+    match entries.get(key).map(|e| &e.value) {
+        None => Ok(None),
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+fn get_list_mut<'a>(entries: &'a mut Keyspace, key: &str) -> StorageResult<Option<&'a mut List>> {
+    match entries.get_mut(key).map(|e| &mut e.value) {
+        None => Ok(None),
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Returns the list stored at `key`, creating an empty one if the key does not exist
+fn get_or_create_list<'a>(entries: &'a mut Keyspace, key: &str) -> StorageResult<&'a mut List> {
+    let entry = entries.get_or_insert_with(key, || Entry::new(Value::List(VecDeque::new())));
+    let Value::List(list) = &mut entry.value else {
+        return Err(StorageError::WrongType);
+    };
+    Ok(list)
+}
+
+fn remove_if_empty(entries: &mut Keyspace, key: &str) {
+    if matches!(entries.get(key).map(|e| &e.value), Some(Value::List(list)) if list.is_empty()) {
+        entries.remove(key);
+    }
+}
+
+fn list_len(list: &List) -> i64 {
+    i64::try_from(list.len()).unwrap_or(i64::MAX)
+}
+
+fn pop_end(list: &mut List, end: ListEnd) -> Option<Vec<u8>> {
+    match end {
+        ListEnd::Left => list.pop_front(),
+        ListEnd::Right => list.pop_back(),
+    }
+}
+
+fn push_end(list: &mut List, end: ListEnd, value: Vec<u8>) {
+    match end {
+        ListEnd::Left => list.push_front(value),
+        ListEnd::Right => list.push_back(value),
+    }
+}
+
+impl ListStorage for MemoryStorage {
+    async fn push(
+        &self,
+        key: &str,
+        values: &[Vec<u8>],
+        end: ListEnd,
+        only_existing: bool,
+    ) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        if only_existing && get_list(&entries, key)?.is_none() {
+            return Ok(0);
+        }
+
+        let list = get_or_create_list(&mut entries, key)?;
+        for value in values {
+            push_end(list, end, value.clone());
+        }
+        Ok(list_len(list))
+    }
+
+    async fn pop(
+        &self,
+        key: &str,
+        end: ListEnd,
+        count: usize,
+    ) -> StorageResult<Option<Vec<Vec<u8>>>> {
+        let mut entries = self.lock_key(key);
+        let Some(list) = get_list_mut(&mut entries, key)? else {
+            return Ok(None);
+        };
+
+        let popped = (0..count).map_while(|_| pop_end(list, end)).collect();
+        remove_if_empty(&mut entries, key);
+        Ok(Some(popped))
+    }
+
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> StorageResult<Vec<Vec<u8>>> {
+        let entries = self.lock_key(key);
+        let Some(list) = get_list(&entries, key)? else {
+            return Ok(Vec::new());
+        };
+        let Some((start, stop)) = normalize_range(start, stop, list.len()) else {
+            return Ok(Vec::new());
+        };
+        Ok(list.range(start..=stop).cloned().collect())
+    }
+
+    async fn lset(&self, key: &str, index: i64, value: &[u8]) -> StorageResult<()> {
+        let mut entries = self.lock_key(key);
+        let Some(list) = get_list_mut(&mut entries, key)? else {
+            return Err(StorageError::InvalidValue("no such key"));
+        };
+        let Some(index) = normalize_index(index, list.len()) else {
+            return Err(StorageError::InvalidValue("index out of range"));
+        };
+        list[index] = value.to_vec();
+        Ok(())
+    }
+
+    async fn linsert(
+        &self,
+        key: &str,
+        before: bool,
+        pivot: &[u8],
+        value: &[u8],
+    ) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(list) = get_list_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+        let Some(position) = list.iter().position(|v| v == pivot) else {
+            return Ok(-1);
+        };
+
+        let index = if before { position } else { position + 1 };
+        list.insert(index, value.to_vec());
+        Ok(list_len(list))
+    }
+
+    async fn lrem(&self, key: &str, count: i64, value: &[u8]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(list) = get_list_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+
+        let limit = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+        let limit = if count == 0 { usize::MAX } else { limit };
+        let mut matches: Vec<usize> = list
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.as_slice() == value)
+            .map(|(i, _)| i)
+            .collect();
+        if count < 0 {
+            matches.reverse();
+        }
+        matches.truncate(limit);
+
+        // Remove from the back so the remaining indexes stay valid
+        matches.sort_unstable();
+        for index in matches.iter().rev() {
+            list.remove(*index);
+        }
+        remove_if_empty(&mut entries, key);
+        Ok(i64::try_from(matches.len()).unwrap_or(i64::MAX))
+    }
+
+    async fn ltrim(&self, key: &str, start: i64, stop: i64) -> StorageResult<()> {
+        let mut entries = self.lock_key(key);
+        let Some(list) = get_list_mut(&mut entries, key)? else {
+            return Ok(());
+        };
+
+        match normalize_range(start, stop, list.len()) {
+            Some((start, stop)) => {
+                list.truncate(stop + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        remove_if_empty(&mut entries, key);
+        Ok(())
+    }
+
+    async fn llen(&self, key: &str) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        Ok(get_list(&entries, key)?.map_or(0, list_len))
+    }
+
+    async fn lmove(
+        &self,
+        source: &str,
+        destination: &str,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> StorageResult<Option<Vec<u8>>> {
+        let keys = [source.to_string(), destination.to_string()];
+        let mut entries = self.lock_keys(&keys);
+
+        if get_list(&entries, source)?.is_none() {
+            return Ok(None);
+        }
+        // The destination is checked before popping so a failed move leaves the source intact
+        get_list(&entries, destination)?;
+        let Some(value) = get_list_mut(&mut entries, source)?.and_then(|l| pop_end(l, from)) else {
+            return Ok(None);
+        };
+
+        let target = get_or_create_list(&mut entries, destination)?;
+        push_end(target, to, value.clone());
+        remove_if_empty(&mut entries, source);
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{KeyStorage, SetOptions, StringStorage};
+
+    fn values(items: &[&str]) -> Vec<Vec<u8>> {
+        items.iter().map(|v| v.as_bytes().to_vec()).collect()
+    }
+
+    async fn list(storage: &MemoryStorage, key: &str, items: &[&str]) {
+        let items = values(items);
+        storage
+            .push(key, &items, ListEnd::Right, false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_push_pop() {
+        let storage = MemoryStorage::new();
+        let items = values(&["a", "b", "c"]);
+        assert_eq!(
+            storage
+                .push("l", &items, ListEnd::Left, false)
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            storage.lrange("l", 0, -1).await.unwrap(),
+            values(&["c", "b", "a"])
+        );
+
+        let popped = storage.pop("l", ListEnd::Right, 2).await.unwrap();
+        assert_eq!(popped, Some(values(&["a", "b"])));
+        let popped = storage.pop("l", ListEnd::Left, 5).await.unwrap();
+        assert_eq!(popped, Some(values(&["c"])));
+
+        // The list is removed once empty, after which PUSHX does nothing
+        assert_eq!(storage.pop("l", ListEnd::Left, 1).await.unwrap(), None);
+        let pushed = storage
+            .push("l", &items, ListEnd::Right, true)
+            .await
+            .unwrap();
+        assert_eq!(pushed, 0);
+        assert_eq!(storage.exists(&["l".to_string()]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_lset_linsert() {
+        let storage = MemoryStorage::new();
+        list(&storage, "l", &["a", "b", "c"]).await;
+
+        storage.lset("l", -1, b"z").await.unwrap();
+        let err = storage.lset("l", 3, b"x").await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR index out of range");
+        let err = storage.lset("missing", 0, b"x").await.unwrap_err();
+        assert_eq!(err.to_string(), "ERR no such key");
+
+        assert_eq!(storage.linsert("l", true, b"b", b"x").await.unwrap(), 4);
+        assert_eq!(storage.linsert("l", false, b"z", b"y").await.unwrap(), 5);
+        assert_eq!(storage.linsert("l", true, b"q", b"y").await.unwrap(), -1);
+        assert_eq!(
+            storage.lrange("l", 0, -1).await.unwrap(),
+            values(&["a", "x", "b", "z", "y"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lrem() {
+        let storage = MemoryStorage::new();
+        list(&storage, "l", &["a", "b", "a", "c", "a"]).await;
+
+        assert_eq!(storage.lrem("l", -2, b"a").await.unwrap(), 2);
+        assert_eq!(
+            storage.lrange("l", 0, -1).await.unwrap(),
+            values(&["a", "b", "c"])
+        );
+        assert_eq!(storage.lrem("l", 0, b"b").await.unwrap(), 1);
+        assert_eq!(storage.lrem("l", 1, b"a").await.unwrap(), 1);
+        assert_eq!(storage.llen("l").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ltrim() {
+        let storage = MemoryStorage::new();
+        list(&storage, "l", &["a", "b", "c", "d"]).await;
+
+        storage.ltrim("l", 1, -2).await.unwrap();
+        assert_eq!(
+            storage.lrange("l", 0, -1).await.unwrap(),
+            values(&["b", "c"])
+        );
+        storage.ltrim("l", 5, 10).await.unwrap();
+        assert_eq!(storage.exists(&["l".to_string()]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_lmove() {
+        let storage = MemoryStorage::new();
+        list(&storage, "src", &["a", "b"]).await;
+
+        let moved = storage.lmove("src", "dst", ListEnd::Left, ListEnd::Right);
+        assert_eq!(moved.await.unwrap(), Some(b"a".to_vec()));
+        let rotated = storage.lmove("src", "src", ListEnd::Left, ListEnd::Right);
+        assert_eq!(rotated.await.unwrap(), Some(b"b".to_vec()));
+        assert_eq!(storage.lrange("src", 0, -1).await.unwrap(), values(&["b"]));
+
+        // A destination of another type fails without popping from the source
+        storage.set("s", b"x", SetOptions::default()).await.unwrap();
+        let moved = storage
+            .lmove("src", "s", ListEnd::Left, ListEnd::Left)
+            .await;
+        assert!(matches!(moved, Err(StorageError::WrongType)));
+        assert_eq!(storage.llen("src").await.unwrap(), 1);
+
+        let moved = storage.lmove("missing", "dst", ListEnd::Left, ListEnd::Left);
+        assert_eq!(moved.await.unwrap(), None);
+    }
+}
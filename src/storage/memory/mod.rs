@@ -0,0 +1,273 @@
+mod exec;
+mod functions;
+mod hashes;
+mod keys;
+mod lists;
+mod pubsub;
+mod sets;
+mod sorted_sets;
+mod stream_groups;
+mod streams;
+mod strings;
+
+use crate::glob::glob_match;
+use crate::storage::memory::exec::UndoLog;
+use crate::storage::memory::sorted_sets::SortedSet;
+use crate::storage::memory::streams::Stream;
+use crate::storage::{FunctionLibrary, KeyKind, now_millis};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone)]
+enum Value {
+    String(Vec<u8>),
+    Hash(HashMap<Vec<u8>, Vec<u8>>),
+    List(VecDeque<Vec<u8>>),
+    Set(HashSet<Vec<u8>>),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+impl Value {
+    fn kind(&self) -> KeyKind {
+        match self {
+            Value::String(_) => KeyKind::String,
+            Value::Hash(_) => KeyKind::Hash,
+            Value::List(_) => KeyKind::List,
+            Value::Set(_) => KeyKind::Set,
+            Value::SortedSet(_) => KeyKind::SortedSet,
+            Value::Stream(_) => KeyKind::Stream,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Entry {
+    value: Value,
+    expires_at: Option<i64>,
+    // Set from the keyspace's sequence whenever the entry is written, for WATCH
+    version: u64,
+}
+
+impl Entry {
+    fn new(value: Value) -> Self {
+        Entry {
+            value,
+            expires_at: None,
+            version: 0,
+        }
+    }
+
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+// The entries by key. Reads go through the map, while writes go through methods that
+// give the entry a new version like the `kv_versions` sequence does in Postgres, and
+// log it for an atomic transaction to undo. Any mutable access counts as a write, but
+// expiry times are only changed through `set_expiry`, which keeps them indexed.
+#[derive(Default)]
+struct Keyspace {
+    entries: HashMap<String, Entry>,
+    last_version: u64,
+    undo: UndoLog<Entry>,
+    // The keys that expire, soonest first
+    expiring: BTreeSet<(i64, String)>,
+}
+
+impl Deref for Keyspace {
+    type Target = HashMap<String, Entry>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl Keyspace {
+    fn next_version(&mut self) -> u64 {
+        self.last_version += 1;
+        self.last_version
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Entry> {
+        self.undo.save(key, Some(self.entries.get(key)?));
+        let version = self.next_version();
+        let entry = self.entries.get_mut(key)?;
+        entry.version = version;
+        Some(entry)
+    }
+
+    fn insert(&mut self, key: String, mut entry: Entry) -> Option<Entry> {
+        self.undo.save(&key, self.entries.get(&key));
+        entry.version = self.next_version();
+        self.put(key, entry)
+    }
+
+    // A removed key has no version, as a deleted row has none
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        self.undo.save(key, self.entries.get(key));
+        self.take(key)
+    }
+
+    fn set_expiry(&mut self, key: &str, expires_at: Option<i64>) {
+        let Some(entry) = self.get_mut(key) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut entry.expires_at, expires_at);
+        if let Some(at) = previous {
+            self.expiring.remove(&(at, key.to_string()));
+        }
+        if let Some(at) = expires_at {
+            self.expiring.insert((at, key.to_string()));
+        }
+    }
+
+    // The keys whose expiry time has passed, at most `limit` of them
+    fn expired(&self, now: i64, limit: usize) -> Vec<String> {
+        self.expiring
+            .iter()
+            .take_while(|(at, _)| *at <= now)
+            .take(limit)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    // Insert and remove an entry, keeping the expiry index in step
+    fn put(&mut self, key: String, entry: Entry) -> Option<Entry> {
+        let at = entry.expires_at;
+        let previous = self.entries.insert(key.clone(), entry);
+        if let Some(at) = previous.as_ref().and_then(|e| e.expires_at) {
+            self.expiring.remove(&(at, key.clone()));
+        }
+        if let Some(at) = at {
+            self.expiring.insert((at, key));
+        }
+        previous
+    }
+
+    fn take(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        if let Some(at) = entry.expires_at {
+            self.expiring.remove(&(at, key.to_string()));
+        }
+        Some(entry)
+    }
+
+    // Returns the entry at `key` for writing, inserting `default()` if there is none
+    fn get_or_insert_with(&mut self, key: &str, default: impl FnOnce() -> Entry) -> &mut Entry {
+        self.undo.save(key, self.entries.get(key));
+        let version = self.next_version();
+        let entry = self.entries.entry(key.to_string()).or_insert_with(default);
+        entry.version = version;
+        entry
+    }
+
+    // Puts back the entries the innermost transaction wrote, with their versions. The
+    // sequence itself never goes back, as versions handed out must not be reused.
+    fn rollback(&mut self) {
+        for (key, entry) in self.undo.rollback() {
+            match entry {
+                Some(entry) => self.put(key, entry),
+                None => self.take(&key),
+            };
+        }
+    }
+}
+
+// Function libraries by name, written like the keyspace so a transaction can undo them
+#[derive(Default)]
+struct Libraries {
+    libraries: BTreeMap<String, FunctionLibrary>,
+    undo: UndoLog<FunctionLibrary>,
+}
+
+impl Deref for Libraries {
+    type Target = BTreeMap<String, FunctionLibrary>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.libraries
+    }
+}
+
+impl Libraries {
+    fn insert(&mut self, library: FunctionLibrary) {
+        self.undo
+            .save(&library.name, self.libraries.get(&library.name));
+        self.libraries.insert(library.name.clone(), library);
+    }
+
+    fn remove(&mut self, name: &str) -> Option<FunctionLibrary> {
+        self.undo.save(name, self.libraries.get(name));
+        self.libraries.remove(name)
+    }
+
+    fn clear(&mut self) {
+        for (name, library) in &self.libraries {
+            self.undo.save(name, Some(library));
+        }
+        self.libraries.clear();
+    }
+
+    fn rollback(&mut self) {
+        for (name, library) in self.undo.rollback() {
+            match library {
+                Some(library) => self.libraries.insert(name, library),
+                None => self.libraries.remove(&name),
+            };
+        }
+    }
+}
+
+// Keeps the whole keyspace in a `HashMap`, intended for tests and ephemeral use
+pub struct MemoryStorage {
+    entries: Mutex<Keyspace>,
+    libraries: Mutex<Libraries>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage {
+            entries: Mutex::new(Keyspace::default()),
+            libraries: Mutex::new(Libraries::default()),
+        }
+    }
+
+    // Locks the keyspace, dropping the entry for `key` first if it has expired
+    fn lock_key(&self, key: &str) -> MutexGuard<'_, Keyspace> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|e| e.is_expired(now_millis())) {
+            entries.remove(key);
+        }
+        entries
+    }
+
+    fn lock_keys(&self, keys: &[String]) -> MutexGuard<'_, Keyspace> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = now_millis();
+        for key in keys {
+            if entries.get(key).is_some_and(|e| e.is_expired(now)) {
+                entries.remove(key);
+            }
+        }
+        entries
+    }
+}
+
+// Returns a page of sorted items starting at the cursor offset, along with the next cursor
+fn scan_page<T: Clone>(items: &[T], cursor: u64, count: usize) -> (u64, Vec<T>) {
+    let offset = usize::try_from(cursor).unwrap_or(usize::MAX);
+    let page: Vec<T> = items.iter().skip(offset).take(count).cloned().collect();
+
+    let next_cursor = if offset.saturating_add(count) >= items.len() {
+        0
+    } else {
+        cursor + page.len() as u64
+    };
+    (next_cursor, page)
+}
+
+fn pattern_matches(pattern: Option<&[u8]>, text: &[u8]) -> bool {
+    pattern.is_none_or(|p| glob_match(p, text))
+}
@@ -0,0 +1,15 @@
+use crate::storage::memory::MemoryStorage;
+use crate::storage::{PubSubStorage, PublishedMessage, StorageResult};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+// The keyspace is private to the process, so there are no other servers to relay to
+impl PubSubStorage for MemoryStorage {
+    async fn publish(&self, _channel: &str, _message: &[u8], _sharded: bool) -> StorageResult<()> {
+        Ok(())
+    }
+
+    async fn listen(&self) -> StorageResult<UnboundedReceiver<PublishedMessage>> {
+        let (_, rx) = unbounded_channel();
+        Ok(rx)
+    }
+}
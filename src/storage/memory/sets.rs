@@ -0,0 +1,336 @@
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value, pattern_matches, scan_page};
+use crate::storage::{SetOp, SetStorage, StorageError, StorageResult};
+use rand::seq::{IndexedRandom, IteratorRandom};
+use std::collections::HashSet;
+
+type Set = HashSet<Vec<u8>>;
+
+fn get_set<'a>(entries: &'a Keyspace, key: &str) -> StorageResult<Option<&'a Set>> {
+    match entries.get(key).map(|e| &e.value) {
+        None => Ok(None),
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+fn get_set_mut<'a>(entries: &'a mut Keyspace, key: &str) -> StorageResult<Option<&'a mut Set>> {
+    match entries.get_mut(key).map(|e| &mut e.value) {
+        None => Ok(None),
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Returns the set stored at `key`, creating an empty one if the key does not exist
+fn get_or_create_set<'a>(entries: &'a mut Keyspace, key: &str) -> StorageResult<&'a mut Set> {
+    let entry = entries.get_or_insert_with(key, || Entry::new(Value::Set(HashSet::new())));
+    let Value::Set(set) = &mut entry.value else {
+        return Err(StorageError::WrongType);
+    };
+    Ok(set)
+}
+
+fn remove_if_empty(entries: &mut Keyspace, key: &str) {
+    if matches!(entries.get(key).map(|e| &e.value), Some(Value::Set(set)) if set.is_empty()) {
+        entries.remove(key);
+    }
+}
+
+fn count(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn sorted(members: impl IntoIterator<Item = Vec<u8>>) -> Vec<Vec<u8>> {
+    let mut members: Vec<Vec<u8>> = members.into_iter().collect();
+    members.sort();
+    members
+}
+
+// Combines the sets held by the keys, failing if any of them holds another type
+fn combine_sets(entries: &Keyspace, op: SetOp, keys: &[String]) -> StorageResult<Set> {
+    let sets = keys
+        .iter()
+        .map(|key| get_set(entries, key))
+        .collect::<StorageResult<Vec<Option<&Set>>>>()?;
+    let empty = Set::new();
+    let mut sets = sets.into_iter().map(|set| set.unwrap_or(&empty));
+
+    let mut result = sets.next().cloned().unwrap_or_default();
+    for set in sets {
+        match op {
+            SetOp::Union => result.extend(set.iter().cloned()),
+            SetOp::Intersect => result.retain(|member| set.contains(member)),
+            SetOp::Difference => result.retain(|member| !set.contains(member)),
+        }
+    }
+    Ok(result)
+}
+
+impl SetStorage for MemoryStorage {
+    async fn sadd(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let set = get_or_create_set(&mut entries, key)?;
+        let added = members.iter().filter(|m| set.insert((*m).clone())).count();
+        Ok(count(added))
+    }
+
+    async fn srem(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(set) = get_set_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+        let removed = members.iter().filter(|m| set.remove(*m)).count();
+        remove_if_empty(&mut entries, key);
+        Ok(count(removed))
+    }
+
+    async fn smembers(&self, key: &str) -> StorageResult<Vec<Vec<u8>>> {
+        let entries = self.lock_key(key);
+        let set = get_set(&entries, key)?;
+        Ok(sorted(set.into_iter().flatten().cloned()))
+    }
+
+    async fn smismember(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<Vec<bool>> {
+        let entries = self.lock_key(key);
+        let set = get_set(&entries, key)?;
+        Ok(members
+            .iter()
+            .map(|m| set.is_some_and(|s| s.contains(m)))
+            .collect())
+    }
+
+    async fn scard(&self, key: &str) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        Ok(get_set(&entries, key)?.map_or(0, |set| count(set.len())))
+    }
+
+    async fn spop(&self, key: &str, count: usize) -> StorageResult<Vec<Vec<u8>>> {
+        let mut entries = self.lock_key(key);
+        let Some(set) = get_set_mut(&mut entries, key)? else {
+            return Ok(Vec::new());
+        };
+
+        let popped: Vec<Vec<u8>> = set.iter().cloned().sample(&mut rand::rng(), count);
+        for member in &popped {
+            set.remove(member);
+        }
+        remove_if_empty(&mut entries, key);
+        Ok(popped)
+    }
+
+    async fn srandmember(&self, key: &str, count: i64) -> StorageResult<Vec<Vec<u8>>> {
+        let entries = self.lock_key(key);
+        let Some(set) = get_set(&entries, key)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut rng = rand::rng();
+        let amount = usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX);
+        if count >= 0 {
+            return Ok(set.iter().cloned().sample(&mut rng, amount));
+        }
+        let members: Vec<&Vec<u8>> = set.iter().collect();
+        Ok((0..amount)
+            .filter_map(|_| members.choose(&mut rng).map(|m| (*m).clone()))
+            .collect())
+    }
+
+    async fn smove(&self, source: &str, destination: &str, member: &[u8]) -> StorageResult<bool> {
+        let keys = [source.to_string(), destination.to_string()];
+        let mut entries = self.lock_keys(&keys);
+
+        let Some(set) = get_set(&entries, source)? else {
+            return Ok(false);
+        };
+        // The destination is checked before removing so a failed move leaves the source intact
+        get_set(&entries, destination)?;
+        if !set.contains(member) {
+            return Ok(false);
+        }
+        if source == destination {
+            return Ok(true);
+        }
+
+        if let Some(set) = get_set_mut(&mut entries, source)? {
+            set.remove(member);
+        }
+        remove_if_empty(&mut entries, source);
+        get_or_create_set(&mut entries, destination)?.insert(member.to_vec());
+        Ok(true)
+    }
+
+    async fn combine(&self, op: SetOp, keys: &[String]) -> StorageResult<Vec<Vec<u8>>> {
+        let entries = self.lock_keys(keys);
+        Ok(sorted(combine_sets(&entries, op, keys)?))
+    }
+
+    async fn combine_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+    ) -> StorageResult<i64> {
+        let mut entries = self.lock_keys(keys);
+        let result = combine_sets(&entries, op, keys)?;
+
+        let len = count(result.len());
+        if result.is_empty() {
+            entries.remove(destination);
+        } else {
+            entries.insert(destination.to_string(), Entry::new(Value::Set(result)));
+        }
+        Ok(len)
+    }
+
+    async fn intercard(&self, keys: &[String], limit: Option<usize>) -> StorageResult<i64> {
+        let entries = self.lock_keys(keys);
+        let result = combine_sets(&entries, SetOp::Intersect, keys)?;
+        Ok(count(limit.map_or(result.len(), |l| result.len().min(l))))
+    }
+
+    async fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> StorageResult<(u64, Vec<Vec<u8>>)> {
+        let entries = self.lock_key(key);
+        let Some(set) = get_set(&entries, key)? else {
+            return Ok((0, Vec::new()));
+        };
+
+        // Members are visited in sorted order so the cursor is a stable offset
+        let members = sorted(set.iter().cloned());
+        let (next_cursor, page) = scan_page(&members, cursor, count);
+        let matched = page
+            .into_iter()
+            .filter(|member| pattern_matches(pattern, member))
+            .collect();
+        Ok((next_cursor, matched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{KeyStorage, SetOptions, StringStorage};
+
+    fn members(items: &[&str]) -> Vec<Vec<u8>> {
+        items.iter().map(|m| m.as_bytes().to_vec()).collect()
+    }
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sadd_srem() {
+        let storage = MemoryStorage::new();
+        let added = storage.sadd("s", &members(&["a", "b", "a"])).await.unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(storage.sadd("s", &members(&["b", "c"])).await.unwrap(), 1);
+        assert_eq!(
+            storage.smembers("s").await.unwrap(),
+            members(&["a", "b", "c"])
+        );
+        assert_eq!(
+            storage
+                .smismember("s", &members(&["a", "x"]))
+                .await
+                .unwrap(),
+            vec![true, false]
+        );
+
+        assert_eq!(
+            storage
+                .srem("s", &members(&["a", "b", "c", "x"]))
+                .await
+                .unwrap(),
+            3
+        );
+        assert_eq!(storage.exists(&keys(&["s"])).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_spop_srandmember() {
+        let storage = MemoryStorage::new();
+        storage.sadd("s", &members(&["a", "b", "c"])).await.unwrap();
+
+        assert_eq!(storage.srandmember("s", 5).await.unwrap().len(), 3);
+        assert_eq!(storage.srandmember("s", -5).await.unwrap().len(), 5);
+        assert_eq!(storage.scard("s").await.unwrap(), 3);
+
+        let popped = storage.spop("s", 2).await.unwrap();
+        assert_eq!(popped.len(), 2);
+        assert_eq!(storage.scard("s").await.unwrap(), 1);
+        assert_eq!(storage.spop("s", 5).await.unwrap().len(), 1);
+        assert_eq!(storage.exists(&keys(&["s"])).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_smove() {
+        let storage = MemoryStorage::new();
+        storage.sadd("src", &members(&["a"])).await.unwrap();
+        storage
+            .set("str", b"x", SetOptions::default())
+            .await
+            .unwrap();
+
+        let moved = storage.smove("src", "str", b"a").await;
+        assert!(matches!(moved, Err(StorageError::WrongType)));
+        assert!(!storage.smove("src", "dst", b"x").await.unwrap());
+        assert!(storage.smove("src", "dst", b"a").await.unwrap());
+        assert_eq!(storage.exists(&keys(&["src"])).await.unwrap(), 0);
+        assert_eq!(storage.smembers("dst").await.unwrap(), members(&["a"]));
+    }
+
+    #[tokio::test]
+    async fn test_combine() {
+        let storage = MemoryStorage::new();
+        storage.sadd("a", &members(&["1", "2", "3"])).await.unwrap();
+        storage.sadd("b", &members(&["2", "3", "4"])).await.unwrap();
+
+        let union = storage.combine(SetOp::Union, &keys(&["a", "b", "x"])).await;
+        assert_eq!(union.unwrap(), members(&["1", "2", "3", "4"]));
+        let inter = storage.combine(SetOp::Intersect, &keys(&["a", "b"])).await;
+        assert_eq!(inter.unwrap(), members(&["2", "3"]));
+        let diff = storage.combine(SetOp::Difference, &keys(&["a", "b"])).await;
+        assert_eq!(diff.unwrap(), members(&["1"]));
+        let inter = storage.combine(SetOp::Intersect, &keys(&["a", "x"])).await;
+        assert!(inter.unwrap().is_empty());
+
+        assert_eq!(
+            storage
+                .intercard(&keys(&["a", "b"]), Some(1))
+                .await
+                .unwrap(),
+            1
+        );
+
+        storage
+            .set("str", b"x", SetOptions::default())
+            .await
+            .unwrap();
+        let union = storage.combine(SetOp::Union, &keys(&["a", "str"])).await;
+        assert!(matches!(union, Err(StorageError::WrongType)));
+    }
+
+    #[tokio::test]
+    async fn test_combine_store() {
+        let storage = MemoryStorage::new();
+        storage.sadd("a", &members(&["1", "2"])).await.unwrap();
+        storage.sadd("b", &members(&["2"])).await.unwrap();
+
+        let sources = keys(&["a", "b"]);
+
+        // The destination may be one of the sources
+        let stored = storage.combine_store(SetOp::Difference, "a", &sources);
+        assert_eq!(stored.await.unwrap(), 1);
+        assert_eq!(storage.smembers("a").await.unwrap(), members(&["1"]));
+
+        let stored = storage.combine_store(SetOp::Intersect, "b", &sources);
+        assert_eq!(stored.await.unwrap(), 0);
+        assert_eq!(storage.exists(&keys(&["b"])).await.unwrap(), 0);
+    }
+}
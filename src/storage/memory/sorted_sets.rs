@@ -0,0 +1,445 @@
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value};
+use crate::storage::range::normalize_range;
+use crate::storage::{
+    ScoreEnd, ScoredMembers, SortedSetStorage, StorageError, StorageResult, ZAddOptions,
+    ZAddOutcome, ZRange, ZRangeBy,
+};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+// Scores are compared with `total_cmp` so they can order a `BTreeSet`
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl Score {
+    // Negative zero would otherwise sort apart from zero
+    fn new(score: f64) -> Self {
+        Score(score + 0.0)
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Members indexed both by name and by (score, member) order
+#[derive(Default, Clone)]
+pub(super) struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    ordered: BTreeSet<(Score, Vec<u8>)>,
+}
+
+impl SortedSet {
+    fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    fn insert(&mut self, member: &[u8], score: f64) {
+        let score = Score::new(score);
+        if let Some(old) = self.scores.insert(member.to_vec(), score.0) {
+            self.ordered.remove(&(Score(old), member.to_vec()));
+        }
+        self.ordered.insert((score, member.to_vec()));
+    }
+
+    fn remove(&mut self, member: &[u8]) -> bool {
+        let Some(score) = self.scores.remove(member) else {
+            return false;
+        };
+        self.ordered.remove(&(Score(score), member.to_vec()));
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    fn ordered(&self, reverse: bool) -> Vec<(&[u8], f64)> {
+        let items = self
+            .ordered
+            .iter()
+            .map(|(score, m)| (m.as_slice(), score.0));
+        if reverse {
+            items.rev().collect()
+        } else {
+            items.collect()
+        }
+    }
+
+    // Returns the members selected by the query in rank order
+    fn select(&self, range: &ZRange) -> Vec<(&[u8], f64)> {
+        let ordered = self.ordered(range.reverse);
+        let selected = match &range.by {
+            ZRangeBy::Rank { start, stop } => match normalize_range(*start, *stop, ordered.len()) {
+                Some((start, stop)) => ordered[start..=stop].to_vec(),
+                None => Vec::new(),
+            },
+            ZRangeBy::Score { min, max } => ordered
+                .into_iter()
+                .filter(|(_, score)| min.above(*score) && max.below(*score))
+                .collect(),
+            ZRangeBy::Lex { min, max } => ordered
+                .into_iter()
+                .filter(|(member, _)| min.above(member) && max.below(member))
+                .collect(),
+        };
+        selected
+            .into_iter()
+            .skip(range.offset)
+            .take(range.count.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+fn get_zset<'a>(entries: &'a Keyspace, key: &str) -> StorageResult<Option<&'a SortedSet>> {
+    match entries.get(key).map(|e| &e.value) {
+        None => Ok(None),
+        Some(Value::SortedSet(set)) => Ok(Some(set)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+fn get_zset_mut<'a>(
+    entries: &'a mut Keyspace,
+    key: &str,
+) -> StorageResult<Option<&'a mut SortedSet>> {
+    match entries.get_mut(key).map(|e| &mut e.value) {
+        None => Ok(None),
+        Some(Value::SortedSet(set)) => Ok(Some(set)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Returns the sorted set stored at `key`, creating an empty one if the key does not exist
+fn get_or_create_zset<'a>(
+    entries: &'a mut Keyspace,
+    key: &str,
+) -> StorageResult<&'a mut SortedSet> {
+    let entry =
+        entries.get_or_insert_with(key, || Entry::new(Value::SortedSet(SortedSet::default())));
+    let Value::SortedSet(set) = &mut entry.value else {
+        return Err(StorageError::WrongType);
+    };
+    Ok(set)
+}
+
+fn remove_if_empty(entries: &mut Keyspace, key: &str) {
+    if matches!(entries.get(key).map(|e| &e.value), Some(Value::SortedSet(set)) if set.len() == 0) {
+        entries.remove(key);
+    }
+}
+
+fn count(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn to_owned(members: Vec<(&[u8], f64)>) -> ScoredMembers {
+    members
+        .into_iter()
+        .map(|(member, score)| (member.to_vec(), score))
+        .collect()
+}
+
+impl SortedSetStorage for MemoryStorage {
+    async fn zadd(
+        &self,
+        key: &str,
+        members: &[(f64, Vec<u8>)],
+        options: ZAddOptions,
+    ) -> StorageResult<ZAddOutcome> {
+        let mut entries = self.lock_key(key);
+        let set = get_or_create_zset(&mut entries, key)?;
+
+        let mut outcome = ZAddOutcome::default();
+        for (score, member) in members {
+            let current = set.score(member);
+            let Some(score) = options.resolve(current, *score, false)? else {
+                continue;
+            };
+            match current {
+                None => outcome.added += 1,
+                Some(current) if Score(current) != Score::new(score) => outcome.changed += 1,
+                Some(_) => continue,
+            }
+            set.insert(member, score);
+        }
+        // XX on a missing key must not leave an empty set behind
+        remove_if_empty(&mut entries, key);
+        Ok(outcome)
+    }
+
+    async fn zincrby(
+        &self,
+        key: &str,
+        member: &[u8],
+        delta: f64,
+        options: ZAddOptions,
+    ) -> StorageResult<Option<f64>> {
+        let mut entries = self.lock_key(key);
+        let set = get_or_create_zset(&mut entries, key)?;
+
+        let score = options.resolve(set.score(member), delta, true);
+        if let Ok(Some(score)) = score {
+            set.insert(member, score);
+        }
+        remove_if_empty(&mut entries, key);
+        score
+    }
+
+    async fn zmscore(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<Vec<Option<f64>>> {
+        let entries = self.lock_key(key);
+        let set = get_zset(&entries, key)?;
+        Ok(members
+            .iter()
+            .map(|m| set.and_then(|s| s.score(m)))
+            .collect())
+    }
+
+    async fn zrank(
+        &self,
+        key: &str,
+        member: &[u8],
+        reverse: bool,
+    ) -> StorageResult<Option<(i64, f64)>> {
+        let entries = self.lock_key(key);
+        let Some(set) = get_zset(&entries, key)? else {
+            return Ok(None);
+        };
+        let rank = set
+            .ordered(reverse)
+            .into_iter()
+            .enumerate()
+            .find(|(_, (m, _))| *m == member);
+        Ok(rank.map(|(rank, (_, score))| (count(rank), score)))
+    }
+
+    async fn zrem(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(set) = get_zset_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+        let removed = members.iter().filter(|m| set.remove(m)).count();
+        remove_if_empty(&mut entries, key);
+        Ok(count(removed))
+    }
+
+    async fn zcard(&self, key: &str) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        Ok(get_zset(&entries, key)?.map_or(0, |set| count(set.len())))
+    }
+
+    async fn zcount(&self, key: &str, range: &ZRangeBy) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        let Some(set) = get_zset(&entries, key)? else {
+            return Ok(0);
+        };
+        Ok(count(set.select(&ZRange::new(range.clone())).len()))
+    }
+
+    async fn zrange(&self, key: &str, range: &ZRange) -> StorageResult<ScoredMembers> {
+        let entries = self.lock_key(key);
+        let Some(set) = get_zset(&entries, key)? else {
+            return Ok(Vec::new());
+        };
+        Ok(to_owned(set.select(range)))
+    }
+
+    async fn zremrange(&self, key: &str, range: &ZRangeBy) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(set) = get_zset_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+        let selected = to_owned(set.select(&ZRange::new(range.clone())));
+        for (member, _) in &selected {
+            set.remove(member);
+        }
+        remove_if_empty(&mut entries, key);
+        Ok(count(selected.len()))
+    }
+
+    async fn zpop(&self, key: &str, end: ScoreEnd, count: usize) -> StorageResult<ScoredMembers> {
+        let mut entries = self.lock_key(key);
+        let Some(set) = get_zset_mut(&mut entries, key)? else {
+            return Ok(Vec::new());
+        };
+        let mut popped = set.ordered(end == ScoreEnd::Max);
+        popped.truncate(count);
+        let popped = to_owned(popped);
+        for (member, _) in &popped {
+            set.remove(member);
+        }
+        remove_if_empty(&mut entries, key);
+        Ok(popped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{KeyStorage, LexBound, ScoreBound, ScoreUpdate, SetCondition};
+
+    fn scored(items: &[(f64, &str)]) -> Vec<(f64, Vec<u8>)> {
+        items
+            .iter()
+            .map(|(s, m)| (*s, m.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn members(result: &ScoredMembers) -> Vec<&str> {
+        result
+            .iter()
+            .map(|(m, _)| std::str::from_utf8(m).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_zadd_options() {
+        let storage = MemoryStorage::new();
+        let items = scored(&[(1.0, "a"), (2.0, "b")]);
+        let outcome = storage.zadd("z", &items, ZAddOptions::default()).await;
+        assert_eq!(
+            outcome.unwrap(),
+            ZAddOutcome {
+                added: 2,
+                changed: 0
+            }
+        );
+
+        let greater = ZAddOptions {
+            condition: SetCondition::IfExists,
+            update: ScoreUpdate::IfGreater,
+        };
+        let items = scored(&[(5.0, "a"), (0.0, "b"), (3.0, "c")]);
+        let outcome = storage.zadd("z", &items, greater).await;
+        assert_eq!(
+            outcome.unwrap(),
+            ZAddOutcome {
+                added: 0,
+                changed: 1
+            }
+        );
+        assert_eq!(
+            storage
+                .zmscore("z", &[b"a".to_vec(), b"b".to_vec(), b"c".to_vec()])
+                .await
+                .unwrap(),
+            vec![Some(5.0), Some(2.0), None]
+        );
+
+        // XX against a missing key does not create it
+        let only_existing = ZAddOptions {
+            condition: SetCondition::IfExists,
+            update: ScoreUpdate::Always,
+        };
+        storage
+            .zadd("missing", &items, only_existing)
+            .await
+            .unwrap();
+        assert_eq!(storage.exists(&["missing".to_string()]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_zincrby() {
+        let storage = MemoryStorage::new();
+        let options = ZAddOptions::default();
+        assert_eq!(
+            storage.zincrby("z", b"a", 2.5, options).await.unwrap(),
+            Some(2.5)
+        );
+        assert_eq!(
+            storage.zincrby("z", b"a", -1.0, options).await.unwrap(),
+            Some(1.5)
+        );
+
+        let inf = storage
+            .zincrby("z", b"b", f64::INFINITY, options)
+            .await
+            .unwrap();
+        assert_eq!(inf, Some(f64::INFINITY));
+        let nan = storage.zincrby("z", b"b", f64::NEG_INFINITY, options).await;
+        assert!(matches!(nan, Err(StorageError::InvalidValue(_))));
+    }
+
+    #[tokio::test]
+    async fn test_zrange() {
+        let storage = MemoryStorage::new();
+        let items = scored(&[(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d")]);
+        storage
+            .zadd("z", &items, ZAddOptions::default())
+            .await
+            .unwrap();
+
+        let by_rank = ZRange::new(ZRangeBy::Rank { start: 1, stop: -2 });
+        assert_eq!(
+            members(&storage.zrange("z", &by_rank).await.unwrap()),
+            ["b", "c"]
+        );
+
+        let mut by_score = ZRange::new(ZRangeBy::Score {
+            min: ScoreBound::Exclusive(1.0),
+            max: ScoreBound::Inclusive(f64::INFINITY),
+        });
+        by_score.reverse = true;
+        by_score.offset = 1;
+        by_score.count = Some(1);
+        assert_eq!(
+            members(&storage.zrange("z", &by_score).await.unwrap()),
+            ["c"]
+        );
+
+        let by_lex = ZRangeBy::Lex {
+            min: LexBound::Exclusive(b"a".to_vec()),
+            max: LexBound::Max,
+        };
+        assert_eq!(storage.zcount("z", &by_lex).await.unwrap(), 3);
+
+        assert_eq!(
+            storage.zrank("z", b"c", false).await.unwrap(),
+            Some((2, 2.0))
+        );
+        assert_eq!(
+            storage.zrank("z", b"c", true).await.unwrap(),
+            Some((1, 2.0))
+        );
+        assert_eq!(storage.zrank("z", b"x", false).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_zremrange_zpop() {
+        let storage = MemoryStorage::new();
+        let items = scored(&[(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d")]);
+        storage
+            .zadd("z", &items, ZAddOptions::default())
+            .await
+            .unwrap();
+
+        let range = ZRangeBy::Score {
+            min: ScoreBound::Inclusive(2.0),
+            max: ScoreBound::Exclusive(3.0),
+        };
+        assert_eq!(storage.zremrange("z", &range).await.unwrap(), 1);
+        assert_eq!(storage.zcard("z").await.unwrap(), 3);
+
+        let popped = storage.zpop("z", ScoreEnd::Max, 2).await.unwrap();
+        assert_eq!(popped, vec![(b"d".to_vec(), 4.0), (b"c".to_vec(), 3.0)]);
+        let popped = storage.zpop("z", ScoreEnd::Min, 5).await.unwrap();
+        assert_eq!(members(&popped), ["a"]);
+        assert_eq!(storage.exists(&["z".to_string()]).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,636 @@
+use crate::storage::memory::streams::{Stream, get_stream, get_stream_mut};
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value};
+use crate::storage::{
+    AutoClaim, ClaimOptions, ConsumerInfo, DeliveredEntry, FieldPairs, GroupInfo, PendingEntry,
+    PendingRange, PendingSummary, STREAM_REQUIRED, StorageError, StorageResult, StreamGroupStorage,
+    StreamId, now_millis,
+};
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry as MapEntry;
+
+#[derive(Clone)]
+pub(super) struct ConsumerGroup {
+    last_id: StreamId,
+    pending: BTreeMap<StreamId, Delivery>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+// The consumer a pending entry was last delivered to, and when
+#[derive(Clone)]
+struct Delivery {
+    consumer: String,
+    delivered_at: i64,
+    deliveries: u64,
+}
+
+#[derive(Clone)]
+struct Consumer {
+    seen_at: i64,
+    active_at: Option<i64>,
+}
+
+impl ConsumerGroup {
+    fn new(last_id: StreamId) -> Self {
+        ConsumerGroup {
+            last_id,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    // Records that the consumer was seen, creating it if needed, and that it was
+    // delivered entries if `active` is set
+    fn touch(&mut self, consumer: &str, now: i64, active: bool) {
+        let state = self
+            .consumers
+            .entry(consumer.to_string())
+            .or_insert(Consumer {
+                seen_at: now,
+                active_at: None,
+            });
+        state.seen_at = now;
+        if active {
+            state.active_at = Some(now);
+        }
+    }
+
+    fn pending_of(&self, consumer: &str) -> u64 {
+        self.pending
+            .values()
+            .filter(|delivery| delivery.consumer == consumer)
+            .count() as u64
+    }
+}
+
+fn no_group(key: &str, group: &str) -> StorageError {
+    StorageError::NoGroup {
+        key: key.to_string(),
+        group: group.to_string(),
+    }
+}
+
+// Returns the entries of the stream together with one of its groups
+fn stream_group<'a>(
+    keyspace: &'a mut Keyspace,
+    key: &str,
+    group: &str,
+) -> StorageResult<(&'a BTreeMap<StreamId, FieldPairs>, &'a mut ConsumerGroup)> {
+    let Some(Stream {
+        entries, groups, ..
+    }) = get_stream_mut(keyspace, key)?
+    else {
+        return Err(no_group(key, group));
+    };
+    let group = groups.get_mut(group).ok_or_else(|| no_group(key, group))?;
+    Ok((entries, group))
+}
+
+fn delivered(id: StreamId, entries: &BTreeMap<StreamId, FieldPairs>) -> DeliveredEntry {
+    DeliveredEntry {
+        id,
+        fields: entries.get(&id).cloned(),
+    }
+}
+
+impl StreamGroupStorage for MemoryStorage {
+    async fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        create: bool,
+    ) -> StorageResult<()> {
+        let mut entries = self.lock_key(key);
+        if get_stream(&entries, key)?.is_none() {
+            if !create {
+                return Err(STREAM_REQUIRED);
+            }
+            entries.insert(
+                key.to_string(),
+                Entry::new(Value::Stream(Stream::default())),
+            );
+        }
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Err(STREAM_REQUIRED);
+        };
+
+        let last_id = id.unwrap_or(stream.last_id);
+        match stream.groups.entry(group.to_string()) {
+            MapEntry::Occupied(_) => Err(StorageError::BusyGroup),
+            MapEntry::Vacant(vacant) => {
+                vacant.insert(ConsumerGroup::new(last_id));
+                Ok(())
+            }
+        }
+    }
+
+    async fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> StorageResult<()> {
+        let mut entries = self.lock_key(key);
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Err(no_group(key, group));
+        };
+        let last_id = id.unwrap_or(stream.last_id);
+        let group = stream
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| no_group(key, group))?;
+        group.last_id = last_id;
+        Ok(())
+    }
+
+    async fn xgroup_destroy(&self, key: &str, group: &str) -> StorageResult<bool> {
+        let mut entries = self.lock_key(key);
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Err(no_group(key, group));
+        };
+        Ok(stream.groups.remove(group).is_some())
+    }
+
+    async fn xgroup_createconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> StorageResult<bool> {
+        let mut entries = self.lock_key(key);
+        let (_, group) = stream_group(&mut entries, key, group)?;
+        if group.consumers.contains_key(consumer) {
+            return Ok(false);
+        }
+        group.touch(consumer, now_millis(), false);
+        Ok(true)
+    }
+
+    async fn xgroup_delconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let (_, group) = stream_group(&mut entries, key, group)?;
+        if group.consumers.remove(consumer).is_none() {
+            return Ok(0);
+        }
+        let before = group.pending.len();
+        group
+            .pending
+            .retain(|_, delivery| delivery.consumer != consumer);
+        Ok(i64::try_from(before - group.pending.len()).unwrap_or(i64::MAX))
+    }
+
+    async fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        streams: &[(String, Option<StreamId>)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> StorageResult<Vec<(String, Vec<DeliveredEntry>)>> {
+        let keys: Vec<String> = streams.iter().map(|(key, _)| key.clone()).collect();
+        let mut entries = self.lock_keys(&keys);
+        // Nothing is delivered unless every stream has the group
+        for key in &keys {
+            stream_group(&mut entries, key, group)?;
+        }
+
+        let now = now_millis();
+        let limit = count.unwrap_or(usize::MAX);
+        let mut result = Vec::new();
+        for (key, after) in streams {
+            let (stream_entries, group) = stream_group(&mut entries, key, group)?;
+            let read: Vec<DeliveredEntry> = match after {
+                None => {
+                    let new: Vec<(StreamId, FieldPairs)> = match group.last_id.next() {
+                        Some(start) => stream_entries
+                            .range(start..)
+                            .take(limit)
+                            .map(|(id, fields)| (*id, fields.clone()))
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    for (id, _) in &new {
+                        group.last_id = *id;
+                        if !noack {
+                            let delivery = Delivery {
+                                consumer: consumer.to_string(),
+                                delivered_at: now,
+                                deliveries: 1,
+                            };
+                            group.pending.insert(*id, delivery);
+                        }
+                    }
+                    new.into_iter()
+                        .map(|(id, fields)| DeliveredEntry {
+                            id,
+                            fields: Some(fields),
+                        })
+                        .collect()
+                }
+                Some(after) => match after.next() {
+                    Some(start) => group
+                        .pending
+                        .range_mut(start..)
+                        .filter(|(_, delivery)| delivery.consumer == *consumer)
+                        .take(limit)
+                        .map(|(id, delivery)| {
+                            delivery.delivered_at = now;
+                            delivery.deliveries += 1;
+                            delivered(*id, stream_entries)
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                },
+            };
+            group.touch(consumer, now, !read.is_empty());
+            // Streams read for new entries are left out when there are none
+            if after.is_some() || !read.is_empty() {
+                result.push((key.clone(), read));
+            }
+        }
+        Ok(result)
+    }
+
+    async fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let group = match stream_group(&mut entries, key, group) {
+            Ok((_, group)) => group,
+            Err(StorageError::NoGroup { .. }) => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let acked = ids
+            .iter()
+            .filter(|id| group.pending.remove(id).is_some())
+            .count();
+        Ok(i64::try_from(acked).unwrap_or(i64::MAX))
+    }
+
+    async fn xpending_summary(&self, key: &str, group: &str) -> StorageResult<PendingSummary> {
+        let mut entries = self.lock_key(key);
+        let (_, group) = stream_group(&mut entries, key, group)?;
+
+        let mut consumers: BTreeMap<&str, u64> = BTreeMap::new();
+        for delivery in group.pending.values() {
+            *consumers.entry(&delivery.consumer).or_default() += 1;
+        }
+        let first = group.pending.keys().next();
+        let last = group.pending.keys().next_back();
+        Ok(PendingSummary {
+            count: group.pending.len() as u64,
+            bounds: first.zip(last).map(|(first, last)| (*first, *last)),
+            consumers: consumers
+                .into_iter()
+                .map(|(name, count)| (name.to_string(), count))
+                .collect(),
+        })
+    }
+
+    async fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+        range: &PendingRange,
+    ) -> StorageResult<Vec<PendingEntry>> {
+        let mut entries = self.lock_key(key);
+        let (_, group) = stream_group(&mut entries, key, group)?;
+        if range.start > range.end {
+            return Ok(Vec::new());
+        }
+
+        let now = now_millis();
+        let min_idle = i64::try_from(range.min_idle).unwrap_or(i64::MAX);
+        Ok(group
+            .pending
+            .range(range.start..=range.end)
+            .filter(|(_, delivery)| {
+                range
+                    .consumer
+                    .as_ref()
+                    .is_none_or(|consumer| delivery.consumer == *consumer)
+            })
+            .filter(|(_, delivery)| now - delivery.delivered_at >= min_idle)
+            .take(range.count)
+            .map(|(id, delivery)| PendingEntry {
+                id: *id,
+                consumer: delivery.consumer.clone(),
+                delivered_at: delivery.delivered_at,
+                deliveries: delivery.deliveries,
+            })
+            .collect())
+    }
+
+    async fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> StorageResult<Vec<DeliveredEntry>> {
+        let mut entries = self.lock_key(key);
+        let (stream_entries, group) = stream_group(&mut entries, key, group)?;
+
+        let now = now_millis();
+        let min_idle = i64::try_from(options.min_idle).unwrap_or(i64::MAX);
+        if let Some(last_id) = options.last_id {
+            group.last_id = group.last_id.max(last_id);
+        }
+
+        let mut claimed = Vec::new();
+        for id in ids {
+            let Some(fields) = stream_entries.get(id) else {
+                group.pending.remove(id);
+                continue;
+            };
+            let delivery = match group.pending.entry(*id) {
+                MapEntry::Occupied(occupied) => {
+                    let delivery = occupied.into_mut();
+                    if now - delivery.delivered_at < min_idle {
+                        continue;
+                    }
+                    delivery
+                }
+                // FORCE adds entries that were never delivered to the list
+                MapEntry::Vacant(vacant) if options.force => vacant.insert(Delivery {
+                    consumer: consumer.to_string(),
+                    delivered_at: now,
+                    deliveries: 0,
+                }),
+                MapEntry::Vacant(_) => continue,
+            };
+            delivery.consumer = consumer.to_string();
+            delivery.delivered_at = options.delivered_at(now);
+            delivery.deliveries = options.deliveries(delivery.deliveries);
+            claimed.push(DeliveredEntry {
+                id: *id,
+                fields: Some(fields.clone()),
+            });
+        }
+        group.touch(consumer, now, !claimed.is_empty());
+        Ok(claimed)
+    }
+
+    async fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        start: StreamId,
+        count: usize,
+        options: &ClaimOptions,
+    ) -> StorageResult<AutoClaim> {
+        let mut entries = self.lock_key(key);
+        let (stream_entries, group) = stream_group(&mut entries, key, group)?;
+
+        let now = now_millis();
+        let min_idle = i64::try_from(options.min_idle).unwrap_or(i64::MAX);
+        let attempts = count.saturating_mul(10);
+        // One ID past the scan is kept to continue from next time
+        let candidates: Vec<StreamId> = group
+            .pending
+            .range(start..)
+            .take(attempts.saturating_add(1))
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut claimed = Vec::new();
+        let mut deleted = Vec::new();
+        let mut scanned = 0;
+        for id in &candidates {
+            if claimed.len() == count || scanned == attempts {
+                break;
+            }
+            scanned += 1;
+            if !stream_entries.contains_key(id) {
+                group.pending.remove(id);
+                deleted.push(*id);
+                continue;
+            }
+            let Some(delivery) = group.pending.get_mut(id) else {
+                continue;
+            };
+            if now - delivery.delivered_at < min_idle {
+                continue;
+            }
+            delivery.consumer = consumer.to_string();
+            delivery.delivered_at = now;
+            delivery.deliveries = options.deliveries(delivery.deliveries);
+            claimed.push(delivered(*id, stream_entries));
+        }
+        group.touch(consumer, now, !claimed.is_empty());
+        Ok(AutoClaim {
+            next: candidates.get(scanned).copied().unwrap_or(StreamId::MIN),
+            claimed,
+            deleted,
+        })
+    }
+
+    async fn xinfo_groups(&self, key: &str) -> StorageResult<Vec<GroupInfo>> {
+        let entries = self.lock_key(key);
+        let Some(stream) = get_stream(&entries, key)? else {
+            return Err(StorageError::InvalidValue("no such key"));
+        };
+
+        let info = stream.info();
+        Ok(stream
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let lag = match group.last_id.next() {
+                    Some(start) => stream.entries.range(start..).count() as u64,
+                    None => 0,
+                };
+                GroupInfo {
+                    name: name.clone(),
+                    consumers: group.consumers.len() as u64,
+                    pending: group.pending.len() as u64,
+                    last_id: group.last_id,
+                    entries_read: info.entries_read(group.last_id, lag),
+                    lag,
+                }
+            })
+            .collect())
+    }
+
+    async fn xinfo_consumers(&self, key: &str, group: &str) -> StorageResult<Vec<ConsumerInfo>> {
+        let mut entries = self.lock_key(key);
+        if get_stream(&entries, key)?.is_none() {
+            return Err(StorageError::InvalidValue("no such key"));
+        }
+        let (_, group) = stream_group(&mut entries, key, group)?;
+        Ok(group
+            .consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: group.pending_of(name),
+                seen_at: consumer.seen_at,
+                active_at: consumer.active_at,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{StreamIdSpec, StreamStorage};
+
+    async fn stream_with(storage: &MemoryStorage, ids: &[u64]) {
+        let fields = vec![(b"f".to_vec(), b"v".to_vec())];
+        for ms in ids {
+            let id = StreamIdSpec::Explicit(StreamId::new(*ms, 0));
+            storage.xadd("s", id, &fields, true, None).await.unwrap();
+        }
+    }
+
+    fn new_entries(key: &str) -> Vec<(String, Option<StreamId>)> {
+        vec![(key.to_string(), None)]
+    }
+
+    fn ids(entries: &[DeliveredEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.id.ms).collect()
+    }
+
+    #[tokio::test]
+    async fn test_xgroup_create() {
+        let storage = MemoryStorage::new();
+        let missing = storage.xgroup_create("s", "g", None, false).await;
+        assert!(matches!(missing, Err(StorageError::InvalidValue(_))));
+
+        storage.xgroup_create("s", "g", None, true).await.unwrap();
+        let busy = storage.xgroup_create("s", "g", None, false).await;
+        assert!(matches!(busy, Err(StorageError::BusyGroup)));
+
+        let setid = storage.xgroup_setid("s", "other", None).await;
+        assert!(matches!(setid, Err(StorageError::NoGroup { .. })));
+        assert!(storage.xgroup_destroy("s", "g").await.unwrap());
+        assert!(!storage.xgroup_destroy("s", "g").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_xreadgroup_pending() {
+        let storage = MemoryStorage::new();
+        stream_with(&storage, &[1, 2, 3]).await;
+        storage
+            .xgroup_create("s", "g", Some(StreamId::MIN), false)
+            .await
+            .unwrap();
+
+        let read = storage
+            .xreadgroup("g", "alice", &new_entries("s"), Some(2), false)
+            .await;
+        assert_eq!(ids(&read.unwrap()[0].1), [1, 2]);
+        let read = storage
+            .xreadgroup("g", "bob", &new_entries("s"), None, false)
+            .await;
+        assert_eq!(ids(&read.unwrap()[0].1), [3]);
+        let read = storage
+            .xreadgroup("g", "bob", &new_entries("s"), None, false)
+            .await;
+        assert!(read.unwrap().is_empty());
+
+        // Reading history delivers the consumer's pending entries again
+        storage.xdel("s", &[StreamId::new(1, 0)]).await.unwrap();
+        let history = vec![("s".to_string(), Some(StreamId::MIN))];
+        let read = storage
+            .xreadgroup("g", "alice", &history, None, false)
+            .await
+            .unwrap();
+        assert_eq!(ids(&read[0].1), [1, 2]);
+        assert_eq!(read[0].1[0].fields, None);
+
+        let summary = storage.xpending_summary("s", "g").await.unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(
+            summary.bounds,
+            Some((StreamId::new(1, 0), StreamId::new(3, 0)))
+        );
+        assert_eq!(summary.consumers, [("alice".into(), 2), ("bob".into(), 1)]);
+
+        let range = PendingRange {
+            start: StreamId::MIN,
+            end: StreamId::MAX,
+            count: 10,
+            consumer: Some("alice".into()),
+            min_idle: 0,
+        };
+        let pending = storage.xpending("s", "g", &range).await.unwrap();
+        assert_eq!(pending[1].deliveries, 2);
+
+        let acked = [StreamId::new(2, 0), StreamId::new(9, 0)];
+        assert_eq!(storage.xack("s", "g", &acked).await.unwrap(), 1);
+        assert_eq!(
+            storage.xgroup_delconsumer("s", "g", "alice").await.unwrap(),
+            1
+        );
+        assert_eq!(storage.xpending_summary("s", "g").await.unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_xclaim_xautoclaim() {
+        let storage = MemoryStorage::new();
+        stream_with(&storage, &[1, 2, 3]).await;
+        storage
+            .xgroup_create("s", "g", Some(StreamId::MIN), false)
+            .await
+            .unwrap();
+        storage
+            .xreadgroup("g", "alice", &new_entries("s"), None, false)
+            .await
+            .unwrap();
+
+        let options = ClaimOptions {
+            min_idle: 60_000,
+            ..ClaimOptions::default()
+        };
+        let ids_to_claim = [StreamId::new(1, 0)];
+        let claimed = storage
+            .xclaim("s", "g", "bob", &ids_to_claim, &options)
+            .await;
+        assert!(claimed.unwrap().is_empty());
+
+        let options = ClaimOptions::default();
+        let claimed = storage
+            .xclaim("s", "g", "bob", &ids_to_claim, &options)
+            .await;
+        assert_eq!(ids(&claimed.unwrap()), [1]);
+
+        storage.xdel("s", &[StreamId::new(2, 0)]).await.unwrap();
+        let auto = storage.xautoclaim("s", "g", "carol", StreamId::MIN, 1, &options);
+        let auto = auto.await.unwrap();
+        assert_eq!(ids(&auto.claimed), [1]);
+        assert_eq!(auto.next, StreamId::new(2, 0));
+
+        let auto = storage.xautoclaim("s", "g", "carol", auto.next, 10, &options);
+        let auto = auto.await.unwrap();
+        assert_eq!(ids(&auto.claimed), [3]);
+        assert_eq!(auto.deleted, [StreamId::new(2, 0)]);
+        assert_eq!(auto.next, StreamId::MIN);
+
+        let consumers = storage.xinfo_consumers("s", "g").await.unwrap();
+        let pending: Vec<u64> = consumers.iter().map(|c| c.pending).collect();
+        assert_eq!(pending, [0, 0, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_xinfo_groups() {
+        let storage = MemoryStorage::new();
+        stream_with(&storage, &[1, 2, 3]).await;
+        storage
+            .xgroup_create("s", "g", Some(StreamId::new(1, 0)), false)
+            .await
+            .unwrap();
+
+        let groups = storage.xinfo_groups("s").await.unwrap();
+        assert_eq!(groups[0].lag, 2);
+        assert_eq!(groups[0].entries_read, Some(1));
+        let missing = storage.xinfo_groups("missing").await;
+        assert!(matches!(missing, Err(StorageError::InvalidValue(_))));
+    }
+}
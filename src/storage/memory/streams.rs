@@ -0,0 +1,318 @@
+use crate::storage::memory::stream_groups::ConsumerGroup;
+use crate::storage::memory::{Entry, Keyspace, MemoryStorage, Value};
+use crate::storage::{
+    FieldPairs, StorageError, StorageResult, StreamEntry, StreamId, StreamIdSpec, StreamInfo,
+    StreamStorage, StreamTrim, StreamTrimBy, now_millis,
+};
+use std::collections::BTreeMap;
+
+#[derive(Default, Clone)]
+pub(super) struct Stream {
+    pub(super) entries: BTreeMap<StreamId, FieldPairs>,
+    pub(super) last_id: StreamId,
+    entries_added: u64,
+    max_deleted_id: StreamId,
+    pub(super) groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl Stream {
+    // Evicts entries from the head, returning how many were removed
+    fn trim(&mut self, trim: StreamTrim) -> usize {
+        let excess = match trim.by {
+            StreamTrimBy::MaxLen(max_len) => {
+                let max_len = usize::try_from(max_len).unwrap_or(usize::MAX);
+                self.entries.len().saturating_sub(max_len)
+            }
+            StreamTrimBy::MinId(min_id) => self.entries.range(..min_id).count(),
+        };
+        let evicted = excess.min(trim.limit.unwrap_or(usize::MAX));
+        for _ in 0..evicted {
+            self.entries.pop_first();
+        }
+        evicted
+    }
+
+    pub(super) fn info(&self) -> StreamInfo {
+        StreamInfo {
+            length: self.entries.len() as u64,
+            last_id: self.last_id,
+            entries_added: self.entries_added,
+            max_deleted_id: self.max_deleted_id,
+            groups: self.groups.len() as u64,
+        }
+    }
+}
+
+pub(super) fn get_stream<'a>(
+    entries: &'a Keyspace,
+    key: &str,
+) -> StorageResult<Option<&'a Stream>> {
+    match entries.get(key).map(|e| &e.value) {
+        None => Ok(None),
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+pub(super) fn get_stream_mut<'a>(
+    entries: &'a mut Keyspace,
+    key: &str,
+) -> StorageResult<Option<&'a mut Stream>> {
+    match entries.get_mut(key).map(|e| &mut e.value) {
+        None => Ok(None),
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+fn count(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn to_entry((id, fields): (&StreamId, &FieldPairs)) -> StreamEntry {
+    StreamEntry {
+        id: *id,
+        fields: fields.clone(),
+    }
+}
+
+impl StreamStorage for MemoryStorage {
+    async fn xadd(
+        &self,
+        key: &str,
+        id: StreamIdSpec,
+        fields: &[(Vec<u8>, Vec<u8>)],
+        create: bool,
+        trim: Option<StreamTrim>,
+    ) -> StorageResult<Option<StreamId>> {
+        let mut entries = self.lock_key(key);
+        if get_stream(&entries, key)?.is_none() {
+            if !create {
+                return Ok(None);
+            }
+            entries.insert(
+                key.to_string(),
+                Entry::new(Value::Stream(Stream::default())),
+            );
+        }
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Ok(None);
+        };
+
+        let now = u64::try_from(now_millis()).unwrap_or(0);
+        let id = id.resolve(stream.last_id, now)?;
+        stream.entries.insert(id, fields.to_vec());
+        stream.last_id = id;
+        stream.entries_added += 1;
+        if let Some(trim) = trim {
+            stream.trim(trim);
+        }
+        Ok(Some(id))
+    }
+
+    async fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> StorageResult<Vec<StreamEntry>> {
+        let entries = self.lock_key(key);
+        let Some(stream) = get_stream(&entries, key)? else {
+            return Ok(Vec::new());
+        };
+        if start > end {
+            return Ok(Vec::new());
+        }
+
+        let range = stream.entries.range(start..=end);
+        let limit = count.unwrap_or(usize::MAX);
+        Ok(if reverse {
+            range.rev().take(limit).map(to_entry).collect()
+        } else {
+            range.take(limit).map(to_entry).collect()
+        })
+    }
+
+    async fn xread(
+        &self,
+        streams: &[(String, StreamId)],
+        count: Option<usize>,
+    ) -> StorageResult<Vec<(String, Vec<StreamEntry>)>> {
+        let keys: Vec<String> = streams.iter().map(|(key, _)| key.clone()).collect();
+        let entries = self.lock_keys(&keys);
+
+        let mut result = Vec::new();
+        for (key, after) in streams {
+            let Some(stream) = get_stream(&entries, key)? else {
+                continue;
+            };
+            let Some(start) = after.next() else {
+                continue;
+            };
+            let read: Vec<StreamEntry> = stream
+                .entries
+                .range(start..)
+                .take(count.unwrap_or(usize::MAX))
+                .map(to_entry)
+                .collect();
+            if !read.is_empty() {
+                result.push((key.clone(), read));
+            }
+        }
+        Ok(result)
+    }
+
+    async fn xlen(&self, key: &str) -> StorageResult<i64> {
+        let entries = self.lock_key(key);
+        Ok(get_stream(&entries, key)?.map_or(0, |stream| count(stream.entries.len())))
+    }
+
+    async fn xdel(&self, key: &str, ids: &[StreamId]) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+
+        let mut deleted = 0;
+        for id in ids {
+            if stream.entries.remove(id).is_some() {
+                stream.max_deleted_id = stream.max_deleted_id.max(*id);
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn xtrim(&self, key: &str, trim: StreamTrim) -> StorageResult<i64> {
+        let mut entries = self.lock_key(key);
+        let Some(stream) = get_stream_mut(&mut entries, key)? else {
+            return Ok(0);
+        };
+        Ok(count(stream.trim(trim)))
+    }
+
+    async fn xinfo(&self, key: &str) -> StorageResult<Option<StreamInfo>> {
+        let entries = self.lock_key(key);
+        Ok(get_stream(&entries, key)?.map(Stream::info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{KeyStorage, SetOptions, StringStorage};
+
+    fn fields(pairs: &[(&str, &str)]) -> FieldPairs {
+        pairs
+            .iter()
+            .map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn explicit(ms: u64, seq: u64) -> StreamIdSpec {
+        StreamIdSpec::Explicit(StreamId::new(ms, seq))
+    }
+
+    #[tokio::test]
+    async fn test_xadd_ids() {
+        let storage = MemoryStorage::new();
+        let data = fields(&[("f", "v")]);
+
+        let added = storage.xadd("s", explicit(5, 1), &data, false, None).await;
+        assert_eq!(added.unwrap(), None);
+        let added = storage.xadd("s", explicit(5, 1), &data, true, None).await;
+        assert_eq!(added.unwrap(), Some(StreamId::new(5, 1)));
+        let added = storage
+            .xadd("s", StreamIdSpec::AutoSeq(5), &data, true, None)
+            .await;
+        assert_eq!(added.unwrap(), Some(StreamId::new(5, 2)));
+
+        let stale = storage.xadd("s", explicit(5, 2), &data, true, None).await;
+        assert!(matches!(stale, Err(StorageError::InvalidValue(_))));
+
+        let added = storage
+            .xadd("s", StreamIdSpec::Auto, &data, true, None)
+            .await;
+        assert!(added.unwrap().unwrap() > StreamId::new(5, 2));
+        assert_eq!(storage.xlen("s").await.unwrap(), 3);
+
+        storage
+            .set("str", b"x", SetOptions::default())
+            .await
+            .unwrap();
+        let wrong = storage
+            .xadd("str", StreamIdSpec::Auto, &data, true, None)
+            .await;
+        assert!(matches!(wrong, Err(StorageError::WrongType)));
+    }
+
+    #[tokio::test]
+    async fn test_xrange_xread() {
+        let storage = MemoryStorage::new();
+        for seq in 1..=4 {
+            let data = fields(&[("n", &seq.to_string())]);
+            storage
+                .xadd("s", explicit(1, seq), &data, true, None)
+                .await
+                .unwrap();
+        }
+
+        let range = storage.xrange("s", StreamId::new(1, 2), StreamId::MAX, None, false);
+        let ids: Vec<StreamId> = range.await.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(
+            ids,
+            [
+                StreamId::new(1, 2),
+                StreamId::new(1, 3),
+                StreamId::new(1, 4)
+            ]
+        );
+
+        let range = storage.xrange("s", StreamId::MIN, StreamId::MAX, Some(1), true);
+        assert_eq!(range.await.unwrap()[0].fields, fields(&[("n", "4")]));
+
+        let streams = vec![
+            ("missing".to_string(), StreamId::MIN),
+            ("s".to_string(), StreamId::new(1, 3)),
+        ];
+        let read = storage.xread(&streams, None).await.unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].1[0].id, StreamId::new(1, 4));
+    }
+
+    #[tokio::test]
+    async fn test_xdel_xtrim() {
+        let storage = MemoryStorage::new();
+        let data = fields(&[("f", "v")]);
+        for seq in 1..=5 {
+            storage
+                .xadd("s", explicit(1, seq), &data, true, None)
+                .await
+                .unwrap();
+        }
+
+        let ids = [StreamId::new(1, 3), StreamId::new(9, 9)];
+        assert_eq!(storage.xdel("s", &ids).await.unwrap(), 1);
+
+        let trim = StreamTrim {
+            by: StreamTrimBy::MaxLen(1),
+            limit: Some(2),
+        };
+        assert_eq!(storage.xtrim("s", trim).await.unwrap(), 2);
+        let trim = StreamTrim {
+            by: StreamTrimBy::MinId(StreamId::MAX),
+            limit: None,
+        };
+        assert_eq!(storage.xtrim("s", trim).await.unwrap(), 2);
+
+        // The stream and its last ID outlive its entries
+        let info = storage.xinfo("s").await.unwrap().unwrap();
+        assert_eq!(info.length, 0);
+        assert_eq!(info.last_id, StreamId::new(1, 5));
+        assert_eq!(info.max_deleted_id, StreamId::new(1, 3));
+        assert_eq!(info.entries_added, 5);
+        assert_eq!(storage.exists(&["s".to_string()]).await.unwrap(), 1);
+    }
+}
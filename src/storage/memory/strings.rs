@@ -0,0 +1,154 @@
+use crate::storage::memory::{Entry, MemoryStorage, Value};
+use crate::storage::{
+    SetCondition, SetExpiry, SetOptions, SetOutcome, StorageError, StorageResult, StringStorage,
+};
+
+impl StringStorage for MemoryStorage {
+    async fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        let entries = self.lock_key(key);
+        match entries.get(key).map(|e| &e.value) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(StorageError::WrongType),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], options: SetOptions) -> StorageResult<SetOutcome> {
+        let mut entries = self.lock_key(key);
+        let existing = entries.get(key);
+
+        // Values of other types are replaced without being reported back
+        let previous = match existing.map(|e| &e.value) {
+            Some(Value::String(value)) => Some(value.clone()),
+            _ => None,
+        };
+
+        let allowed = match options.condition {
+            SetCondition::Always => true,
+            SetCondition::IfNotExists => existing.is_none(),
+            SetCondition::IfExists => existing.is_some(),
+        };
+        if !allowed {
+            return Ok(SetOutcome {
+                written: false,
+                previous,
+            });
+        }
+
+        let expires_at = match options.expiry {
+            SetExpiry::Clear => None,
+            SetExpiry::Keep => existing.and_then(|e| e.expires_at),
+            SetExpiry::At(at) => Some(at),
+        };
+        let entry = Entry {
+            expires_at,
+            ..Entry::new(Value::String(value.to_vec()))
+        };
+        entries.insert(key.to_string(), entry);
+
+        Ok(SetOutcome {
+            written: true,
+            previous,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ExpireCondition, HashStorage, KeyStorage, now_millis};
+    use std::time::Duration;
+
+    async fn set(storage: &MemoryStorage, key: &str, value: &[u8]) {
+        storage
+            .set(key, value, SetOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_set() {
+        let storage = MemoryStorage::new();
+        assert_eq!(storage.get("foo").await.unwrap(), None);
+
+        set(&storage, "foo", b"bar").await;
+        assert_eq!(storage.get("foo").await.unwrap(), Some(b"bar".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_get_wrong_type() {
+        let storage = MemoryStorage::new();
+        let pairs = vec![(b"f".to_vec(), b"v".to_vec())];
+        storage.hset("h", &pairs).await.unwrap();
+
+        let got = storage.get("h").await;
+        assert!(matches!(got, Err(StorageError::WrongType)));
+
+        // SET replaces a value of any type
+        set(&storage, "h", b"x").await;
+        assert_eq!(storage.get("h").await.unwrap(), Some(b"x".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_set_conditions() {
+        let storage = MemoryStorage::new();
+        let nx = SetOptions {
+            condition: SetCondition::IfNotExists,
+            ..SetOptions::default()
+        };
+        let xx = SetOptions {
+            condition: SetCondition::IfExists,
+            ..SetOptions::default()
+        };
+
+        let outcome = storage.set("foo", b"1", xx).await.unwrap();
+        assert!(!outcome.written);
+
+        let outcome = storage.set("foo", b"1", nx).await.unwrap();
+        assert!(outcome.written);
+        assert_eq!(outcome.previous, None);
+
+        let outcome = storage.set("foo", b"2", nx).await.unwrap();
+        assert!(!outcome.written);
+        assert_eq!(outcome.previous, Some(b"1".to_vec()));
+
+        let outcome = storage.set("foo", b"3", xx).await.unwrap();
+        assert!(outcome.written);
+        assert_eq!(storage.get("foo").await.unwrap(), Some(b"3".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_set_expiry() {
+        let storage = MemoryStorage::new();
+        let expiring = SetOptions {
+            expiry: SetExpiry::At(now_millis() + 50),
+            ..SetOptions::default()
+        };
+        let keep = SetOptions {
+            expiry: SetExpiry::Keep,
+            ..SetOptions::default()
+        };
+
+        storage.set("foo", b"1", expiring).await.unwrap();
+        storage.set("foo", b"2", keep).await.unwrap();
+        assert_eq!(storage.get("foo").await.unwrap(), Some(b"2".to_vec()));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(storage.get("foo").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_set_clears_expiry() {
+        let storage = MemoryStorage::new();
+        set(&storage, "foo", b"bar").await;
+        let at = Some(now_millis() + 50);
+        storage
+            .expire("foo", at, ExpireCondition::Always)
+            .await
+            .unwrap();
+        set(&storage, "foo", b"baz").await;
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(storage.get("foo").await.unwrap(), Some(b"baz".to_vec()));
+    }
+}
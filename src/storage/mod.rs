@@ -0,0 +1,33 @@
+mod error;
+mod kind;
+mod memory;
+mod numeric;
+mod options;
+mod postgres;
+mod range;
+mod stream;
+mod time;
+mod traits;
+
+pub use error::StorageError;
+pub use kind::KeyKind;
+pub use memory::MemoryStorage;
+pub use numeric::format_float;
+pub use options::{
+    ExpireCondition, KeyExpiry, LexBound, ListEnd, ScoreBound, ScoreEnd, ScoreUpdate, SetCondition,
+    SetExpiry, SetOp, SetOptions, SetOutcome, ZAddOptions, ZAddOutcome, ZRange, ZRangeBy,
+};
+pub use postgres::PostgresStorage;
+pub use stream::{
+    AutoClaim, ClaimOptions, ConsumerInfo, DeliveredEntry, GroupInfo, PendingEntry, PendingRange,
+    PendingSummary, STREAM_REQUIRED, StreamEntry, StreamId, StreamIdSpec, StreamInfo, StreamTrim,
+    StreamTrimBy,
+};
+pub use time::now_millis;
+pub use traits::{
+    FieldPairs, FunctionLibrary, FunctionStorage, HashStorage, KeyStorage, LibraryFunction,
+    ListStorage, PubSubStorage, PublishedMessage, ScoredMembers, SetStorage, SortedSetStorage,
+    Storage, StreamGroupStorage, StreamStorage, StringStorage, TransactionStorage, watched_changed,
+};
+
+pub type StorageResult<T> = Result<T, StorageError>;
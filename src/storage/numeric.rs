@@ -0,0 +1,103 @@
+use crate::storage::{StorageError, StorageResult};
+
+// Parses a stored value as a 64-bit integer the way Redis does (no whitespace or '+')
+pub fn parse_int(bytes: &[u8]) -> Option<i64> {
+    if bytes.first() == Some(&b'+') {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+// Parses a stored value as a finite float
+pub fn parse_float(bytes: &[u8]) -> Option<f64> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    if text.trim() != text {
+        return None;
+    }
+    text.parse::<f64>().ok().filter(|f| f.is_finite())
+}
+
+// Formats a float in the shortest form that parses back to the same value
+pub fn format_float(value: f64) -> String {
+    format!("{value}")
+}
+
+// Adds `delta` to an optional stored integer (missing counts as zero)
+pub fn increment_int(
+    current: Option<&[u8]>,
+    delta: i64,
+    not_integer: &'static str,
+) -> StorageResult<i64> {
+    let current = match current {
+        Some(bytes) => parse_int(bytes).ok_or(StorageError::InvalidValue(not_integer))?,
+        None => 0,
+    };
+    current.checked_add(delta).ok_or(StorageError::InvalidValue(
+        "increment or decrement would overflow",
+    ))
+}
+
+// Adds `delta` to an optional stored float (missing counts as zero)
+pub fn increment_float(
+    current: Option<&[u8]>,
+    delta: f64,
+    not_float: &'static str,
+) -> StorageResult<f64> {
+    let current = match current {
+        Some(bytes) => parse_float(bytes).ok_or(StorageError::InvalidValue(not_float))?,
+        None => 0.0,
+    };
+    let result = current + delta;
+    if result.is_finite() {
+        Ok(result)
+    } else {
+        Err(StorageError::InvalidValue(
+            "increment would produce NaN or Infinity",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int(b"42"), Some(42));
+        assert_eq!(parse_int(b"-7"), Some(-7));
+        assert_eq!(parse_int(b"+7"), None);
+        assert_eq!(parse_int(b" 7"), None);
+        assert_eq!(parse_int(b"1.5"), None);
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(parse_float(b"1.5"), Some(1.5));
+        assert_eq!(parse_float(b"10"), Some(10.0));
+        assert_eq!(parse_float(b"inf"), None);
+        assert_eq!(parse_float(b"abc"), None);
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(10.5), "10.5");
+        assert_eq!(format_float(5.0), "5");
+        assert_eq!(format_float(-0.25), "-0.25");
+    }
+
+    #[test]
+    fn test_increment_int() {
+        assert_eq!(increment_int(None, 5, "not int").unwrap(), 5);
+        assert_eq!(increment_int(Some(b"10"), -3, "not int").unwrap(), 7);
+        assert!(increment_int(Some(b"x"), 1, "not int").is_err());
+        assert!(increment_int(Some(b"9223372036854775807"), 1, "not int").is_err());
+    }
+
+    #[test]
+    fn test_increment_float() {
+        let value = increment_float(Some(b"10.5"), 0.1, "not float").unwrap();
+        assert_eq!(format_float(value), "10.6");
+        assert!(increment_float(Some(b"x"), 1.0, "not float").is_err());
+        assert!(increment_float(None, f64::MAX * 2.0, "not float").is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::storage::{StorageError, StorageResult};
+
+// Condition under which SET writes its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    Always,
+    IfNotExists,
+    IfExists,
+}
+
+// How SET treats the expiry of the key it writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetExpiry {
+    Clear,
+    Keep,
+    At(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetOptions {
+    pub condition: SetCondition,
+    pub expiry: SetExpiry,
+}
+
+impl Default for SetOptions {
+    fn default() -> Self {
+        SetOptions {
+            condition: SetCondition::Always,
+            expiry: SetExpiry::Clear,
+        }
+    }
+}
+
+// Result of a SET, including the value it replaced (if any)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetOutcome {
+    pub written: bool,
+    pub previous: Option<Vec<u8>>,
+}
+
+// Condition under which the EXPIRE family updates an expiry (NX, XX, GT, LT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpireCondition {
+    Always,
+    IfNoExpiry,
+    IfHasExpiry,
+    IfGreater,
+    IfLess,
+    IfHasExpiryAndLess,
+}
+
+impl ExpireCondition {
+    // Keys without an expiry are treated as having an infinite TTL when comparing
+    pub fn allows(self, current: Option<i64>, new: Option<i64>) -> bool {
+        let infinite = |at: Option<i64>| at.unwrap_or(i64::MAX);
+        match self {
+            ExpireCondition::Always => true,
+            ExpireCondition::IfNoExpiry => current.is_none(),
+            ExpireCondition::IfHasExpiry => current.is_some(),
+            ExpireCondition::IfGreater => current.is_some() && infinite(new) > infinite(current),
+            ExpireCondition::IfLess => infinite(new) < infinite(current),
+            ExpireCondition::IfHasExpiryAndLess => {
+                current.is_some() && infinite(new) < infinite(current)
+            }
+        }
+    }
+}
+
+// Expiry state of a key as reported by TTL and friends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyExpiry {
+    Missing,
+    Persistent,
+    At(i64),
+}
+
+// Which end of a list an element is pushed to or popped from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+// How SUNION, SINTER and SDIFF combine the members of their sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOp {
+    Union,
+    Intersect,
+    Difference,
+}
+
+// Which scores ZADD accepts when updating an existing member (GT, LT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreUpdate {
+    Always,
+    IfGreater,
+    IfLess,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZAddOptions {
+    pub condition: SetCondition,
+    pub update: ScoreUpdate,
+}
+
+impl Default for ZAddOptions {
+    fn default() -> Self {
+        ZAddOptions {
+            condition: SetCondition::Always,
+            update: ScoreUpdate::Always,
+        }
+    }
+}
+
+impl ZAddOptions {
+    // Returns the score to store for a member given its current one, or `None` if the
+    // options skip it. With `increment` the score is added to the current one
+    pub fn resolve(
+        self,
+        current: Option<f64>,
+        score: f64,
+        increment: bool,
+    ) -> StorageResult<Option<f64>> {
+        match (self.condition, current) {
+            (SetCondition::IfNotExists, Some(_)) | (SetCondition::IfExists, None) => Ok(None),
+            (_, None) => Ok(Some(score)),
+            (_, Some(current)) => {
+                let new = if increment { current + score } else { score };
+                if new.is_nan() {
+                    return Err(StorageError::InvalidValue(
+                        "resulting score is not a number (NaN)",
+                    ));
+                }
+                let allowed = match self.update {
+                    ScoreUpdate::Always => true,
+                    ScoreUpdate::IfGreater => new > current,
+                    ScoreUpdate::IfLess => new < current,
+                };
+                Ok(allowed.then_some(new))
+            }
+        }
+    }
+}
+
+// How many members ZADD added and how many existing scores it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ZAddOutcome {
+    pub added: i64,
+    pub changed: i64,
+}
+
+// Bound of a score range, where -inf and +inf are infinite floats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl ScoreBound {
+    pub fn above(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(min) => score >= min,
+            ScoreBound::Exclusive(min) => score > min,
+        }
+    }
+
+    pub fn below(self, score: f64) -> bool {
+        match self {
+            ScoreBound::Inclusive(max) => score <= max,
+            ScoreBound::Exclusive(max) => score < max,
+        }
+    }
+}
+
+// Bound of a lexicographic range, where `Min` and `Max` are written `-` and `+`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(Vec<u8>),
+    Exclusive(Vec<u8>),
+}
+
+impl LexBound {
+    pub fn above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(min) => member >= min.as_slice(),
+            LexBound::Exclusive(min) => member > min.as_slice(),
+        }
+    }
+
+    pub fn below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(max) => member <= max.as_slice(),
+            LexBound::Exclusive(max) => member < max.as_slice(),
+        }
+    }
+}
+
+// Members of a sorted set selected by rank (as with LRANGE), score or member
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZRangeBy {
+    Rank { start: i64, stop: i64 },
+    Score { min: ScoreBound, max: ScoreBound },
+    Lex { min: LexBound, max: LexBound },
+}
+
+// A ZRANGE query, where score and lex ranges can be paged with an offset and count
+// Ranks count from the highest score when `reverse` is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRange {
+    pub by: ZRangeBy,
+    pub reverse: bool,
+    pub offset: usize,
+    pub count: Option<usize>,
+}
+
+impl ZRange {
+    pub fn new(by: ZRangeBy) -> Self {
+        ZRange {
+            by,
+            reverse: false,
+            offset: 0,
+            count: None,
+        }
+    }
+}
+
+// Which end of a sorted set ZPOPMIN and ZPOPMAX take members from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreEnd {
+    Min,
+    Max,
+}
@@ -0,0 +1,189 @@
+use crate::storage::postgres::PostgresStorage;
+use crate::storage::{StorageResult, TransactionStorage, watched_changed};
+use deadpool_postgres::{GenericClient, Transaction};
+use tokio::sync::Mutex;
+
+// Each command runs within a savepoint of the transaction, so a failed command leaves
+// the rest of the transaction intact
+const SAVEPOINT: &str = "exec_command";
+
+impl TransactionStorage for PostgresStorage {
+    type Transaction<'a> = PostgresStorage<Mutex<Transaction<'a>>>;
+
+    async fn exec<I: IntoIterator, T>(
+        &self,
+        watched: &[(String, Option<i64>)],
+        items: I,
+        atomic: bool,
+        mut run: impl AsyncFnMut(&Self::Transaction<'_>, I::Item) -> StorageResult<T>,
+    ) -> StorageResult<Option<Vec<StorageResult<T>>>> {
+        let mut client = self.client().await?;
+        let transaction = client.transaction().await?;
+        let storage = PostgresStorage {
+            connections: Mutex::new(transaction),
+            config: self.config.clone(),
+            node_id: self.node_id,
+        };
+        if watched_changed(&storage, watched).await? {
+            return Ok(None);
+        }
+
+        let mut results = Vec::new();
+        for item in items {
+            if atomic {
+                // Dropping the transaction rolls it back
+                results.push(Ok(run(&storage, item).await?));
+                continue;
+            }
+            let client = storage.client().await?;
+            client
+                .batch_execute(&format!("SAVEPOINT {SAVEPOINT}"))
+                .await?;
+            drop(client);
+
+            let result = run(&storage, item).await;
+            let statement = if result.is_ok() {
+                format!("RELEASE SAVEPOINT {SAVEPOINT}")
+            } else {
+                format!("ROLLBACK TO SAVEPOINT {SAVEPOINT}")
+            };
+            storage.client().await?.batch_execute(&statement).await?;
+            results.push(result);
+        }
+
+        storage.connections.into_inner().commit().await?;
+        Ok(Some(results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::postgres::tests::connect;
+    use crate::storage::{KeyStorage, SetOptions, StorageError, StringStorage, TransactionStorage};
+    use std::time::{Duration, Instant};
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_watch_missing_key() {
+        let Some(storage) = connect().await else {
+            return;
+        };
+        let options = SetOptions::default();
+        let keys = vec!["watch:missing".to_string(), "watch:written".to_string()];
+        storage.del(&keys).await.unwrap();
+        let watched = vec![(keys[0].clone(), None)];
+
+        // A watched key created by another node while EXEC runs waits for it to commit
+        let (started_tx, started_rx) = oneshot::channel();
+        let mut started_tx = Some(started_tx);
+        let exec = storage.exec(&watched, [()], true, async |storage, ()| {
+            storage.set("watch:written", b"1", options).await?;
+            if let Some(started_tx) = started_tx.take() {
+                started_tx.send(Instant::now()).unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(())
+        });
+        let create = async {
+            let started = started_rx.await.unwrap();
+            storage.set("watch:missing", b"x", options).await.unwrap();
+            started.elapsed()
+        };
+        let (executed, waited) = tokio::join!(exec, create);
+        assert!(executed.unwrap().is_some());
+        assert!(waited >= Duration::from_millis(200), "waited {waited:?}");
+
+        // Created once EXEC committed, the key fails the next EXEC watching it missing
+        let exec = storage.exec(&watched, [()], true, async |_, ()| Ok(()));
+        assert!(exec.await.unwrap().is_none());
+        storage.del(&keys).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let Some(storage) = connect().await else {
+            return;
+        };
+        let options = SetOptions::default();
+        let keys = vec!["rollback:kept".to_string(), "rollback:created".to_string()];
+        storage.del(&keys).await.unwrap();
+        storage.set("rollback:kept", b"1", options).await.unwrap();
+
+        // A failure in an atomic EXEC undoes everything before it
+        let failed = storage.exec(&[], [()], true, async |storage, ()| {
+            storage.set("rollback:kept", b"2", options).await?;
+            storage.set("rollback:created", b"3", options).await?;
+            Err::<(), _>(StorageError::WrongType)
+        });
+        assert!(matches!(failed.await, Err(StorageError::WrongType)));
+        assert_eq!(
+            storage.get("rollback:kept").await.unwrap(),
+            Some(b"1".to_vec())
+        );
+        assert_eq!(storage.get("rollback:created").await.unwrap(), None);
+
+        // Otherwise only the failed command is undone
+        let commands = [
+            ("rollback:kept", b"4", false),
+            ("rollback:created", b"5", true),
+        ];
+        let exec = storage.exec(&[], commands, false, async |storage, (key, value, fail)| {
+            storage.set(key, value, options).await?;
+            if fail {
+                return Err(StorageError::WrongType);
+            }
+            Ok(())
+        });
+        let results = exec.await.unwrap().unwrap();
+        assert!(matches!(
+            results[..],
+            [Ok(()), Err(StorageError::WrongType)]
+        ));
+        assert_eq!(
+            storage.get("rollback:kept").await.unwrap(),
+            Some(b"4".to_vec())
+        );
+        assert_eq!(storage.get("rollback:created").await.unwrap(), None);
+        storage.del(&keys).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_invalidation() {
+        let Some(storage) = connect().await else {
+            return;
+        };
+        let options = SetOptions::default();
+        let keys = vec!["watch:kept".to_string(), "watch:changed".to_string()];
+        storage.del(&keys).await.unwrap();
+        storage.set("watch:kept", b"1", options).await.unwrap();
+        storage.set("watch:changed", b"1", options).await.unwrap();
+        let versions = storage.versions(&keys).await.unwrap();
+        let watched: Vec<_> = keys.iter().cloned().zip(versions).collect();
+
+        // Reads leave the watched keys valid
+        storage.get("watch:changed").await.unwrap();
+        let exec = storage.exec(&watched, [()], true, async |_, ()| Ok(()));
+        assert!(exec.await.unwrap().is_some());
+
+        // Rewriting a key, even with its own value, fails the EXEC without running it
+        storage.set("watch:changed", b"1", options).await.unwrap();
+        let exec = storage.exec(&watched, [()], true, async |storage, ()| {
+            storage.set("watch:kept", b"2", options).await
+        });
+        assert!(exec.await.unwrap().is_none());
+        assert_eq!(
+            storage.get("watch:kept").await.unwrap(),
+            Some(b"1".to_vec())
+        );
+
+        // As does deleting one
+        let watched = vec![(
+            keys[1].clone(),
+            storage.versions(&keys[1..]).await.unwrap()[0],
+        )];
+        storage.del(&keys[1..]).await.unwrap();
+        let exec = storage.exec(&watched, [()], true, async |_, ()| Ok(()));
+        assert!(exec.await.unwrap().is_none());
+        storage.del(&keys).await.unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use crate::storage::postgres::{Connect, PostgresStorage};
+use crate::storage::{
+    FunctionLibrary, FunctionStorage, LibraryFunction, StorageError, StorageResult,
+};
+use deadpool_postgres::GenericClient;
+use tokio_postgres::Row;
+
+// Libraries with their functions, one row per function (every library has at least one)
+const SELECT_LIBRARIES: &str = "
+    SELECT l.name, l.code, f.name, f.description, f.flags
+    FROM function_libraries l JOIN functions f ON f.library = l.name";
+
+// Function names are unique across libraries through the primary key of `functions`
+impl<C: Connect> FunctionStorage for PostgresStorage<C> {
+    async fn load_library(&self, library: &FunctionLibrary, replace: bool) -> StorageResult<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let inserted = tx
+            .execute(
+                "INSERT INTO function_libraries (name, code) VALUES ($1, $2)
+                 ON CONFLICT (name) DO NOTHING",
+                &[&library.name, &library.code],
+            )
+            .await?;
+        if inserted == 0 {
+            if !replace {
+                return Err(StorageError::LibraryExists(library.name.clone()));
+            }
+            tx.execute(
+                "UPDATE function_libraries SET code = $2 WHERE name = $1",
+                &[&library.name, &library.code],
+            )
+            .await?;
+            tx.execute("DELETE FROM functions WHERE library = $1", &[&library.name])
+                .await?;
+        }
+
+        for function in &library.functions {
+            let inserted = tx
+                .execute(
+                    "INSERT INTO functions (name, library, description, flags)
+                     VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (name) DO NOTHING",
+                    &[
+                        &function.name,
+                        &library.name,
+                        &function.description,
+                        &function.flags,
+                    ],
+                )
+                .await?;
+            if inserted == 0 {
+                return Err(StorageError::FunctionExists(function.name.clone()));
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_library(&self, name: &str) -> StorageResult<bool> {
+        let client = self.client().await?;
+        let deleted = client
+            .execute("DELETE FROM function_libraries WHERE name = $1", &[&name])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn flush_libraries(&self) -> StorageResult<()> {
+        let client = self.client().await?;
+        client
+            .execute("DELETE FROM function_libraries", &[])
+            .await?;
+        Ok(())
+    }
+
+    async fn libraries(&self) -> StorageResult<Vec<FunctionLibrary>> {
+        let client = self.client().await?;
+        let rows = client
+            .query(&format!("{SELECT_LIBRARIES} ORDER BY l.name, f.name"), &[])
+            .await?;
+        Ok(group_libraries(&rows))
+    }
+
+    async fn function_library(&self, function: &str) -> StorageResult<Option<FunctionLibrary>> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "{SELECT_LIBRARIES}
+                     WHERE l.name = (SELECT library FROM functions WHERE name = $1)
+                     ORDER BY f.name"
+                ),
+                &[&function],
+            )
+            .await?;
+        Ok(group_libraries(&rows).pop())
+    }
+}
+
+// Collects the rows of consecutive functions of the same library into it
+fn group_libraries(rows: &[Row]) -> Vec<FunctionLibrary> {
+    let mut libraries: Vec<FunctionLibrary> = Vec::new();
+    for row in rows {
+        let name: String = row.get(0);
+        if libraries.last().is_none_or(|library| library.name != name) {
+            libraries.push(FunctionLibrary {
+                name,
+                code: row.get(1),
+                functions: Vec::new(),
+            });
+        }
+        let library = libraries.last_mut().expect("a library was just pushed");
+        library.functions.push(LibraryFunction {
+            name: row.get(2),
+            description: row.get(3),
+            flags: row.get(4),
+        });
+    }
+    libraries
+}
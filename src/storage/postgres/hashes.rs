@@ -0,0 +1,217 @@
+use crate::glob::glob_match;
+use crate::storage::numeric::{format_float, increment_float, increment_int};
+use crate::storage::postgres::{
+    Connect, PostgresStorage, check_kind, delete_if_empty, lock_kind, lock_or_create,
+};
+use crate::storage::{FieldPairs, HashStorage, KeyKind, StorageResult};
+use deadpool_postgres::GenericClient;
+use std::collections::BTreeMap;
+
+// Reads a field with its row locked so increments are atomic across connections
+async fn lock_field(
+    client: &impl GenericClient,
+    key: &str,
+    field: &[u8],
+) -> StorageResult<Option<Vec<u8>>> {
+    let row = client
+        .query_opt(
+            "SELECT value FROM hashes WHERE key = $1 AND field = $2 FOR UPDATE",
+            &[&key, &field],
+        )
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+async fn write_field(
+    client: &impl GenericClient,
+    key: &str,
+    field: &[u8],
+    value: &[u8],
+) -> StorageResult<()> {
+    client
+        .execute(
+            "INSERT INTO hashes (key, field, value) VALUES ($1, $2, $3)
+             ON CONFLICT (key, field) DO UPDATE SET value = EXCLUDED.value",
+            &[&key, &field, &value],
+        )
+        .await?;
+    Ok(())
+}
+
+impl<C: Connect> HashStorage for PostgresStorage<C> {
+    async fn hset(&self, key: &str, pairs: &[(Vec<u8>, Vec<u8>)]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_or_create(&tx, key, KeyKind::Hash).await?;
+
+        // A single upsert cannot touch the same row twice, so the last value wins
+        let unique: BTreeMap<&[u8], &[u8]> = pairs
+            .iter()
+            .map(|(field, value)| (field.as_slice(), value.as_slice()))
+            .collect();
+        let fields: Vec<&[u8]> = unique.keys().copied().collect();
+        let values: Vec<&[u8]> = unique.values().copied().collect();
+
+        // `xmax` is only zero for rows that were inserted rather than updated
+        let row = tx
+            .query_one(
+                "WITH written AS (
+                    INSERT INTO hashes (key, field, value)
+                    SELECT $1, field, value FROM unnest($2::bytea[], $3::bytea[])
+                        AS pairs(field, value)
+                    ON CONFLICT (key, field) DO UPDATE SET value = EXCLUDED.value
+                    RETURNING xmax = 0 AS inserted
+                 )
+                 SELECT count(*) FROM written WHERE inserted",
+                &[&key, &fields, &values],
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(row.get(0))
+    }
+
+    async fn hsetnx(&self, key: &str, field: &[u8], value: &[u8]) -> StorageResult<bool> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_or_create(&tx, key, KeyKind::Hash).await?;
+
+        let inserted = tx
+            .execute(
+                "INSERT INTO hashes (key, field, value) VALUES ($1, $2, $3)
+                 ON CONFLICT (key, field) DO NOTHING",
+                &[&key, &field, &value],
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(inserted > 0)
+    }
+
+    async fn hmget(&self, key: &str, fields: &[Vec<u8>]) -> StorageResult<Vec<Option<Vec<u8>>>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Hash).await? {
+            return Ok(vec![None; fields.len()]);
+        }
+
+        let rows = client
+            .query(
+                "SELECT hashes.value FROM unnest($2::bytea[]) WITH ORDINALITY
+                    AS requested(field, position)
+                 LEFT JOIN hashes ON hashes.key = $1 AND hashes.field = requested.field
+                 ORDER BY requested.position",
+                &[&key, &fields],
+            )
+            .await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn hgetall(&self, key: &str) -> StorageResult<FieldPairs> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Hash).await? {
+            return Ok(Vec::new());
+        }
+
+        let rows = client
+            .query(
+                "SELECT field, value FROM hashes WHERE key = $1 ORDER BY field",
+                &[&key],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    async fn hlen(&self, key: &str) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Hash).await? {
+            return Ok(0);
+        }
+
+        let row = client
+            .query_one("SELECT count(*) FROM hashes WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn hdel(&self, key: &str, fields: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Hash).await? {
+            return Ok(0);
+        }
+
+        let deleted = tx
+            .execute(
+                "DELETE FROM hashes WHERE key = $1 AND field = ANY($2)",
+                &[&key, &fields],
+            )
+            .await?;
+        // An empty hash is removed along with its key
+        delete_if_empty(&tx, key, KeyKind::Hash).await?;
+        tx.commit().await?;
+        Ok(i64::try_from(deleted).unwrap_or(i64::MAX))
+    }
+
+    async fn hincrby(&self, key: &str, field: &[u8], delta: i64) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_or_create(&tx, key, KeyKind::Hash).await?;
+
+        // Errors roll back the transaction, including a key created above
+        let current = lock_field(&tx, key, field).await?;
+        let value = increment_int(current.as_deref(), delta, "hash value is not an integer")?;
+        write_field(&tx, key, field, value.to_string().as_bytes()).await?;
+        tx.commit().await?;
+        Ok(value)
+    }
+
+    async fn hincrbyfloat(&self, key: &str, field: &[u8], delta: f64) -> StorageResult<f64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_or_create(&tx, key, KeyKind::Hash).await?;
+
+        let current = lock_field(&tx, key, field).await?;
+        let value = increment_float(current.as_deref(), delta, "hash value is not a float")?;
+        write_field(&tx, key, field, format_float(value).as_bytes()).await?;
+        tx.commit().await?;
+        Ok(value)
+    }
+
+    async fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> StorageResult<(u64, FieldPairs)> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Hash).await? {
+            return Ok((0, Vec::new()));
+        }
+        let offset = i64::try_from(cursor).unwrap_or(i64::MAX);
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+
+        // Fields are visited in sorted order so the cursor is a stable offset
+        let rows = client
+            .query(
+                "SELECT field, value FROM hashes WHERE key = $1
+                 ORDER BY field OFFSET $2 LIMIT $3",
+                &[&key, &offset, &limit],
+            )
+            .await?;
+
+        let next_cursor = if rows.len() < count {
+            0
+        } else {
+            cursor + rows.len() as u64
+        };
+
+        let pairs = rows
+            .into_iter()
+            .map(|row| (row.get::<_, Vec<u8>>(0), row.get::<_, Vec<u8>>(1)))
+            .filter(|(field, _)| pattern.is_none_or(|p| glob_match(p, field)))
+            .collect();
+        Ok((next_cursor, pairs))
+    }
+}
@@ -0,0 +1,181 @@
+use crate::glob::glob_match;
+use crate::storage::postgres::{Connect, PostgresStorage, expire_keys, parse_kind};
+use crate::storage::{ExpireCondition, KeyExpiry, KeyKind, KeyStorage, StorageResult, now_millis};
+use deadpool_postgres::GenericClient;
+use std::collections::HashMap;
+
+// A key's version is drawn from a sequence on every write, so a key that is deleted and
+// created again never returns to a version it had before
+impl<C: Connect> KeyStorage for PostgresStorage<C> {
+    type Version = Option<i64>;
+
+    async fn del(&self, keys: &[String]) -> StorageResult<i64> {
+        let client = self.client().await?;
+
+        // Expired rows are removed as well but only live keys are counted
+        let row = client
+            .query_one(
+                "WITH deleted AS (
+                    DELETE FROM kv WHERE key = ANY($1) RETURNING expires_at
+                 )
+                 SELECT count(*) FROM deleted WHERE expires_at IS NULL OR expires_at > $2",
+                &[&keys, &now_millis()],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn exists(&self, keys: &[String]) -> StorageResult<i64> {
+        let client = self.client().await?;
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        expire_keys(&*client, &key_refs).await?;
+
+        let row = client
+            .query_one(
+                "SELECT count(*) FROM unnest($1::text[]) AS requested(key)
+                 JOIN kv USING (key)",
+                &[&keys],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn key_type(&self, key: &str) -> StorageResult<Option<KeyKind>> {
+        let client = self.client().await?;
+        expire_keys(&*client, &[key]).await?;
+
+        let row = client
+            .query_opt("SELECT kind FROM kv WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.map(|row| parse_kind(row.get(0))))
+    }
+
+    async fn expire(
+        &self,
+        key: &str,
+        expires_at: Option<i64>,
+        condition: ExpireCondition,
+    ) -> StorageResult<bool> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        expire_keys(&tx, &[key]).await?;
+
+        let current: Option<i64> = match tx
+            .query_opt(
+                "SELECT expires_at FROM kv WHERE key = $1 FOR UPDATE",
+                &[&key],
+            )
+            .await?
+        {
+            Some(row) => row.get(0),
+            None => return Ok(false),
+        };
+        if !condition.allows(current, expires_at) {
+            return Ok(false);
+        }
+
+        if expires_at.is_some_and(|at| at <= now_millis()) {
+            tx.execute("DELETE FROM kv WHERE key = $1", &[&key]).await?;
+        } else {
+            tx.execute(
+                "UPDATE kv SET expires_at = $2 WHERE key = $1",
+                &[&key, &expires_at],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn expire_time(&self, key: &str) -> StorageResult<KeyExpiry> {
+        let client = self.client().await?;
+        expire_keys(&*client, &[key]).await?;
+
+        let row = client
+            .query_opt("SELECT expires_at FROM kv WHERE key = $1", &[&key])
+            .await?;
+        Ok(match row.map(|row| row.get::<_, Option<i64>>(0)) {
+            None => KeyExpiry::Missing,
+            Some(None) => KeyExpiry::Persistent,
+            Some(Some(at)) => KeyExpiry::At(at),
+        })
+    }
+
+    async fn purge_expired(&self, limit: usize) -> StorageResult<usize> {
+        let client = self.client().await?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+        // Rows locked by in-flight commands (or other nodes sweeping) are skipped
+        let deleted = client
+            .execute(
+                "DELETE FROM kv WHERE key IN (
+                    SELECT key FROM kv WHERE expires_at <= $1
+                    LIMIT $2 FOR UPDATE SKIP LOCKED
+                 )",
+                &[&now_millis(), &limit],
+            )
+            .await?;
+        Ok(usize::try_from(deleted).unwrap_or(usize::MAX))
+    }
+
+    async fn versions(&self, keys: &[String]) -> StorageResult<Vec<Option<i64>>> {
+        let client = self.client().await?;
+        let key_refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        expire_keys(&*client, &key_refs).await?;
+
+        // Locking the rows holds writers on other servers off until EXEC commits, and
+        // the advisory locks hold off those creating the keys that are missing
+        client
+            .execute(
+                "SELECT pg_advisory_xact_lock(hashtextextended(key, 0))
+                 FROM (SELECT DISTINCT key FROM unnest($1::text[]) AS key ORDER BY key) AS keys",
+                &[&keys],
+            )
+            .await?;
+        let rows = client
+            .query(
+                "SELECT key, version FROM kv WHERE key = ANY($1) ORDER BY key FOR UPDATE",
+                &[&keys],
+            )
+            .await?;
+        let versions: HashMap<String, i64> =
+            rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        Ok(keys.iter().map(|key| versions.get(key).copied()).collect())
+    }
+
+    async fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        kind: Option<KeyKind>,
+        count: usize,
+    ) -> StorageResult<(u64, Vec<String>)> {
+        let client = self.client().await?;
+        let offset = i64::try_from(cursor).unwrap_or(i64::MAX);
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+
+        // Keys are visited in sorted order so the cursor is a stable offset
+        let rows = client
+            .query(
+                "SELECT key, kind FROM kv
+                 WHERE expires_at IS NULL OR expires_at > $1
+                 ORDER BY key OFFSET $2 LIMIT $3",
+                &[&now_millis(), &offset, &limit],
+            )
+            .await?;
+
+        let next_cursor = if rows.len() < count {
+            0
+        } else {
+            cursor + rows.len() as u64
+        };
+
+        let keys = rows
+            .into_iter()
+            .filter(|row| kind.is_none_or(|kind| parse_kind(row.get(1)) == kind))
+            .map(|row| row.get::<_, String>(0))
+            .filter(|k| pattern.is_none_or(|p| glob_match(p.as_bytes(), k.as_bytes())))
+            .collect();
+        Ok((next_cursor, keys))
+    }
+}
@@ -0,0 +1,306 @@
+use crate::storage::postgres::{
+    Connect, PostgresStorage, check_kind, delete_if_empty, lock_keys, lock_kind, lock_or_create,
+};
+use crate::storage::{KeyKind, ListEnd, ListStorage, StorageError, StorageResult};
+use deadpool_postgres::GenericClient;
+
+// Elements are ordered by position, which only has to be increasing along the list
+// Pushes extend the positions outwards from either end, inserts shift the elements after
+// the pivot, and `idx` numbers the elements from zero for index based commands
+const NUMBERED: &str = "
+    SELECT position, value,
+        row_number() OVER (ORDER BY position) - 1 AS idx,
+        count(*) OVER () AS len
+    FROM lists WHERE key = $1
+";
+
+// Matches the numbered elements between the inclusive indexes in `$2` and `$3`
+const IN_RANGE: &str = "
+    idx >= CASE WHEN $2::bigint < 0 THEN len + $2 ELSE $2 END
+    AND idx <= CASE WHEN $3::bigint < 0 THEN len + $3 ELSE $3 END
+";
+
+async fn list_len(client: &impl GenericClient, key: &str) -> StorageResult<i64> {
+    let row = client
+        .query_one("SELECT count(*) FROM lists WHERE key = $1", &[&key])
+        .await?;
+    Ok(row.get(0))
+}
+
+async fn push_values(
+    client: &impl GenericClient,
+    key: &str,
+    values: &[Vec<u8>],
+    end: ListEnd,
+) -> StorageResult<()> {
+    let row = client
+        .query_one(
+            "SELECT min(position), max(position) FROM lists WHERE key = $1",
+            &[&key],
+        )
+        .await?;
+    let (first, last): (Option<i64>, Option<i64>) = (row.get(0), row.get(1));
+
+    let (statement, base) = match end {
+        ListEnd::Left => (
+            "INSERT INTO lists (key, position, value)
+             SELECT $1, $2 - ordinality, value
+             FROM unnest($3::bytea[]) WITH ORDINALITY AS pushed(value, ordinality)",
+            first.unwrap_or(0),
+        ),
+        ListEnd::Right => (
+            "INSERT INTO lists (key, position, value)
+             SELECT $1, $2 + ordinality, value
+             FROM unnest($3::bytea[]) WITH ORDINALITY AS pushed(value, ordinality)",
+            last.unwrap_or(-1),
+        ),
+    };
+    client.execute(statement, &[&key, &base, &values]).await?;
+    Ok(())
+}
+
+async fn pop_values(
+    client: &impl GenericClient,
+    key: &str,
+    end: ListEnd,
+    count: usize,
+) -> StorageResult<Vec<Vec<u8>>> {
+    let statement = match end {
+        ListEnd::Left => {
+            "DELETE FROM lists WHERE key = $1 AND position IN (
+                SELECT position FROM lists WHERE key = $1 ORDER BY position LIMIT $2
+             )
+             RETURNING position, value"
+        }
+        ListEnd::Right => {
+            "DELETE FROM lists WHERE key = $1 AND position IN (
+                SELECT position FROM lists WHERE key = $1 ORDER BY position DESC LIMIT $2
+             )
+             RETURNING position, value"
+        }
+    };
+    let limit = i64::try_from(count).unwrap_or(i64::MAX);
+    let mut rows: Vec<(i64, Vec<u8>)> = client
+        .query(statement, &[&key, &limit])
+        .await?
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+
+    // RETURNING has no defined order, so restore the order elements were popped in
+    rows.sort_unstable_by_key(|(position, _)| *position);
+    if end == ListEnd::Right {
+        rows.reverse();
+    }
+    Ok(rows.into_iter().map(|(_, value)| value).collect())
+}
+
+impl<C: Connect> ListStorage for PostgresStorage<C> {
+    async fn push(
+        &self,
+        key: &str,
+        values: &[Vec<u8>],
+        end: ListEnd,
+        only_existing: bool,
+    ) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if only_existing {
+            if !lock_kind(&tx, key, KeyKind::List).await? {
+                return Ok(0);
+            }
+        } else {
+            lock_or_create(&tx, key, KeyKind::List).await?;
+        }
+
+        push_values(&tx, key, values, end).await?;
+        let len = list_len(&tx, key).await?;
+        tx.commit().await?;
+        Ok(len)
+    }
+
+    async fn pop(
+        &self,
+        key: &str,
+        end: ListEnd,
+        count: usize,
+    ) -> StorageResult<Option<Vec<Vec<u8>>>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::List).await? {
+            return Ok(None);
+        }
+
+        let popped = pop_values(&tx, key, end, count).await?;
+        delete_if_empty(&tx, key, KeyKind::List).await?;
+        tx.commit().await?;
+        Ok(Some(popped))
+    }
+
+    async fn lrange(&self, key: &str, start: i64, stop: i64) -> StorageResult<Vec<Vec<u8>>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::List).await? {
+            return Ok(Vec::new());
+        }
+
+        let statement =
+            format!("SELECT value FROM ({NUMBERED}) AS numbered WHERE {IN_RANGE} ORDER BY idx");
+        let rows = client.query(&statement, &[&key, &start, &stop]).await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn lset(&self, key: &str, index: i64, value: &[u8]) -> StorageResult<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::List).await? {
+            return Err(StorageError::InvalidValue("no such key"));
+        }
+
+        let statement = format!(
+            "UPDATE lists SET value = $4 WHERE key = $1 AND position = (
+                SELECT position FROM ({NUMBERED}) AS numbered WHERE {IN_RANGE}
+             )"
+        );
+        let updated = tx
+            .execute(&statement, &[&key, &index, &index, &value])
+            .await?;
+        if updated == 0 {
+            return Err(StorageError::InvalidValue("index out of range"));
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn linsert(
+        &self,
+        key: &str,
+        before: bool,
+        pivot: &[u8],
+        value: &[u8],
+    ) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::List).await? {
+            return Ok(0);
+        }
+
+        let row = tx
+            .query_opt(
+                "SELECT position FROM lists WHERE key = $1 AND value = $2
+                 ORDER BY position LIMIT 1",
+                &[&key, &pivot],
+            )
+            .await?;
+        let Some(pivot_position) = row.map(|row| row.get::<_, i64>(0)) else {
+            return Ok(-1);
+        };
+
+        // Make room by shifting the following elements, the primary key is only checked
+        // at commit so the positions may overlap in between
+        let position = if before {
+            pivot_position
+        } else {
+            pivot_position + 1
+        };
+        tx.execute(
+            "UPDATE lists SET position = position + 1 WHERE key = $1 AND position >= $2",
+            &[&key, &position],
+        )
+        .await?;
+        tx.execute(
+            "INSERT INTO lists (key, position, value) VALUES ($1, $2, $3)",
+            &[&key, &position, &value],
+        )
+        .await?;
+
+        let len = list_len(&tx, key).await?;
+        tx.commit().await?;
+        Ok(len)
+    }
+
+    async fn lrem(&self, key: &str, count: i64, value: &[u8]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::List).await? {
+            return Ok(0);
+        }
+
+        let statement = if count < 0 {
+            "DELETE FROM lists WHERE key = $1 AND position IN (
+                SELECT position FROM lists WHERE key = $1 AND value = $2
+                ORDER BY position DESC LIMIT $3
+             )"
+        } else {
+            "DELETE FROM lists WHERE key = $1 AND position IN (
+                SELECT position FROM lists WHERE key = $1 AND value = $2
+                ORDER BY position LIMIT $3
+             )"
+        };
+        // A NULL limit removes every occurrence
+        let limit = (count != 0).then(|| count.saturating_abs());
+        let removed = tx.execute(statement, &[&key, &value, &limit]).await?;
+
+        delete_if_empty(&tx, key, KeyKind::List).await?;
+        tx.commit().await?;
+        Ok(i64::try_from(removed).unwrap_or(i64::MAX))
+    }
+
+    async fn ltrim(&self, key: &str, start: i64, stop: i64) -> StorageResult<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::List).await? {
+            return Ok(());
+        }
+
+        let statement = format!(
+            "DELETE FROM lists WHERE key = $1 AND position IN (
+                SELECT position FROM ({NUMBERED}) AS numbered WHERE NOT ({IN_RANGE})
+             )"
+        );
+        tx.execute(&statement, &[&key, &start, &stop]).await?;
+
+        delete_if_empty(&tx, key, KeyKind::List).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn llen(&self, key: &str) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::List).await? {
+            return Ok(0);
+        }
+        list_len(&*client, key).await
+    }
+
+    async fn lmove(
+        &self,
+        source: &str,
+        destination: &str,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> StorageResult<Option<Vec<u8>>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let kinds = lock_keys(&tx, &[source, destination]).await?;
+        let kind_of = |key: &str| kinds.get(key).copied();
+        match kind_of(source) {
+            None => return Ok(None),
+            Some(KeyKind::List) => {}
+            Some(_) => return Err(StorageError::WrongType),
+        }
+        // The destination is checked before popping so a failed move leaves the source intact
+        if kind_of(destination).is_some_and(|kind| kind != KeyKind::List) {
+            return Err(StorageError::WrongType);
+        }
+
+        let Some(value) = pop_values(&tx, source, from, 1).await?.pop() else {
+            return Ok(None);
+        };
+        lock_or_create(&tx, destination, KeyKind::List).await?;
+        push_values(&tx, destination, std::slice::from_ref(&value), to).await?;
+
+        delete_if_empty(&tx, source, KeyKind::List).await?;
+        tx.commit().await?;
+        Ok(Some(value))
+    }
+}
@@ -0,0 +1,397 @@
+mod exec;
+mod functions;
+mod hashes;
+mod keys;
+mod lists;
+mod pubsub;
+mod sets;
+mod sorted_sets;
+mod stream_groups;
+mod streams;
+mod strings;
+
+use crate::storage::{KeyKind, StorageError, StorageResult, now_millis};
+use deadpool_postgres::{
+    GenericClient, Manager, ManagerConfig, Object, Pool, RecyclingMethod, Transaction,
+};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::NoTls;
+use tokio_postgres::types::ToSql;
+
+// Parameters of a statement built at runtime
+type Params<'a> = Vec<&'a (dyn ToSql + Sync)>;
+
+// Schema is created on startup so a fresh database is usable immediately
+// Later columns are added with `IF NOT EXISTS` to upgrade existing tables in place
+// Every key has a row in `kv`, values of other types live in their own tables and
+// are removed along with it
+// Writes to any of them give the key a new version for WATCH, through triggers, and
+// creating a key takes a shared advisory lock on it, which EXEC holds exclusively for
+// each key it watches so that keys missing when checked stay missing until it commits
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS kv (
+        key text PRIMARY KEY,
+        value bytea NOT NULL
+    );
+    ALTER TABLE kv ADD COLUMN IF NOT EXISTS expires_at bigint;
+    CREATE INDEX IF NOT EXISTS kv_expires_at_idx ON kv (expires_at)
+        WHERE expires_at IS NOT NULL;
+    ALTER TABLE kv ADD COLUMN IF NOT EXISTS kind text NOT NULL DEFAULT 'string';
+    ALTER TABLE kv ALTER COLUMN value DROP NOT NULL;
+    CREATE TABLE IF NOT EXISTS hashes (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        field bytea NOT NULL,
+        value bytea NOT NULL,
+        PRIMARY KEY (key, field)
+    );
+    CREATE TABLE IF NOT EXISTS lists (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        position bigint NOT NULL,
+        value bytea NOT NULL,
+        PRIMARY KEY (key, position) DEFERRABLE INITIALLY DEFERRED
+    );
+    CREATE TABLE IF NOT EXISTS sets (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        member bytea NOT NULL,
+        PRIMARY KEY (key, member)
+    );
+    CREATE TABLE IF NOT EXISTS zsets (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        member bytea NOT NULL,
+        score double precision NOT NULL,
+        PRIMARY KEY (key, member)
+    );
+    CREATE INDEX IF NOT EXISTS zsets_score_idx ON zsets (key, score, member);
+    CREATE TABLE IF NOT EXISTS streams (
+        key text PRIMARY KEY REFERENCES kv (key) ON DELETE CASCADE,
+        last_ms bigint NOT NULL DEFAULT 0,
+        last_seq bigint NOT NULL DEFAULT 0,
+        entries_added bigint NOT NULL DEFAULT 0,
+        max_deleted_ms bigint NOT NULL DEFAULT 0,
+        max_deleted_seq bigint NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS stream_entries (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        ms bigint NOT NULL,
+        seq bigint NOT NULL,
+        fields bytea[] NOT NULL,
+        PRIMARY KEY (key, ms, seq)
+    );
+    CREATE TABLE IF NOT EXISTS stream_groups (
+        key text NOT NULL REFERENCES kv (key) ON DELETE CASCADE,
+        name text NOT NULL,
+        last_ms bigint NOT NULL,
+        last_seq bigint NOT NULL,
+        PRIMARY KEY (key, name)
+    );
+    CREATE TABLE IF NOT EXISTS stream_consumers (
+        key text NOT NULL,
+        group_name text NOT NULL,
+        name text NOT NULL,
+        seen_at bigint NOT NULL,
+        active_at bigint,
+        PRIMARY KEY (key, group_name, name),
+        FOREIGN KEY (key, group_name) REFERENCES stream_groups (key, name) ON DELETE CASCADE
+    );
+    CREATE TABLE IF NOT EXISTS stream_pending (
+        key text NOT NULL,
+        group_name text NOT NULL,
+        ms bigint NOT NULL,
+        seq bigint NOT NULL,
+        consumer text NOT NULL,
+        delivered_at bigint NOT NULL,
+        deliveries bigint NOT NULL,
+        PRIMARY KEY (key, group_name, ms, seq),
+        FOREIGN KEY (key, group_name, consumer)
+            REFERENCES stream_consumers (key, group_name, name) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS stream_pending_consumer_idx
+        ON stream_pending (key, group_name, consumer, ms, seq);
+    CREATE TABLE IF NOT EXISTS pubsub_messages (
+        id bigserial PRIMARY KEY,
+        channel bytea NOT NULL,
+        message bytea NOT NULL,
+        published_at bigint NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS pubsub_messages_published_at_idx
+        ON pubsub_messages (published_at);
+    CREATE TABLE IF NOT EXISTS function_libraries (
+        name text PRIMARY KEY,
+        code text NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS functions (
+        name text PRIMARY KEY,
+        library text NOT NULL REFERENCES function_libraries (name) ON DELETE CASCADE,
+        description text,
+        flags text[] NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS functions_library_idx ON functions (library);
+    CREATE SEQUENCE IF NOT EXISTS kv_versions;
+    ALTER TABLE kv ADD COLUMN IF NOT EXISTS version bigint NOT NULL
+        DEFAULT nextval('kv_versions');
+    CREATE OR REPLACE FUNCTION kv_bump_version() RETURNS trigger AS $$
+    BEGIN
+        NEW.version := nextval('kv_versions');
+        RETURN NEW;
+    END $$ LANGUAGE plpgsql;
+    CREATE OR REPLACE TRIGGER kv_version BEFORE UPDATE ON kv
+        FOR EACH ROW WHEN (NEW.version = OLD.version) EXECUTE FUNCTION kv_bump_version();
+    CREATE OR REPLACE FUNCTION kv_lock_created() RETURNS trigger AS $$
+    BEGIN
+        PERFORM pg_advisory_xact_lock_shared(hashtextextended(NEW.key, 0));
+        RETURN NEW;
+    END $$ LANGUAGE plpgsql;
+    CREATE OR REPLACE TRIGGER kv_create BEFORE INSERT ON kv
+        FOR EACH ROW EXECUTE FUNCTION kv_lock_created();
+    CREATE OR REPLACE FUNCTION kv_touch_keys() RETURNS trigger AS $$
+    BEGIN
+        UPDATE kv SET version = nextval('kv_versions')
+            WHERE key IN (SELECT DISTINCT key FROM changed_rows);
+        RETURN NULL;
+    END $$ LANGUAGE plpgsql;
+    DO $$
+    DECLARE
+        value_table text;
+    BEGIN
+        FOREACH value_table IN ARRAY ARRAY[
+            'hashes', 'lists', 'sets', 'zsets', 'streams', 'stream_entries',
+            'stream_groups', 'stream_consumers', 'stream_pending'
+        ] LOOP
+            EXECUTE format('CREATE OR REPLACE TRIGGER %1$s_insert_version AFTER INSERT
+                ON %1$I REFERENCING NEW TABLE AS changed_rows
+                FOR EACH STATEMENT EXECUTE FUNCTION kv_touch_keys()', value_table);
+            EXECUTE format('CREATE OR REPLACE TRIGGER %1$s_update_version AFTER UPDATE
+                ON %1$I REFERENCING NEW TABLE AS changed_rows
+                FOR EACH STATEMENT EXECUTE FUNCTION kv_touch_keys()', value_table);
+            EXECUTE format('CREATE OR REPLACE TRIGGER %1$s_delete_version AFTER DELETE
+                ON %1$I REFERENCING OLD TABLE AS changed_rows
+                FOR EACH STATEMENT EXECUTE FUNCTION kv_touch_keys()', value_table);
+        END LOOP;
+    END $$;
+";
+
+// Commands run on connections from the pool, except within EXEC where they share
+// the connection of its transaction
+pub struct PostgresStorage<C = Pool> {
+    connections: C,
+    // Pub/sub listeners connect outside the pool
+    config: tokio_postgres::Config,
+    // Identifies this server's published messages among those of the others
+    node_id: u64,
+}
+
+impl PostgresStorage {
+    // Connects with a pool of at most `pool_size` connections, creating the schema
+    pub async fn connect(url: &str, pool_size: usize) -> StorageResult<Self> {
+        let pg_config: tokio_postgres::Config = url.parse()?;
+        let manager_config = ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        };
+        let manager = Manager::from_config(pg_config.clone(), NoTls, manager_config);
+        let pool = Pool::builder(manager)
+            .max_size(pool_size)
+            .build()
+            .expect("Failed to build connection pool");
+
+        let storage = PostgresStorage {
+            connections: pool,
+            config: pg_config,
+            node_id: rand::random(),
+        };
+        storage.migrate().await?;
+        Ok(storage)
+    }
+
+    async fn migrate(&self) -> StorageResult<()> {
+        let client = self.client().await?;
+        client.batch_execute(SCHEMA).await?;
+        Ok(())
+    }
+}
+
+impl<C: Connect> PostgresStorage<C> {
+    async fn client(&self) -> StorageResult<C::Connection<'_>> {
+        self.connections.connect().await
+    }
+}
+
+// Where statements run, handing out a client that derefs to a connection or transaction
+// Transactions begun on the client of a transaction are savepoints
+pub trait Connect: Send + Sync {
+    type Client: GenericClient + Send;
+    type Connection<'a>: DerefMut<Target = Self::Client> + Send
+    where
+        Self: 'a;
+
+    fn connect(&self) -> impl Future<Output = StorageResult<Self::Connection<'_>>> + Send;
+}
+
+impl Connect for Pool {
+    type Client = Object;
+    type Connection<'a> = PooledConnection;
+
+    async fn connect(&self) -> StorageResult<PooledConnection> {
+        Ok(PooledConnection(self.get().await?))
+    }
+}
+
+// A pooled connection, which derefs to the object wrapping it rather than the client
+// within so it can be used as a `GenericClient`
+pub struct PooledConnection(Object);
+
+impl Deref for PooledConnection {
+    type Target = Object;
+
+    fn deref(&self) -> &Object {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Object {
+        &mut self.0
+    }
+}
+
+// The transaction of an EXEC, which its commands take turns to use
+impl<'t> Connect for Mutex<Transaction<'t>> {
+    type Client = Transaction<'t>;
+    type Connection<'a>
+        = MutexGuard<'a, Transaction<'t>>
+    where
+        Self: 'a;
+
+    async fn connect(&self) -> StorageResult<MutexGuard<'_, Transaction<'t>>> {
+        Ok(self.lock().await)
+    }
+}
+
+// Lazily deletes any of the keys that have expired, so the statements that follow
+// can treat every remaining row as live
+async fn expire_keys(client: &impl GenericClient, keys: &[&str]) -> StorageResult<()> {
+    client
+        .execute(
+            "DELETE FROM kv WHERE key = ANY($1) AND expires_at <= $2",
+            &[&keys, &now_millis()],
+        )
+        .await?;
+    Ok(())
+}
+
+fn parse_kind(name: &str) -> KeyKind {
+    KeyKind::from_name(name).unwrap_or(KeyKind::String)
+}
+
+// Expires the key and returns whether it exists, failing if it holds another type
+async fn check_kind(client: &impl GenericClient, key: &str, kind: KeyKind) -> StorageResult<bool> {
+    expire_keys(client, &[key]).await?;
+    let row = client
+        .query_opt("SELECT kind FROM kv WHERE key = $1", &[&key])
+        .await?;
+    match row.map(|row| parse_kind(row.get(0))) {
+        None => Ok(false),
+        Some(found) if found == kind => Ok(true),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Like `check_kind` but also locks the key row until the transaction ends
+async fn lock_kind(client: &impl GenericClient, key: &str, kind: KeyKind) -> StorageResult<bool> {
+    expire_keys(client, &[key]).await?;
+    let row = client
+        .query_opt("SELECT kind FROM kv WHERE key = $1 FOR UPDATE", &[&key])
+        .await?;
+    match row.map(|row| parse_kind(row.get(0))) {
+        None => Ok(false),
+        Some(found) if found == kind => Ok(true),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+// Expires and locks the rows of several keys, returning the type of each existing one
+// Rows are locked in a consistent order so concurrent commands cannot deadlock
+async fn lock_keys(
+    client: &impl GenericClient,
+    keys: &[&str],
+) -> StorageResult<HashMap<String, KeyKind>> {
+    expire_keys(client, keys).await?;
+    let rows = client
+        .query(
+            "SELECT key, kind FROM kv WHERE key = ANY($1) ORDER BY key FOR UPDATE",
+            &[&keys],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get(0), parse_kind(row.get(1))))
+        .collect())
+}
+
+// Creates the key row for a value of the given type if missing and locks it
+async fn lock_or_create(
+    client: &impl GenericClient,
+    key: &str,
+    kind: KeyKind,
+) -> StorageResult<()> {
+    if lock_kind(client, key, kind).await? {
+        return Ok(());
+    }
+    client
+        .execute(
+            "INSERT INTO kv (key, kind) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING",
+            &[&key, &kind.as_str()],
+        )
+        .await?;
+    // Another node may have created the key first, so check its type again
+    lock_kind(client, key, kind).await?;
+    Ok(())
+}
+
+// Deletes the key once the table holding its elements has no rows left for it
+// Streams are kept when empty since they remember their last ID
+async fn delete_if_empty(
+    client: &impl GenericClient,
+    key: &str,
+    kind: KeyKind,
+) -> StorageResult<()> {
+    let statement = match kind {
+        KeyKind::Hash => {
+            "DELETE FROM kv WHERE key = $1
+             AND NOT EXISTS (SELECT 1 FROM hashes WHERE key = $1)"
+        }
+        KeyKind::List => {
+            "DELETE FROM kv WHERE key = $1
+             AND NOT EXISTS (SELECT 1 FROM lists WHERE key = $1)"
+        }
+        KeyKind::Set => {
+            "DELETE FROM kv WHERE key = $1
+             AND NOT EXISTS (SELECT 1 FROM sets WHERE key = $1)"
+        }
+        KeyKind::SortedSet => {
+            "DELETE FROM kv WHERE key = $1
+             AND NOT EXISTS (SELECT 1 FROM zsets WHERE key = $1)"
+        }
+        KeyKind::String | KeyKind::Stream => return Ok(()),
+    };
+    client.execute(statement, &[&key]).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::PostgresStorage;
+    use tokio::sync::Mutex;
+
+    // Connects to the database named by POSTGREDIS_TEST_URL, or returns None to skip the
+    // test when it is unset. Tests share the database, so each uses keys of its own.
+    pub(super) async fn connect() -> Option<PostgresStorage> {
+        // The schema cannot be created by several connections at once
+        static MIGRATING: Mutex<()> = Mutex::const_new(());
+        let url = std::env::var("POSTGREDIS_TEST_URL").ok()?;
+        let _migrating = MIGRATING.lock().await;
+        let storage = PostgresStorage::connect(&url, 4).await;
+        Some(storage.expect("Failed to connect to POSTGREDIS_TEST_URL"))
+    }
+}
@@ -0,0 +1,260 @@
+use crate::log::{Level, log};
+use crate::storage::postgres::{Connect, PostgresStorage};
+use crate::storage::{PubSubStorage, PublishedMessage, StorageError, StorageResult, now_millis};
+use deadpool_postgres::GenericClient;
+use std::fmt::Write;
+use std::future::poll_fn;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use tokio_postgres::{AsyncMessage, Client, Config, NoTls, Notification};
+
+// The Postgres channel every server listens on for published messages
+const NOTIFY_CHANNEL: &str = "postgredis_pubsub";
+
+// Postgres rejects notification payloads of 8000 bytes or more, larger messages are
+// stored in `pubsub_messages` and only their ID is sent
+const MAX_PAYLOAD_LEN: usize = 7999;
+
+// How long spilled messages are kept for listeners to fetch
+const SPILL_RETENTION_MS: i64 = 60_000;
+
+// How long to wait before reconnecting a listener whose connection was lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// Notification payloads are `<node> <kind> <channel> <message>` with the channel and
+// message hex encoded, or `<node> <kind> #<id>` for a message spilled to the table.
+// The kind is `m` for PUBLISH and `s` for SPUBLISH, and the node lets a server skip
+// its own messages, which it has already delivered locally
+enum Payload {
+    Inline(PublishedMessage),
+    Spilled { id: i64, sharded: bool },
+}
+
+// Within EXEC the notification is sent when the transaction commits
+impl<C: Connect> PubSubStorage for PostgresStorage<C> {
+    async fn publish(&self, channel: &str, message: &[u8], sharded: bool) -> StorageResult<()> {
+        let client = self.client().await?;
+        let kind = if sharded { 's' } else { 'm' };
+        let header = format!("{:016x} {kind}", self.node_id);
+
+        let payload = format!("{header} {} {}", hex(channel.as_bytes()), hex(message));
+        if payload.len() <= MAX_PAYLOAD_LEN {
+            client
+                .execute("SELECT pg_notify($1, $2)", &[&NOTIFY_CHANNEL, &payload])
+                .await?;
+            return Ok(());
+        }
+
+        // Spilled messages expire on their own, since any server may be the one to publish
+        let now = now_millis();
+        client
+            .execute(
+                "DELETE FROM pubsub_messages WHERE published_at < $1",
+                &[&(now - SPILL_RETENTION_MS)],
+            )
+            .await?;
+        client
+            .execute(
+                "WITH spilled AS (
+                    INSERT INTO pubsub_messages (channel, message, published_at)
+                    VALUES ($1, $2, $3)
+                    RETURNING id
+                )
+                SELECT pg_notify($4, $5::text || ' #' || id) FROM spilled",
+                &[
+                    &channel.as_bytes(),
+                    &message,
+                    &now,
+                    &NOTIFY_CHANNEL,
+                    &header,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn listen(&self) -> StorageResult<UnboundedReceiver<PublishedMessage>> {
+        let (tx, rx) = unbounded_channel();
+
+        // The first connection is made up front so a listener that cannot be set up fails
+        // startup, later ones are retried in the background
+        let listener = Listener::connect(&self.config).await?;
+        tokio::spawn(relay(listener, self.config.clone(), self.node_id, tx));
+        Ok(rx)
+    }
+}
+
+// A dedicated connection (outside the pool) subscribed to the notify channel
+struct Listener {
+    // Also fetches spilled messages
+    client: Client,
+    notifications: UnboundedReceiver<Notification>,
+    connection: JoinHandle<Result<(), tokio_postgres::Error>>,
+}
+
+impl Listener {
+    async fn connect(config: &Config) -> StorageResult<Self> {
+        let (client, mut connection) = config.connect(NoTls).await?;
+
+        // Notifications arrive as asynchronous messages of the connection, which must be
+        // driven for queries on the client to complete as well
+        let (tx, notifications) = unbounded_channel();
+        let connection = tokio::spawn(async move {
+            while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+                if let AsyncMessage::Notification(notification) = message? {
+                    let _ = tx.send(notification);
+                }
+            }
+            Ok(())
+        });
+
+        client
+            .batch_execute(&format!("LISTEN {NOTIFY_CHANNEL}"))
+            .await?;
+        Ok(Listener {
+            client,
+            notifications,
+            connection,
+        })
+    }
+}
+
+// Forwards messages published by other servers until the server stops receiving them,
+// reconnecting whenever the listener's connection is lost
+async fn relay(
+    mut listener: Listener,
+    config: Config,
+    node_id: u64,
+    tx: UnboundedSender<PublishedMessage>,
+) {
+    loop {
+        while let Some(notification) = listener.notifications.recv().await {
+            match receive(&listener.client, node_id, notification.payload()).await {
+                Ok(Some(message)) => {
+                    if tx.send(message).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => log!(Level::Warning, "Failed to receive published message: {e:?}"),
+            }
+        }
+        if let Ok(Err(e)) = listener.connection.await {
+            log!(Level::Warning, "Pub/sub listener disconnected: {e:?}");
+        }
+
+        // Messages published while reconnecting are missed, as with a Redis replica
+        listener = loop {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            match Listener::connect(&config).await {
+                Ok(listener) => break listener,
+                Err(e) => log!(
+                    Level::Warning,
+                    "Failed to reconnect pub/sub listener: {e:?}"
+                ),
+            }
+        };
+    }
+}
+
+// Decodes a notification, returning `None` for messages this server published itself
+async fn receive(
+    client: &Client,
+    node_id: u64,
+    payload: &str,
+) -> StorageResult<Option<PublishedMessage>> {
+    let Some((node, payload)) = parse_payload(payload) else {
+        return Err(StorageError::InvalidValue("malformed pub/sub notification"));
+    };
+    if node == node_id {
+        return Ok(None);
+    }
+    match payload {
+        Payload::Inline(message) => Ok(Some(message)),
+        Payload::Spilled { id, sharded } => {
+            let row = client
+                .query_opt(
+                    "SELECT channel, message FROM pubsub_messages WHERE id = $1",
+                    &[&id],
+                )
+                .await?;
+            Ok(row.map(|row| PublishedMessage {
+                channel: String::from_utf8_lossy(row.get::<_, &[u8]>(0)).into_owned(),
+                message: row.get(1),
+                sharded,
+            }))
+        }
+    }
+}
+
+fn parse_payload(payload: &str) -> Option<(u64, Payload)> {
+    let (node, rest) = payload.split_once(' ')?;
+    let node = u64::from_str_radix(node, 16).ok()?;
+    let (kind, rest) = rest.split_once(' ')?;
+    let sharded = match kind {
+        "m" => false,
+        "s" => true,
+        _ => return None,
+    };
+    if let Some(id) = rest.strip_prefix('#') {
+        let id = id.parse().ok()?;
+        return Some((node, Payload::Spilled { id, sharded }));
+    }
+    let (channel, message) = rest.split_once(' ')?;
+    let message = PublishedMessage {
+        channel: String::from_utf8(unhex(channel)?).ok()?,
+        message: unhex(message)?,
+        sharded,
+    };
+    Some((node, Payload::Inline(message)))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let payload = format!("{:016x} m {} {}", 42, hex(b"news"), hex(b"\x00hi"));
+        let Some((42, Payload::Inline(message))) = parse_payload(&payload) else {
+            panic!("expected an inline message from node 42");
+        };
+        assert_eq!(message.channel, "news");
+        assert_eq!(message.message, b"\x00hi");
+        assert!(!message.sharded);
+
+        assert!(matches!(
+            parse_payload("000000000000002a s #7"),
+            Some((
+                42,
+                Payload::Spilled {
+                    id: 7,
+                    sharded: true
+                }
+            ))
+        ));
+        assert!(parse_payload("2a m 6e6").is_none());
+        assert!(parse_payload("2a x #7").is_none());
+        assert!(parse_payload("bogus").is_none());
+    }
+}
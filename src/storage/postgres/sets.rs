@@ -0,0 +1,349 @@
+use crate::glob::glob_match;
+use crate::storage::postgres::{
+    Connect, Params, PostgresStorage, check_kind, delete_if_empty, expire_keys, lock_keys,
+    lock_kind, lock_or_create,
+};
+use crate::storage::{KeyKind, SetOp, SetStorage, StorageError, StorageResult};
+use deadpool_postgres::GenericClient;
+use std::collections::HashSet;
+use tokio_postgres::types::ToSql;
+
+fn count(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+// Builds a compound query over the members of each key so Postgres does the set
+// algebra, with the keys bound to consecutive parameters starting at `first`
+fn combine_query(op: SetOp, keys: usize, first: usize) -> String {
+    let operator = match op {
+        SetOp::Union => " UNION ",
+        SetOp::Intersect => " INTERSECT ",
+        SetOp::Difference => " EXCEPT ",
+    };
+    (first..first + keys)
+        .map(|n| format!("SELECT member FROM sets WHERE key = ${n}"))
+        .collect::<Vec<_>>()
+        .join(operator)
+}
+
+fn key_params(keys: &[String]) -> Params<'_> {
+    keys.iter().map(|key| key as &(dyn ToSql + Sync)).collect()
+}
+
+// Fails if any of the keys holds a value other than a set
+async fn check_sets(client: &impl GenericClient, keys: &[&str]) -> StorageResult<()> {
+    expire_keys(client, keys).await?;
+    let row = client
+        .query_opt(
+            "SELECT 1 FROM kv WHERE key = ANY($1) AND kind <> $2 LIMIT 1",
+            &[&keys, &KeyKind::Set.as_str()],
+        )
+        .await?;
+    match row {
+        None => Ok(()),
+        Some(_) => Err(StorageError::WrongType),
+    }
+}
+
+impl<C: Connect> SetStorage for PostgresStorage<C> {
+    async fn sadd(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_or_create(&tx, key, KeyKind::Set).await?;
+
+        let added = tx
+            .execute(
+                "INSERT INTO sets (key, member) SELECT $1, member FROM unnest($2::bytea[]) AS member
+                 ON CONFLICT (key, member) DO NOTHING",
+                &[&key, &members],
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(count(added))
+    }
+
+    async fn srem(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Set).await? {
+            return Ok(0);
+        }
+
+        let removed = tx
+            .execute(
+                "DELETE FROM sets WHERE key = $1 AND member = ANY($2)",
+                &[&key, &members],
+            )
+            .await?;
+        delete_if_empty(&tx, key, KeyKind::Set).await?;
+        tx.commit().await?;
+        Ok(count(removed))
+    }
+
+    async fn smembers(&self, key: &str) -> StorageResult<Vec<Vec<u8>>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Set).await? {
+            return Ok(Vec::new());
+        }
+
+        let rows = client
+            .query(
+                "SELECT member FROM sets WHERE key = $1 ORDER BY member",
+                &[&key],
+            )
+            .await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn smismember(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<Vec<bool>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Set).await? {
+            return Ok(vec![false; members.len()]);
+        }
+
+        let rows = client
+            .query(
+                "SELECT member FROM sets WHERE key = $1 AND member = ANY($2)",
+                &[&key, &members],
+            )
+            .await?;
+        let found: HashSet<Vec<u8>> = rows.into_iter().map(|row| row.get(0)).collect();
+        Ok(members.iter().map(|m| found.contains(m)).collect())
+    }
+
+    async fn scard(&self, key: &str) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Set).await? {
+            return Ok(0);
+        }
+
+        let row = client
+            .query_one("SELECT count(*) FROM sets WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn spop(&self, key: &str, count: usize) -> StorageResult<Vec<Vec<u8>>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Set).await? {
+            return Ok(Vec::new());
+        }
+
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+        let rows = tx
+            .query(
+                "DELETE FROM sets WHERE key = $1 AND member IN (
+                    SELECT member FROM sets WHERE key = $1 ORDER BY random() LIMIT $2
+                 )
+                 RETURNING member",
+                &[&key, &limit],
+            )
+            .await?;
+        delete_if_empty(&tx, key, KeyKind::Set).await?;
+        tx.commit().await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn srandmember(&self, key: &str, count: i64) -> StorageResult<Vec<Vec<u8>>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Set).await? {
+            return Ok(Vec::new());
+        }
+
+        // Repeated picks number the members and join them against random indexes
+        let statement = if count >= 0 {
+            "SELECT member FROM sets WHERE key = $1 ORDER BY random() LIMIT $2"
+        } else {
+            "WITH numbered AS (
+                SELECT member, row_number() OVER () - 1 AS idx, count(*) OVER () AS len
+                FROM sets WHERE key = $1
+             ),
+             picks AS (
+                SELECT floor(random() * (SELECT max(len) FROM numbered))::bigint AS idx
+                FROM generate_series(1, $2::bigint)
+             )
+             SELECT member FROM picks JOIN numbered USING (idx)"
+        };
+        let rows = client
+            .query(statement, &[&key, &count.saturating_abs()])
+            .await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn smove(&self, source: &str, destination: &str, member: &[u8]) -> StorageResult<bool> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+
+        let kinds = lock_keys(&tx, &[source, destination]).await?;
+        match kinds.get(source) {
+            None => return Ok(false),
+            Some(KeyKind::Set) => {}
+            Some(_) => return Err(StorageError::WrongType),
+        }
+        // The destination is checked before removing so a failed move leaves the source intact
+        if kinds
+            .get(destination)
+            .is_some_and(|kind| *kind != KeyKind::Set)
+        {
+            return Err(StorageError::WrongType);
+        }
+
+        if source == destination {
+            let row = tx
+                .query_opt(
+                    "SELECT 1 FROM sets WHERE key = $1 AND member = $2",
+                    &[&source, &member],
+                )
+                .await?;
+            return Ok(row.is_some());
+        }
+
+        let removed = tx
+            .execute(
+                "DELETE FROM sets WHERE key = $1 AND member = $2",
+                &[&source, &member],
+            )
+            .await?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        lock_or_create(&tx, destination, KeyKind::Set).await?;
+        tx.execute(
+            "INSERT INTO sets (key, member) VALUES ($1, $2) ON CONFLICT (key, member) DO NOTHING",
+            &[&destination, &member],
+        )
+        .await?;
+
+        delete_if_empty(&tx, source, KeyKind::Set).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn combine(&self, op: SetOp, keys: &[String]) -> StorageResult<Vec<Vec<u8>>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let names: Vec<&str> = keys.iter().map(String::as_str).collect();
+        check_sets(&tx, &names).await?;
+
+        let statement = format!("{} ORDER BY member", combine_query(op, keys.len(), 1));
+        let rows = tx.query(&statement, &key_params(keys)).await?;
+        tx.commit().await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn combine_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+    ) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+
+        let mut names: Vec<&str> = keys.iter().map(String::as_str).collect();
+        names.push(destination);
+        let kinds = lock_keys(&tx, &names).await?;
+        let is_set = |key: &String| kinds.get(key).is_none_or(|kind| *kind == KeyKind::Set);
+        if !keys.iter().all(is_set) {
+            return Err(StorageError::WrongType);
+        }
+
+        // Any other value at the destination is replaced, and a kept set loses its TTL
+        match kinds.get(destination) {
+            Some(KeyKind::Set) => {
+                tx.execute(
+                    "UPDATE kv SET expires_at = NULL WHERE key = $1",
+                    &[&destination],
+                )
+                .await?;
+            }
+            Some(_) => {
+                tx.execute("DELETE FROM kv WHERE key = $1", &[&destination])
+                    .await?;
+            }
+            None => {}
+        }
+        lock_or_create(&tx, destination, KeyKind::Set).await?;
+
+        // Every part of the statement reads the same snapshot, so the destination can
+        // also be one of the sources
+        let statement = format!(
+            "WITH combined AS MATERIALIZED ({}),
+             removed AS (
+                DELETE FROM sets WHERE key = $1
+                AND member NOT IN (SELECT member FROM combined)
+             ),
+             inserted AS (
+                INSERT INTO sets (key, member) SELECT $1, member FROM combined
+                ON CONFLICT (key, member) DO NOTHING
+             )
+             SELECT count(*) FROM combined",
+            combine_query(op, keys.len(), 2)
+        );
+        let mut params: Params<'_> = vec![&destination];
+        params.extend(key_params(keys));
+        let row = tx.query_one(&statement, &params).await?;
+
+        delete_if_empty(&tx, destination, KeyKind::Set).await?;
+        tx.commit().await?;
+        Ok(row.get(0))
+    }
+
+    async fn intercard(&self, keys: &[String], limit: Option<usize>) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let names: Vec<&str> = keys.iter().map(String::as_str).collect();
+        check_sets(&tx, &names).await?;
+
+        // A NULL limit counts the whole intersection
+        let limit = limit.map(|l| i64::try_from(l).unwrap_or(i64::MAX));
+        let statement = format!(
+            "SELECT count(*) FROM ({} LIMIT ${}) AS limited",
+            combine_query(SetOp::Intersect, keys.len(), 1),
+            keys.len() + 1
+        );
+        let mut params = key_params(keys);
+        params.push(&limit);
+        let row = tx.query_one(&statement, &params).await?;
+        tx.commit().await?;
+        Ok(row.get(0))
+    }
+
+    async fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> StorageResult<(u64, Vec<Vec<u8>>)> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Set).await? {
+            return Ok((0, Vec::new()));
+        }
+        let offset = i64::try_from(cursor).unwrap_or(i64::MAX);
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+
+        // Members are visited in sorted order so the cursor is a stable offset
+        let rows = client
+            .query(
+                "SELECT member FROM sets WHERE key = $1
+                 ORDER BY member OFFSET $2 LIMIT $3",
+                &[&key, &offset, &limit],
+            )
+            .await?;
+
+        let next_cursor = if rows.len() < count {
+            0
+        } else {
+            cursor + rows.len() as u64
+        };
+
+        let members = rows
+            .into_iter()
+            .map(|row| row.get::<_, Vec<u8>>(0))
+            .filter(|member| pattern.is_none_or(|p| glob_match(p, member)))
+            .collect();
+        Ok((next_cursor, members))
+    }
+}
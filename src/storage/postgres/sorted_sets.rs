@@ -0,0 +1,412 @@
+use crate::storage::postgres::{
+    Connect, Params, PostgresStorage, check_kind, delete_if_empty, lock_kind, lock_or_create,
+};
+use crate::storage::range::normalize_range;
+use crate::storage::{
+    KeyKind, LexBound, ScoreBound, ScoreEnd, ScoreUpdate, ScoredMembers, SetCondition,
+    SortedSetStorage, StorageResult, ZAddOptions, ZAddOutcome, ZRange, ZRangeBy,
+};
+use deadpool_postgres::GenericClient;
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use tokio_postgres::types::ToSql;
+
+// OFFSET and LIMIT applied to the members in rank order, where no limit selects the rest
+type Window = (i64, Option<i64>);
+
+fn order(reverse: bool) -> &'static str {
+    if reverse {
+        "score DESC, member DESC"
+    } else {
+        "score, member"
+    }
+}
+
+fn count(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn bind<'a>(params: &mut Params<'a>, value: &'a (dyn ToSql + Sync)) -> String {
+    params.push(value);
+    format!("${}", params.len())
+}
+
+fn score_condition<'a>(bound: &'a ScoreBound, lower: bool, params: &mut Params<'a>) -> String {
+    let (operator, score) = match bound {
+        ScoreBound::Inclusive(score) => (if lower { ">=" } else { "<=" }, score),
+        ScoreBound::Exclusive(score) => (if lower { ">" } else { "<" }, score),
+    };
+    format!("score {operator} {}", bind(params, score))
+}
+
+fn lex_condition<'a>(bound: &'a LexBound, lower: bool, params: &mut Params<'a>) -> String {
+    let (operator, member) = match bound {
+        LexBound::Min => return if lower { "TRUE" } else { "FALSE" }.to_string(),
+        LexBound::Max => return if lower { "FALSE" } else { "TRUE" }.to_string(),
+        LexBound::Inclusive(member) => (if lower { ">=" } else { "<=" }, member),
+        LexBound::Exclusive(member) => (if lower { ">" } else { "<" }, member),
+    };
+    format!("member {operator} {}", bind(params, member))
+}
+
+// Builds a query for the members the range selects in rank order, binding its
+// parameters after the key in `$1`. Ranks are applied through the window
+fn selection<'a>(
+    range: &'a ZRangeBy,
+    reverse: bool,
+    window: &'a Window,
+    params: &mut Params<'a>,
+) -> String {
+    let condition = match range {
+        ZRangeBy::Rank { .. } => "TRUE".to_string(),
+        ZRangeBy::Score { min, max } => format!(
+            "{} AND {}",
+            score_condition(min, true, params),
+            score_condition(max, false, params)
+        ),
+        ZRangeBy::Lex { min, max } => format!(
+            "{} AND {}",
+            lex_condition(min, true, params),
+            lex_condition(max, false, params)
+        ),
+    };
+    let offset = bind(params, &window.0);
+    let limit = bind(params, &window.1);
+    format!(
+        "SELECT member, score FROM zsets WHERE key = $1 AND {condition}
+         ORDER BY {} OFFSET {offset} LIMIT {limit}",
+        order(reverse)
+    )
+}
+
+// Resolves the paging of a range, returning `None` if a rank range selects nothing
+async fn window(
+    client: &impl GenericClient,
+    key: &str,
+    range: &ZRangeBy,
+    offset: usize,
+    limit: Option<usize>,
+) -> StorageResult<Option<Window>> {
+    let to_i64 = |n: usize| i64::try_from(n).unwrap_or(i64::MAX);
+    let ZRangeBy::Rank { start, stop } = range else {
+        return Ok(Some((to_i64(offset), limit.map(to_i64))));
+    };
+
+    let row = client
+        .query_one("SELECT count(*) FROM zsets WHERE key = $1", &[&key])
+        .await?;
+    let len = usize::try_from(row.get::<_, i64>(0)).unwrap_or(0);
+    Ok(normalize_range(*start, *stop, len)
+        .map(|(start, stop)| (to_i64(start), Some(to_i64(stop - start + 1)))))
+}
+
+// Combines repeated members the way applying them one after another would
+fn dedupe(members: &[(f64, Vec<u8>)], options: ZAddOptions) -> BTreeMap<&[u8], f64> {
+    let mut unique = BTreeMap::new();
+    for (score, member) in members {
+        match unique.entry(member.as_slice()) {
+            Entry::Vacant(entry) => {
+                entry.insert(*score);
+            }
+            Entry::Occupied(mut entry) => {
+                let current = *entry.get();
+                let kept = match (options.condition, options.update) {
+                    (SetCondition::IfNotExists, _) => current,
+                    (_, ScoreUpdate::IfGreater) => current.max(*score),
+                    (_, ScoreUpdate::IfLess) => current.min(*score),
+                    (_, ScoreUpdate::Always) => *score,
+                };
+                entry.insert(kept);
+            }
+        }
+    }
+    unique
+}
+
+// Condition on an existing row's score under which ZADD overwrites it with `$new`
+fn update_condition(update: ScoreUpdate, new: &str) -> String {
+    match update {
+        ScoreUpdate::Always => format!("{new} <> zsets.score"),
+        ScoreUpdate::IfGreater => format!("{new} > zsets.score"),
+        ScoreUpdate::IfLess => format!("{new} < zsets.score"),
+    }
+}
+
+async fn write_score(
+    client: &impl GenericClient,
+    key: &str,
+    member: &[u8],
+    score: f64,
+) -> StorageResult<()> {
+    client
+        .execute(
+            "INSERT INTO zsets (key, member, score) VALUES ($1, $2, $3)
+             ON CONFLICT (key, member) DO UPDATE SET score = EXCLUDED.score",
+            &[&key, &member, &score],
+        )
+        .await?;
+    Ok(())
+}
+
+fn scored_members(rows: Vec<tokio_postgres::Row>) -> ScoredMembers {
+    rows.into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect()
+}
+
+impl<C: Connect> SortedSetStorage for PostgresStorage<C> {
+    async fn zadd(
+        &self,
+        key: &str,
+        members: &[(f64, Vec<u8>)],
+        options: ZAddOptions,
+    ) -> StorageResult<ZAddOutcome> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+
+        let unique = dedupe(members, options);
+        let names: Vec<&[u8]> = unique.keys().copied().collect();
+        let scores: Vec<f64> = unique.values().copied().collect();
+
+        // XX only updates, so a missing key is left alone
+        if options.condition == SetCondition::IfExists {
+            if !lock_kind(&tx, key, KeyKind::SortedSet).await? {
+                return Ok(ZAddOutcome::default());
+            }
+            let statement = format!(
+                "UPDATE zsets SET score = input.score
+                 FROM unnest($2::bytea[], $3::float8[]) AS input(member, score)
+                 WHERE zsets.key = $1 AND zsets.member = input.member AND {}",
+                update_condition(options.update, "input.score")
+            );
+            let changed = tx.execute(&statement, &[&key, &names, &scores]).await?;
+            tx.commit().await?;
+            return Ok(ZAddOutcome {
+                added: 0,
+                changed: count(changed),
+            });
+        }
+
+        lock_or_create(&tx, key, KeyKind::SortedSet).await?;
+        let on_conflict = if options.condition == SetCondition::IfNotExists {
+            "DO NOTHING".to_string()
+        } else {
+            format!(
+                "DO UPDATE SET score = EXCLUDED.score WHERE {}",
+                update_condition(options.update, "EXCLUDED.score")
+            )
+        };
+        // `xmax` is only zero for rows that were inserted rather than updated
+        let statement = format!(
+            "WITH written AS (
+                INSERT INTO zsets (key, member, score)
+                SELECT $1, member, score FROM unnest($2::bytea[], $3::float8[])
+                    AS input(member, score)
+                ON CONFLICT (key, member) {on_conflict}
+                RETURNING xmax = 0 AS inserted
+             )
+             SELECT count(*) FILTER (WHERE inserted), count(*) FILTER (WHERE NOT inserted)
+             FROM written"
+        );
+        let row = tx.query_one(&statement, &[&key, &names, &scores]).await?;
+        tx.commit().await?;
+        Ok(ZAddOutcome {
+            added: row.get(0),
+            changed: row.get(1),
+        })
+    }
+
+    async fn zincrby(
+        &self,
+        key: &str,
+        member: &[u8],
+        delta: f64,
+        options: ZAddOptions,
+    ) -> StorageResult<Option<f64>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if options.condition == SetCondition::IfExists {
+            if !lock_kind(&tx, key, KeyKind::SortedSet).await? {
+                return Ok(None);
+            }
+        } else {
+            lock_or_create(&tx, key, KeyKind::SortedSet).await?;
+        }
+
+        // Errors roll back the transaction, including a key created above
+        let row = tx
+            .query_opt(
+                "SELECT score FROM zsets WHERE key = $1 AND member = $2 FOR UPDATE",
+                &[&key, &member],
+            )
+            .await?;
+        let Some(score) = options.resolve(row.map(|row| row.get(0)), delta, true)? else {
+            return Ok(None);
+        };
+        write_score(&tx, key, member, score).await?;
+        tx.commit().await?;
+        Ok(Some(score))
+    }
+
+    async fn zmscore(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<Vec<Option<f64>>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::SortedSet).await? {
+            return Ok(vec![None; members.len()]);
+        }
+
+        let rows = client
+            .query(
+                "SELECT zsets.score FROM unnest($2::bytea[]) WITH ORDINALITY
+                    AS requested(member, position)
+                 LEFT JOIN zsets ON zsets.key = $1 AND zsets.member = requested.member
+                 ORDER BY requested.position",
+                &[&key, &members],
+            )
+            .await?;
+        Ok(rows.into_iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn zrank(
+        &self,
+        key: &str,
+        member: &[u8],
+        reverse: bool,
+    ) -> StorageResult<Option<(i64, f64)>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::SortedSet).await? {
+            return Ok(None);
+        }
+
+        // The rank counts the members ordered before this one, read from the score index
+        let operator = if reverse { ">" } else { "<" };
+        let statement = format!(
+            "SELECT found.score, (
+                SELECT count(*) FROM zsets
+                WHERE key = $1 AND (score, member) {operator} (found.score, found.member)
+             )
+             FROM zsets AS found WHERE found.key = $1 AND found.member = $2"
+        );
+        let row = client.query_opt(&statement, &[&key, &member]).await?;
+        Ok(row.map(|row| (row.get(1), row.get(0))))
+    }
+
+    async fn zrem(&self, key: &str, members: &[Vec<u8>]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::SortedSet).await? {
+            return Ok(0);
+        }
+
+        let removed = tx
+            .execute(
+                "DELETE FROM zsets WHERE key = $1 AND member = ANY($2)",
+                &[&key, &members],
+            )
+            .await?;
+        delete_if_empty(&tx, key, KeyKind::SortedSet).await?;
+        tx.commit().await?;
+        Ok(count(removed))
+    }
+
+    async fn zcard(&self, key: &str) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::SortedSet).await? {
+            return Ok(0);
+        }
+
+        let row = client
+            .query_one("SELECT count(*) FROM zsets WHERE key = $1", &[&key])
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn zcount(&self, key: &str, range: &ZRangeBy) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::SortedSet).await? {
+            return Ok(0);
+        }
+        let Some(window) = window(&*client, key, range, 0, None).await? else {
+            return Ok(0);
+        };
+
+        let mut params: Params<'_> = vec![&key];
+        let statement = format!(
+            "SELECT count(*) FROM ({}) AS selected",
+            selection(range, false, &window, &mut params)
+        );
+        let row = client.query_one(&statement, &params).await?;
+        Ok(row.get(0))
+    }
+
+    async fn zrange(&self, key: &str, range: &ZRange) -> StorageResult<ScoredMembers> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::SortedSet).await? {
+            return Ok(Vec::new());
+        }
+        let window = window(&*client, key, &range.by, range.offset, range.count).await?;
+        let Some(window) = window else {
+            return Ok(Vec::new());
+        };
+
+        let mut params: Params<'_> = vec![&key];
+        let statement = selection(&range.by, range.reverse, &window, &mut params);
+        let rows = client.query(&statement, &params).await?;
+        Ok(scored_members(rows))
+    }
+
+    async fn zremrange(&self, key: &str, range: &ZRangeBy) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::SortedSet).await? {
+            return Ok(0);
+        }
+        let Some(window) = window(&tx, key, range, 0, None).await? else {
+            return Ok(0);
+        };
+
+        let mut params: Params<'_> = vec![&key];
+        let statement = format!(
+            "DELETE FROM zsets WHERE key = $1 AND member IN (
+                SELECT member FROM ({}) AS selected
+             )",
+            selection(range, false, &window, &mut params)
+        );
+        let removed = tx.execute(&statement, &params).await?;
+        delete_if_empty(&tx, key, KeyKind::SortedSet).await?;
+        tx.commit().await?;
+        Ok(count(removed))
+    }
+
+    async fn zpop(&self, key: &str, end: ScoreEnd, count: usize) -> StorageResult<ScoredMembers> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::SortedSet).await? {
+            return Ok(Vec::new());
+        }
+
+        let reverse = end == ScoreEnd::Max;
+        let limit = i64::try_from(count).unwrap_or(i64::MAX);
+        let statement = format!(
+            "DELETE FROM zsets WHERE key = $1 AND member IN (
+                SELECT member FROM zsets WHERE key = $1 ORDER BY {} LIMIT $2
+             )
+             RETURNING member, score",
+            order(reverse)
+        );
+        let rows = tx.query(&statement, &[&key, &limit]).await?;
+        delete_if_empty(&tx, key, KeyKind::SortedSet).await?;
+        tx.commit().await?;
+
+        // DELETE does not return rows in any particular order
+        let mut popped = scored_members(rows);
+        popped.sort_by(|(a_member, a_score), (b_member, b_score)| {
+            a_score
+                .total_cmp(b_score)
+                .then_with(|| a_member.cmp(b_member))
+        });
+        if reverse {
+            popped.reverse();
+        }
+        Ok(popped)
+    }
+}
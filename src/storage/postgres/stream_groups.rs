@@ -0,0 +1,674 @@
+use crate::storage::postgres::streams::{create_stream, stream_info, to_entry};
+use crate::storage::postgres::{Connect, PostgresStorage, check_kind, lock_keys, lock_kind};
+use crate::storage::{
+    AutoClaim, ClaimOptions, ConsumerInfo, DeliveredEntry, GroupInfo, KeyKind, PendingEntry,
+    PendingRange, PendingSummary, STREAM_REQUIRED, StorageError, StorageResult, StreamGroupStorage,
+    StreamId, now_millis,
+};
+use deadpool_postgres::GenericClient;
+use tokio_postgres::Row;
+
+fn count(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn no_group(key: &str, group: &str) -> StorageError {
+    StorageError::NoGroup {
+        key: key.to_string(),
+        group: group.to_string(),
+    }
+}
+
+// Splits IDs into the arrays of their parts, to be bound to `unnest`
+fn id_arrays(ids: &[StreamId]) -> (Vec<i64>, Vec<i64>) {
+    ids.iter().map(|id| id.to_sql()).unzip()
+}
+
+// Reads a delivered entry from `ms, seq, fields` columns, where fields are null
+// once the entry was deleted
+fn to_delivered(row: &Row) -> DeliveredEntry {
+    let fields: Option<Vec<Vec<u8>>> = row.get(2);
+    DeliveredEntry {
+        id: StreamId::from_sql(row.get(0), row.get(1)),
+        fields: fields.map(|_| to_entry(row).fields),
+    }
+}
+
+// Returns the group's last delivered ID, failing if the stream or group is missing
+async fn check_group(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+) -> StorageResult<StreamId> {
+    if !check_kind(client, key, KeyKind::Stream).await? {
+        return Err(no_group(key, group));
+    }
+    group_last_id(client, key, group).await
+}
+
+// Like `check_group` but also locks the stream until the transaction ends
+async fn lock_group(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+) -> StorageResult<StreamId> {
+    if !lock_kind(client, key, KeyKind::Stream).await? {
+        return Err(no_group(key, group));
+    }
+    group_last_id(client, key, group).await
+}
+
+async fn group_last_id(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+) -> StorageResult<StreamId> {
+    let row = client
+        .query_opt(
+            "SELECT last_ms, last_seq FROM stream_groups WHERE key = $1 AND name = $2",
+            &[&key, &group],
+        )
+        .await?;
+    match row {
+        Some(row) => Ok(StreamId::from_sql(row.get(0), row.get(1))),
+        None => Err(no_group(key, group)),
+    }
+}
+
+async fn set_last_id(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    id: StreamId,
+) -> StorageResult<()> {
+    let (ms, seq) = id.to_sql();
+    client
+        .execute(
+            "UPDATE stream_groups SET last_ms = $3, last_seq = $4 WHERE key = $1 AND name = $2",
+            &[&key, &group, &ms, &seq],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn stream_last_id(client: &impl GenericClient, key: &str) -> StorageResult<StreamId> {
+    let row = client
+        .query_one(
+            "SELECT last_ms, last_seq FROM streams WHERE key = $1",
+            &[&key],
+        )
+        .await?;
+    Ok(StreamId::from_sql(row.get(0), row.get(1)))
+}
+
+// Records that the consumer was seen, creating it if needed, and that it was
+// delivered entries if `active` is set
+async fn touch(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    now: i64,
+    active: bool,
+) -> StorageResult<()> {
+    client
+        .execute(
+            "INSERT INTO stream_consumers (key, group_name, name, seen_at, active_at)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (key, group_name, name) DO UPDATE SET seen_at = excluded.seen_at,
+                 active_at = COALESCE(excluded.active_at, stream_consumers.active_at)",
+            &[&key, &group, &consumer, &now, &active.then_some(now)],
+        )
+        .await?;
+    Ok(())
+}
+
+// Assigns pending entries to the consumer with their new delivery counts, adding the
+// ones that were not pending yet
+async fn assign(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    delivered_at: i64,
+    deliveries: &[(StreamId, u64)],
+) -> StorageResult<()> {
+    let ids: Vec<StreamId> = deliveries.iter().map(|(id, _)| *id).collect();
+    let (ms, seq) = id_arrays(&ids);
+    let counts: Vec<i64> = deliveries.iter().map(|(_, n)| count(*n)).collect();
+    client
+        .execute(
+            "INSERT INTO stream_pending
+                 (key, group_name, ms, seq, consumer, delivered_at, deliveries)
+             SELECT $1, $2, ms, seq, $3, $4, deliveries
+             FROM unnest($5::bigint[], $6::bigint[], $7::bigint[]) AS t (ms, seq, deliveries)
+             ON CONFLICT (key, group_name, ms, seq) DO UPDATE SET
+                 consumer = excluded.consumer,
+                 delivered_at = excluded.delivered_at,
+                 deliveries = excluded.deliveries",
+            &[&key, &group, &consumer, &delivered_at, &ms, &seq, &counts],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn drop_pending(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    ids: &[StreamId],
+) -> StorageResult<u64> {
+    let (ms, seq) = id_arrays(ids);
+    let dropped = client
+        .execute(
+            "DELETE FROM stream_pending WHERE key = $1 AND group_name = $2
+             AND (ms, seq) IN (SELECT * FROM unnest($3::bigint[], $4::bigint[]))",
+            &[&key, &group, &ms, &seq],
+        )
+        .await?;
+    Ok(dropped)
+}
+
+// Delivers the entries after the group's last delivered ID
+async fn read_new(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    count: Option<i64>,
+    noack: bool,
+) -> StorageResult<Vec<DeliveredEntry>> {
+    let (ms, seq) = group_last_id(client, key, group).await?.to_sql();
+    let rows = client
+        .query(
+            "SELECT ms, seq, fields FROM stream_entries
+             WHERE key = $1 AND (ms, seq) > ($2, $3) ORDER BY ms, seq LIMIT $4",
+            &[&key, &ms, &seq, &count],
+        )
+        .await?;
+    let read: Vec<DeliveredEntry> = rows.iter().map(to_delivered).collect();
+
+    let now = now_millis();
+    touch(client, key, group, consumer, now, !read.is_empty()).await?;
+    if let Some(last) = read.last() {
+        set_last_id(client, key, group, last.id).await?;
+    }
+    if !noack {
+        let deliveries: Vec<(StreamId, u64)> = read.iter().map(|entry| (entry.id, 1)).collect();
+        assign(client, key, group, consumer, now, &deliveries).await?;
+    }
+    Ok(read)
+}
+
+// Delivers the consumer's pending entries after the ID again
+async fn read_history(
+    client: &impl GenericClient,
+    key: &str,
+    group: &str,
+    consumer: &str,
+    after: StreamId,
+    count: Option<i64>,
+) -> StorageResult<Vec<DeliveredEntry>> {
+    let now = now_millis();
+    let (ms, seq) = after.to_sql();
+    let rows = client
+        .query(
+            "WITH delivered AS (
+                 UPDATE stream_pending SET delivered_at = $4, deliveries = deliveries + 1
+                 WHERE key = $1 AND group_name = $2 AND (ms, seq) IN (
+                     SELECT ms, seq FROM stream_pending
+                     WHERE key = $1 AND group_name = $2 AND consumer = $3
+                     AND (ms, seq) > ($5, $6)
+                     ORDER BY ms, seq LIMIT $7
+                 )
+                 RETURNING ms, seq
+             )
+             SELECT d.ms, d.seq, e.fields FROM delivered d
+             LEFT JOIN stream_entries e ON e.key = $1 AND e.ms = d.ms AND e.seq = d.seq
+             ORDER BY d.ms, d.seq",
+            &[&key, &group, &consumer, &now, &ms, &seq, &count],
+        )
+        .await?;
+    let read: Vec<DeliveredEntry> = rows.iter().map(to_delivered).collect();
+    touch(client, key, group, consumer, now, !read.is_empty()).await?;
+    Ok(read)
+}
+
+impl<C: Connect> StreamGroupStorage for PostgresStorage<C> {
+    async fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        create: bool,
+    ) -> StorageResult<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Stream).await? {
+            if !create {
+                return Err(STREAM_REQUIRED);
+            }
+            create_stream(&tx, key).await?;
+        }
+
+        let (ms, seq) = match id {
+            Some(id) => id,
+            None => stream_last_id(&tx, key).await?,
+        }
+        .to_sql();
+        let created = tx
+            .execute(
+                "INSERT INTO stream_groups (key, name, last_ms, last_seq) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (key, name) DO NOTHING",
+                &[&key, &group, &ms, &seq],
+            )
+            .await?;
+        if created == 0 {
+            return Err(StorageError::BusyGroup);
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> StorageResult<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_group(&tx, key, group).await?;
+        let id = match id {
+            Some(id) => id,
+            None => stream_last_id(&tx, key).await?,
+        };
+        set_last_id(&tx, key, group, id).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn xgroup_destroy(&self, key: &str, group: &str) -> StorageResult<bool> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Stream).await? {
+            return Err(no_group(key, group));
+        }
+        let destroyed = tx
+            .execute(
+                "DELETE FROM stream_groups WHERE key = $1 AND name = $2",
+                &[&key, &group],
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(destroyed > 0)
+    }
+
+    async fn xgroup_createconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> StorageResult<bool> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_group(&tx, key, group).await?;
+        let created = tx
+            .execute(
+                "INSERT INTO stream_consumers (key, group_name, name, seen_at) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (key, group_name, name) DO NOTHING",
+                &[&key, &group, &consumer, &now_millis()],
+            )
+            .await?;
+        tx.commit().await?;
+        Ok(created > 0)
+    }
+
+    async fn xgroup_delconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_group(&tx, key, group).await?;
+        let pending = tx
+            .execute(
+                "DELETE FROM stream_pending WHERE key = $1 AND group_name = $2 AND consumer = $3",
+                &[&key, &group, &consumer],
+            )
+            .await?;
+        tx.execute(
+            "DELETE FROM stream_consumers WHERE key = $1 AND group_name = $2 AND name = $3",
+            &[&key, &group, &consumer],
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(count(pending))
+    }
+
+    async fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        streams: &[(String, Option<StreamId>)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> StorageResult<Vec<(String, Vec<DeliveredEntry>)>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let keys: Vec<&str> = streams.iter().map(|(key, _)| key.as_str()).collect();
+        let kinds = lock_keys(&tx, &keys).await?;
+        // Nothing is delivered unless every stream has the group
+        for key in &keys {
+            match kinds.get(*key) {
+                Some(KeyKind::Stream) => group_last_id(&tx, key, group).await.map(|_| ())?,
+                Some(_) => return Err(StorageError::WrongType),
+                None => return Err(no_group(key, group)),
+            }
+        }
+
+        let count = count.map(|n| i64::try_from(n).unwrap_or(i64::MAX));
+        let mut result = Vec::new();
+        for (key, after) in streams {
+            match after {
+                None => {
+                    let read = read_new(&tx, key, group, consumer, count, noack).await?;
+                    // Streams read for new entries are left out when there are none
+                    if !read.is_empty() {
+                        result.push((key.clone(), read));
+                    }
+                }
+                Some(after) => {
+                    let read = read_history(&tx, key, group, consumer, *after, count).await?;
+                    result.push((key.clone(), read));
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    async fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Stream).await? {
+            return Ok(0);
+        }
+        Ok(count(drop_pending(&*client, key, group, ids).await?))
+    }
+
+    async fn xpending_summary(&self, key: &str, group: &str) -> StorageResult<PendingSummary> {
+        let client = self.client().await?;
+        check_group(&*client, key, group).await?;
+
+        let row = client
+            .query_one(
+                "SELECT count(*), min(ARRAY[ms, seq]), max(ARRAY[ms, seq]) FROM stream_pending
+                 WHERE key = $1 AND group_name = $2",
+                &[&key, &group],
+            )
+            .await?;
+        let bound = |index: usize| {
+            let parts: Option<Vec<i64>> = row.get(index);
+            parts.map(|parts| StreamId::from_sql(parts[0], parts[1]))
+        };
+        let consumers = client
+            .query(
+                "SELECT consumer, count(*) FROM stream_pending WHERE key = $1 AND group_name = $2
+                 GROUP BY consumer ORDER BY consumer COLLATE \"C\"",
+                &[&key, &group],
+            )
+            .await?;
+        Ok(PendingSummary {
+            count: u64::try_from(row.get::<_, i64>(0)).unwrap_or(0),
+            bounds: bound(1).zip(bound(2)),
+            consumers: consumers
+                .iter()
+                .map(|row| (row.get(0), u64::try_from(row.get::<_, i64>(1)).unwrap_or(0)))
+                .collect(),
+        })
+    }
+
+    async fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+        range: &PendingRange,
+    ) -> StorageResult<Vec<PendingEntry>> {
+        let client = self.client().await?;
+        check_group(&*client, key, group).await?;
+
+        let (start_ms, start_seq) = range.start.to_sql();
+        let (end_ms, end_seq) = range.end.to_sql();
+        let idle_before = now_millis().saturating_sub(count(range.min_idle));
+        let limit = i64::try_from(range.count).unwrap_or(i64::MAX);
+        let rows = client
+            .query(
+                "SELECT ms, seq, consumer, delivered_at, deliveries FROM stream_pending
+                 WHERE key = $1 AND group_name = $2
+                 AND (ms, seq) >= ($3, $4) AND (ms, seq) <= ($5, $6)
+                 AND ($7::text IS NULL OR consumer = $7) AND delivered_at <= $8
+                 ORDER BY ms, seq LIMIT $9",
+                &[
+                    &key,
+                    &group,
+                    &start_ms,
+                    &start_seq,
+                    &end_ms,
+                    &end_seq,
+                    &range.consumer,
+                    &idle_before,
+                    &limit,
+                ],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| PendingEntry {
+                id: StreamId::from_sql(row.get(0), row.get(1)),
+                consumer: row.get(2),
+                delivered_at: row.get(3),
+                deliveries: u64::try_from(row.get::<_, i64>(4)).unwrap_or(0),
+            })
+            .collect())
+    }
+
+    async fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> StorageResult<Vec<DeliveredEntry>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        let last_id = lock_group(&tx, key, group).await?;
+        if let Some(id) = options.last_id.filter(|id| *id > last_id) {
+            set_last_id(&tx, key, group, id).await?;
+        }
+
+        // Each ID is claimed once, in the order given
+        let mut unique: Vec<StreamId> = Vec::with_capacity(ids.len());
+        for id in ids {
+            if !unique.contains(id) {
+                unique.push(*id);
+            }
+        }
+        let (ms, seq) = id_arrays(&unique);
+        let rows = tx
+            .query(
+                "SELECT t.ms, t.seq, e.fields, p.delivered_at, p.deliveries
+                 FROM unnest($3::bigint[], $4::bigint[]) WITH ORDINALITY AS t (ms, seq, position)
+                 LEFT JOIN stream_entries e ON e.key = $1 AND e.ms = t.ms AND e.seq = t.seq
+                 LEFT JOIN stream_pending p ON p.key = $1 AND p.group_name = $2
+                     AND p.ms = t.ms AND p.seq = t.seq
+                 ORDER BY t.position",
+                &[&key, &group, &ms, &seq],
+            )
+            .await?;
+
+        let now = now_millis();
+        let min_idle = count(options.min_idle);
+        let mut claimed = Vec::new();
+        let mut deliveries = Vec::new();
+        let mut deleted = Vec::new();
+        for row in &rows {
+            let entry = to_delivered(row);
+            let pending: Option<(i64, i64)> = row.get::<_, Option<i64>>(3).zip(row.get(4));
+            let previous = match pending {
+                _ if entry.fields.is_none() => {
+                    if pending.is_some() {
+                        deleted.push(entry.id);
+                    }
+                    continue;
+                }
+                Some((delivered_at, _)) if now - delivered_at < min_idle => continue,
+                Some((_, previous)) => u64::try_from(previous).unwrap_or(0),
+                // FORCE adds entries that were never delivered to the list
+                None if options.force => 0,
+                None => continue,
+            };
+            deliveries.push((entry.id, options.deliveries(previous)));
+            claimed.push(entry);
+        }
+
+        drop_pending(&tx, key, group, &deleted).await?;
+        touch(&tx, key, group, consumer, now, !claimed.is_empty()).await?;
+        let delivered_at = options.delivered_at(now);
+        assign(&tx, key, group, consumer, delivered_at, &deliveries).await?;
+        tx.commit().await?;
+        Ok(claimed)
+    }
+
+    async fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        start: StreamId,
+        count: usize,
+        options: &ClaimOptions,
+    ) -> StorageResult<AutoClaim> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        lock_group(&tx, key, group).await?;
+
+        let attempts = count.saturating_mul(10);
+        // One ID past the scan is kept to continue from next time
+        let limit = i64::try_from(attempts.saturating_add(1)).unwrap_or(i64::MAX);
+        let (ms, seq) = start.to_sql();
+        let rows = tx
+            .query(
+                "SELECT p.ms, p.seq, e.fields, p.delivered_at, p.deliveries FROM stream_pending p
+                 LEFT JOIN stream_entries e ON e.key = p.key AND e.ms = p.ms AND e.seq = p.seq
+                 WHERE p.key = $1 AND p.group_name = $2 AND (p.ms, p.seq) >= ($3, $4)
+                 ORDER BY p.ms, p.seq LIMIT $5",
+                &[&key, &group, &ms, &seq, &limit],
+            )
+            .await?;
+
+        let now = now_millis();
+        let min_idle = i64::try_from(options.min_idle).unwrap_or(i64::MAX);
+        let mut claimed = Vec::new();
+        let mut deliveries = Vec::new();
+        let mut deleted = Vec::new();
+        let mut scanned = 0;
+        for row in &rows {
+            if claimed.len() == count || scanned == attempts {
+                break;
+            }
+            scanned += 1;
+            let entry = to_delivered(row);
+            if entry.fields.is_none() {
+                deleted.push(entry.id);
+                continue;
+            }
+            if now - row.get::<_, i64>(3) < min_idle {
+                continue;
+            }
+            let previous = u64::try_from(row.get::<_, i64>(4)).unwrap_or(0);
+            deliveries.push((entry.id, options.deliveries(previous)));
+            claimed.push(entry);
+        }
+
+        drop_pending(&tx, key, group, &deleted).await?;
+        touch(&tx, key, group, consumer, now, !claimed.is_empty()).await?;
+        assign(&tx, key, group, consumer, now, &deliveries).await?;
+        tx.commit().await?;
+        let next = rows.get(scanned).map_or(StreamId::MIN, |row| {
+            StreamId::from_sql(row.get(0), row.get(1))
+        });
+        Ok(AutoClaim {
+            next,
+            claimed,
+            deleted,
+        })
+    }
+
+    async fn xinfo_groups(&self, key: &str) -> StorageResult<Vec<GroupInfo>> {
+        let client = self.client().await?;
+        let Some(info) = stream_info(&*client, key).await? else {
+            return Err(StorageError::InvalidValue("no such key"));
+        };
+
+        let rows = client
+            .query(
+                "SELECT g.name, g.last_ms, g.last_seq,
+                     (SELECT count(*) FROM stream_consumers c
+                      WHERE c.key = g.key AND c.group_name = g.name),
+                     (SELECT count(*) FROM stream_pending p
+                      WHERE p.key = g.key AND p.group_name = g.name),
+                     (SELECT count(*) FROM stream_entries e
+                      WHERE e.key = g.key AND (e.ms, e.seq) > (g.last_ms, g.last_seq))
+                 FROM stream_groups g WHERE g.key = $1 ORDER BY g.name COLLATE \"C\"",
+                &[&key],
+            )
+            .await?;
+        let part = |row: &Row, index: usize| u64::try_from(row.get::<_, i64>(index)).unwrap_or(0);
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let last_id = StreamId::from_sql(row.get(1), row.get(2));
+                let lag = part(row, 5);
+                GroupInfo {
+                    name: row.get(0),
+                    consumers: part(row, 3),
+                    pending: part(row, 4),
+                    last_id,
+                    entries_read: info.entries_read(last_id, lag),
+                    lag,
+                }
+            })
+            .collect())
+    }
+
+    async fn xinfo_consumers(&self, key: &str, group: &str) -> StorageResult<Vec<ConsumerInfo>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Stream).await? {
+            return Err(StorageError::InvalidValue("no such key"));
+        }
+        group_last_id(&*client, key, group).await?;
+
+        let rows = client
+            .query(
+                "SELECT c.name, c.seen_at, c.active_at,
+                     (SELECT count(*) FROM stream_pending p WHERE p.key = c.key
+                      AND p.group_name = c.group_name AND p.consumer = c.name)
+                 FROM stream_consumers c WHERE c.key = $1 AND c.group_name = $2
+                 ORDER BY c.name COLLATE \"C\"",
+                &[&key, &group],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| ConsumerInfo {
+                name: row.get(0),
+                pending: u64::try_from(row.get::<_, i64>(3)).unwrap_or(0),
+                seen_at: row.get(1),
+                active_at: row.get(2),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,287 @@
+use crate::storage::postgres::{Connect, PostgresStorage, check_kind, lock_kind, lock_or_create};
+use crate::storage::{
+    FieldPairs, KeyKind, StorageResult, StreamEntry, StreamId, StreamIdSpec, StreamInfo,
+    StreamStorage, StreamTrim, StreamTrimBy, now_millis,
+};
+use deadpool_postgres::GenericClient;
+use tokio_postgres::Row;
+
+fn count(n: u64) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn limit(n: Option<usize>) -> Option<i64> {
+    n.map(|n| i64::try_from(n).unwrap_or(i64::MAX))
+}
+
+// Fields are stored as one flattened array of field and value pairs
+fn flatten(fields: &[(Vec<u8>, Vec<u8>)]) -> Vec<&[u8]> {
+    fields
+        .iter()
+        .flat_map(|(field, value)| [field.as_slice(), value.as_slice()])
+        .collect()
+}
+
+// Reads an entry from `ms, seq, fields` columns
+pub(super) fn to_entry(row: &Row) -> StreamEntry {
+    let flat: Vec<Vec<u8>> = row.get(2);
+    let mut flat = flat.into_iter();
+    let mut fields: FieldPairs = Vec::new();
+    while let (Some(field), Some(value)) = (flat.next(), flat.next()) {
+        fields.push((field, value));
+    }
+    StreamEntry {
+        id: StreamId::from_sql(row.get(0), row.get(1)),
+        fields,
+    }
+}
+
+// Creates an empty stream and locks it
+pub(super) async fn create_stream(client: &impl GenericClient, key: &str) -> StorageResult<()> {
+    lock_or_create(client, key, KeyKind::Stream).await?;
+    client
+        .execute(
+            "INSERT INTO streams (key) VALUES ($1) ON CONFLICT (key) DO NOTHING",
+            &[&key],
+        )
+        .await?;
+    Ok(())
+}
+
+// Returns the metadata of the stream, or `None` if it does not exist
+pub(super) async fn stream_info(
+    client: &impl GenericClient,
+    key: &str,
+) -> StorageResult<Option<StreamInfo>> {
+    if !check_kind(client, key, KeyKind::Stream).await? {
+        return Ok(None);
+    }
+    let row = client
+        .query_one(
+            "SELECT last_ms, last_seq, entries_added, max_deleted_ms, max_deleted_seq,
+                 (SELECT count(*) FROM stream_entries WHERE key = $1),
+                 (SELECT count(*) FROM stream_groups WHERE key = $1)
+             FROM streams WHERE key = $1",
+            &[&key],
+        )
+        .await?;
+    let part = |n: i64| u64::try_from(n).unwrap_or(0);
+    Ok(Some(StreamInfo {
+        length: part(row.get(5)),
+        last_id: StreamId::from_sql(row.get(0), row.get(1)),
+        entries_added: part(row.get(2)),
+        max_deleted_id: StreamId::from_sql(row.get(3), row.get(4)),
+        groups: part(row.get(6)),
+    }))
+}
+
+// Evicts entries from the head of a locked stream, returning how many were removed
+async fn trim(client: &impl GenericClient, key: &str, trim: StreamTrim) -> StorageResult<u64> {
+    let limit = limit(trim.limit);
+    let trimmed = match trim.by {
+        StreamTrimBy::MaxLen(max_len) => {
+            let max_len = count(max_len);
+            client
+                .execute(
+                    "DELETE FROM stream_entries WHERE key = $1 AND (ms, seq) IN (
+                         SELECT ms, seq FROM stream_entries WHERE key = $1 ORDER BY ms, seq
+                         LIMIT LEAST(GREATEST(
+                             (SELECT count(*) FROM stream_entries WHERE key = $1) - $2::bigint,
+                             0
+                         ), $3::bigint)
+                     )",
+                    &[&key, &max_len, &limit],
+                )
+                .await?
+        }
+        StreamTrimBy::MinId(min_id) => {
+            let (ms, seq) = min_id.to_sql();
+            client
+                .execute(
+                    "DELETE FROM stream_entries WHERE key = $1 AND (ms, seq) IN (
+                         SELECT ms, seq FROM stream_entries
+                         WHERE key = $1 AND (ms, seq) < ($2, $3)
+                         ORDER BY ms, seq LIMIT $4
+                     )",
+                    &[&key, &ms, &seq, &limit],
+                )
+                .await?
+        }
+    };
+    Ok(trimmed)
+}
+
+impl<C: Connect> StreamStorage for PostgresStorage<C> {
+    async fn xadd(
+        &self,
+        key: &str,
+        id: StreamIdSpec,
+        fields: &[(Vec<u8>, Vec<u8>)],
+        create: bool,
+        trim_by: Option<StreamTrim>,
+    ) -> StorageResult<Option<StreamId>> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Stream).await? {
+            if !create {
+                return Ok(None);
+            }
+            create_stream(&tx, key).await?;
+        }
+
+        let row = tx
+            .query_one(
+                "SELECT last_ms, last_seq FROM streams WHERE key = $1",
+                &[&key],
+            )
+            .await?;
+        let last = StreamId::from_sql(row.get(0), row.get(1));
+        let now = u64::try_from(now_millis()).unwrap_or(0);
+        let id = id.resolve(last, now)?;
+        let (ms, seq) = id.to_sql();
+
+        tx.execute(
+            "INSERT INTO stream_entries (key, ms, seq, fields) VALUES ($1, $2, $3, $4)",
+            &[&key, &ms, &seq, &flatten(fields)],
+        )
+        .await?;
+        tx.execute(
+            "UPDATE streams SET last_ms = $2, last_seq = $3, entries_added = entries_added + 1
+             WHERE key = $1",
+            &[&key, &ms, &seq],
+        )
+        .await?;
+        if let Some(trim_by) = trim_by {
+            trim(&tx, key, trim_by).await?;
+        }
+        tx.commit().await?;
+        Ok(Some(id))
+    }
+
+    async fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> StorageResult<Vec<StreamEntry>> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Stream).await? {
+            return Ok(Vec::new());
+        }
+
+        let statement = format!(
+            "SELECT ms, seq, fields FROM stream_entries
+             WHERE key = $1 AND (ms, seq) >= ($2, $3) AND (ms, seq) <= ($4, $5)
+             ORDER BY ms {order}, seq {order} LIMIT $6",
+            order = if reverse { "DESC" } else { "ASC" }
+        );
+        let (start_ms, start_seq) = start.to_sql();
+        let (end_ms, end_seq) = end.to_sql();
+        let rows = client
+            .query(
+                &statement,
+                &[
+                    &key,
+                    &start_ms,
+                    &start_seq,
+                    &end_ms,
+                    &end_seq,
+                    &limit(count),
+                ],
+            )
+            .await?;
+        Ok(rows.iter().map(to_entry).collect())
+    }
+
+    async fn xread(
+        &self,
+        streams: &[(String, StreamId)],
+        count: Option<usize>,
+    ) -> StorageResult<Vec<(String, Vec<StreamEntry>)>> {
+        let client = self.client().await?;
+        let mut result = Vec::new();
+        for (key, after) in streams {
+            if !check_kind(&*client, key, KeyKind::Stream).await? {
+                continue;
+            }
+            let (ms, seq) = after.to_sql();
+            let rows = client
+                .query(
+                    "SELECT ms, seq, fields FROM stream_entries
+                     WHERE key = $1 AND (ms, seq) > ($2, $3)
+                     ORDER BY ms, seq LIMIT $4",
+                    &[&key, &ms, &seq, &limit(count)],
+                )
+                .await?;
+            if !rows.is_empty() {
+                result.push((key.clone(), rows.iter().map(to_entry).collect()));
+            }
+        }
+        Ok(result)
+    }
+
+    async fn xlen(&self, key: &str) -> StorageResult<i64> {
+        let client = self.client().await?;
+        if !check_kind(&*client, key, KeyKind::Stream).await? {
+            return Ok(0);
+        }
+        let row = client
+            .query_one(
+                "SELECT count(*) FROM stream_entries WHERE key = $1",
+                &[&key],
+            )
+            .await?;
+        Ok(row.get(0))
+    }
+
+    async fn xdel(&self, key: &str, ids: &[StreamId]) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Stream).await? {
+            return Ok(0);
+        }
+
+        let (ms, seq): (Vec<i64>, Vec<i64>) = ids.iter().map(|id| id.to_sql()).unzip();
+        let rows = tx
+            .query(
+                "DELETE FROM stream_entries WHERE key = $1
+                 AND (ms, seq) IN (SELECT * FROM unnest($2::bigint[], $3::bigint[]))
+                 RETURNING ms, seq",
+                &[&key, &ms, &seq],
+            )
+            .await?;
+        let deleted = rows
+            .iter()
+            .map(|row| StreamId::from_sql(row.get(0), row.get(1)))
+            .max();
+        if let Some(deleted) = deleted {
+            let (ms, seq) = deleted.to_sql();
+            tx.execute(
+                "UPDATE streams SET max_deleted_ms = $2, max_deleted_seq = $3
+                 WHERE key = $1 AND (max_deleted_ms, max_deleted_seq) < ($2, $3)",
+                &[&key, &ms, &seq],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(count(rows.len() as u64))
+    }
+
+    async fn xtrim(&self, key: &str, trim_by: StreamTrim) -> StorageResult<i64> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        if !lock_kind(&tx, key, KeyKind::Stream).await? {
+            return Ok(0);
+        }
+        let trimmed = trim(&tx, key, trim_by).await?;
+        tx.commit().await?;
+        Ok(count(trimmed))
+    }
+
+    async fn xinfo(&self, key: &str) -> StorageResult<Option<StreamInfo>> {
+        let client = self.client().await?;
+        stream_info(&*client, key).await
+    }
+}
@@ -0,0 +1,120 @@
+use crate::storage::postgres::{Connect, PostgresStorage, expire_keys, parse_kind};
+use crate::storage::{
+    KeyKind, SetCondition, SetExpiry, SetOptions, SetOutcome, StorageError, StorageResult,
+    StringStorage,
+};
+use deadpool_postgres::GenericClient;
+
+impl<C: Connect> StringStorage for PostgresStorage<C> {
+    async fn get(&self, key: &str) -> StorageResult<Option<Vec<u8>>> {
+        let client = self.client().await?;
+        expire_keys(&*client, &[key]).await?;
+
+        let row = client
+            .query_opt("SELECT value, kind FROM kv WHERE key = $1", &[&key])
+            .await?;
+        match row {
+            None => Ok(None),
+            Some(row) if parse_kind(row.get(1)) == KeyKind::String => Ok(row.get(0)),
+            Some(_) => Err(StorageError::WrongType),
+        }
+    }
+
+    async fn set(&self, key: &str, value: &[u8], options: SetOptions) -> StorageResult<SetOutcome> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        expire_keys(&tx, &[key]).await?;
+
+        // Lock the current row (if any) so the condition and write are atomic
+        let existing: Option<(Option<Vec<u8>>, Option<i64>, KeyKind)> = tx
+            .query_opt(
+                "SELECT value, expires_at, kind FROM kv WHERE key = $1 FOR UPDATE",
+                &[&key],
+            )
+            .await?
+            .map(|row| (row.get(0), row.get(1), parse_kind(row.get(2))));
+
+        let allowed = match options.condition {
+            SetCondition::Always => true,
+            SetCondition::IfNotExists => existing.is_none(),
+            SetCondition::IfExists => existing.is_some(),
+        };
+        let expires_at = match options.expiry {
+            SetExpiry::Clear => None,
+            SetExpiry::Keep => existing.as_ref().and_then(|(_, at, _)| *at),
+            SetExpiry::At(at) => Some(at),
+        };
+        let replaces_other_kind = existing
+            .as_ref()
+            .is_some_and(|(_, _, kind)| *kind != KeyKind::String);
+        let previous = existing.and_then(|(value, _, _)| value);
+
+        if !allowed {
+            return Ok(SetOutcome {
+                written: false,
+                previous,
+            });
+        }
+
+        // Values of other types are dropped before the key becomes a string
+        if replaces_other_kind {
+            tx.execute("DELETE FROM kv WHERE key = $1", &[&key]).await?;
+        }
+
+        // A row inserted concurrently by another node is never replaced when the key
+        // must not exist, so NX remains safe to use as a lock
+        let written = tx
+            .execute(
+                "INSERT INTO kv (key, value, expires_at) VALUES ($1, $2, $3)
+                 ON CONFLICT (key) DO UPDATE
+                 SET value = EXCLUDED.value, expires_at = EXCLUDED.expires_at
+                 WHERE NOT $4",
+                &[
+                    &key,
+                    &value,
+                    &expires_at,
+                    &(options.condition == SetCondition::IfNotExists),
+                ],
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(SetOutcome {
+            written: written > 0,
+            previous,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::postgres::tests::connect;
+    use crate::storage::{HashStorage, KeyStorage, SetOptions, StorageError, StringStorage};
+
+    #[tokio::test]
+    async fn test_get_set_del() {
+        let Some(storage) = connect().await else {
+            return;
+        };
+        let keys = vec!["strings:a".to_string(), "strings:h".to_string()];
+        storage.del(&keys).await.unwrap();
+        assert_eq!(storage.get("strings:a").await.unwrap(), None);
+
+        let options = SetOptions::default();
+        storage.set("strings:a", b"1", options).await.unwrap();
+        storage.set("strings:a", b"2", options).await.unwrap();
+        assert_eq!(storage.get("strings:a").await.unwrap(), Some(b"2".to_vec()));
+
+        // GET refuses other types, which SET replaces
+        let pairs = vec![(b"f".to_vec(), b"v".to_vec())];
+        storage.hset("strings:h", &pairs).await.unwrap();
+        let got = storage.get("strings:h").await;
+        assert!(matches!(got, Err(StorageError::WrongType)));
+        storage.set("strings:h", b"3", options).await.unwrap();
+        assert_eq!(storage.get("strings:h").await.unwrap(), Some(b"3".to_vec()));
+
+        assert_eq!(storage.del(&keys).await.unwrap(), 2);
+        assert_eq!(storage.del(&keys).await.unwrap(), 0);
+        assert_eq!(storage.get("strings:a").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,47 @@
+// Resolves an inclusive index range that may count back from the end (-1 is the last
+// element) against a sequence of `len` items, returning `None` if it selects nothing
+pub fn normalize_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = i64::try_from(len).unwrap_or(i64::MAX);
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start.try_into().ok()?, stop.try_into().ok()?))
+}
+
+// Resolves a single index that may count back from the end
+pub fn normalize_index(index: i64, len: usize) -> Option<usize> {
+    normalize_range(index, index, len).map(|(start, _)| start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_range() {
+        assert_eq!(normalize_range(0, -1, 5), Some((0, 4)));
+        assert_eq!(normalize_range(-3, 10, 5), Some((2, 4)));
+        assert_eq!(normalize_range(-10, 1, 5), Some((0, 1)));
+        assert_eq!(normalize_range(3, 1, 5), None);
+        assert_eq!(normalize_range(5, 10, 5), None);
+        assert_eq!(normalize_range(0, -1, 0), None);
+    }
+
+    #[test]
+    fn test_normalize_index() {
+        assert_eq!(normalize_index(0, 3), Some(0));
+        assert_eq!(normalize_index(-1, 3), Some(2));
+        assert_eq!(normalize_index(3, 3), None);
+        assert_eq!(normalize_index(-4, 3), None);
+    }
+}
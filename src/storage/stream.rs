@@ -0,0 +1,351 @@
+use crate::storage::{FieldPairs, StorageError, StorageResult};
+use std::fmt;
+
+// Identifies a stream entry by the millisecond time it was added and a sequence number
+// for entries added within the same millisecond. Both parts are limited to 63 bits so
+// they fit in Postgres `bigint` columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+const MAX_PART: u64 = i64::MAX as u64;
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: MAX_PART,
+        seq: MAX_PART,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    // Parses `ms-seq`, or a bare `ms` with the given sequence number
+    pub fn parse(text: &[u8], default_seq: u64) -> Option<StreamId> {
+        let text = std::str::from_utf8(text).ok()?;
+        match text.split_once('-') {
+            Some((ms, seq)) => Some(StreamId::new(parse_part(ms)?, parse_part(seq)?)),
+            None => Some(StreamId::new(parse_part(text)?, default_seq)),
+        }
+    }
+
+    // The smallest ID after this one
+    pub fn next(self) -> Option<StreamId> {
+        if self.seq < MAX_PART {
+            Some(StreamId::new(self.ms, self.seq + 1))
+        } else if self.ms < MAX_PART {
+            Some(StreamId::new(self.ms + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    // The largest ID before this one
+    pub fn prev(self) -> Option<StreamId> {
+        if self.seq > 0 {
+            Some(StreamId::new(self.ms, self.seq - 1))
+        } else if self.ms > 0 {
+            Some(StreamId::new(self.ms - 1, MAX_PART))
+        } else {
+            None
+        }
+    }
+
+    // The parts as stored in Postgres
+    pub fn to_sql(self) -> (i64, i64) {
+        let part = |n: u64| i64::try_from(n).unwrap_or(i64::MAX);
+        (part(self.ms), part(self.seq))
+    }
+
+    pub fn from_sql(ms: i64, seq: i64) -> Self {
+        let part = |n: i64| u64::try_from(n).unwrap_or(0);
+        StreamId::new(part(ms), part(seq))
+    }
+}
+
+// Digits only, so signs and whitespace are rejected
+fn parse_part(text: &str) -> Option<u64> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok().filter(|n| *n <= MAX_PART)
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+// The ID requested for a new entry: `*`, `ms-*` or an explicit ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamIdSpec {
+    Auto,
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+impl StreamIdSpec {
+    // Picks the ID for a new entry, which must be greater than every ID the stream
+    // has ever had
+    pub fn resolve(self, last: StreamId, now_ms: u64) -> StorageResult<StreamId> {
+        match self {
+            StreamIdSpec::Auto if now_ms > last.ms => Ok(StreamId::new(now_ms, 0)),
+            StreamIdSpec::Auto => last.next().ok_or(StorageError::InvalidValue(
+                "The stream has exhausted the last possible ID, unable to add more items",
+            )),
+            StreamIdSpec::AutoSeq(ms) if ms > last.ms => Ok(StreamId::new(ms, 0)),
+            StreamIdSpec::AutoSeq(ms) if ms == last.ms && last.seq < MAX_PART => {
+                Ok(StreamId::new(ms, last.seq + 1))
+            }
+            StreamIdSpec::Explicit(StreamId::MIN) => Err(StorageError::InvalidValue(
+                "The ID specified in XADD must be greater than 0-0",
+            )),
+            StreamIdSpec::Explicit(id) if id > last => Ok(id),
+            StreamIdSpec::AutoSeq(_) | StreamIdSpec::Explicit(_) => {
+                Err(StorageError::InvalidValue(
+                    "The ID specified in XADD is equal or smaller than the target stream top item",
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamEntry {
+    pub id: StreamId,
+    pub fields: FieldPairs,
+}
+
+// Which entries XTRIM (or XADD with a trim option) evicts from the head of a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamTrimBy {
+    MaxLen(u64),
+    MinId(StreamId),
+}
+
+// Trimming is always exact, and `limit` caps how many entries are evicted at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTrim {
+    pub by: StreamTrimBy,
+    pub limit: Option<usize>,
+}
+
+// Metadata of a stream that outlives its entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub length: u64,
+    pub last_id: StreamId,
+    pub entries_added: u64,
+    pub max_deleted_id: StreamId,
+    pub groups: u64,
+}
+
+impl StreamInfo {
+    // How many entries a group positioned at `id` with `lag` entries after it has read,
+    // known when it is at the end of the stream or no entry was ever removed
+    pub fn entries_read(&self, id: StreamId, lag: u64) -> Option<u64> {
+        if id >= self.last_id {
+            Some(self.entries_added)
+        } else if self.entries_added == self.length {
+            Some(self.length - lag)
+        } else {
+            None
+        }
+    }
+}
+
+// XGROUP CREATE without MKSTREAM needs an existing stream
+pub const STREAM_REQUIRED: StorageError = StorageError::InvalidValue(
+    "The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to \
+     use the MKSTREAM option to create an empty stream automatically.",
+);
+
+// An entry delivered to a consumer, without fields if it was deleted from the stream
+// after it was first delivered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveredEntry {
+    pub id: StreamId,
+    pub fields: Option<FieldPairs>,
+}
+
+// A delivered entry in a group's pending entries list, awaiting acknowledgement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEntry {
+    pub id: StreamId,
+    pub consumer: String,
+    pub delivered_at: i64,
+    pub deliveries: u64,
+}
+
+// The pending entries of a group as a whole, with the count owned by each consumer
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PendingSummary {
+    pub count: u64,
+    pub bounds: Option<(StreamId, StreamId)>,
+    pub consumers: Vec<(String, u64)>,
+}
+
+// Selects pending entries between the inclusive IDs that have been idle at least
+// `min_idle` milliseconds, optionally of a single consumer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRange {
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<String>,
+    pub min_idle: u64,
+}
+
+// How XCLAIM and XAUTOCLAIM take over pending entries idle at least `min_idle`
+// milliseconds. XAUTOCLAIM only uses `min_idle` and `just_id`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClaimOptions {
+    pub min_idle: u64,
+    pub idle: Option<u64>,
+    pub time: Option<i64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+impl ClaimOptions {
+    // The delivery time recorded for claimed entries
+    pub fn delivered_at(&self, now: i64) -> i64 {
+        self.time.unwrap_or_else(|| {
+            let idle = i64::try_from(self.idle.unwrap_or(0)).unwrap_or(i64::MAX);
+            now.saturating_sub(idle)
+        })
+    }
+
+    // The delivery count of a claimed entry that had been delivered `deliveries` times
+    pub fn deliveries(&self, deliveries: u64) -> u64 {
+        match self.retry_count {
+            Some(count) => count,
+            None if self.just_id => deliveries,
+            None => deliveries + 1,
+        }
+    }
+}
+
+// The result of XAUTOCLAIM: the ID to continue scanning from (`0-0` once the whole
+// list was scanned), the claimed entries, and the pending IDs of deleted entries,
+// which are dropped from the list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoClaim {
+    pub next: StreamId,
+    pub claimed: Vec<DeliveredEntry>,
+    pub deleted: Vec<StreamId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    pub name: String,
+    pub consumers: u64,
+    pub pending: u64,
+    pub last_id: StreamId,
+    pub entries_read: Option<u64>,
+    pub lag: u64,
+}
+
+// `active_at` is when the consumer was last delivered an entry, if ever
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumerInfo {
+    pub name: String,
+    pub pending: u64,
+    pub seen_at: i64,
+    pub active_at: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(StreamId::parse(b"5-3", 0), Some(StreamId::new(5, 3)));
+        assert_eq!(StreamId::parse(b"5", 7), Some(StreamId::new(5, 7)));
+        assert_eq!(StreamId::parse(b"5-", 0), None);
+        assert_eq!(StreamId::parse(b"-5", 0), None);
+        assert_eq!(StreamId::parse(b"+5", 0), None);
+        assert_eq!(StreamId::parse(b"9223372036854775808", 0), None);
+        assert_eq!(StreamId::new(1, 2).to_string(), "1-2");
+    }
+
+    #[test]
+    fn test_next_prev() {
+        assert_eq!(StreamId::new(1, 2).next(), Some(StreamId::new(1, 3)));
+        assert_eq!(StreamId::new(1, MAX_PART).next(), Some(StreamId::new(2, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(StreamId::new(2, 0).prev(), Some(StreamId::new(1, MAX_PART)));
+        assert_eq!(StreamId::MIN.prev(), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let last = StreamId::new(10, 4);
+        assert_eq!(
+            StreamIdSpec::Auto.resolve(last, 20).unwrap(),
+            StreamId::new(20, 0)
+        );
+        // A clock that went backwards still produces increasing IDs
+        assert_eq!(
+            StreamIdSpec::Auto.resolve(last, 5).unwrap(),
+            StreamId::new(10, 5)
+        );
+        assert_eq!(
+            StreamIdSpec::AutoSeq(10).resolve(last, 0).unwrap(),
+            StreamId::new(10, 5)
+        );
+        assert_eq!(
+            StreamIdSpec::AutoSeq(0).resolve(StreamId::MIN, 0).unwrap(),
+            StreamId::new(0, 1)
+        );
+        assert!(StreamIdSpec::AutoSeq(9).resolve(last, 0).is_err());
+        assert!(StreamIdSpec::Explicit(last).resolve(last, 0).is_err());
+        assert!(
+            StreamIdSpec::Explicit(StreamId::MIN)
+                .resolve(StreamId::MIN, 0)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_entries_read() {
+        let mut info = StreamInfo {
+            length: 3,
+            last_id: StreamId::new(3, 0),
+            entries_added: 3,
+            max_deleted_id: StreamId::MIN,
+            groups: 0,
+        };
+        assert_eq!(info.entries_read(StreamId::MIN, 3), Some(0));
+        assert_eq!(info.entries_read(StreamId::new(2, 0), 1), Some(2));
+
+        info.length = 2;
+        assert_eq!(info.entries_read(StreamId::new(2, 0), 1), None);
+        assert_eq!(info.entries_read(StreamId::new(3, 0), 0), Some(3));
+    }
+
+    #[test]
+    fn test_claim_options() {
+        let options = ClaimOptions {
+            idle: Some(500),
+            ..ClaimOptions::default()
+        };
+        assert_eq!(options.delivered_at(1000), 500);
+        assert_eq!(options.deliveries(2), 3);
+
+        let options = ClaimOptions {
+            time: Some(42),
+            just_id: true,
+            ..ClaimOptions::default()
+        };
+        assert_eq!(options.delivered_at(1000), 42);
+        assert_eq!(options.deliveries(2), 2);
+    }
+}
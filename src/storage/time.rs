@@ -0,0 +1,9 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Current Unix time in milliseconds, used for all expiry timestamps
+pub fn now_millis() -> i64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch");
+    i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX)
+}
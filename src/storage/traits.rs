@@ -0,0 +1,677 @@
+use crate::storage::{
+    AutoClaim, ClaimOptions, ConsumerInfo, DeliveredEntry, ExpireCondition, GroupInfo, KeyExpiry,
+    KeyKind, ListEnd, PendingEntry, PendingRange, PendingSummary, ScoreEnd, SetOp, SetOptions,
+    SetOutcome, StorageResult, StreamEntry, StreamId, StreamIdSpec, StreamInfo, StreamTrim,
+    ZAddOptions, ZAddOutcome, ZRange, ZRangeBy,
+};
+use std::future::Future;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+// Common interface implemented by every storage engine, made up of one trait per
+// family of commands. All timestamps are absolute Unix time in milliseconds.
+pub trait Storage:
+    KeyStorage
+    + StringStorage
+    + HashStorage
+    + ListStorage
+    + SetStorage
+    + SortedSetStorage
+    + StreamStorage
+    + StreamGroupStorage
+    + PubSubStorage
+    + FunctionStorage
+    + Send
+    + Sync
+{
+}
+
+impl<T> Storage for T where
+    T: KeyStorage
+        + StringStorage
+        + HashStorage
+        + ListStorage
+        + SetStorage
+        + SortedSetStorage
+        + StreamStorage
+        + StreamGroupStorage
+        + PubSubStorage
+        + FunctionStorage
+        + Send
+        + Sync
+{
+}
+
+// Operations that apply to keys of any type
+pub trait KeyStorage {
+    // Identifies the state of a key, changing whenever the key is written
+    type Version: PartialEq + Send + Sync;
+
+    // Removes the keys, returning how many existed
+    fn del(&self, keys: &[String]) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Counts how many of the keys exist (duplicates are counted each time)
+    fn exists(&self, keys: &[String]) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Returns the type of value held by a key, if it exists
+    fn key_type(&self, key: &str) -> impl Future<Output = StorageResult<Option<KeyKind>>> + Send;
+
+    // Sets or clears (`None`) the expiry of a key if the condition allows it
+    // A timestamp in the past deletes the key, returns false if nothing was changed
+    fn expire(
+        &self,
+        key: &str,
+        expires_at: Option<i64>,
+        condition: ExpireCondition,
+    ) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    // Returns the expiry state of a key
+    fn expire_time(&self, key: &str) -> impl Future<Output = StorageResult<KeyExpiry>> + Send;
+
+    // Deletes up to `limit` expired keys, returning how many were removed
+    fn purge_expired(&self, limit: usize) -> impl Future<Output = StorageResult<usize>> + Send;
+
+    // Returns the version of each key, which WATCH compares again at EXEC
+    // Within a transaction the keys stay locked until it ends, missing ones included
+    fn versions(
+        &self,
+        keys: &[String],
+    ) -> impl Future<Output = StorageResult<Vec<Self::Version>>> + Send;
+
+    // Iterates the keyspace, returning the next cursor (0 when complete) and a page of keys
+    // The pattern and type filters are applied after the page has been fetched
+    fn scan(
+        &self,
+        cursor: u64,
+        pattern: Option<&str>,
+        kind: Option<KeyKind>,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<(u64, Vec<String>)>> + Send;
+}
+
+// Operations on string values
+pub trait StringStorage {
+    // Returns the string value of a key, if it exists
+    fn get(&self, key: &str) -> impl Future<Output = StorageResult<Option<Vec<u8>>>> + Send;
+
+    // Stores a string value subject to the SET condition and expiry options
+    // Unlike other writes, SET replaces a key holding any type of value
+    fn set(
+        &self,
+        key: &str,
+        value: &[u8],
+        options: SetOptions,
+    ) -> impl Future<Output = StorageResult<SetOutcome>> + Send;
+}
+
+// Field and value pairs of a hash
+pub type FieldPairs = Vec<(Vec<u8>, Vec<u8>)>;
+
+// Members of a sorted set along with their scores, in rank order
+pub type ScoredMembers = Vec<(Vec<u8>, f64)>;
+
+// Operations on hash values, where an empty hash never exists as a key
+pub trait HashStorage {
+    // Sets the fields, returning how many of them were newly added
+    fn hset(
+        &self,
+        key: &str,
+        pairs: &[(Vec<u8>, Vec<u8>)],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Sets the field only if it does not exist yet, returning whether it was set
+    fn hsetnx(
+        &self,
+        key: &str,
+        field: &[u8],
+        value: &[u8],
+    ) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    // Returns the value of each requested field, in order
+    fn hmget(
+        &self,
+        key: &str,
+        fields: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<Vec<Option<Vec<u8>>>>> + Send;
+
+    // Returns every field and value, ordered by field
+    fn hgetall(&self, key: &str) -> impl Future<Output = StorageResult<FieldPairs>> + Send;
+
+    // Returns the number of fields
+    fn hlen(&self, key: &str) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes the fields, returning how many existed
+    fn hdel(
+        &self,
+        key: &str,
+        fields: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Atomically adds to the integer value of a field, returning the new value
+    fn hincrby(
+        &self,
+        key: &str,
+        field: &[u8],
+        delta: i64,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Atomically adds to the float value of a field, returning the new value
+    fn hincrbyfloat(
+        &self,
+        key: &str,
+        field: &[u8],
+        delta: f64,
+    ) -> impl Future<Output = StorageResult<f64>> + Send;
+
+    // Iterates the fields like SCAN, returning the next cursor and a page of pairs
+    fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<(u64, FieldPairs)>> + Send;
+}
+
+// Operations on list values, where indexes may count back from the end (-1 is the last
+// element) and an empty list never exists as a key
+pub trait ListStorage {
+    // Pushes the values one at a time onto an end of the list, returning its new length
+    // With `only_existing` nothing is pushed (and 0 returned) unless the list exists
+    fn push(
+        &self,
+        key: &str,
+        values: &[Vec<u8>],
+        end: ListEnd,
+        only_existing: bool,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes up to `count` elements from an end, or returns `None` if the list is missing
+    fn pop(
+        &self,
+        key: &str,
+        end: ListEnd,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<Option<Vec<Vec<u8>>>>> + Send;
+
+    // Returns the elements between two inclusive indexes
+    fn lrange(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> impl Future<Output = StorageResult<Vec<Vec<u8>>>> + Send;
+
+    // Replaces the element at an index, failing if the list or index does not exist
+    fn lset(
+        &self,
+        key: &str,
+        index: i64,
+        value: &[u8],
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Inserts a value next to the first occurrence of the pivot, returning the new length
+    // Returns 0 if the list is missing and -1 if the pivot was not found
+    fn linsert(
+        &self,
+        key: &str,
+        before: bool,
+        pivot: &[u8],
+        value: &[u8],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes occurrences of a value, from the head if `count` is positive, from the tail
+    // if it is negative or all of them if it is zero, returning how many were removed
+    fn lrem(
+        &self,
+        key: &str,
+        count: i64,
+        value: &[u8],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Keeps only the elements between two inclusive indexes
+    fn ltrim(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Returns the length of the list
+    fn llen(&self, key: &str) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Atomically pops an element from one list and pushes it onto another (or the same)
+    fn lmove(
+        &self,
+        source: &str,
+        destination: &str,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> impl Future<Output = StorageResult<Option<Vec<u8>>>> + Send;
+}
+
+// Operations on set values, where an empty set never exists as a key
+pub trait SetStorage {
+    // Adds the members, returning how many were not already present
+    fn sadd(
+        &self,
+        key: &str,
+        members: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes the members, returning how many were present
+    fn srem(
+        &self,
+        key: &str,
+        members: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Returns every member of the set
+    fn smembers(&self, key: &str) -> impl Future<Output = StorageResult<Vec<Vec<u8>>>> + Send;
+
+    // Returns whether each of the members is in the set, in order
+    fn smismember(
+        &self,
+        key: &str,
+        members: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<Vec<bool>>> + Send;
+
+    // Returns the number of members
+    fn scard(&self, key: &str) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes and returns up to `count` random members
+    fn spop(
+        &self,
+        key: &str,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<Vec<Vec<u8>>>> + Send;
+
+    // Returns random members, distinct for a positive count and possibly repeated
+    // (exactly `-count` of them) for a negative one
+    fn srandmember(
+        &self,
+        key: &str,
+        count: i64,
+    ) -> impl Future<Output = StorageResult<Vec<Vec<u8>>>> + Send;
+
+    // Atomically moves a member between sets, returning false if it was not in the source
+    fn smove(
+        &self,
+        source: &str,
+        destination: &str,
+        member: &[u8],
+    ) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    // Returns the union, intersection or difference (of the first set and the rest)
+    // Missing keys count as empty sets
+    fn combine(
+        &self,
+        op: SetOp,
+        keys: &[String],
+    ) -> impl Future<Output = StorageResult<Vec<Vec<u8>>>> + Send;
+
+    // Stores the combined members in the destination, replacing any value it held, and
+    // returns the size of the result
+    fn combine_store(
+        &self,
+        op: SetOp,
+        destination: &str,
+        keys: &[String],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Counts the members of the intersection, stopping early at the limit if given
+    fn intercard(
+        &self,
+        keys: &[String],
+        limit: Option<usize>,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Iterates the members like SCAN, returning the next cursor and a page of members
+    fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        pattern: Option<&[u8]>,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<(u64, Vec<Vec<u8>>)>> + Send;
+}
+
+// Operations on sorted set values, where members are ordered by score and then
+// lexicographically, and an empty sorted set never exists as a key
+pub trait SortedSetStorage {
+    // Adds members or updates their scores as the options allow
+    fn zadd(
+        &self,
+        key: &str,
+        members: &[(f64, Vec<u8>)],
+        options: ZAddOptions,
+    ) -> impl Future<Output = StorageResult<ZAddOutcome>> + Send;
+
+    // Adds `delta` to the score of a member (missing counts as zero), returning the new
+    // score or `None` if the options skipped it
+    fn zincrby(
+        &self,
+        key: &str,
+        member: &[u8],
+        delta: f64,
+        options: ZAddOptions,
+    ) -> impl Future<Output = StorageResult<Option<f64>>> + Send;
+
+    // Returns the score of each member, in order
+    fn zmscore(
+        &self,
+        key: &str,
+        members: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<Vec<Option<f64>>>> + Send;
+
+    // Returns the rank of a member and its score, counting from the highest score
+    // when `reverse` is set
+    fn zrank(
+        &self,
+        key: &str,
+        member: &[u8],
+        reverse: bool,
+    ) -> impl Future<Output = StorageResult<Option<(i64, f64)>>> + Send;
+
+    // Removes the members, returning how many were present
+    fn zrem(
+        &self,
+        key: &str,
+        members: &[Vec<u8>],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Returns the number of members
+    fn zcard(&self, key: &str) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Counts the members within the range
+    fn zcount(
+        &self,
+        key: &str,
+        range: &ZRangeBy,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Returns the members selected by the query with their scores
+    fn zrange(
+        &self,
+        key: &str,
+        range: &ZRange,
+    ) -> impl Future<Output = StorageResult<ScoredMembers>> + Send;
+
+    // Removes the members within the range, returning how many were removed
+    fn zremrange(
+        &self,
+        key: &str,
+        range: &ZRangeBy,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Removes and returns up to `count` members from the lowest or highest scores
+    fn zpop(
+        &self,
+        key: &str,
+        end: ScoreEnd,
+        count: usize,
+    ) -> impl Future<Output = StorageResult<ScoredMembers>> + Send;
+}
+
+// Operations on stream values. Unlike other types a stream keeps existing once empty,
+// along with the last ID it generated
+pub trait StreamStorage {
+    // Appends an entry and trims the stream if requested, returning the new entry's ID
+    // A missing stream is only created when `create` is set, otherwise `None` is returned
+    fn xadd(
+        &self,
+        key: &str,
+        id: StreamIdSpec,
+        fields: &[(Vec<u8>, Vec<u8>)],
+        create: bool,
+        trim: Option<StreamTrim>,
+    ) -> impl Future<Output = StorageResult<Option<StreamId>>> + Send;
+
+    // Returns the entries between the inclusive IDs, newest first when `reverse` is set
+    fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> impl Future<Output = StorageResult<Vec<StreamEntry>>> + Send;
+
+    // Returns up to `count` entries after the given ID of each stream, leaving out the
+    // streams that have none
+    fn xread(
+        &self,
+        streams: &[(String, StreamId)],
+        count: Option<usize>,
+    ) -> impl Future<Output = StorageResult<Vec<(String, Vec<StreamEntry>)>>> + Send;
+
+    // Returns the number of entries
+    fn xlen(&self, key: &str) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Deletes the entries, returning how many existed
+    fn xdel(&self, key: &str, ids: &[StreamId]) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Evicts entries from the head of the stream, returning how many were removed
+    fn xtrim(&self, key: &str, trim: StreamTrim)
+    -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Returns the stream's length and ID bookkeeping, or `None` if it does not exist
+    fn xinfo(&self, key: &str) -> impl Future<Output = StorageResult<Option<StreamInfo>>> + Send;
+}
+
+// Consumer groups of streams. Commands on a missing stream or group fail with
+// `StorageError::NoGroup` unless noted otherwise
+pub trait StreamGroupStorage {
+    // Creates a group positioned at `id`, or at the last entry when `None`
+    // A missing stream is created empty when `create` is set
+    fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        create: bool,
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Moves the group's last delivered ID, to the last entry when `None`
+    fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Removes the group with its consumers and pending entries, returning whether it
+    // existed
+    fn xgroup_destroy(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    // Creates a consumer, returning whether it was new
+    fn xgroup_createconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    // Removes a consumer and its pending entries, returning how many it had
+    fn xgroup_delconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    // Delivers entries of each stream to a consumer of the group. A `None` ID delivers
+    // the entries after the group's last delivered ID, recording them as pending unless
+    // `noack` is set, and leaves out streams that have none. Otherwise the consumer's
+    // pending entries after the ID are delivered again
+    fn xreadgroup(
+        &self,
+        group: &str,
+        consumer: &str,
+        streams: &[(String, Option<StreamId>)],
+        count: Option<usize>,
+        noack: bool,
+    ) -> impl Future<Output = StorageResult<Vec<(String, Vec<DeliveredEntry>)>>> + Send;
+
+    // Removes entries from the pending entries list, returning how many were pending
+    // A missing stream or group has none
+    fn xack(
+        &self,
+        key: &str,
+        group: &str,
+        ids: &[StreamId],
+    ) -> impl Future<Output = StorageResult<i64>> + Send;
+
+    fn xpending_summary(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> impl Future<Output = StorageResult<PendingSummary>> + Send;
+
+    fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+        range: &PendingRange,
+    ) -> impl Future<Output = StorageResult<Vec<PendingEntry>>> + Send;
+
+    // Transfers pending entries to the consumer, returning the claimed ones. Entries
+    // deleted from the stream are dropped from the pending entries list instead
+    fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        ids: &[StreamId],
+        options: &ClaimOptions,
+    ) -> impl Future<Output = StorageResult<Vec<DeliveredEntry>>> + Send;
+
+    // Claims up to `count` idle pending entries from `start` on, scanning at most ten
+    // times as many
+    fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        start: StreamId,
+        count: usize,
+        options: &ClaimOptions,
+    ) -> impl Future<Output = StorageResult<AutoClaim>> + Send;
+
+    // Fails with `StorageError::InvalidValue` if the stream does not exist
+    fn xinfo_groups(&self, key: &str)
+    -> impl Future<Output = StorageResult<Vec<GroupInfo>>> + Send;
+
+    // Fails with `StorageError::InvalidValue` if the stream does not exist
+    fn xinfo_consumers(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> impl Future<Output = StorageResult<Vec<ConsumerInfo>>> + Send;
+}
+
+// A message published to a channel by any server sharing the storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedMessage {
+    pub channel: String,
+    pub message: Vec<u8>,
+    // Published with SPUBLISH to a shard channel
+    pub sharded: bool,
+}
+
+// Relays published messages between servers sharing the same storage, each of which
+// delivers them to its own subscribers
+pub trait PubSubStorage {
+    // Broadcasts a message to the other servers
+    fn publish(
+        &self,
+        channel: &str,
+        message: &[u8],
+        sharded: bool,
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Starts receiving the messages other servers publish
+    fn listen(
+        &self,
+    ) -> impl Future<Output = StorageResult<UnboundedReceiver<PublishedMessage>>> + Send;
+}
+
+// A library of functions loaded with FUNCTION LOAD
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionLibrary {
+    pub name: String,
+    pub code: String,
+    pub functions: Vec<LibraryFunction>,
+}
+
+// A function a library registered, with the flags it was registered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryFunction {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+// Keeps function libraries alongside the keyspace, so every server sharing the storage
+// sees the same functions
+pub trait FunctionStorage {
+    // Stores a library, replacing the library of the same name only if `replace` is set
+    // Fails if another library already registered one of its functions
+    fn load_library(
+        &self,
+        library: &FunctionLibrary,
+        replace: bool,
+    ) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Removes a library along with its functions, returning whether it existed
+    fn delete_library(&self, name: &str) -> impl Future<Output = StorageResult<bool>> + Send;
+
+    fn flush_libraries(&self) -> impl Future<Output = StorageResult<()>> + Send;
+
+    // Returns every library ordered by name
+    fn libraries(&self) -> impl Future<Output = StorageResult<Vec<FunctionLibrary>>> + Send;
+
+    // Returns the library that registered a function
+    fn function_library(
+        &self,
+        function: &str,
+    ) -> impl Future<Output = StorageResult<Option<FunctionLibrary>>> + Send;
+}
+
+// Engines able to run the commands of an EXEC on a single transaction
+pub trait TransactionStorage: Storage {
+    // The storage as seen by the commands running in the transaction
+    type Transaction<'a>: Storage + KeyStorage<Version = Self::Version>
+    where
+        Self: 'a;
+
+    // Runs `run` for each item in order on one transaction, returning every result, or
+    // `None` without running anything if a watched key is no longer at its version
+    // A failed command only has its own changes undone, unless `atomic` is set in which
+    // case the whole transaction is rolled back and the error returned instead
+    fn exec<I: IntoIterator, T>(
+        &self,
+        watched: &[(String, Self::Version)],
+        items: I,
+        atomic: bool,
+        run: impl AsyncFnMut(&Self::Transaction<'_>, I::Item) -> StorageResult<T>,
+    ) -> impl Future<Output = StorageResult<Option<Vec<StorageResult<T>>>>>;
+}
+
+// Whether any of the watched keys was written since its version was taken
+pub async fn watched_changed<S: KeyStorage>(
+    storage: &S,
+    watched: &[(String, S::Version)],
+) -> StorageResult<bool> {
+    if watched.is_empty() {
+        return Ok(false);
+    }
+    let keys: Vec<String> = watched.iter().map(|(key, _)| key.clone()).collect();
+    let versions = storage.versions(&keys).await?;
+    Ok(!versions
+        .iter()
+        .eq(watched.iter().map(|(_, version)| version)))
+}